@@ -138,7 +138,7 @@ fn server_update_system(
 ) {
     for event in server_events.read() {
         match event {
-            ServerEvent::ClientConnected { client_id } => {
+            ServerEvent::ClientConnected { client_id, .. } => {
                 println!("Player {} connected.", client_id);
                 // Spawn player cube
                 let player_entity = commands
@@ -2,7 +2,7 @@ pub use renet;
 
 use bevy::prelude::*;
 
-use renet::{RenetClient, RenetServer, ServerEvent};
+use renet::{ConnectionEvent, RenetClient, RenetServer, ServerEvent};
 
 #[cfg(feature = "transport")]
 pub mod transport;
@@ -27,6 +27,10 @@ pub struct RenetSend;
 
 pub struct RenetServerPlugin;
 
+/// Updates the [`RenetClient`] resource and sends its [`ConnectionEvent`]s as Bevy events,
+/// after the transports received the packets.
+///
+/// The events are read from the client by the plugin, use an `EventReader<ConnectionEvent>` instead of [`RenetClient::next_event`].
 pub struct RenetClientPlugin;
 
 impl Plugin for RenetServerPlugin {
@@ -57,7 +61,14 @@ impl RenetServerPlugin {
 
 impl Plugin for RenetClientPlugin {
     fn build(&self, app: &mut App) {
+        app.add_event::<ConnectionEvent>();
         app.add_systems(PreUpdate, Self::update_system.run_if(resource_exists::<RenetClient>));
+        app.add_systems(
+            PreUpdate,
+            Self::emit_client_events_system
+                .after(RenetReceive)
+                .run_if(resource_exists::<RenetClient>),
+        );
     }
 }
 
@@ -65,6 +76,12 @@ impl RenetClientPlugin {
     pub fn update_system(mut client: ResMut<RenetClient>, time: Res<Time>) {
         client.update(time.delta());
     }
+
+    pub fn emit_client_events_system(mut client: ResMut<RenetClient>, mut client_events: EventWriter<ConnectionEvent>) {
+        while let Some(event) = client.next_event() {
+            client_events.send(event);
+        }
+    }
 }
 
 pub fn client_connected(client: Option<Res<RenetClient>>) -> bool {
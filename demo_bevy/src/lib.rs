@@ -1,7 +1,7 @@
 use std::{f32::consts::PI, time::Duration};
 
 use bevy::prelude::*;
-use bevy_renet::renet::{ChannelConfig, ClientId, ConnectionConfig, SendType};
+use bevy_renet::renet::{ChannelConfig, ClientId, ConnectionConfig, FullQueuePolicy, SendType};
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "transport")]
@@ -81,6 +81,7 @@ impl ClientChannel {
                 send_type: SendType::ReliableOrdered {
                     resend_time: Duration::ZERO,
                 },
+                ..Default::default()
             },
             ChannelConfig {
                 channel_id: Self::Command.into(),
@@ -88,6 +89,7 @@ impl ClientChannel {
                 send_type: SendType::ReliableOrdered {
                     resend_time: Duration::ZERO,
                 },
+                ..Default::default()
             },
         ]
     }
@@ -109,6 +111,10 @@ impl ServerChannel {
                 channel_id: Self::NetworkedEntities.into(),
                 max_memory_usage_bytes: 10 * 1024 * 1024,
                 send_type: SendType::Unreliable,
+                // Only the latest entity states matter
+                full_queue_policy: FullQueuePolicy::DropOldest,
+                resend_backoff: None,
+                max_resends: None,
             },
             ChannelConfig {
                 channel_id: Self::ServerMessages.into(),
@@ -116,6 +122,7 @@ impl ServerChannel {
                 send_type: SendType::ReliableOrdered {
                     resend_time: Duration::from_millis(200),
                 },
+                ..Default::default()
             },
         ]
     }
@@ -126,6 +133,7 @@ pub fn connection_config() -> ConnectionConfig {
         available_bytes_per_tick: 1024 * 1024,
         client_channels_config: ClientChannel::channels_config(),
         server_channels_config: ServerChannel::channels_config(),
+        ..Default::default()
     }
 }
 
@@ -62,7 +62,7 @@ impl ChatServer {
 
         while let Some(event) = self.server.get_event() {
             match event {
-                ServerEvent::ClientConnected { client_id } => {
+                ServerEvent::ClientConnected { client_id, .. } => {
                     let user_data = self.transport.user_data(client_id).unwrap();
                     self.visualizer.add_client(client_id);
                     let username = Username::from_user_data(&user_data).0;
@@ -95,7 +95,7 @@ fn server(public_addr: SocketAddr) {
 
         while let Some(event) = server.get_event() {
             match event {
-                ServerEvent::ClientConnected { client_id } => {
+                ServerEvent::ClientConnected { client_id, .. } => {
                     let user_data = transport.user_data(client_id).unwrap();
                     let username = Username::from_user_data(&user_data);
                     server.broadcast_message_except(
@@ -0,0 +1,74 @@
+//! Server and client running in the same process over a `MemoryNetwork`.
+//!
+//! It doesn't use sockets, threads or the system time, so it also runs on wasm32-unknown-unknown:
+//! - Native: `cargo run --example in_memory`
+//! - Browser: `cargo build --example in_memory --target wasm32-unknown-unknown`
+//!
+//! In a browser game, the loop is driven by `requestAnimationFrame` instead,
+//! and the client connects to a real server with `WebRtcTransport` (webrtc feature).
+use std::{net::SocketAddr, time::Duration};
+
+use renet::{
+    transport::{ClientAuthentication, MemoryNetwork, NetcodeClientTransport, NetcodeServerTransport, ServerAuthentication, ServerConfig},
+    Clock, ConnectionConfig, DefaultChannel, ManualClock, RenetClient, RenetServer, ServerEvent,
+};
+
+const PROTOCOL_ID: u64 = 7;
+
+fn main() {
+    let network = MemoryNetwork::new();
+    let server_addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+    let clock = ManualClock::default();
+
+    let server_config = ServerConfig {
+        current_time: clock.now(),
+        max_clients: 8,
+        protocol_id: PROTOCOL_ID,
+        public_addresses: vec![server_addr],
+        authentication: ServerAuthentication::Unsecure,
+    };
+    let mut server_transport = NetcodeServerTransport::new_with_transport_socket(server_config, network.bind(server_addr)).unwrap();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+
+    let authentication = ClientAuthentication::Unsecure {
+        protocol_id: PROTOCOL_ID,
+        client_id: 1,
+        server_addr,
+        user_data: None,
+    };
+    let client_socket = network.bind("127.0.0.1:6000".parse().unwrap());
+    let mut client_transport = NetcodeClientTransport::new_with_transport_socket(clock.now(), authentication, client_socket).unwrap();
+    let mut client = RenetClient::new(ConnectionConfig::default());
+
+    let delta = Duration::from_millis(16);
+    let mut replies = 0;
+    while replies < 3 {
+        clock.advance(delta);
+
+        client.update(delta);
+        client_transport.update(delta, &mut client).unwrap();
+        if client.is_connected() {
+            client.send_message(DefaultChannel::ReliableOrdered, format!("ping at {:?}", clock.now()));
+            while let Some(message) = client.receive_message(DefaultChannel::ReliableOrdered) {
+                println!("Client received: {}", String::from_utf8_lossy(&message));
+                replies += 1;
+            }
+        }
+        client_transport.send_packets(&mut client).unwrap();
+
+        server.update(delta);
+        server_transport.update(delta, &mut server).unwrap();
+        while let Some(event) = server.get_event() {
+            if let ServerEvent::ClientConnected { client_id, .. } = event {
+                println!("Client {client_id} connected");
+            }
+        }
+        for client_id in server.clients_id() {
+            while let Some(message) = server.receive_message(client_id, DefaultChannel::ReliableOrdered) {
+                let reply = format!("pong to '{}'", String::from_utf8_lossy(&message));
+                server.send_message(client_id, DefaultChannel::ReliableOrdered, reply);
+            }
+        }
+        server_transport.send_packets(&mut server);
+    }
+}
@@ -0,0 +1,238 @@
+//! Bit-level serialization, for packing flags and small values without padding them to whole bytes.
+//!
+//! Bits are written most significant first, so values that end in a byte boundary
+//! have the same representation as big-endian integers.
+//!
+//! ```
+//! use renet::bits::{BitReader, BitWriter};
+//!
+//! let mut writer = BitWriter::new();
+//! writer.write_bool(true);
+//! writer.write_range(37, 0, 100);
+//! writer.write_bits(0xABC, 12);
+//! let bytes = writer.finish();
+//! assert_eq!(bytes.len(), 3);
+//!
+//! let mut reader = BitReader::new(&bytes);
+//! assert_eq!(reader.read_bool(), Some(true));
+//! assert_eq!(reader.read_range(0, 100), Some(37));
+//! assert_eq!(reader.read_bits(12), Some(0xABC));
+//! ```
+
+/// Returns the number of bits needed to represent all values up to `max`.
+pub const fn bits_required(max: u64) -> u32 {
+    u64::BITS - max.leading_zeros()
+}
+
+/// Writes values with an arbitrary number of bits into a byte buffer.
+#[derive(Debug, Default, Clone)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    // Bits used in the last byte, 0 when it's full or there are no bytes
+    bit_offset: u32,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(bytes: usize) -> Self {
+        Self {
+            bytes: Vec::with_capacity(bytes),
+            bit_offset: 0,
+        }
+    }
+
+    /// Writes the lowest `bits` bits of the value. Panics if `bits` is above 64.
+    pub fn write_bits(&mut self, value: u64, bits: u32) {
+        assert!(bits <= u64::BITS, "cannot write more than 64 bits at once");
+        let mut remaining = bits;
+        while remaining > 0 {
+            if self.bit_offset == 0 {
+                self.bytes.push(0);
+            }
+
+            let free = 8 - self.bit_offset;
+            let count = free.min(remaining);
+            let chunk = ((value >> (remaining - count)) & ((1 << count) - 1)) as u8;
+            *self.bytes.last_mut().unwrap() |= chunk << (free - count);
+
+            remaining -= count;
+            self.bit_offset = (self.bit_offset + count) % 8;
+        }
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_bits(value as u64, 1);
+    }
+
+    /// Writes a value between `min` and `max` (inclusive), using only the bits needed for the range.
+    /// Panics if the value is outside the range.
+    pub fn write_range(&mut self, value: u64, min: u64, max: u64) {
+        assert!(min <= value && value <= max, "value {value} is outside of the range {min}..={max}");
+        self.write_bits(value - min, bits_required(max - min));
+    }
+
+    /// Pads the current byte with zeros, so the next value starts in a new byte.
+    pub fn align(&mut self) {
+        self.bit_offset = 0;
+    }
+
+    /// Aligns and writes the bytes.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.align();
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Returns the number of bits written, including the padding of aligned values.
+    pub fn bits_written(&self) -> usize {
+        match self.bit_offset {
+            0 => self.bytes.len() * 8,
+            offset => (self.bytes.len() - 1) * 8 + offset as usize,
+        }
+    }
+
+    /// Returns the written bytes, the last byte is padded with zeros.
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads values written with a [`BitWriter`].
+/// All reads return `None` if the buffer doesn't have enough bits.
+#[derive(Debug, Clone)]
+pub struct BitReader<'a> {
+    bytes: &'a [u8],
+    bit_position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, bit_position: 0 }
+    }
+
+    /// Reads a value with `bits` bits. Panics if `bits` is above 64.
+    pub fn read_bits(&mut self, bits: u32) -> Option<u64> {
+        assert!(bits <= u64::BITS, "cannot read more than 64 bits at once");
+        if self.bits_remaining() < bits as usize {
+            return None;
+        }
+
+        let mut value: u64 = 0;
+        let mut remaining = bits;
+        while remaining > 0 {
+            let byte = self.bytes[self.bit_position / 8];
+            let offset = (self.bit_position % 8) as u32;
+            let count = (8 - offset).min(remaining);
+            let chunk = (byte >> (8 - offset - count)) & ((1u16 << count) - 1) as u8;
+            value = (value << count) | chunk as u64;
+
+            remaining -= count;
+            self.bit_position += count as usize;
+        }
+
+        Some(value)
+    }
+
+    pub fn read_bool(&mut self) -> Option<bool> {
+        Some(self.read_bits(1)? == 1)
+    }
+
+    /// Reads a value written with [`BitWriter::write_range`], returns `None` if it's outside the range
+    /// or if the range is empty (`max` below `min`).
+    pub fn read_range(&mut self, min: u64, max: u64) -> Option<u64> {
+        let value = min.checked_add(self.read_bits(bits_required(max.checked_sub(min)?))?)?;
+        (value <= max).then_some(value)
+    }
+
+    /// Skips the padding until the next byte.
+    pub fn align(&mut self) {
+        self.bit_position = self.bit_position.div_ceil(8) * 8;
+    }
+
+    /// Aligns and reads `len` bytes.
+    pub fn read_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        self.align();
+        let start = self.bit_position / 8;
+        let bytes = self.bytes.get(start..start.checked_add(len)?)?;
+        self.bit_position += len * 8;
+        Some(bytes)
+    }
+
+    /// Aligns and returns all the remaining bytes.
+    pub fn remaining_bytes(&mut self) -> &'a [u8] {
+        self.align();
+        let start = (self.bit_position / 8).min(self.bytes.len());
+        self.bit_position = self.bytes.len() * 8;
+        &self.bytes[start..]
+    }
+
+    pub fn bits_remaining(&self) -> usize {
+        (self.bytes.len() * 8).saturating_sub(self.bit_position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits_required_for_ranges() {
+        assert_eq!(bits_required(0), 0);
+        assert_eq!(bits_required(1), 1);
+        assert_eq!(bits_required(255), 8);
+        assert_eq!(bits_required(256), 9);
+        assert_eq!(bits_required(u64::MAX), 64);
+    }
+
+    #[test]
+    fn write_and_read() {
+        let mut writer = BitWriter::new();
+        writer.write_bits(0b101, 3);
+        writer.write_bits(u64::MAX, 64);
+        writer.write_bool(false);
+        writer.write_range(7, 5, 10);
+        writer.write_bytes(&[1, 2]);
+        writer.write_bits(0, 0);
+        assert_eq!(writer.bits_written(), 88);
+        let bytes = writer.finish();
+
+        let mut reader = BitReader::new(&bytes);
+        assert_eq!(reader.read_bits(3), Some(0b101));
+        assert_eq!(reader.read_bits(64), Some(u64::MAX));
+        assert_eq!(reader.read_bool(), Some(false));
+        assert_eq!(reader.read_range(5, 10), Some(7));
+        assert_eq!(reader.read_bytes(2), Some(&[1, 2][..]));
+        assert_eq!(reader.bits_remaining(), 0);
+        assert_eq!(reader.read_bool(), None);
+    }
+
+    #[test]
+    fn byte_aligned_values_are_big_endian() {
+        let mut writer = BitWriter::new();
+        writer.write_bits(0x1234, 16);
+        assert_eq!(writer.finish(), [0x12, 0x34]);
+
+        let mut writer = BitWriter::new();
+        writer.write_bits(0b1, 1);
+        writer.write_bits(0b1, 1);
+        assert_eq!(writer.finish(), [0b1100_0000]);
+    }
+
+    #[test]
+    fn invalid_range() {
+        let mut writer = BitWriter::new();
+        writer.write_bits(7, 3);
+        let bytes = writer.finish();
+
+        // 7 fits in the 3 bits used for the range 0..=4, but it's outside of it
+        let mut reader = BitReader::new(&bytes);
+        assert_eq!(reader.read_range(0, 4), None);
+
+        // Empty range, nothing is read
+        let mut reader = BitReader::new(&bytes);
+        assert_eq!(reader.read_range(5, 4), None);
+        assert_eq!(reader.bits_remaining(), 8);
+    }
+}
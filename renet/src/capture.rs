@@ -0,0 +1,242 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    net::Ipv6Addr,
+    path::Path,
+    sync::Mutex,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(target_arch = "wasm32")]
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use crate::ClientId;
+
+/// Direction of a captured packet, from the point of view of the connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureDirection {
+    Sent,
+    Received,
+}
+
+/// Hook called with every packet sent and received by a connection.
+///
+/// Packets are captured as generated and processed by renet, before any encryption
+/// done by the transport layer.
+/// See [`RenetClient::set_packet_capture`](crate::RenetClient::set_packet_capture)
+/// and [`RenetServer::set_packet_capture`](crate::RenetServer::set_packet_capture).
+pub trait PacketCapture: fmt::Debug + Send + Sync + 'static {
+    /// `client_id` is the remote client for connections of a server, and `None` for clients.
+    fn capture(&self, client_id: Option<ClientId>, direction: CaptureDirection, packet: &[u8]);
+}
+
+const PCAP_MAGIC: u32 = 0xA1B2_C3D4;
+const PCAP_SNAPLEN: u32 = 65535;
+// Raw IP packets, without a link layer header
+const LINKTYPE_RAW: u32 = 101;
+const IPV6_HEADER_BYTES: usize = 40;
+const UDP_HEADER_BYTES: usize = 8;
+const UDP_PROTOCOL: u8 = 17;
+const CAPTURE_PORT: u16 = 5000;
+
+/// Writes captured packets to a pcap file, to be inspected with Wireshark.
+///
+/// Packets are wrapped in fake IPv6 and UDP headers, both sides using port 5000:
+/// the server is `fd00::1`, clients are `fd00:0:0:1::/64` with their client id in the last 64 bits,
+/// and standalone clients are `fd00:0:0:1::`. Timestamps are taken when the packet is captured.
+///
+/// # Usage
+/// ```no_run
+/// # use renet::{ConnectionConfig, PcapWriter, RenetServer};
+/// # use std::sync::Arc;
+/// let mut server = RenetServer::new(ConnectionConfig::default());
+/// server.set_packet_capture(Arc::new(PcapWriter::create("server.pcap").unwrap()));
+/// ```
+pub struct PcapWriter<W: Write + Send = BufWriter<File>> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> fmt::Debug for PcapWriter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PcapWriter").finish_non_exhaustive()
+    }
+}
+
+impl PcapWriter {
+    /// Creates the file and writes the pcap header.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write + Send> PcapWriter<W> {
+    /// Writes the pcap header to the writer.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(&PCAP_MAGIC.to_le_bytes())?;
+        writer.write_all(&2u16.to_le_bytes())?;
+        writer.write_all(&4u16.to_le_bytes())?;
+        // Timezone offset and timestamp accuracy, always 0
+        writer.write_all(&[0; 8])?;
+        writer.write_all(&PCAP_SNAPLEN.to_le_bytes())?;
+        writer.write_all(&LINKTYPE_RAW.to_le_bytes())?;
+
+        Ok(Self {
+            writer: Mutex::new(writer),
+        })
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.writer.lock().unwrap().flush()
+    }
+
+    /// Returns the inner writer, without flushing it.
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().unwrap()
+    }
+
+    fn write_packet(&self, client_id: Option<ClientId>, direction: CaptureDirection, packet: &[u8]) -> io::Result<()> {
+        const SERVER: Ipv6Addr = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1);
+        let client = Ipv6Addr::from(0xfd00_0000_0000_0001_u128 << 64 | client_id.map_or(0, |id| id.raw()) as u128);
+        // Packets are sent by the server for connections with a client id
+        let (source, destination) = match (client_id.is_some(), direction) {
+            (true, CaptureDirection::Sent) | (false, CaptureDirection::Received) => (SERVER, client),
+            (true, CaptureDirection::Received) | (false, CaptureDirection::Sent) => (client, SERVER),
+        };
+
+        let udp_len = UDP_HEADER_BYTES + packet.len();
+        let Ok(udp_len) = u16::try_from(udp_len) else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "packet too big to be captured"));
+        };
+        let record_len = (IPV6_HEADER_BYTES + udp_len as usize) as u32;
+
+        let mut record = Vec::with_capacity(16 + record_len as usize);
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        record.extend_from_slice(&(timestamp.as_secs() as u32).to_le_bytes());
+        record.extend_from_slice(&timestamp.subsec_micros().to_le_bytes());
+        record.extend_from_slice(&record_len.to_le_bytes());
+        record.extend_from_slice(&record_len.to_le_bytes());
+
+        // IPv6 header: version 6, no traffic class or flow label, hop limit 64
+        record.extend_from_slice(&[0x60, 0, 0, 0]);
+        record.extend_from_slice(&udp_len.to_be_bytes());
+        record.extend_from_slice(&[UDP_PROTOCOL, 64]);
+        record.extend_from_slice(&source.octets());
+        record.extend_from_slice(&destination.octets());
+
+        record.extend_from_slice(&CAPTURE_PORT.to_be_bytes());
+        record.extend_from_slice(&CAPTURE_PORT.to_be_bytes());
+        record.extend_from_slice(&udp_len.to_be_bytes());
+        record.extend_from_slice(&udp_checksum(source, destination, udp_len, packet).to_be_bytes());
+        record.extend_from_slice(packet);
+
+        self.writer.lock().unwrap().write_all(&record)
+    }
+}
+
+impl<W: Write + Send + 'static> PacketCapture for PcapWriter<W> {
+    fn capture(&self, client_id: Option<ClientId>, direction: CaptureDirection, packet: &[u8]) {
+        if let Err(e) = self.write_packet(client_id, direction, packet) {
+            log::error!("Failed to write captured packet: {e}");
+        }
+    }
+}
+
+// Checksum over the IPv6 pseudo header, the UDP header and the payload (RFC 8200, section 8.1).
+fn udp_checksum(source: Ipv6Addr, destination: Ipv6Addr, udp_len: u16, payload: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    let mut add = |bytes: &[u8]| {
+        for chunk in bytes.chunks(2) {
+            let word = match *chunk {
+                [high, low] => u16::from_be_bytes([high, low]),
+                [high] => u16::from_be_bytes([high, 0]),
+                _ => unreachable!(),
+            };
+            sum += word as u32;
+        }
+    };
+
+    add(&source.octets());
+    add(&destination.octets());
+    add(&(udp_len as u32).to_be_bytes());
+    add(&[0, 0, 0, UDP_PROTOCOL]);
+    add(&CAPTURE_PORT.to_be_bytes());
+    add(&CAPTURE_PORT.to_be_bytes());
+    add(&udp_len.to_be_bytes());
+    add(payload);
+
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    match !(sum as u16) {
+        // A zero checksum means no checksum, it's sent as all ones instead
+        0 => 0xFFFF,
+        checksum => checksum,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pcap_records() {
+        let writer = PcapWriter::new(Vec::new()).unwrap();
+        writer.capture(Some(ClientId::from_raw(0xAB)), CaptureDirection::Sent, &[1, 2, 3]);
+        writer.capture(None, CaptureDirection::Sent, &[4]);
+        let bytes = writer.into_inner();
+
+        assert_eq!(&bytes[..4], &[0xD4, 0xC3, 0xB2, 0xA1]);
+        assert_eq!(&bytes[20..24], &LINKTYPE_RAW.to_le_bytes());
+
+        let record = &bytes[24..];
+        let len = IPV6_HEADER_BYTES + UDP_HEADER_BYTES + 3;
+        assert_eq!(&record[8..12], &(len as u32).to_le_bytes());
+        let ip = &record[16..16 + len];
+        assert_eq!(ip[0], 0x60);
+        assert_eq!(&ip[4..6], &11u16.to_be_bytes());
+        // Sent by the server to the client 0xAB
+        assert_eq!(ip[23], 1);
+        assert_eq!(ip[39], 0xAB);
+        assert_eq!(&ip[48..], &[1, 2, 3]);
+
+        let record = &record[16 + len..];
+        let ip = &record[16..];
+        assert_eq!(ip.len(), IPV6_HEADER_BYTES + UDP_HEADER_BYTES + 1);
+        // Sent by a client to the server
+        assert_eq!(&ip[8..16], &[0xfd, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(ip[39], 1);
+    }
+
+    #[test]
+    fn checksum_is_valid() {
+        let source = Ipv6Addr::new(0xfd00, 0, 0, 1, 0, 0, 0, 7);
+        let destination = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1);
+        let payload = [0xDE, 0xAD, 0xBE];
+        let checksum = udp_checksum(source, destination, 11, &payload);
+
+        // Summing everything including the checksum gives all ones
+        let mut sum: u32 = 0;
+        for word in [
+            &source.octets()[..],
+            &destination.octets(),
+            &11u32.to_be_bytes(),
+            &[0, 0, 0, UDP_PROTOCOL],
+            &CAPTURE_PORT.to_be_bytes(),
+            &CAPTURE_PORT.to_be_bytes(),
+            &11u16.to_be_bytes(),
+            &checksum.to_be_bytes(),
+            &[0xDE, 0xAD, 0xBE, 0],
+        ]
+        .concat()
+        .chunks(2)
+        {
+            sum += u16::from_be_bytes([word[0], word[1]]) as u32;
+        }
+        while sum > 0xFFFF {
+            sum = (sum & 0xFFFF) + (sum >> 16);
+        }
+        assert_eq!(sum, 0xFFFF);
+    }
+}
@@ -4,8 +4,13 @@ pub(crate) mod unreliable;
 
 use std::time::Duration;
 
+use crate::error::ConfigError;
+
 pub(crate) use slice_constructor::SliceConstructor;
 
+const DEFAULT_MAX_MEMORY_USAGE_BYTES: usize = 5 * 1024 * 1024;
+const DEFAULT_RESEND_TIME: Duration = Duration::from_millis(300);
+
 /// Delivery garantee of a channel
 #[derive(Debug, Clone)]
 pub enum SendType {
@@ -21,6 +26,67 @@ pub enum SendType {
     },
 }
 
+/// What a send channel does with a new message when it doesn't fit in its max memory usage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FullQueuePolicy {
+    /// Queued messages are dropped, oldest first, until the new one fits. Useful for state streams
+    /// where only the latest messages matter. Only supported by unreliable channels, since a dropped
+    /// reliable message would never be received and hold back the ones after it.
+    DropOldest,
+    /// The new message is dropped and sending it still succeeds.
+    /// Dropped messages are counted in [`ChannelStats::messages_dropped`](crate::ChannelStats::messages_dropped).
+    DropNewest,
+    /// Sending the message returns an error. With [`RenetClient::send_message`](crate::RenetClient::send_message)
+    /// unreliable channels drop the message, and reliable channels disconnect.
+    #[default]
+    Error,
+}
+
+/// Exponential backoff of the resends of reliable messages, so a congested link isn't flooded with resends.
+///
+/// Each message waits the resend time of its channel before the first resend, and the wait is multiplied
+/// after each resend of the message, up to the max resend time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResendBackoff {
+    /// Factor applied to the wait after each resend, must be at least 1.
+    /// Default: 2.0
+    pub multiplier: f64,
+    /// Maximum wait between resends of a message.
+    /// Default: 2s
+    pub max_resend_time: Duration,
+}
+
+impl Default for ResendBackoff {
+    fn default() -> Self {
+        Self {
+            multiplier: 2.0,
+            max_resend_time: Duration::from_secs(2),
+        }
+    }
+}
+
+impl ResendBackoff {
+    /// Returns how long to wait for an ack after the message was resent this many times.
+    pub(crate) fn resend_time(&self, resend_time: Duration, resends: u32) -> Duration {
+        let max = self.max_resend_time.max(resend_time);
+        let backoff = resend_time.as_secs_f64() * self.multiplier.powi(resends.min(i32::MAX as u32) as i32);
+        Duration::from_secs_f64(backoff.min(max.as_secs_f64()))
+    }
+}
+
+/// Limit of resends of each reliable message, for links that stopped delivering packets without timing out.
+///
+/// A message resent this many times without being acknowledged is no longer sent, and a
+/// [`ConnectionEvent::DeliveryFailed`](crate::ConnectionEvent::DeliveryFailed) is generated.
+/// In ordered channels the remote side won't receive the messages after it, so disconnecting is usually preferred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxResends {
+    /// Number of resends of a message, or of one of its slices, before its delivery fails.
+    pub resends: u32,
+    /// Disconnects with [`ChannelError::DeliveryFailed`](crate::ChannelError::DeliveryFailed) when a delivery fails.
+    pub disconnect: bool,
+}
+
 /// Configuration of a channel for a server or client
 /// Channels are unilateral and message based.
 #[derive(Debug, Clone)]
@@ -29,15 +95,191 @@ pub struct ChannelConfig {
     /// but it can be repeated between the server and client lists.
     pub channel_id: u8,
     /// Maximum number of bytes that the channel may hold without acknowledgement of messages before becoming full.
-    /// Unreliable channels will drop new messages when this value is reached.
-    /// Reliable channels will cause a disconnect when this value is reached.
+    /// What is done with new messages when this value is reached is decided by the full queue policy.
     pub max_memory_usage_bytes: usize,
     /// Delivery garantee of the channel.
     pub send_type: SendType,
+    /// Behavior when a new message doesn't fit in the channel.
+    pub full_queue_policy: FullQueuePolicy,
+    /// Backoff of the resend time of reliable channels, messages are resent every resend time if `None`.
+    pub resend_backoff: Option<ResendBackoff>,
+    /// Limit of resends of reliable messages, messages are resent until acknowledged if `None`.
+    pub max_resends: Option<MaxResends>,
+}
+
+impl Default for ChannelConfig {
+    /// An unreliable channel with id 0 and a max memory usage of 5 MiB,
+    /// with the default full queue policy and no resend limits.
+    fn default() -> Self {
+        Self {
+            channel_id: 0,
+            max_memory_usage_bytes: DEFAULT_MAX_MEMORY_USAGE_BYTES,
+            send_type: SendType::Unreliable,
+            full_queue_policy: FullQueuePolicy::default(),
+            resend_backoff: None,
+            max_resends: None,
+        }
+    }
+}
+
+impl ChannelConfig {
+    /// Starts building an unreliable channel, with a max memory usage of 5 MiB.
+    pub fn unreliable(channel_id: u8) -> ChannelConfigBuilder {
+        ChannelConfigBuilder::new(channel_id, SendType::Unreliable)
+    }
+
+    /// Starts building a reliable ordered channel, with a max memory usage of 5 MiB and a resend time of 300ms.
+    pub fn reliable_ordered(channel_id: u8) -> ChannelConfigBuilder {
+        ChannelConfigBuilder::new(
+            channel_id,
+            SendType::ReliableOrdered {
+                resend_time: DEFAULT_RESEND_TIME,
+            },
+        )
+    }
+
+    /// Starts building a reliable unordered channel, with a max memory usage of 5 MiB and a resend time of 300ms.
+    pub fn reliable_unordered(channel_id: u8) -> ChannelConfigBuilder {
+        ChannelConfigBuilder::new(
+            channel_id,
+            SendType::ReliableUnordered {
+                resend_time: DEFAULT_RESEND_TIME,
+            },
+        )
+    }
+
+    /// Checks the invariants of the channel that don't depend on the rest of the connection config.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let channel_id = self.channel_id;
+        if self.max_memory_usage_bytes == 0 {
+            return Err(ConfigError::ZeroMemoryUsage { channel_id });
+        }
+        if self.resend_time().is_some() && self.full_queue_policy == FullQueuePolicy::DropOldest {
+            return Err(ConfigError::DropOldestOnReliableChannel { channel_id });
+        }
+        if let Some(backoff) = self.resend_backoff {
+            if !(backoff.multiplier >= 1.0 && backoff.multiplier.is_finite()) {
+                return Err(ConfigError::InvalidResendBackoff { channel_id });
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn resend_time(&self) -> Option<Duration> {
+        match self.send_type {
+            SendType::Unreliable => None,
+            SendType::ReliableOrdered { resend_time } | SendType::ReliableUnordered { resend_time } => Some(resend_time),
+        }
+    }
+}
+
+/// Builder of a [`ChannelConfig`], validating it when built.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use renet::ChannelConfig;
+/// let channel = ChannelConfig::reliable_ordered(0)
+///     .max_memory_usage_bytes(1024 * 1024)
+///     .resend_time(Duration::from_millis(100))
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChannelConfigBuilder {
+    config: ChannelConfig,
+}
+
+impl ChannelConfigBuilder {
+    fn new(channel_id: u8, send_type: SendType) -> Self {
+        Self {
+            config: ChannelConfig {
+                channel_id,
+                send_type,
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn max_memory_usage_bytes(mut self, max_memory_usage_bytes: usize) -> Self {
+        self.config.max_memory_usage_bytes = max_memory_usage_bytes;
+        self
+    }
+
+    /// Sets what is done with new messages when the channel is full.
+    pub fn full_queue_policy(mut self, policy: FullQueuePolicy) -> Self {
+        self.config.full_queue_policy = policy;
+        self
+    }
+
+    /// Sets the backoff of the resend time, ignored by unreliable channels.
+    pub fn resend_backoff(mut self, backoff: ResendBackoff) -> Self {
+        self.config.resend_backoff = Some(backoff);
+        self
+    }
+
+    /// Sets the limit of resends of each message, ignored by unreliable channels.
+    pub fn max_resends(mut self, resends: u32, disconnect: bool) -> Self {
+        self.config.max_resends = Some(MaxResends { resends, disconnect });
+        self
+    }
+
+    /// Sets how long to wait for an ack before resending a message, ignored by unreliable channels.
+    pub fn resend_time(mut self, resend_time: Duration) -> Self {
+        match &mut self.config.send_type {
+            SendType::Unreliable => {}
+            SendType::ReliableOrdered { resend_time: time } | SendType::ReliableUnordered { resend_time: time } => *time = resend_time,
+        }
+        self
+    }
+
+    /// Returns the channel config, or the first invariant it breaks.
+    /// The resend time is checked against the connection timeout by [`ConnectionConfig::validate`](crate::ConnectionConfig::validate).
+    pub fn build(self) -> Result<ChannelConfig, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+/// Type identifying the channels of an application, usually a fieldless enum,
+/// so messages are sent with `send_message(GameChannel::Input, message)` instead of a raw number.
+///
+/// ```
+/// # use renet::ChannelId;
+/// #[derive(Clone, Copy)]
+/// enum GameChannel {
+///     Input,
+///     Chat,
+/// }
+///
+/// impl From<GameChannel> for u8 {
+///     fn from(channel: GameChannel) -> Self {
+///         channel as u8
+///     }
+/// }
+///
+/// impl ChannelId for GameChannel {
+///     const ALL: &'static [Self] = &[GameChannel::Input, GameChannel::Chat];
+/// }
+/// ```
+///
+/// With the `derive` feature, `#[derive(ChannelId)]` generates both impls for fieldless enums,
+/// using the discriminants as channel ids.
+pub trait ChannelId: Copy + Into<u8> + 'static {
+    /// All the channels of the type.
+    const ALL: &'static [Self];
+
+    /// Returns whether all the channels of the type exist in the list.
+    fn configured_in(channels: &[ChannelConfig]) -> bool {
+        Self::ALL
+            .iter()
+            .all(|&channel| channels.iter().any(|config| config.channel_id == channel.into()))
+    }
 }
 
 /// Utility enumerator when using the default channels configuration.
 /// The default configuration has 3 channels: unreliable, reliable ordered, and reliable unordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DefaultChannel {
     Unreliable,
     ReliableOrdered,
@@ -54,27 +296,38 @@ impl From<DefaultChannel> for u8 {
     }
 }
 
+impl ChannelId for DefaultChannel {
+    const ALL: &'static [Self] = &[
+        DefaultChannel::Unreliable,
+        DefaultChannel::ReliableUnordered,
+        DefaultChannel::ReliableOrdered,
+    ];
+}
+
 impl DefaultChannel {
     pub fn config() -> Vec<ChannelConfig> {
         vec![
             ChannelConfig {
                 channel_id: 0,
-                max_memory_usage_bytes: 5 * 1024 * 1024,
+                max_memory_usage_bytes: DEFAULT_MAX_MEMORY_USAGE_BYTES,
                 send_type: SendType::Unreliable,
+                ..Default::default()
             },
             ChannelConfig {
                 channel_id: 1,
-                max_memory_usage_bytes: 5 * 1024 * 1024,
+                max_memory_usage_bytes: DEFAULT_MAX_MEMORY_USAGE_BYTES,
                 send_type: SendType::ReliableUnordered {
-                    resend_time: Duration::from_millis(300),
+                    resend_time: DEFAULT_RESEND_TIME,
                 },
+                ..Default::default()
             },
             ChannelConfig {
                 channel_id: 2,
-                max_memory_usage_bytes: 5 * 1024 * 1024,
+                max_memory_usage_bytes: DEFAULT_MAX_MEMORY_USAGE_BYTES,
                 send_type: SendType::ReliableOrdered {
-                    resend_time: Duration::from_millis(300),
+                    resend_time: DEFAULT_RESEND_TIME,
                 },
+                ..Default::default()
             },
         ]
     }
@@ -5,7 +5,7 @@ use std::{
 
 use bytes::Bytes;
 
-use super::SliceConstructor;
+use super::{FullQueuePolicy, MaxResends, ResendBackoff, SliceConstructor};
 use crate::{
     error::ChannelError,
     packet::{Packet, Slice, SLICE_SIZE},
@@ -16,6 +16,7 @@ enum UnackedMessage {
     Small {
         message: Bytes,
         last_sent: Option<Duration>,
+        resends: u32,
     },
     Sliced {
         message: Bytes,
@@ -24,6 +25,7 @@ enum UnackedMessage {
         next_slice_to_send: usize,
         acked: Vec<bool>,
         last_sent: Vec<Option<Duration>>,
+        resends: Vec<u32>,
     },
 }
 
@@ -33,8 +35,16 @@ pub struct SendChannelReliable {
     unacked_messages: BTreeMap<u64, UnackedMessage>,
     next_reliable_message_id: u64,
     resend_time: Duration,
+    resend_backoff: Option<ResendBackoff>,
+    max_resends: Option<MaxResends>,
     max_memory_usage_bytes: usize,
     memory_usage_bytes: usize,
+    full_queue_policy: FullQueuePolicy,
+    ordered: bool,
+    // Messages and slices sent again because they were not acked in time
+    resent: u64,
+    // Messages no longer sent because of the max resends, since the last call to `take_failed_deliveries`
+    failed_deliveries: usize,
 }
 
 #[derive(Debug)]
@@ -58,7 +68,7 @@ pub struct ReceiveChannelReliable {
 
 impl UnackedMessage {
     fn new_sliced(payload: Bytes) -> Self {
-        let num_slices = (payload.len() + SLICE_SIZE - 1) / SLICE_SIZE;
+        let num_slices = payload.len().div_ceil(SLICE_SIZE);
 
         Self::Sliced {
             message: payload,
@@ -67,31 +77,72 @@ impl UnackedMessage {
             next_slice_to_send: 0,
             acked: vec![false; num_slices],
             last_sent: vec![None; num_slices],
+            resends: vec![0; num_slices],
         }
     }
 }
 
 impl SendChannelReliable {
-    pub fn new(channel_id: u8, resend_time: Duration, max_memory_usage_bytes: usize) -> Self {
+    pub fn new(
+        channel_id: u8,
+        resend_time: Duration,
+        resend_backoff: Option<ResendBackoff>,
+        max_resends: Option<MaxResends>,
+        max_memory_usage_bytes: usize,
+        full_queue_policy: FullQueuePolicy,
+        ordered: bool,
+    ) -> Self {
         Self {
             channel_id,
             unacked_messages: BTreeMap::new(),
             next_reliable_message_id: 0,
             resend_time,
+            resend_backoff,
+            max_resends,
             max_memory_usage_bytes,
             memory_usage_bytes: 0,
+            full_queue_policy,
+            ordered,
+            resent: 0,
+            failed_deliveries: 0,
         }
     }
 
+    /// Number of messages and slices that were sent again.
+    pub fn resent(&self) -> u64 {
+        self.resent
+    }
+
+    /// Returns how many messages reached the max resends since the last call, and whether the connection should be disconnected.
+    pub fn take_failed_deliveries(&mut self) -> (usize, bool) {
+        let failed = std::mem::take(&mut self.failed_deliveries);
+        (failed, failed > 0 && self.max_resends.is_some_and(|max| max.disconnect))
+    }
+
     pub fn available_memory(&self) -> usize {
         self.max_memory_usage_bytes - self.memory_usage_bytes
     }
 
+    /// Number of messages not yet acknowledged.
+    pub fn queued_messages(&self) -> usize {
+        self.unacked_messages.len()
+    }
+
     pub fn can_send_message(&self, size_bytes: usize) -> bool {
         size_bytes + self.memory_usage_bytes <= self.max_memory_usage_bytes
     }
 
-    pub fn get_packets_to_send(&mut self, packet_sequence: &mut u64, available_bytes: &mut u64, current_time: Duration) -> Vec<Packet> {
+    pub fn is_ordered(&self) -> bool {
+        self.ordered
+    }
+
+    pub fn get_packets_to_send(
+        &mut self,
+        packet_sequence: &mut u64,
+        available_bytes: &mut u64,
+        max_small_messages_bytes: usize,
+        current_time: Duration,
+    ) -> Vec<Packet> {
         if self.unacked_messages.is_empty() {
             return vec![];
         }
@@ -101,25 +152,40 @@ impl SendChannelReliable {
         let mut small_messages: Vec<(u64, Bytes)> = vec![];
         let mut small_messages_bytes = 0;
 
+        let (resend_time, resend_backoff) = (self.resend_time, self.resend_backoff);
+        let wait = |resends: u32| resend_backoff.map_or(resend_time, |backoff| backoff.resend_time(resend_time, resends));
+        let max_resends = self.max_resends.map_or(u32::MAX, |max| max.resends);
+        let mut failed: Vec<u64> = vec![];
+
         'messages: for (&message_id, unacked_message) in self.unacked_messages.iter_mut() {
             match unacked_message {
-                UnackedMessage::Small { message, last_sent } => {
+                UnackedMessage::Small {
+                    message,
+                    last_sent,
+                    resends,
+                } => {
                     if *available_bytes < message.len() as u64 {
                         // Skip message, no bytes available to send this message
                         continue;
                     }
 
                     if let Some(last_sent) = last_sent {
-                        if current_time - *last_sent < self.resend_time {
+                        if current_time - *last_sent < wait(*resends) {
+                            continue;
+                        }
+                        if *resends >= max_resends {
+                            failed.push(message_id);
                             continue;
                         }
+                        self.resent += 1;
+                        *resends += 1;
                     }
 
                     *available_bytes -= message.len() as u64;
 
                     // Generate packet with small messages if you cannot fit
                     let serialized_size = message.len() + octets::varint_len(message.len() as u64) + octets::varint_len(message_id);
-                    if small_messages_bytes + serialized_size > SLICE_SIZE {
+                    if small_messages_bytes + serialized_size > max_small_messages_bytes && !small_messages.is_empty() {
                         packets.push(Packet::SmallReliable {
                             sequence: *packet_sequence,
                             channel_id: self.channel_id,
@@ -130,6 +196,7 @@ impl SendChannelReliable {
                     }
 
                     small_messages_bytes += serialized_size;
+                    // Only increases the reference count, resends never copy the message
                     small_messages.push((message_id, message.clone()));
                     *last_sent = Some(current_time);
 
@@ -141,6 +208,7 @@ impl SendChannelReliable {
                     acked,
                     last_sent,
                     next_slice_to_send,
+                    resends,
                     ..
                 } => {
                     let start_index = *next_slice_to_send;
@@ -156,9 +224,15 @@ impl SendChannelReliable {
                         }
 
                         if let Some(last_sent) = last_sent[i] {
-                            if current_time - last_sent < self.resend_time {
+                            if current_time - last_sent < wait(resends[i]) {
                                 continue;
                             }
+                            if resends[i] >= max_resends {
+                                failed.push(message_id);
+                                continue 'messages;
+                            }
+                            self.resent += 1;
+                            resends[i] += 1;
                         }
 
                         let start = i * SLICE_SIZE;
@@ -188,6 +262,15 @@ impl SendChannelReliable {
             }
         }
 
+        for message_id in failed {
+            if let Some(UnackedMessage::Small { message, .. } | UnackedMessage::Sliced { message, .. }) =
+                self.unacked_messages.remove(&message_id)
+            {
+                self.memory_usage_bytes -= message.len();
+                self.failed_deliveries += 1;
+            }
+        }
+
         // Generate final packet for remaining small messages
         if !small_messages.is_empty() {
             packets.push(Packet::SmallReliable {
@@ -201,8 +284,16 @@ impl SendChannelReliable {
         packets
     }
 
-    pub fn send_message(&mut self, message: Bytes) -> Result<(), ChannelError> {
+    /// Returns whether the message was queued, it's dropped when the channel is full with [`FullQueuePolicy::DropNewest`].
+    pub fn send_message(&mut self, message: Bytes) -> Result<bool, ChannelError> {
         if self.memory_usage_bytes + message.len() > self.max_memory_usage_bytes {
+            // The new message doesn't have an id yet, so the receiver never waits for it
+            if self.full_queue_policy == FullQueuePolicy::DropNewest {
+                log::debug!("dropped new message because channel {} is full", self.channel_id);
+                return Ok(false);
+            }
+            // Dropping the oldest messages is rejected by the channel config validation:
+            // they were already sent, and the receiver would wait for them forever.
             return Err(ChannelError::ReliableChannelMaxMemoryReached);
         }
 
@@ -210,29 +301,46 @@ impl SendChannelReliable {
         let unacked_message = if message.len() > SLICE_SIZE {
             UnackedMessage::new_sliced(message)
         } else {
-            UnackedMessage::Small { message, last_sent: None }
+            UnackedMessage::Small {
+                message,
+                last_sent: None,
+                resends: 0,
+            }
         };
 
         self.unacked_messages.insert(self.next_reliable_message_id, unacked_message);
         self.next_reliable_message_id += 1;
 
-        Ok(())
+        Ok(true)
     }
 
-    pub fn process_message_ack(&mut self, message_id: u64) {
-        if self.unacked_messages.contains_key(&message_id) {
-            let unacked_message = self.unacked_messages.remove(&message_id).unwrap();
-            let UnackedMessage::Small { message: payload, .. } = unacked_message else {
-                unreachable!("called ack on small message but found sliced");
-            };
-            self.memory_usage_bytes -= payload.len();
+    /// Returns the message if it was acknowledged for the first time.
+    pub fn process_message_ack(&mut self, message_id: u64) -> Option<Bytes> {
+        let unacked_message = self.unacked_messages.remove(&message_id)?;
+        let UnackedMessage::Small { message: payload, .. } = unacked_message else {
+            unreachable!("called ack on small message but found sliced");
+        };
+        self.memory_usage_bytes -= payload.len();
+        Some(payload)
+    }
+
+    /// Updates when a message was sent, for packets released later by the pacing.
+    pub fn message_sent(&mut self, message_id: u64, sent_at: Duration) {
+        if let Some(UnackedMessage::Small { last_sent, .. }) = self.unacked_messages.get_mut(&message_id) {
+            *last_sent = Some(sent_at);
         }
     }
 
-    pub fn process_slice_message_ack(&mut self, message_id: u64, slice_index: usize) {
-        let Some(unacked_message) = self.unacked_messages.get_mut(&message_id) else {
-            return;
-        };
+    /// Updates when a slice was sent, for packets released later by the pacing.
+    pub fn slice_sent(&mut self, message_id: u64, slice_index: usize, sent_at: Duration) {
+        if let Some(UnackedMessage::Sliced { last_sent, .. }) = self.unacked_messages.get_mut(&message_id) {
+            last_sent[slice_index] = Some(sent_at);
+        }
+    }
+
+    /// Returns the message when all its slices are acknowledged.
+    pub fn process_slice_message_ack(&mut self, message_id: u64, slice_index: usize) -> Option<Bytes> {
+        let unacked_message = self.unacked_messages.get_mut(&message_id)?;
 
         let UnackedMessage::Sliced {
             message,
@@ -246,7 +354,7 @@ impl SendChannelReliable {
         };
 
         if acked[slice_index] {
-            return;
+            return None;
         }
 
         acked[slice_index] = true;
@@ -254,8 +362,13 @@ impl SendChannelReliable {
 
         if *num_acked_slices == *num_slices {
             self.memory_usage_bytes -= message.len();
-            self.unacked_messages.remove(&message_id);
+            let Some(UnackedMessage::Sliced { message, .. }) = self.unacked_messages.remove(&message_id) else {
+                unreachable!();
+            };
+            return Some(message);
         }
+
+        None
     }
 }
 
@@ -278,6 +391,10 @@ impl ReceiveChannelReliable {
         }
     }
 
+    pub fn is_ordered(&self) -> bool {
+        matches!(self.reliable_order, ReliableOrder::Ordered)
+    }
+
     pub fn process_message(&mut self, message: Bytes, message_id: u64) -> Result<(), ChannelError> {
         if message_id < self.oldest_pending_message_id {
             // Discard old message already received
@@ -347,21 +464,30 @@ impl ReceiveChannelReliable {
         Ok(())
     }
 
+    /// Number of messages that can be received right now.
+    pub fn available_messages(&self) -> usize {
+        match self.reliable_order {
+            ReliableOrder::Ordered => self
+                .messages
+                .keys()
+                .zip(self.oldest_pending_message_id..)
+                .take_while(|(&message_id, expected)| message_id == *expected)
+                .count(),
+            ReliableOrder::Unordered { .. } => self.messages.len(),
+        }
+    }
+
     pub fn receive_message(&mut self) -> Option<Bytes> {
         match &mut self.reliable_order {
             ReliableOrder::Ordered => {
-                let Some(message) = self.messages.remove(&self.oldest_pending_message_id) else {
-                    return None;
-                };
+                let message = self.messages.remove(&self.oldest_pending_message_id)?;
 
                 self.oldest_pending_message_id += 1;
                 self.memory_usage_bytes -= message.len();
                 Some(message)
             }
             ReliableOrder::Unordered { received_messages, .. } => {
-                let Some((message_id, message)) = self.messages.pop_first() else {
-                    return None;
-                };
+                let (message_id, message) = self.messages.pop_first()?;
 
                 if self.oldest_pending_message_id == message_id {
                     // Remove all next items that could have been received out of order,
@@ -393,7 +519,7 @@ mod tests {
         let mut current_time: Duration = Duration::ZERO;
         let resend_time = Duration::from_millis(100);
         let mut recv = ReceiveChannelReliable::new(max_memory, true);
-        let mut send = SendChannelReliable::new(0, resend_time, max_memory);
+        let mut send = SendChannelReliable::new(0, resend_time, None, None, max_memory, FullQueuePolicy::Error, true);
 
         let message1 = vec![1, 2, 3];
         let message2 = vec![3, 4, 5];
@@ -401,7 +527,7 @@ mod tests {
         send.send_message(message1.clone().into()).unwrap();
         send.send_message(message2.clone().into()).unwrap();
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
         for packet in packets {
             let Packet::SmallReliable {
                 sequence: 0,
@@ -423,12 +549,12 @@ mod tests {
         assert_eq!(message2, new_message2);
 
         // Should not resend anything
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
         assert!(packets.is_empty());
 
         current_time += resend_time;
         // Should resend now
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
         assert_eq!(packets.len(), 1);
 
         // Should not resend after ack
@@ -436,7 +562,7 @@ mod tests {
         send.process_message_ack(0);
         send.process_message_ack(1);
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
         assert!(packets.is_empty());
     }
 
@@ -448,7 +574,7 @@ mod tests {
         let mut current_time: Duration = Duration::ZERO;
         let resend_time = Duration::from_millis(100);
         let mut recv = ReceiveChannelReliable::new(max_memory, false);
-        let mut send = SendChannelReliable::new(0, resend_time, max_memory);
+        let mut send = SendChannelReliable::new(0, resend_time, None, None, max_memory, FullQueuePolicy::Error, false);
 
         let message1 = vec![1, 2, 3];
         let message2 = vec![3, 4, 5];
@@ -458,7 +584,7 @@ mod tests {
         send.send_message(message2.clone().into()).unwrap();
         send.send_message(message3.clone().into()).unwrap();
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
         assert_eq!(packets.len(), 1);
         let Packet::SmallReliable { messages, .. } = &packets[0] else {
             unreachable!();
@@ -492,12 +618,12 @@ mod tests {
         }
 
         // Should not resend anything
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
         assert!(packets.is_empty());
 
         current_time += resend_time;
         // Should resend now
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
         assert_eq!(packets.len(), 1);
 
         // Should not resend after ack
@@ -506,7 +632,7 @@ mod tests {
         send.process_message_ack(1);
         send.process_message_ack(2);
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
         assert!(packets.is_empty());
     }
 
@@ -518,13 +644,13 @@ mod tests {
         let mut current_time: Duration = Duration::ZERO;
         let resend_time = Duration::from_millis(100);
         let mut recv = ReceiveChannelReliable::new(max_memory, true);
-        let mut send = SendChannelReliable::new(0, resend_time, max_memory);
+        let mut send = SendChannelReliable::new(0, resend_time, None, None, max_memory, FullQueuePolicy::Error, true);
 
         let message = vec![5; SLICE_SIZE * 3];
 
         send.send_message(message.clone().into()).unwrap();
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
         for packet in packets {
             let Packet::ReliableSlice { channel_id: 0, slice, .. } = packet else {
                 unreachable!();
@@ -536,12 +662,12 @@ mod tests {
         assert_eq!(message, new_message);
 
         // Should not resend anything
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
         assert!(packets.is_empty());
 
         current_time += resend_time;
         // Should resend now
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
         assert_eq!(packets.len(), 3);
 
         // Should not resend after ack
@@ -550,7 +676,7 @@ mod tests {
         send.process_slice_message_ack(0, 1);
         send.process_slice_message_ack(0, 2);
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
         assert!(packets.is_empty());
     }
 
@@ -561,14 +687,14 @@ mod tests {
         let current_time: Duration = Duration::ZERO;
         let resend_time = Duration::from_millis(100);
         let mut recv = ReceiveChannelReliable::new(99, true);
-        let mut send = SendChannelReliable::new(0, resend_time, 101);
+        let mut send = SendChannelReliable::new(0, resend_time, None, None, 101, FullQueuePolicy::Error, true);
 
         let message = vec![5; 100];
 
         // Can send one message without reaching memory limit
         send.send_message(message.clone().into()).unwrap();
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
         for packet in packets {
             let Packet::SmallReliable {
                 sequence: 0,
@@ -590,6 +716,103 @@ mod tests {
             unreachable!()
         };
         assert_eq!(send_err, ChannelError::ReliableChannelMaxMemoryReached);
+
+        // The new message is dropped, the queued ones are still sent
+        let mut send = SendChannelReliable::new(0, resend_time, None, None, 101, FullQueuePolicy::DropNewest, true);
+        assert_eq!(send.send_message(vec![1; 100].into()), Ok(true));
+        assert_eq!(send.send_message(vec![2; 100].into()), Ok(false));
+        assert_eq!(send.queued_messages(), 1);
+    }
+
+    #[test]
+    fn resends_share_the_message() {
+        let mut sequence: u64 = 0;
+        let mut current_time: Duration = Duration::ZERO;
+        let resend_time = Duration::from_millis(100);
+        let mut send = SendChannelReliable::new(0, resend_time, None, None, usize::MAX, FullQueuePolicy::Error, true);
+
+        let small: Bytes = vec![1u8; 100].into();
+        let sliced: Bytes = vec![2u8; SLICE_SIZE * 2].into();
+        send.send_message(small.clone()).unwrap();
+        send.send_message(sliced.clone()).unwrap();
+
+        for _ in 0..2 {
+            let mut available_bytes = u64::MAX;
+            let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
+            assert_eq!(packets.len(), 3);
+            for packet in packets {
+                match packet {
+                    Packet::SmallReliable { messages, .. } => assert_eq!(messages[0].1.as_ptr(), small.as_ptr()),
+                    Packet::ReliableSlice { slice, .. } => {
+                        let offset = slice.slice_index * SLICE_SIZE;
+                        assert_eq!(slice.payload.as_ptr(), sliced[offset..].as_ptr());
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            current_time += resend_time;
+        }
+        assert_eq!(send.resent(), 3);
+    }
+
+    #[test]
+    fn resend_backoff() {
+        let resend_time = Duration::from_millis(100);
+        let backoff = ResendBackoff {
+            multiplier: 2.0,
+            max_resend_time: Duration::from_millis(300),
+        };
+        let mut send = SendChannelReliable::new(0, resend_time, Some(backoff), None, usize::MAX, FullQueuePolicy::Error, true);
+        send.send_message(vec![1u8; 100].into()).unwrap();
+        send.send_message(vec![2u8; SLICE_SIZE * 2].into()).unwrap();
+
+        // Times at which the small message and both slices are sent, in ms
+        let mut sent_at = vec![];
+        let mut sequence = 0;
+        for ms in (0..=1000).step_by(10) {
+            let mut available_bytes = u64::MAX;
+            let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, Duration::from_millis(ms));
+            if !packets.is_empty() {
+                assert_eq!(packets.len(), 3);
+                sent_at.push(ms);
+            }
+        }
+        assert_eq!(sent_at, vec![0, 100, 300, 600, 900]);
+        assert_eq!(send.resent(), 12);
+
+        assert_eq!(backoff.resend_time(resend_time, u32::MAX), Duration::from_millis(300));
+    }
+
+    #[test]
+    fn max_resends() {
+        let mut sequence: u64 = 0;
+        let mut current_time: Duration = Duration::ZERO;
+        let resend_time = Duration::from_millis(100);
+        let max_resends = MaxResends {
+            resends: 2,
+            disconnect: true,
+        };
+        let mut send = SendChannelReliable::new(0, resend_time, None, Some(max_resends), usize::MAX, FullQueuePolicy::Error, true);
+        send.send_message(vec![1u8; 100].into()).unwrap();
+        send.send_message(vec![2u8; SLICE_SIZE * 2].into()).unwrap();
+
+        for _ in 0..3 {
+            let mut available_bytes = u64::MAX;
+            let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
+            assert_eq!(packets.len(), 3);
+            current_time += resend_time;
+        }
+        assert_eq!(send.take_failed_deliveries(), (0, false));
+
+        // A message acked in time is not affected
+        send.process_message_ack(0);
+        let mut available_bytes = u64::MAX;
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
+        assert!(packets.is_empty());
+        assert_eq!(send.take_failed_deliveries(), (1, true));
+        assert_eq!(send.take_failed_deliveries(), (0, false));
+        assert_eq!(send.queued_messages(), 0);
+        assert_eq!(send.available_memory(), usize::MAX);
     }
 
     #[test]
@@ -597,7 +820,7 @@ mod tests {
         let mut sequence: u64 = 0;
         let current_time: Duration = Duration::ZERO;
         let resend_time = Duration::from_millis(100);
-        let mut send = SendChannelReliable::new(0, resend_time, usize::MAX);
+        let mut send = SendChannelReliable::new(0, resend_time, None, None, usize::MAX, FullQueuePolicy::Error, true);
 
         let message: Bytes = vec![0u8; 100].into();
         send.send_message(message.clone()).unwrap();
@@ -605,22 +828,22 @@ mod tests {
 
         // No available bytes
         let mut available_bytes: u64 = 50;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
         assert_eq!(packets.len(), 0);
 
         // Bytes for 1 message
         let mut available_bytes: u64 = 100;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
         assert_eq!(packets.len(), 1);
 
         // Bytes for 1 message
         let mut available_bytes: u64 = 100;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
         assert_eq!(packets.len(), 1);
 
         // No more messages to send
         let mut available_bytes: u64 = u64::MAX;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
         assert_eq!(packets.len(), 0);
     }
 
@@ -630,7 +853,7 @@ mod tests {
         let current_time: Duration = Duration::ZERO;
         let mut available_bytes = u64::MAX;
         let resend_time = Duration::from_millis(100);
-        let mut send = SendChannelReliable::new(0, resend_time, usize::MAX);
+        let mut send = SendChannelReliable::new(0, resend_time, None, None, usize::MAX, FullQueuePolicy::Error, true);
 
         // 4 bytes
         let message: Bytes = vec![0, 1, 2, 3].into();
@@ -640,7 +863,7 @@ mod tests {
             send.send_message(message.clone()).unwrap();
         }
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE, current_time);
         assert_eq!(packets.len(), 2);
         let mut buffer = [0u8; 1400];
         for packet in packets {
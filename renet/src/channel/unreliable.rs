@@ -6,7 +6,7 @@ use std::{
 use bytes::Bytes;
 
 use crate::{
-    channel::SliceConstructor,
+    channel::{FullQueuePolicy, SliceConstructor},
     error::ChannelError,
     packet::{Packet, Slice, SLICE_SIZE},
 };
@@ -18,6 +18,7 @@ pub struct SendChannelUnreliable {
     sliced_message_id: u64,
     max_memory_usage_bytes: usize,
     memory_usage_bytes: usize,
+    full_queue_policy: FullQueuePolicy,
 }
 
 #[derive(Debug)]
@@ -31,13 +32,14 @@ pub struct ReceiveChannelUnreliable {
 }
 
 impl SendChannelUnreliable {
-    pub fn new(channel_id: u8, max_memory_usage_bytes: usize) -> Self {
+    pub fn new(channel_id: u8, max_memory_usage_bytes: usize, full_queue_policy: FullQueuePolicy) -> Self {
         Self {
             channel_id,
             unreliable_messages: VecDeque::new(),
             sliced_message_id: 0,
             max_memory_usage_bytes,
             memory_usage_bytes: 0,
+            full_queue_policy,
         }
     }
 
@@ -49,7 +51,16 @@ impl SendChannelUnreliable {
         self.max_memory_usage_bytes - self.memory_usage_bytes
     }
 
-    pub fn get_packets_to_send(&mut self, packet_sequence: &mut u64, available_bytes: &mut u64) -> Vec<Packet> {
+    pub fn queued_messages(&self) -> usize {
+        self.unreliable_messages.len()
+    }
+
+    pub fn get_packets_to_send(
+        &mut self,
+        packet_sequence: &mut u64,
+        available_bytes: &mut u64,
+        max_small_messages_bytes: usize,
+    ) -> Vec<Packet> {
         let mut packets: Vec<Packet> = vec![];
         let mut small_messages: Vec<Bytes> = vec![];
         let mut small_messages_bytes = 0;
@@ -63,7 +74,7 @@ impl SendChannelUnreliable {
 
             *available_bytes -= message.len() as u64;
             if message.len() > SLICE_SIZE {
-                let num_slices = (message.len() + SLICE_SIZE - 1) / SLICE_SIZE;
+                let num_slices = message.len().div_ceil(SLICE_SIZE);
 
                 for slice_index in 0..num_slices {
                     let start = slice_index * SLICE_SIZE;
@@ -88,7 +99,7 @@ impl SendChannelUnreliable {
                 self.sliced_message_id += 1;
             } else {
                 let serialized_size = message.len() + octets::varint_len(message.len() as u64);
-                if small_messages_bytes + serialized_size > SLICE_SIZE {
+                if small_messages_bytes + serialized_size > max_small_messages_bytes && !small_messages.is_empty() {
                     packets.push(Packet::SmallUnreliable {
                         sequence: *packet_sequence,
                         channel_id: self.channel_id,
@@ -116,13 +127,27 @@ impl SendChannelUnreliable {
         packets
     }
 
-    pub fn send_message(&mut self, message: Bytes) {
+    /// Returns whether the message was queued, it's dropped when the channel is full with [`FullQueuePolicy::DropNewest`].
+    pub fn send_message(&mut self, message: Bytes) -> Result<bool, ChannelError> {
         if self.memory_usage_bytes + message.len() > self.max_memory_usage_bytes {
-            log::warn!(
-                "dropped unreliable message sent because channel {} is memory limited",
-                self.channel_id
-            );
-            return;
+            match self.full_queue_policy {
+                FullQueuePolicy::Error => return Err(ChannelError::UnreliableChannelMaxMemoryReached),
+                // A message that can't fit even in an empty channel is always an error
+                FullQueuePolicy::DropOldest if message.len() > self.max_memory_usage_bytes => {
+                    return Err(ChannelError::UnreliableChannelMaxMemoryReached)
+                }
+                FullQueuePolicy::DropNewest => {
+                    log::debug!("dropped new message because channel {} is full", self.channel_id);
+                    return Ok(false);
+                }
+                FullQueuePolicy::DropOldest => {
+                    while self.memory_usage_bytes + message.len() > self.max_memory_usage_bytes {
+                        let oldest = self.unreliable_messages.pop_front().expect("queued messages use the memory");
+                        self.memory_usage_bytes -= oldest.len();
+                    }
+                    log::debug!("dropped oldest messages because channel {} is full", self.channel_id);
+                }
+            }
         }
 
         let num_fragments = message.len() / SLICE_SIZE;
@@ -134,6 +159,8 @@ impl SendChannelUnreliable {
 
         self.memory_usage_bytes += message.len();
         self.unreliable_messages.push_back(message);
+
+        Ok(true)
     }
 }
 
@@ -214,6 +241,10 @@ impl ReceiveChannelUnreliable {
         }
     }
 
+    pub fn available_messages(&self) -> usize {
+        self.messages.len()
+    }
+
     pub fn receive_message(&mut self) -> Option<Bytes> {
         if let Some(message) = self.messages.pop_front() {
             self.memory_usage_bytes -= message.len();
@@ -236,15 +267,15 @@ mod tests {
         let mut available_bytes = u64::MAX;
         let mut sequence: u64 = 0;
         let mut recv = ReceiveChannelUnreliable::new(0, max_memory);
-        let mut send = SendChannelUnreliable::new(0, max_memory);
+        let mut send = SendChannelUnreliable::new(0, max_memory, FullQueuePolicy::Error);
 
         let message1 = vec![1, 2, 3];
         let message2 = vec![3, 4, 5];
 
-        send.send_message(message1.clone().into());
-        send.send_message(message2.clone().into());
+        send.send_message(message1.clone().into()).unwrap();
+        send.send_message(message2.clone().into()).unwrap();
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE);
         for packet in packets {
             let Packet::SmallUnreliable { messages, .. } = packet else {
                 unreachable!();
@@ -261,7 +292,7 @@ mod tests {
         assert_eq!(message1, new_message1);
         assert_eq!(message2, new_message2);
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE);
         assert!(packets.is_empty());
     }
 
@@ -272,13 +303,13 @@ mod tests {
         let mut sequence: u64 = 0;
         let current_time = Duration::ZERO;
         let mut recv = ReceiveChannelUnreliable::new(0, max_memory);
-        let mut send = SendChannelUnreliable::new(0, max_memory);
+        let mut send = SendChannelUnreliable::new(0, max_memory, FullQueuePolicy::Error);
 
         let message = vec![5; SLICE_SIZE * 3];
 
-        send.send_message(message.clone().into());
+        send.send_message(message.clone().into()).unwrap();
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE);
         for packet in packets {
             let Packet::UnreliableSlice { slice, .. } = packet else {
                 unreachable!();
@@ -291,7 +322,7 @@ mod tests {
 
         assert_eq!(message, new_message);
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE);
         assert!(packets.is_empty());
     }
 
@@ -299,21 +330,24 @@ mod tests {
     fn max_memory() {
         let mut sequence: u64 = 0;
         let mut available_bytes = u64::MAX;
-        let mut recv = ReceiveChannelUnreliable::new(0, 50);
-        let mut send = SendChannelUnreliable::new(0, 40);
+        let mut recv = ReceiveChannelUnreliable::new(0, 49);
+        let mut send = SendChannelUnreliable::new(0, 60, FullQueuePolicy::Error);
 
         let message = vec![5; 50];
 
-        send.send_message(message.clone().into());
-        send.send_message(message.into());
+        send.send_message(message.clone().into()).unwrap();
+        assert_eq!(
+            send.send_message(message.into()),
+            Err(ChannelError::UnreliableChannelMaxMemoryReached)
+        );
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE);
         for packet in packets {
             let Packet::SmallUnreliable { messages, .. } = packet else {
                 unreachable!();
             };
 
-            // Second message was dropped
+            // Second message was not sent
             assert_eq!(messages.len(), 1);
             for message in messages {
                 recv.process_message(message);
@@ -324,35 +358,77 @@ mod tests {
         assert!(recv.receive_message().is_none());
     }
 
+    #[test]
+    fn full_queue_policy() {
+        let queued = |send: &mut SendChannelUnreliable| -> Vec<u8> {
+            let (mut sequence, mut available_bytes) = (0, u64::MAX);
+            let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE);
+            packets
+                .into_iter()
+                .flat_map(|packet| match packet {
+                    Packet::SmallUnreliable { messages, .. } => messages,
+                    _ => unreachable!(),
+                })
+                .map(|message| message[0])
+                .collect()
+        };
+
+        let mut send = SendChannelUnreliable::new(0, 30, FullQueuePolicy::DropOldest);
+        for i in 0..5 {
+            send.send_message(vec![i; 10].into()).unwrap();
+        }
+        assert_eq!(queued(&mut send), vec![2, 3, 4]);
+        send.send_message(vec![0; 10].into()).unwrap();
+        send.send_message(vec![1; 25].into()).unwrap();
+        assert_eq!(queued(&mut send), vec![1]);
+        assert!(send.send_message(vec![0; 31].into()).is_err());
+
+        let mut send = SendChannelUnreliable::new(0, 30, FullQueuePolicy::DropNewest);
+        for i in 0..5 {
+            assert_eq!(send.send_message(vec![i; 10].into()), Ok(i < 3));
+        }
+        assert_eq!(queued(&mut send), vec![0, 1, 2]);
+
+        let mut send = SendChannelUnreliable::new(0, 30, FullQueuePolicy::Error);
+        for i in 0..3 {
+            send.send_message(vec![i; 10].into()).unwrap();
+        }
+        assert_eq!(
+            send.send_message(vec![3; 10].into()),
+            Err(ChannelError::UnreliableChannelMaxMemoryReached)
+        );
+        assert_eq!(queued(&mut send), vec![0, 1, 2]);
+    }
+
     #[test]
     fn available_bytes() {
         let mut sequence: u64 = 0;
-        let mut send = SendChannelUnreliable::new(0, usize::MAX);
+        let mut send = SendChannelUnreliable::new(0, usize::MAX, FullQueuePolicy::Error);
 
         let message: Bytes = vec![0u8; 100].into();
-        send.send_message(message.clone());
+        send.send_message(message.clone()).unwrap();
 
         // No available bytes
         let mut available_bytes: u64 = 50;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE);
         assert_eq!(packets.len(), 0);
 
         // Available space but message was dropped
         let mut available_bytes: u64 = u64::MAX;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE);
         assert_eq!(packets.len(), 0);
 
-        send.send_message(message.clone());
-        send.send_message(message);
+        send.send_message(message.clone()).unwrap();
+        send.send_message(message).unwrap();
 
         // Space for 1 message
         let mut available_bytes: u64 = 100;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE);
         assert_eq!(packets.len(), 1);
 
         // Second message was dropped
         let mut available_bytes: u64 = u64::MAX;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE);
         assert_eq!(packets.len(), 0);
     }
 
@@ -360,17 +436,17 @@ mod tests {
     fn small_packet_max_size() {
         let mut sequence: u64 = 0;
         let mut available_bytes = u64::MAX;
-        let mut send = SendChannelUnreliable::new(0, usize::MAX);
+        let mut send = SendChannelUnreliable::new(0, usize::MAX, FullQueuePolicy::Error);
 
         // 4 bytes
         let message: Bytes = vec![0, 1, 2, 3].into();
 
         // (4 + 1) * 400 = 2000 = 2 packets
         for _ in 0..400 {
-            send.send_message(message.clone());
+            send.send_message(message.clone()).unwrap();
         }
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, SLICE_SIZE);
         assert_eq!(packets.len(), 2);
         let mut buffer = [0u8; 1400];
         for packet in packets {
@@ -0,0 +1,122 @@
+use crate::packet::{DefaultPacketSerializer, Packet, PacketSerializer, SerializationError};
+
+const CHECKSUM_BYTES: usize = 4;
+
+/// Prefixes packets with a CRC32 of their content, packets with an invalid checksum are dropped.
+///
+/// Transports with encryption like netcode already authenticate the packets, this is for
+/// transports that don't, so corrupted datagrams are detected before they are parsed.
+///
+/// # Usage
+/// ```
+/// # use renet::{ChecksumPacketSerializer, ConnectionConfig};
+/// # use std::sync::Arc;
+/// let config = ConnectionConfig {
+///     packet_serializer: Arc::new(ChecksumPacketSerializer::new()),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct ChecksumPacketSerializer<S = DefaultPacketSerializer> {
+    inner: S,
+}
+
+impl ChecksumPacketSerializer {
+    /// Adds checksums to packets in the default format.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<S: PacketSerializer> ChecksumPacketSerializer<S> {
+    pub fn with_serializer(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S: PacketSerializer> PacketSerializer for ChecksumPacketSerializer<S> {
+    fn serialize(&self, packet: &Packet, buffer: &mut [u8]) -> Result<usize, SerializationError> {
+        if buffer.len() < CHECKSUM_BYTES {
+            return Err(SerializationError::BufferTooShort);
+        }
+
+        let (checksum, content) = buffer.split_at_mut(CHECKSUM_BYTES);
+        let len = self.inner.serialize(packet, content)?;
+        checksum.copy_from_slice(&crc32(&content[..len]).to_be_bytes());
+
+        Ok(len + CHECKSUM_BYTES)
+    }
+
+    fn deserialize(&self, buffer: &[u8]) -> Result<Packet, SerializationError> {
+        if buffer.len() < CHECKSUM_BYTES {
+            return Err(SerializationError::BufferTooShort);
+        }
+
+        let (checksum, content) = buffer.split_at(CHECKSUM_BYTES);
+        if checksum != crc32(content).to_be_bytes() {
+            return Err(SerializationError::InvalidChecksum);
+        }
+
+        self.inner.deserialize(content)
+    }
+
+    fn overhead(&self) -> usize {
+        CHECKSUM_BYTES + self.inner.overhead()
+    }
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// CRC32 (IEEE 802.3) of the data.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc = CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(&[]), 0);
+    }
+
+    #[test]
+    fn corrupted_packet() {
+        let serializer = ChecksumPacketSerializer::new();
+        let packet = Packet::SmallUnreliable {
+            sequence: 3,
+            channel_id: 0,
+            messages: vec![vec![1, 2, 3].into()],
+        };
+
+        let mut buffer = [0u8; 64];
+        let len = serializer.serialize(&packet, &mut buffer).unwrap();
+        assert_eq!(serializer.deserialize(&buffer[..len]).unwrap(), packet);
+
+        buffer[len - 1] ^= 0x10;
+        assert_eq!(serializer.deserialize(&buffer[..len]), Err(SerializationError::InvalidChecksum));
+        assert_eq!(serializer.deserialize(&buffer[..2]), Err(SerializationError::BufferTooShort));
+    }
+}
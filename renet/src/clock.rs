@@ -0,0 +1,102 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+// std Instant panics on wasm32-unknown-unknown, web_time reads the time from the browser
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// Source of the current time, for code that computes the delta passed to `update` itself.
+///
+/// Connections and channels never read the time, they only advance with the durations given to
+/// [`RenetClient::update`](crate::RenetClient::update) and [`RenetServer::update`](crate::RenetServer::update).
+/// Helpers that measure the elapsed time, like the async transports, read it from a clock,
+/// so tests and lockstep or replay systems can drive them with a [`ManualClock`].
+pub trait Clock: Debug + Send + Sync + 'static {
+    /// Time elapsed since an arbitrary starting point, must never decrease.
+    fn now(&self) -> Duration;
+}
+
+/// Clock following the wall-clock time, starting from when it was created.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self { start: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Clock that only moves when advanced, clones share the same time.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use renet::{Clock, ManualClock};
+/// let clock = ManualClock::default();
+/// let handle = clock.clone();
+/// handle.advance(Duration::from_millis(16));
+/// assert_eq!(clock.now(), Duration::from_millis(16));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl ManualClock {
+    pub fn new(start: Duration) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    pub fn advance(&self, delta: Duration) {
+        *self.now.lock().unwrap() += delta;
+    }
+
+    /// Sets the current time, ignored if it's before the current one.
+    pub fn set(&self, time: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now = time.max(*now);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_clock() {
+        let clock = ManualClock::new(Duration::from_secs(1));
+        let shared: Arc<dyn Clock> = Arc::new(clock.clone());
+
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(shared.now(), Duration::from_millis(1500));
+
+        clock.set(Duration::from_secs(1));
+        assert_eq!(shared.now(), Duration::from_millis(1500));
+        clock.set(Duration::from_secs(3));
+        assert_eq!(shared.now(), Duration::from_secs(3));
+    }
+}
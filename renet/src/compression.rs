@@ -0,0 +1,245 @@
+use crate::packet::{DefaultPacketSerializer, Packet, PacketSerializer, SerializationError};
+use crate::remote_connection::MAX_PACKET_BYTES;
+
+// First byte of the packets, followed by the packet of the inner serializer
+const UNCOMPRESSED: u8 = 0;
+const COMPRESSED: u8 = 1;
+
+/// Algorithm used by the [`CompressedPacketSerializer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionAlgorithm {
+    /// LZ4 block compression, requires the `lz4` feature.
+    #[cfg(feature = "lz4")]
+    Lz4,
+    /// Snappy raw compression, requires the `snappy` feature.
+    #[cfg(feature = "snappy")]
+    Snappy,
+}
+
+/// Compresses packets bigger than a threshold, when the compressed packet is smaller.
+///
+/// Packets start with a byte telling if the rest is compressed, then the whole packet of the inner serializer,
+/// so it can wrap any serializer, and be wrapped by any, like the [`ChecksumPacketSerializer`](crate::ChecksumPacketSerializer).
+/// Mtu probes are never compressed.
+///
+/// # Usage
+/// ```
+/// # use renet::{CompressedPacketSerializer, CompressionAlgorithm, ConnectionConfig};
+/// # use std::sync::Arc;
+/// # #[cfg(feature = "lz4")]
+/// let config = ConnectionConfig {
+///     packet_serializer: Arc::new(CompressedPacketSerializer::new(CompressionAlgorithm::Lz4).with_threshold(256)),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct CompressedPacketSerializer<S = DefaultPacketSerializer> {
+    inner: S,
+    algorithm: CompressionAlgorithm,
+    threshold: usize,
+}
+
+impl CompressedPacketSerializer {
+    /// Compresses packets in the default format with a threshold of 128 bytes.
+    pub fn new(algorithm: CompressionAlgorithm) -> Self {
+        Self::with_serializer(DefaultPacketSerializer, algorithm)
+    }
+}
+
+impl<S: PacketSerializer> CompressedPacketSerializer<S> {
+    pub fn with_serializer(inner: S, algorithm: CompressionAlgorithm) -> Self {
+        Self {
+            inner,
+            algorithm,
+            threshold: 128,
+        }
+    }
+
+    /// Only packets bigger than the threshold, in bytes, are compressed.
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    // Compresses the input into the output, fails if it doesn't fit.
+    fn compress(&self, input: &[u8], output: &mut [u8]) -> Option<usize> {
+        // The encoders require space for the worst case
+        let mut compressed = [0u8; MAX_PACKET_BYTES * 2];
+        let len = match self.algorithm {
+            #[cfg(feature = "lz4")]
+            CompressionAlgorithm::Lz4 => lz4_flex::block::compress_into(input, &mut compressed).ok()?,
+            #[cfg(feature = "snappy")]
+            CompressionAlgorithm::Snappy => snap::raw::Encoder::new().compress(input, &mut compressed).ok()?,
+        };
+        output.get_mut(..len)?.copy_from_slice(&compressed[..len]);
+        Some(len)
+    }
+
+    fn decompress(&self, input: &[u8], output: &mut [u8]) -> Option<usize> {
+        match self.algorithm {
+            #[cfg(feature = "lz4")]
+            CompressionAlgorithm::Lz4 => lz4_flex::block::decompress_into(input, output).ok(),
+            #[cfg(feature = "snappy")]
+            CompressionAlgorithm::Snappy => {
+                if snap::raw::decompress_len(input).ok()? > output.len() {
+                    return None;
+                }
+                snap::raw::Decoder::new().decompress(input, output).ok()
+            }
+        }
+    }
+}
+
+impl<S: PacketSerializer> PacketSerializer for CompressedPacketSerializer<S> {
+    fn serialize(&self, packet: &Packet, buffer: &mut [u8]) -> Result<usize, SerializationError> {
+        if buffer.is_empty() {
+            return Err(SerializationError::BufferTooShort);
+        }
+
+        let mut uncompressed = [0u8; MAX_PACKET_BYTES];
+        let uncompressed = &mut uncompressed[..(buffer.len() - 1).min(MAX_PACKET_BYTES)];
+        let len = self.inner.serialize(packet, uncompressed)?;
+
+        if len > self.threshold.max(1) && !matches!(packet, Packet::MtuProbe { .. }) {
+            // Only keep the compressed packet if it's smaller
+            if let Some(compressed_len) = self.compress(&uncompressed[..len], &mut buffer[1..len]) {
+                buffer[0] = COMPRESSED;
+                return Ok(compressed_len + 1);
+            }
+        }
+
+        buffer[0] = UNCOMPRESSED;
+        buffer[1..len + 1].copy_from_slice(&uncompressed[..len]);
+        Ok(len + 1)
+    }
+
+    fn deserialize(&self, buffer: &[u8]) -> Result<Packet, SerializationError> {
+        match buffer.split_first() {
+            Some((&UNCOMPRESSED, packet)) => self.inner.deserialize(packet),
+            Some((&COMPRESSED, compressed)) => {
+                let mut decompressed = [0u8; MAX_PACKET_BYTES];
+                let len = self
+                    .decompress(compressed, &mut decompressed)
+                    .ok_or(SerializationError::InvalidCompression)?;
+                self.inner.deserialize(&decompressed[..len])
+            }
+            Some(_) => Err(SerializationError::InvalidCompression),
+            None => Err(SerializationError::BufferTooShort),
+        }
+    }
+
+    fn overhead(&self) -> usize {
+        1 + self.inner.overhead()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ChecksumPacketSerializer;
+
+    fn algorithms() -> Vec<CompressionAlgorithm> {
+        vec![
+            #[cfg(feature = "lz4")]
+            CompressionAlgorithm::Lz4,
+            #[cfg(feature = "snappy")]
+            CompressionAlgorithm::Snappy,
+        ]
+    }
+
+    #[test]
+    fn compress_above_threshold() {
+        for algorithm in algorithms() {
+            let serializer = CompressedPacketSerializer::new(algorithm);
+            let mut buffer = [0u8; MAX_PACKET_BYTES];
+
+            let small = Packet::SmallUnreliable {
+                sequence: 0,
+                channel_id: 0,
+                messages: vec![vec![1; 64].into()],
+            };
+            let len = serializer.serialize(&small, &mut buffer).unwrap();
+            assert_eq!(buffer[0], UNCOMPRESSED);
+            assert_eq!(serializer.deserialize(&buffer[..len]).unwrap(), small);
+
+            let big = Packet::SmallUnreliable {
+                sequence: 0,
+                channel_id: 0,
+                messages: vec![vec![1; 1000].into()],
+            };
+            let len = serializer.serialize(&big, &mut buffer).unwrap();
+            assert_eq!(buffer[0], COMPRESSED);
+            assert!(len < 100);
+            assert_eq!(serializer.deserialize(&buffer[..len]).unwrap(), big);
+        }
+    }
+
+    #[test]
+    fn incompressible_packet() {
+        for algorithm in algorithms() {
+            let serializer = CompressedPacketSerializer::new(algorithm).with_threshold(0);
+            let mut buffer = [0u8; MAX_PACKET_BYTES];
+
+            // Pseudo random payload, compressing it doesn't reduce its size
+            let mut state: u32 = 7;
+            let payload: Vec<u8> = (0..1000)
+                .map(|_| {
+                    state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    (state >> 16) as u8
+                })
+                .collect();
+            let packet = Packet::SmallUnreliable {
+                sequence: 0,
+                channel_id: 0,
+                messages: vec![payload.into()],
+            };
+            let len = serializer.serialize(&packet, &mut buffer).unwrap();
+            assert_eq!(buffer[0], UNCOMPRESSED);
+            assert_eq!(serializer.deserialize(&buffer[..len]).unwrap(), packet);
+
+            let mtu_probe = Packet::MtuProbe { sequence: 0, size: 1200 };
+            let len = serializer.serialize(&mtu_probe, &mut buffer).unwrap();
+            assert_eq!(len, 1200 + 1);
+        }
+    }
+
+    #[test]
+    fn invalid_compressed_packet() {
+        for algorithm in algorithms() {
+            let serializer = CompressedPacketSerializer::new(algorithm);
+            let buffer = [COMPRESSED, 0xFF, 0xFF, 0xFF];
+            assert_eq!(serializer.deserialize(&buffer), Err(SerializationError::InvalidCompression));
+            assert_eq!(serializer.deserialize(&[2, 0]), Err(SerializationError::InvalidCompression));
+            assert_eq!(serializer.deserialize(&[]), Err(SerializationError::BufferTooShort));
+        }
+    }
+
+    #[test]
+    fn nested_with_checksums() {
+        fn roundtrip(serializer: &dyn PacketSerializer) {
+            let mut buffer = [0u8; MAX_PACKET_BYTES];
+            // Different sequences give checksums with any first byte
+            for sequence in 0..64 {
+                for size in [10, 1000] {
+                    let packet = Packet::SmallUnreliable {
+                        sequence,
+                        channel_id: 0,
+                        messages: vec![vec![1; size].into()],
+                    };
+                    let len = serializer.serialize(&packet, &mut buffer).unwrap();
+                    assert_eq!(serializer.deserialize(&buffer[..len]).unwrap(), packet);
+                }
+            }
+        }
+
+        for algorithm in algorithms() {
+            let compressed_checksums = CompressedPacketSerializer::with_serializer(ChecksumPacketSerializer::new(), algorithm);
+            assert_eq!(compressed_checksums.overhead(), 1 + 4);
+            roundtrip(&compressed_checksums);
+
+            let checksummed_compression = ChecksumPacketSerializer::with_serializer(CompressedPacketSerializer::new(algorithm));
+            assert_eq!(checksummed_compression.overhead(), 4 + 1);
+            roundtrip(&checksummed_compression);
+        }
+    }
+}
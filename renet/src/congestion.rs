@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+/// Configuration for the good/bad mode congestion avoidance.
+///
+/// The connection starts in [`CongestionMode::Good`], when the rtt or packet loss goes above
+/// the thresholds it switches to [`CongestionMode::Bad`] and reduces the bytes sent per tick.
+/// It only returns to good mode after the conditions stay good for the recovery time.
+/// The recovery time doubles if the connection goes bad again shortly after recovering,
+/// and is halved for each recovery time the connection stays in good mode.
+#[derive(Debug, Clone)]
+pub struct CongestionControl {
+    /// Round-trip time, in seconds, above which the connection switches to bad mode.
+    /// Default: 0.25
+    pub bad_rtt: f64,
+    /// Packet loss above which the connection switches to bad mode.
+    /// Default: 0.1 (10%)
+    pub bad_packet_loss: f64,
+    /// Fraction of `available_bytes_per_tick` that can be used in bad mode.
+    /// Default: 0.25
+    pub bad_mode_bytes_factor: f64,
+    /// Minimum time with good conditions before switching back to good mode.
+    /// Default: 1 second
+    pub min_recovery_time: Duration,
+    /// Maximum time with good conditions before switching back to good mode.
+    /// Default: 60 seconds
+    pub max_recovery_time: Duration,
+}
+
+/// Current mode of the congestion avoidance.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CongestionMode {
+    #[default]
+    Good,
+    Bad,
+}
+
+impl Default for CongestionControl {
+    fn default() -> Self {
+        Self {
+            bad_rtt: 0.25,
+            bad_packet_loss: 0.1,
+            bad_mode_bytes_factor: 0.25,
+            min_recovery_time: Duration::from_secs(1),
+            max_recovery_time: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct CongestionController {
+    config: CongestionControl,
+    mode: CongestionMode,
+    recovery_time: Duration,
+    // When the connection last switched from bad to good mode
+    recovered_at: Option<Duration>,
+    // In good mode: when the recovery time was last reduced
+    // In bad mode: since when the conditions are good
+    measure_since: Option<Duration>,
+}
+
+impl CongestionController {
+    pub fn new(config: CongestionControl) -> Self {
+        Self {
+            recovery_time: config.min_recovery_time,
+            config,
+            mode: CongestionMode::Good,
+            recovered_at: None,
+            measure_since: Some(Duration::ZERO),
+        }
+    }
+
+    pub fn mode(&self) -> CongestionMode {
+        self.mode
+    }
+
+    pub fn available_bytes(&self, available_bytes_per_tick: u64) -> u64 {
+        match self.mode {
+            CongestionMode::Good => available_bytes_per_tick,
+            CongestionMode::Bad => (available_bytes_per_tick as f64 * self.config.bad_mode_bytes_factor) as u64,
+        }
+    }
+
+    pub fn update(&mut self, current_time: Duration, rtt: f64, packet_loss: f64) {
+        let bad_conditions = rtt > self.config.bad_rtt || packet_loss > self.config.bad_packet_loss;
+
+        match self.mode {
+            CongestionMode::Good => {
+                if bad_conditions {
+                    // Dropped back to bad mode shortly after recovering
+                    if matches!(self.recovered_at, Some(recovered_at) if current_time - recovered_at < self.recovery_time) {
+                        self.recovery_time = (self.recovery_time * 2).min(self.config.max_recovery_time);
+                    }
+                    self.mode = CongestionMode::Bad;
+                    self.measure_since = None;
+                    return;
+                }
+
+                let since = self.measure_since.get_or_insert(current_time);
+                if current_time - *since >= self.recovery_time {
+                    self.recovery_time = (self.recovery_time / 2).max(self.config.min_recovery_time);
+                    self.measure_since = Some(current_time);
+                }
+            }
+            CongestionMode::Bad => {
+                if bad_conditions {
+                    self.measure_since = None;
+                    return;
+                }
+
+                let since = *self.measure_since.get_or_insert(current_time);
+                if current_time - since >= self.recovery_time {
+                    self.mode = CongestionMode::Good;
+                    self.recovered_at = Some(current_time);
+                    self.measure_since = Some(current_time);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: Duration = Duration::from_millis(100);
+
+    fn run(controller: &mut CongestionController, current_time: &mut Duration, duration: Duration, rtt: f64) {
+        let end = *current_time + duration;
+        while *current_time < end {
+            *current_time += TICK;
+            controller.update(*current_time, rtt, 0.0);
+        }
+    }
+
+    #[test]
+    fn mode_switching() {
+        let mut current_time = Duration::ZERO;
+        let mut controller = CongestionController::new(CongestionControl::default());
+        assert_eq!(controller.available_bytes(1000), 1000);
+
+        run(&mut controller, &mut current_time, Duration::from_secs(2), 0.1);
+        assert_eq!(controller.mode(), CongestionMode::Good);
+
+        run(&mut controller, &mut current_time, TICK, 0.5);
+        assert_eq!(controller.mode(), CongestionMode::Bad);
+        assert_eq!(controller.available_bytes(1000), 250);
+
+        // Needs good conditions for the whole recovery time
+        run(&mut controller, &mut current_time, Duration::from_millis(500), 0.1);
+        run(&mut controller, &mut current_time, TICK, 0.5);
+        run(&mut controller, &mut current_time, Duration::from_millis(900), 0.1);
+        assert_eq!(controller.mode(), CongestionMode::Bad);
+
+        run(&mut controller, &mut current_time, Duration::from_millis(200), 0.1);
+        assert_eq!(controller.mode(), CongestionMode::Good);
+    }
+
+    #[test]
+    fn recovery_time() {
+        let mut current_time = Duration::ZERO;
+        let mut controller = CongestionController::new(CongestionControl::default());
+
+        // Going bad again right after recovering doubles the recovery time
+        for expected in [2, 4, 8] {
+            run(&mut controller, &mut current_time, TICK, 0.5);
+            run(&mut controller, &mut current_time, Duration::from_secs(expected / 2) + TICK, 0.1);
+            assert_eq!(controller.mode(), CongestionMode::Good);
+            run(&mut controller, &mut current_time, TICK, 0.5);
+            assert_eq!(controller.recovery_time, Duration::from_secs(expected));
+        }
+
+        // Staying in good mode reduces it again
+        run(&mut controller, &mut current_time, Duration::from_secs(8) + TICK, 0.1);
+        assert_eq!(controller.mode(), CongestionMode::Good);
+        run(&mut controller, &mut current_time, Duration::from_secs(30), 0.1);
+        assert_eq!(controller.recovery_time, Duration::from_secs(1));
+    }
+}
@@ -1,35 +1,216 @@
+use std::collections::{BTreeMap, VecDeque};
 use std::time::Duration;
 
 const RESOLUTION: Duration = Duration::from_millis(300);
-const WINDOW: Duration = Duration::from_millis(6000);
-const SIZE: usize = (WINDOW.as_millis() / RESOLUTION.as_millis()) as usize;
+pub(crate) const DEFAULT_WINDOW: Duration = Duration::from_millis(6000);
+// The packet loss ignores the last 3 resolutions, the window must be bigger than that
+const MIN_SIZE: usize = 4;
 
-#[derive(Debug, Default)]
+/// Totals of a connection since it was created, see [`RenetClient::connection_stats`](crate::RenetClient::connection_stats).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct ConnectionStats {
-    packets_sent: [u64; SIZE],
-    packets_acked: [u64; SIZE],
-    bytes_sent: [u64; SIZE],
-    bytes_received: [u64; SIZE],
-    current_index: usize,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub packets_acked: u64,
+    /// Packets not acknowledged within 3 seconds of being sent.
+    pub packets_lost: u64,
+    /// Stats of each channel with sent or received messages.
+    pub channels: BTreeMap<u8, ChannelStats>,
+}
+
+/// Totals of a channel, see [`ConnectionStats::channels`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ChannelStats {
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Reliable messages and slices sent again because they were not acknowledged in time.
+    pub messages_resent: u64,
+    /// Messages dropped because the channel was full, with [`FullQueuePolicy::DropNewest`](crate::FullQueuePolicy::DropNewest).
+    /// They are not counted in the sent messages.
+    pub messages_dropped: u64,
 }
 
 impl ConnectionStats {
-    pub fn new() -> Self {
+    pub(crate) fn sent_message(&mut self, channel_id: u8, bytes: usize) {
+        let channel = self.channels.entry(channel_id).or_default();
+        channel.messages_sent += 1;
+        channel.bytes_sent += bytes as u64;
+    }
+
+    pub(crate) fn dropped_message(&mut self, channel_id: u8) {
+        self.channels.entry(channel_id).or_default().messages_dropped += 1;
+    }
+
+    pub(crate) fn received_message(&mut self, channel_id: u8, bytes: usize) {
+        let channel = self.channels.entry(channel_id).or_default();
+        channel.messages_received += 1;
+        channel.bytes_received += bytes as u64;
+    }
+}
+
+const HISTORY_INTERVAL: Duration = Duration::from_secs(1);
+const HISTORY_SAMPLES: usize = 60;
+
+/// Network conditions of a connection over one second, see [`NetworkHistory`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NetworkSample {
+    /// Time since the connection was created, at the end of the sample.
+    pub time: Duration,
+    pub sent_kbps: f64,
+    pub received_kbps: f64,
+    /// Packet loss estimate at the end of the sample, between 0 and 1.
+    pub packet_loss: f64,
+    /// Round-trip time in seconds, at the end of the sample.
+    pub rtt: f64,
+}
+
+/// Samples of the last 60 seconds of a connection, one per second, for drawing graphs.
+/// See [`RenetClient::network_history`](crate::RenetClient::network_history).
+#[derive(Debug, Clone, Default)]
+pub struct NetworkHistory {
+    samples: VecDeque<NetworkSample>,
+    last_sample_at: Duration,
+    last_bytes_sent: u64,
+    last_bytes_received: u64,
+}
+
+impl NetworkHistory {
+    /// Returns the samples from the oldest to the newest.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &NetworkSample> + ExactSizeIterator + '_ {
+        self.samples.iter()
+    }
+
+    pub fn latest(&self) -> Option<&NetworkSample> {
+        self.samples.back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    // Takes a sample when a second has passed since the last one.
+    pub(crate) fn update(&mut self, current_time: Duration, stats: &ConnectionStats, packet_loss: f64, rtt: f64) {
+        let elapsed = current_time.saturating_sub(self.last_sample_at);
+        if elapsed < HISTORY_INTERVAL {
+            return;
+        }
+
+        let to_kbps = |bytes: u64| bytes as f64 * 8.0 / 1000.0 / elapsed.as_secs_f64();
+        if self.samples.len() == HISTORY_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(NetworkSample {
+            time: current_time,
+            sent_kbps: to_kbps(stats.bytes_sent - self.last_bytes_sent),
+            received_kbps: to_kbps(stats.bytes_received - self.last_bytes_received),
+            packet_loss,
+            rtt,
+        });
+
+        self.last_sample_at = current_time;
+        self.last_bytes_sent = stats.bytes_sent;
+        self.last_bytes_received = stats.bytes_received;
+    }
+}
+
+const RTT_SAMPLES: usize = 128;
+
+/// Distribution of the recent round-trip times in seconds, see [`RenetClient::rtt_percentiles`](crate::RenetClient::rtt_percentiles).
+/// All values are 0 until a packet is acknowledged.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RttPercentiles {
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    /// Mean absolute deviation from the average round-trip time.
+    pub jitter: f64,
+}
+
+// Round-trip times of the last acknowledged packets.
+#[derive(Debug, Default)]
+pub struct RttSamples {
+    samples: VecDeque<f64>,
+}
+
+impl RttSamples {
+    pub fn push(&mut self, rtt: f64) {
+        if self.samples.len() == RTT_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(rtt);
+    }
+
+    pub fn percentiles(&self) -> RttPercentiles {
+        if self.samples.is_empty() {
+            return RttPercentiles::default();
+        }
+
+        let mut sorted: Vec<f64> = self.samples.iter().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+        // Nearest rank
+        let percentile = |p: f64| sorted[((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len()) - 1];
+
+        let mean = sorted.iter().sum::<f64>() / sorted.len() as f64;
+        let jitter = sorted.iter().map(|rtt| (rtt - mean).abs()).sum::<f64>() / sorted.len() as f64;
+
+        RttPercentiles {
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            jitter,
+        }
+    }
+}
+
+// Stats over a rolling window, used for the packet loss and bandwidth estimates.
+#[derive(Debug)]
+pub struct StatsWindow {
+    packets_sent: Box<[u64]>,
+    packets_acked: Box<[u64]>,
+    bytes_sent: Box<[u64]>,
+    bytes_received: Box<[u64]>,
+    current_index: usize,
+    window: Duration,
+}
+
+impl Default for StatsWindow {
+    fn default() -> Self {
+        Self::new(DEFAULT_WINDOW)
+    }
+}
+
+impl StatsWindow {
+    /// Creates stats over the given window, rounded to multiples of 300ms (minimum 1.2s).
+    pub fn new(window: Duration) -> Self {
+        let size = ((window.as_millis() / RESOLUTION.as_millis()) as usize).max(MIN_SIZE);
         Self {
-            packets_sent: [0; SIZE],
-            packets_acked: [0; SIZE],
-            bytes_sent: [0; SIZE],
-            bytes_received: [0; SIZE],
+            packets_sent: vec![0; size].into_boxed_slice(),
+            packets_acked: vec![0; size].into_boxed_slice(),
+            bytes_sent: vec![0; size].into_boxed_slice(),
+            bytes_received: vec![0; size].into_boxed_slice(),
             current_index: 0,
+            window: RESOLUTION * size as u32,
         }
     }
 
-    fn index(time: Duration) -> usize {
-        (time.as_millis() / RESOLUTION.as_millis()) as usize % SIZE
+    fn size(&self) -> usize {
+        self.packets_sent.len()
+    }
+
+    fn index(&self, time: Duration) -> usize {
+        (time.as_millis() / RESOLUTION.as_millis()) as usize % self.size()
     }
 
     pub fn update(&mut self, current_time: Duration) {
-        let i = Self::index(current_time);
+        let i = self.index(current_time);
         if self.current_index != i {
             self.current_index = i;
             self.packets_sent[i] = 0;
@@ -50,56 +231,58 @@ impl ConnectionStats {
 
     pub fn acked_packet(&mut self, sent_at: Duration, current_time: Duration) {
         let delta = current_time - sent_at;
-        if delta > WINDOW {
+        if delta > self.window {
             // Out of the duration window, discard it
             return;
         }
 
-        self.packets_acked[Self::index(sent_at)] += 1;
+        let index = self.index(sent_at);
+        self.packets_acked[index] += 1;
     }
 
     pub fn bytes_sent_per_second(&self, current_time: Duration) -> f64 {
         let mut total_bytes: u64 = self.bytes_sent.iter().sum();
 
-        if current_time < WINDOW {
+        if current_time < self.window {
             return total_bytes as f64 / current_time.as_secs_f64();
         }
 
         // Ignore the current incomplete resolution
         total_bytes -= self.bytes_sent[self.current_index];
 
-        total_bytes as f64 / (WINDOW - RESOLUTION).as_secs_f64()
+        total_bytes as f64 / (self.window - RESOLUTION).as_secs_f64()
     }
 
     pub fn bytes_received_per_second(&self, current_time: Duration) -> f64 {
         let mut total_bytes: u64 = self.bytes_received.iter().sum();
 
-        if current_time < WINDOW {
+        if current_time < self.window {
             return total_bytes as f64 / current_time.as_secs_f64();
         }
 
         // Ignore the current incomplete resolution
         total_bytes -= self.bytes_received[self.current_index];
-        total_bytes as f64 / (WINDOW - RESOLUTION).as_secs_f64()
+        total_bytes as f64 / (self.window - RESOLUTION).as_secs_f64()
     }
 
     pub fn packet_loss(&self) -> f64 {
+        let size = self.size();
         let total_packets_sent = {
             let mut sum: u64 = self.packets_sent.iter().sum();
 
             // Ignore the current and last 2 resolutions,
             // because the message or its ack could be in flight
             sum -= self.packets_sent[self.current_index];
-            sum -= self.packets_sent[(self.current_index + SIZE - 1) % SIZE];
-            sum -= self.packets_sent[(self.current_index + SIZE - 2) % SIZE];
+            sum -= self.packets_sent[(self.current_index + size - 1) % size];
+            sum -= self.packets_sent[(self.current_index + size - 2) % size];
             sum as f64
         };
 
         let total_packets_acked = {
             let mut sum: u64 = self.packets_acked.iter().sum();
             sum -= self.packets_acked[self.current_index];
-            sum -= self.packets_acked[(self.current_index + SIZE - 1) % SIZE];
-            sum -= self.packets_acked[(self.current_index + SIZE - 2) % SIZE];
+            sum -= self.packets_acked[(self.current_index + size - 1) % size];
+            sum -= self.packets_acked[(self.current_index + size - 2) % size];
             sum as f64
         };
 
@@ -111,14 +294,159 @@ impl ConnectionStats {
     }
 }
 
+const BANDWIDTH_RESOLUTION: Duration = Duration::from_millis(100);
+const BANDWIDTH_WINDOW: Duration = Duration::from_secs(1);
+// The buckets of the last second, and the current incomplete one
+const BANDWIDTH_BUCKETS: usize = 11;
+
+// Bytes sent and received over the last second, for the kbps stats.
+#[derive(Debug, Default)]
+pub struct BandwidthWindow {
+    bytes_sent: [u64; BANDWIDTH_BUCKETS],
+    bytes_received: [u64; BANDWIDTH_BUCKETS],
+    current_bucket: u64,
+}
+
+impl BandwidthWindow {
+    pub fn update(&mut self, current_time: Duration) {
+        let bucket = (current_time.as_millis() / BANDWIDTH_RESOLUTION.as_millis()) as u64;
+        // Clear the buckets skipped since the last update, they had no traffic
+        let skipped = (bucket - self.current_bucket).min(BANDWIDTH_BUCKETS as u64);
+        for i in 1..=skipped {
+            let index = ((self.current_bucket + i) % BANDWIDTH_BUCKETS as u64) as usize;
+            self.bytes_sent[index] = 0;
+            self.bytes_received[index] = 0;
+        }
+        self.current_bucket = bucket;
+    }
+
+    fn current_index(&self) -> usize {
+        (self.current_bucket % BANDWIDTH_BUCKETS as u64) as usize
+    }
+
+    pub fn sent_packets(&mut self, bytes: u64) {
+        self.bytes_sent[self.current_index()] += bytes;
+    }
+
+    pub fn received_packet(&mut self, bytes: u64) {
+        self.bytes_received[self.current_index()] += bytes;
+    }
+
+    pub fn bytes_sent_per_second(&self, current_time: Duration) -> f64 {
+        self.per_second(&self.bytes_sent, current_time)
+    }
+
+    pub fn bytes_received_per_second(&self, current_time: Duration) -> f64 {
+        self.per_second(&self.bytes_received, current_time)
+    }
+
+    fn per_second(&self, buckets: &[u64; BANDWIDTH_BUCKETS], current_time: Duration) -> f64 {
+        let total_bytes: u64 = buckets.iter().sum();
+        if current_time.is_zero() {
+            return 0.0;
+        }
+        if current_time <= BANDWIDTH_WINDOW {
+            return total_bytes as f64 / current_time.as_secs_f64();
+        }
+
+        // Ignore the current incomplete resolution
+        (total_bytes - buckets[self.current_index()]) as f64 / BANDWIDTH_WINDOW.as_secs_f64()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn bandwidth_window() {
+        let mut window = BandwidthWindow::default();
+        assert_eq!(window.bytes_sent_per_second(Duration::ZERO), 0.0);
+
+        let mut current_time = Duration::ZERO;
+        for _ in 0..20 {
+            current_time += Duration::from_millis(100);
+            window.update(current_time);
+            window.sent_packets(100);
+        }
+        // Only the last second is counted, without the current 100ms
+        assert!((window.bytes_sent_per_second(current_time) - 1000.0).abs() < 1e-9);
+        assert_eq!(window.bytes_received_per_second(current_time), 0.0);
+
+        // Nothing was sent in the skipped buckets
+        current_time += Duration::from_millis(500);
+        window.update(current_time);
+        assert!((window.bytes_sent_per_second(current_time) - 600.0).abs() < 1e-9);
+        current_time += Duration::from_secs(10);
+        window.update(current_time);
+        assert_eq!(window.bytes_sent_per_second(current_time), 0.0);
+    }
+
+    #[test]
+    fn rtt_percentiles() {
+        let mut samples = RttSamples::default();
+        assert_eq!(samples.percentiles(), RttPercentiles::default());
+
+        // Older samples are discarded
+        for _ in 0..RTT_SAMPLES {
+            samples.push(1.0);
+        }
+        for i in 1..=100 {
+            samples.push(i as f64 / 1000.);
+        }
+        samples.push(0.5);
+        for _ in 0..27 {
+            samples.push(0.05);
+        }
+
+        let percentiles = samples.percentiles();
+        assert_eq!(percentiles.p50, 0.05);
+        assert_eq!(percentiles.p95, 0.095);
+        assert_eq!(percentiles.p99, 0.1);
+
+        let mut samples = RttSamples::default();
+        for rtt in [0.1, 0.2, 0.1, 0.2] {
+            samples.push(rtt);
+        }
+        assert!((samples.percentiles().jitter - 0.05).abs() < 1e-9);
+    }
+
+    #[test]
+    fn history_samples() {
+        let mut history = NetworkHistory::default();
+        let mut stats = ConnectionStats::default();
+        let mut current_time = Duration::ZERO;
+
+        for _ in 0..100 {
+            current_time += Duration::from_millis(100);
+            stats.bytes_sent += 500;
+            stats.bytes_received += 250;
+            history.update(current_time, &stats, 0.1, 0.05);
+        }
+
+        assert_eq!(history.len(), 10);
+        let sample = history.latest().unwrap();
+        assert_eq!(sample.time, Duration::from_secs(10));
+        assert!((sample.sent_kbps - 40.0).abs() < 1e-9);
+        assert!((sample.received_kbps - 20.0).abs() < 1e-9);
+        assert_eq!(sample.packet_loss, 0.1);
+
+        // Only the last minute is kept
+        for _ in 0..100 {
+            current_time += Duration::from_secs(1);
+            history.update(current_time, &stats, 0.0, 0.05);
+        }
+        assert_eq!(history.len(), HISTORY_SAMPLES);
+        assert_eq!(history.iter().next().unwrap().time, current_time - Duration::from_secs(59));
+        assert_eq!(history.latest().unwrap().sent_kbps, 0.0);
+    }
+
+    const SIZE: usize = (DEFAULT_WINDOW.as_millis() / RESOLUTION.as_millis()) as usize;
+
     #[test]
     fn bytes_per_sec() {
         let mut current_time = Duration::ZERO;
-        let mut window = ConnectionStats::default();
+        let mut window = StatsWindow::default();
 
         for _ in 0..10 {
             window.update(current_time);
@@ -136,15 +464,15 @@ mod tests {
         }
 
         // Check after 6 seconds
-        assert_eq!(window.packets_sent, [30; SIZE]);
-        assert_eq!(window.bytes_sent, [300; SIZE]);
+        assert_eq!(*window.packets_sent, [30; SIZE]);
+        assert_eq!(*window.bytes_sent, [300; SIZE]);
         assert_eq!(window.bytes_sent_per_second(current_time), 1000.);
     }
 
     #[test]
     fn packet_loss() {
         let mut current_time = Duration::ZERO;
-        let mut window = ConnectionStats::default();
+        let mut window = StatsWindow::default();
 
         for _ in 0..20 {
             window.update(current_time);
@@ -165,8 +493,34 @@ mod tests {
         }
 
         // Check after 6 seconds
-        assert_eq!(window.packets_sent, [6; SIZE]);
-        assert_eq!(window.packets_acked, [3; SIZE]);
+        assert_eq!(*window.packets_sent, [6; SIZE]);
+        assert_eq!(*window.packets_acked, [3; SIZE]);
         assert_eq!(window.packet_loss(), 0.5);
     }
+
+    #[test]
+    fn packet_loss_window() {
+        let mut current_time = Duration::ZERO;
+        let mut window = StatsWindow::new(Duration::from_millis(1500));
+        assert_eq!(window.size(), 5);
+
+        for _ in 0..30 {
+            window.update(current_time);
+            window.sent_packets(4, 100);
+            window.acked_packet(current_time, current_time);
+            current_time += Duration::from_millis(100);
+        }
+        assert_eq!(window.packet_loss(), 0.75);
+
+        // Stops losing packets, only the recent history is considered
+        for _ in 0..15 {
+            window.update(current_time);
+            window.sent_packets(4, 100);
+            for _ in 0..4 {
+                window.acked_packet(current_time, current_time);
+            }
+            current_time += Duration::from_millis(100);
+        }
+        assert_eq!(window.packet_loss(), 0.0);
+    }
 }
@@ -0,0 +1,334 @@
+use std::collections::{HashMap, VecDeque};
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use crate::packet::SLICE_SIZE;
+use crate::varint::{read_varint, write_varint};
+
+const KEYFRAME: u8 = 0;
+const DELTA: u8 = 1;
+
+/// Configuration of the states sent with [`RenetClient::send_state`](crate::RenetClient::send_state).
+///
+/// States are sent as a diff against the most recent state acknowledged by the remote side,
+/// or in full when there's no acknowledged state available or a keyframe is due.
+#[derive(Debug, Clone)]
+pub struct DeltaCompression {
+    /// Number of recent states kept by both sides to be used as baselines.
+    /// Default: 32
+    pub baselines: usize,
+    /// A full state is sent when this many ticks passed since the last one, even if a baseline is available.
+    /// Default: 60
+    pub keyframe_interval: u64,
+}
+
+impl Default for DeltaCompression {
+    fn default() -> Self {
+        Self {
+            baselines: 32,
+            keyframe_interval: 60,
+        }
+    }
+}
+
+// Header of a state message: kind (1 byte), tick (8 bytes) and for deltas the baseline tick (8 bytes)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    tick: u64,
+    baseline: Option<u64>,
+}
+
+impl Header {
+    fn read(message: &[u8]) -> Option<(Header, &[u8])> {
+        let (&kind, rest) = message.split_first()?;
+        let tick = u64::from_be_bytes(rest.get(..8)?.try_into().ok()?);
+        match kind {
+            KEYFRAME => Some((Header { tick, baseline: None }, &rest[8..])),
+            DELTA => {
+                let baseline = u64::from_be_bytes(rest.get(8..16)?.try_into().ok()?);
+                Some((
+                    Header {
+                        tick,
+                        baseline: Some(baseline),
+                    },
+                    &rest[16..],
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    fn write(&self, buffer: &mut BytesMut) {
+        match self.baseline {
+            None => {
+                buffer.put_u8(KEYFRAME);
+                buffer.put_u64(self.tick);
+            }
+            Some(baseline) => {
+                buffer.put_u8(DELTA);
+                buffer.put_u64(self.tick);
+                buffer.put_u64(baseline);
+            }
+        }
+    }
+}
+
+/// Returns the tick of a state message, used to track which packets carried it.
+pub(crate) fn state_tick(message: &[u8]) -> Option<u64> {
+    Header::read(message).map(|(header, _)| header.tick)
+}
+
+#[derive(Debug)]
+pub(crate) struct DeltaSender {
+    config: DeltaCompression,
+    states: VecDeque<(u64, Bytes)>,
+    acked: Option<u64>,
+    last_keyframe: Option<u64>,
+    // Packets of each sent message that were not acked yet
+    pending_parts: HashMap<u64, usize>,
+}
+
+impl DeltaSender {
+    pub fn new(config: DeltaCompression) -> Self {
+        Self {
+            config,
+            states: VecDeque::new(),
+            acked: None,
+            last_keyframe: None,
+            pending_parts: HashMap::new(),
+        }
+    }
+
+    pub fn acked(&self) -> Option<u64> {
+        self.acked
+    }
+
+    /// Returns the message to send for the state, ticks must increase.
+    pub fn encode(&mut self, tick: u64, state: Bytes) -> Bytes {
+        let keyframe_due = self
+            .last_keyframe
+            .is_none_or(|keyframe| tick.saturating_sub(keyframe) >= self.config.keyframe_interval);
+        let baseline = match self.acked {
+            Some(acked) if !keyframe_due && acked < tick => self.states.iter().find(|(t, _)| *t == acked),
+            _ => None,
+        };
+
+        let mut message = BytesMut::new();
+        match baseline {
+            Some((baseline_tick, baseline)) => {
+                Header {
+                    tick,
+                    baseline: Some(*baseline_tick),
+                }
+                .write(&mut message);
+                write_diff(&mut message, baseline, &state);
+            }
+            None => {
+                Header { tick, baseline: None }.write(&mut message);
+                message.extend_from_slice(&state);
+                self.last_keyframe = Some(tick);
+            }
+        }
+
+        if self.states.len() >= self.config.baselines.max(1) {
+            if let Some((oldest, _)) = self.states.pop_front() {
+                self.pending_parts.retain(|t, _| *t > oldest);
+            }
+        }
+        self.states.push_back((tick, state));
+
+        // Messages above the slice size are split in slices of one packet each
+        let parts = if message.len() > SLICE_SIZE { message.len().div_ceil(SLICE_SIZE) } else { 1 };
+        self.pending_parts.insert(tick, parts);
+
+        message.freeze()
+    }
+
+    /// Called when a packet with the message of the tick, or one of its slices, is acknowledged.
+    pub fn packet_acked(&mut self, tick: u64) {
+        let Some(parts) = self.pending_parts.get_mut(&tick) else {
+            return;
+        };
+        *parts -= 1;
+        if *parts == 0 {
+            self.pending_parts.remove(&tick);
+            self.acked = self.acked.max(Some(tick));
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct DeltaReceiver {
+    baselines: usize,
+    states: VecDeque<(u64, Bytes)>,
+}
+
+impl DeltaReceiver {
+    pub fn new(config: &DeltaCompression) -> Self {
+        Self {
+            baselines: config.baselines.max(1),
+            states: VecDeque::new(),
+        }
+    }
+
+    /// Returns the state of the message, or `None` if it's older than the latest state,
+    /// its baseline is no longer available, or it's malformed.
+    pub fn decode(&mut self, message: &[u8]) -> Option<(u64, Bytes)> {
+        let (header, body) = Header::read(message)?;
+        if self.states.back().is_some_and(|(latest, _)| header.tick <= *latest) {
+            return None;
+        }
+
+        let state = match header.baseline {
+            None => Bytes::copy_from_slice(body),
+            Some(baseline_tick) => {
+                let (_, baseline) = self.states.iter().find(|(t, _)| *t == baseline_tick)?;
+                apply_diff(baseline, body)?
+            }
+        };
+
+        if self.states.len() >= self.baselines {
+            self.states.pop_front();
+        }
+        self.states.push_back((header.tick, state.clone()));
+
+        Some((header.tick, state))
+    }
+}
+
+// Diff format: new length, then runs of `unchanged: varint`, `changed: varint`, `changed` bytes
+// xored with the baseline. Bytes past the end of the baseline are always in changed runs, xored with zeros.
+pub(crate) fn write_diff(buffer: &mut BytesMut, baseline: &[u8], state: &[u8]) {
+    // Short unchanged runs are cheaper to include in the changed bytes than to start a new run
+    const MIN_UNCHANGED_RUN: usize = 3;
+
+    let xored = |i: usize| state[i] ^ baseline.get(i).copied().unwrap_or(0);
+    let unchanged_at = |i: usize| i < baseline.len() && state[i] == baseline[i];
+    write_varint(buffer, state.len() as u64);
+
+    let mut i = 0;
+    while i < state.len() {
+        let unchanged_start = i;
+        while i < state.len() && unchanged_at(i) {
+            i += 1;
+        }
+        if i == state.len() {
+            break;
+        }
+
+        let changed_start = i;
+        let mut unchanged = 0;
+        while i < state.len() && unchanged < MIN_UNCHANGED_RUN {
+            unchanged = if unchanged_at(i) { unchanged + 1 } else { 0 };
+            i += 1;
+        }
+        let changed_end = i - unchanged;
+        i = changed_end;
+
+        write_varint(buffer, (changed_start - unchanged_start) as u64);
+        write_varint(buffer, (changed_end - changed_start) as u64);
+        buffer.extend((changed_start..changed_end).map(xored));
+    }
+}
+
+pub(crate) fn apply_diff(baseline: &[u8], mut diff: &[u8]) -> Option<Bytes> {
+    let len = read_varint(&mut diff)? as usize;
+    // New bytes are all in the diff, this avoids huge allocations from invalid messages
+    if len > baseline.len() + diff.len() {
+        return None;
+    }
+
+    let mut state = BytesMut::zeroed(len);
+    let copied = len.min(baseline.len());
+    state[..copied].copy_from_slice(&baseline[..copied]);
+
+    let mut position = 0usize;
+    while !diff.is_empty() {
+        position = position.checked_add(read_varint(&mut diff)? as usize)?;
+        let changed = read_varint(&mut diff)? as usize;
+        let end = position.checked_add(changed)?;
+        if end > len || changed > diff.len() {
+            return None;
+        }
+        for (byte, change) in state[position..end].iter_mut().zip(&diff[..changed]) {
+            *byte ^= change;
+        }
+        diff = &diff[changed..];
+        position = end;
+    }
+
+    Some(state.freeze())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_roundtrip() {
+        let baseline: Vec<u8> = (0..200).collect();
+        let mut state = baseline.clone();
+        state[10] = 0;
+        state[11] = 0;
+        state[100..104].copy_from_slice(&[1, 2, 3, 4]);
+        state.extend_from_slice(&[9; 20]);
+
+        let mut diff = BytesMut::new();
+        write_diff(&mut diff, &baseline, &state);
+        assert!(diff.len() < 40);
+        assert_eq!(apply_diff(&baseline, &diff).unwrap(), state);
+
+        let mut diff = BytesMut::new();
+        write_diff(&mut diff, &baseline, &baseline[..50]);
+        assert_eq!(apply_diff(&baseline, &diff).unwrap(), baseline[..50]);
+
+        assert!(apply_diff(&baseline, &[0xff, 0xff, 0xff, 0x0f]).is_none());
+        assert!(apply_diff(&baseline, &[10, 5, 10]).is_none());
+    }
+
+    #[test]
+    fn baselines() {
+        let config = DeltaCompression {
+            baselines: 4,
+            keyframe_interval: 10,
+        };
+        let mut sender = DeltaSender::new(config.clone());
+        let mut receiver = DeltaReceiver::new(&config);
+        let state = |tick: u64| {
+            let mut state = vec![0u8; 100];
+            state[50] = tick as u8;
+            Bytes::from(state)
+        };
+
+        // No acked state, keyframes are sent
+        let keyframe = sender.encode(0, state(0));
+        assert_eq!(Header::read(&keyframe).unwrap().0, Header { tick: 0, baseline: None });
+        assert_eq!(receiver.decode(&keyframe), Some((0, state(0))));
+
+        sender.packet_acked(0);
+        let delta = sender.encode(1, state(1));
+        assert_eq!(Header::read(&delta).unwrap().0.baseline, Some(0));
+        assert!(delta.len() < 30);
+
+        // Lost, the next delta still uses the acked baseline
+        let delta = sender.encode(2, state(2));
+        assert_eq!(receiver.decode(&delta), Some((2, state(2))));
+        // Old messages are ignored
+        assert_eq!(receiver.decode(&keyframe), None);
+
+        // The acked baseline is no longer stored
+        for tick in 3..8 {
+            sender.encode(tick, state(tick));
+        }
+        let message = sender.encode(8, state(8));
+        assert_eq!(Header::read(&message).unwrap().0.baseline, None);
+
+        // Keyframe interval
+        sender.packet_acked(8);
+        assert_eq!(sender.acked(), Some(8));
+        let message = sender.encode(9, state(9));
+        assert_eq!(Header::read(&message).unwrap().0.baseline, Some(8));
+        let message = sender.encode(18, state(18));
+        assert_eq!(Header::read(&message).unwrap().0.baseline, None);
+    }
+}
@@ -1,6 +1,10 @@
-use std::fmt;
+use std::{fmt, io, time::Duration};
 
+#[cfg(feature = "serde")]
+use crate::message::MessageError;
 use crate::packet::SerializationError;
+#[cfg(feature = "transport")]
+use crate::transport::{NetcodeError, NetcodeTransportError};
 
 /// Possible reasons for a disconnection.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -21,6 +25,103 @@ pub enum DisconnectReason {
     SendChannelError { channel_id: u8, error: ChannelError },
     /// Error occurred in a receive channel
     ReceiveChannelError { channel_id: u8, error: ChannelError },
+    /// No packet was received from the remote side within the connection timeout
+    TimedOut,
+    /// Connection was terminated by the server because it's shutting down
+    ServerShutdown,
+    /// Connection was terminated by the application with a custom code
+    Custom(u8),
+    /// Connection was terminated by the remote side, with the reason it sent
+    DisconnectedByRemote(DisconnectCode),
+    /// The remote side uses a version that is not compatible, see [`ConnectionConfig::version`](crate::ConnectionConfig::version)
+    IncompatibleVersion { remote_version: u16 },
+    /// The remote side encodes packets with another [`WIRE_VERSION`](crate::packet::WIRE_VERSION)
+    IncompatibleWireVersion { remote_wire_version: u16 },
+}
+
+/// Reason code sent to the remote side of the connection when disconnecting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisconnectCode {
+    /// The remote side disconnected
+    Disconnected,
+    /// The client was disconnected by the server
+    Kicked,
+    /// The remote side did not receive packets within its timeout
+    Timeout,
+    /// The server is shutting down
+    ServerShutdown,
+    /// The remote side received or generated invalid packets or messages
+    ProtocolError,
+    /// Application specific code
+    Custom(u8),
+    /// The versions of the two sides are not compatible
+    IncompatibleVersion,
+}
+
+impl DisconnectCode {
+    pub(crate) fn to_bytes(self) -> (u8, u8) {
+        match self {
+            DisconnectCode::Disconnected => (0, 0),
+            DisconnectCode::Kicked => (1, 0),
+            DisconnectCode::Timeout => (2, 0),
+            DisconnectCode::ServerShutdown => (3, 0),
+            DisconnectCode::ProtocolError => (4, 0),
+            DisconnectCode::Custom(value) => (5, value),
+            DisconnectCode::IncompatibleVersion => (6, 0),
+        }
+    }
+
+    pub(crate) fn from_bytes(code: u8, value: u8) -> Option<Self> {
+        match code {
+            0 => Some(DisconnectCode::Disconnected),
+            1 => Some(DisconnectCode::Kicked),
+            2 => Some(DisconnectCode::Timeout),
+            3 => Some(DisconnectCode::ServerShutdown),
+            4 => Some(DisconnectCode::ProtocolError),
+            5 => Some(DisconnectCode::Custom(value)),
+            6 => Some(DisconnectCode::IncompatibleVersion),
+            _ => None,
+        }
+    }
+}
+
+impl DisconnectReason {
+    /// Returns the code sent to the remote side when disconnecting for this reason.
+    /// Returns `None` when the remote side can't or doesn't need to be notified.
+    pub fn code(&self) -> Option<DisconnectCode> {
+        use DisconnectReason::*;
+
+        match *self {
+            Transport | DisconnectedByRemote(_) => None,
+            DisconnectedByClient => Some(DisconnectCode::Disconnected),
+            DisconnectedByServer => Some(DisconnectCode::Kicked),
+            PacketSerialization(_)
+            | PacketDeserialization(_)
+            | ReceivedInvalidChannelId(_)
+            | SendChannelError { .. }
+            | ReceiveChannelError { .. } => Some(DisconnectCode::ProtocolError),
+            TimedOut => Some(DisconnectCode::Timeout),
+            ServerShutdown => Some(DisconnectCode::ServerShutdown),
+            Custom(value) => Some(DisconnectCode::Custom(value)),
+            IncompatibleVersion { .. } | IncompatibleWireVersion { .. } => Some(DisconnectCode::IncompatibleVersion),
+        }
+    }
+}
+
+impl fmt::Display for DisconnectCode {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use DisconnectCode::*;
+
+        match *self {
+            Disconnected => write!(fmt, "disconnected"),
+            Kicked => write!(fmt, "kicked"),
+            Timeout => write!(fmt, "timed out"),
+            ServerShutdown => write!(fmt, "server shutdown"),
+            ProtocolError => write!(fmt, "protocol error"),
+            Custom(value) => write!(fmt, "custom code {value}"),
+            IncompatibleVersion => write!(fmt, "incompatible version"),
+        }
+    }
 }
 
 /// Possibles errors that can occur in a channel.
@@ -30,6 +131,10 @@ pub enum ChannelError {
     ReliableChannelMaxMemoryReached,
     /// Received an invalid slice message in the channel.
     InvalidSliceMessage,
+    /// Unreliable channel reached maximum allowed memory, the message was not sent
+    UnreliableChannelMaxMemoryReached,
+    /// A reliable message was resent the maximum number of times without being acknowledged
+    DeliveryFailed,
 }
 
 impl fmt::Display for ChannelError {
@@ -39,6 +144,8 @@ impl fmt::Display for ChannelError {
         match *self {
             ReliableChannelMaxMemoryReached => write!(fmt, "reliable channel memory usage was exausted"),
             InvalidSliceMessage => write!(fmt, "received an invalid slice packet"),
+            UnreliableChannelMaxMemoryReached => write!(fmt, "unreliable channel memory usage was exhausted"),
+            DeliveryFailed => write!(fmt, "reliable message was not acknowledged after the maximum number of resends"),
         }
     }
 }
@@ -56,6 +163,14 @@ impl fmt::Display for DisconnectReason {
             ReceivedInvalidChannelId(id) => write!(fmt, "received message with invalid channel {id}"),
             SendChannelError { channel_id, error } => write!(fmt, "send channel {channel_id} with error: {error}"),
             ReceiveChannelError { channel_id, error } => write!(fmt, "receive channel {channel_id} with error: {error}"),
+            TimedOut => write!(fmt, "connection timed out"),
+            ServerShutdown => write!(fmt, "server shutdown"),
+            Custom(value) => write!(fmt, "connection terminated with custom code {value}"),
+            DisconnectedByRemote(code) => write!(fmt, "connection terminated by the remote side: {code}"),
+            IncompatibleVersion { remote_version } => write!(fmt, "remote side has incompatible version {remote_version}"),
+            IncompatibleWireVersion { remote_wire_version } => {
+                write!(fmt, "remote side has incompatible wire format version {remote_wire_version}")
+            }
         }
     }
 }
@@ -72,3 +187,184 @@ impl fmt::Display for ClientNotFound {
         write!(fmt, "client with given id was not found")
     }
 }
+
+/// Invalid value in a [`ChannelConfig`](crate::ChannelConfig) or [`ConnectionConfig`](crate::ConnectionConfig).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The channel can't hold any message
+    ZeroMemoryUsage { channel_id: u8 },
+    /// The reliable channel would only resend its messages after the connection timed out
+    ResendTimeAboveTimeout {
+        channel_id: u8,
+        resend_time: Duration,
+        timeout: Duration,
+    },
+    /// The reliable channel would drop messages the receiver is waiting for
+    DropOldestOnReliableChannel { channel_id: u8 },
+    /// The resend backoff multiplier is below 1 or not finite
+    InvalidResendBackoff { channel_id: u8 },
+    /// The same channel id is used twice in a list of channels
+    DuplicateChannelId(u8),
+    /// The maximum packet size can't fit message slices
+    MaxPacketSizeTooSmall { size: usize, min: usize },
+    /// The maximum packet size is above the size of the packet buffers
+    MaxPacketSizeTooLarge { size: usize, max: usize },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use ConfigError::*;
+
+        match *self {
+            ZeroMemoryUsage { channel_id } => write!(fmt, "channel {channel_id} has a max memory usage of 0 bytes"),
+            ResendTimeAboveTimeout {
+                channel_id,
+                resend_time,
+                timeout,
+            } => write!(
+                fmt,
+                "reliable channel {channel_id} has a resend time of {resend_time:?}, it must be below the connection timeout of {timeout:?}"
+            ),
+            DropOldestOnReliableChannel { channel_id } => {
+                write!(fmt, "reliable channel {channel_id} can't drop the oldest messages when it's full")
+            }
+            InvalidResendBackoff { channel_id } => {
+                write!(
+                    fmt,
+                    "reliable channel {channel_id} has a resend backoff multiplier below 1 or not finite"
+                )
+            }
+            DuplicateChannelId(channel_id) => write!(fmt, "channel id {channel_id} is used by more than one channel"),
+            MaxPacketSizeTooSmall { size, min } => {
+                write!(
+                    fmt,
+                    "max packet size {size} is too small to fit message slices, must be at least {min}"
+                )
+            }
+            MaxPacketSizeTooLarge { size, max } => write!(fmt, "max packet size {size} is above the limit of {max} bytes"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Errors returned by the fallible APIs of renet, wrapping the more specific errors.
+#[derive(Debug)]
+pub enum RenetError {
+    /// The connection is disconnected
+    Disconnected(DisconnectReason),
+    /// No channel with this id is configured
+    InvalidChannelId(u8),
+    /// The channel doesn't deliver messages reliably and in order, as streams require
+    UnorderedChannel(u8),
+    /// Error occurred in a channel
+    Channel { channel_id: u8, error: ChannelError },
+    /// Failed to serialize or deserialize a packet
+    Serialization(SerializationError),
+    /// The connection config is invalid
+    Config(ConfigError),
+    /// Client with the given id was not found
+    ClientNotFound,
+    /// Failed to serialize or deserialize a typed message
+    #[cfg(feature = "serde")]
+    Message(MessageError),
+    /// Error from the netcode protocol, while connecting or processing packets
+    #[cfg(feature = "transport")]
+    Netcode(NetcodeError),
+    /// IO error from a transport
+    Io(io::Error),
+}
+
+impl fmt::Display for RenetError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use RenetError::*;
+
+        match self {
+            Disconnected(reason) => write!(fmt, "disconnected: {reason}"),
+            InvalidChannelId(channel_id) => write!(fmt, "no channel with id {channel_id}"),
+            UnorderedChannel(channel_id) => write!(fmt, "channel {channel_id} is not reliable ordered"),
+            Channel { channel_id, error } => write!(fmt, "channel {channel_id} with error: {error}"),
+            Serialization(err) => err.fmt(fmt),
+            Config(err) => write!(fmt, "invalid config: {err}"),
+            ClientNotFound => write!(fmt, "client with given id was not found"),
+            #[cfg(feature = "serde")]
+            Message(err) => err.fmt(fmt),
+            #[cfg(feature = "transport")]
+            Netcode(err) => err.fmt(fmt),
+            Io(err) => err.fmt(fmt),
+        }
+    }
+}
+
+impl std::error::Error for RenetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use RenetError::*;
+
+        match self {
+            Disconnected(_) | InvalidChannelId(_) | UnorderedChannel(_) | ClientNotFound => None,
+            Channel { error, .. } => Some(error),
+            Serialization(err) => Some(err),
+            Config(err) => Some(err),
+            #[cfg(feature = "serde")]
+            Message(err) => Some(err),
+            #[cfg(feature = "transport")]
+            Netcode(err) => Some(err),
+            Io(err) => Some(err),
+        }
+    }
+}
+
+impl From<DisconnectReason> for RenetError {
+    fn from(inner: DisconnectReason) -> Self {
+        RenetError::Disconnected(inner)
+    }
+}
+
+impl From<SerializationError> for RenetError {
+    fn from(inner: SerializationError) -> Self {
+        RenetError::Serialization(inner)
+    }
+}
+
+impl From<ConfigError> for RenetError {
+    fn from(inner: ConfigError) -> Self {
+        RenetError::Config(inner)
+    }
+}
+
+impl From<ClientNotFound> for RenetError {
+    fn from(_: ClientNotFound) -> Self {
+        RenetError::ClientNotFound
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<MessageError> for RenetError {
+    fn from(inner: MessageError) -> Self {
+        RenetError::Message(inner)
+    }
+}
+
+#[cfg(feature = "transport")]
+impl From<NetcodeError> for RenetError {
+    fn from(inner: NetcodeError) -> Self {
+        RenetError::Netcode(inner)
+    }
+}
+
+#[cfg(feature = "transport")]
+impl From<NetcodeTransportError> for RenetError {
+    fn from(inner: NetcodeTransportError) -> Self {
+        match inner {
+            NetcodeTransportError::Netcode(err) => RenetError::Netcode(err),
+            NetcodeTransportError::Renet(reason) => RenetError::Disconnected(reason),
+            NetcodeTransportError::IO(err) => RenetError::Io(err),
+        }
+    }
+}
+
+impl From<io::Error> for RenetError {
+    fn from(inner: io::Error) -> Self {
+        RenetError::Io(inner)
+    }
+}
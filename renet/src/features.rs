@@ -0,0 +1,175 @@
+use std::{fmt, ops::RangeInclusive};
+
+use crate::{packet::WIRE_VERSION, ConnectionConfig, DisconnectReason};
+
+/// Set of optional protocol features supported by a connection.
+///
+/// Each side of a connection advertises the features it supports, only the features
+/// supported by both sides are used, so peers with different optional features can still communicate.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ProtocolFeatures(u64);
+
+impl ProtocolFeatures {
+    /// No optional features.
+    pub const NONE: Self = Self(0);
+    /// Peers exchange their application activity, see [`ClientActivity`](crate::ClientActivity).
+    pub const CLIENT_ACTIVITY: Self = Self(1 << 0);
+    /// Peers send keep-alive packets when idle, see [`ConnectionConfig::keep_alive_interval`](crate::ConnectionConfig::keep_alive_interval).
+    pub const KEEP_ALIVE: Self = Self(1 << 1);
+    /// Peers send the reason of the disconnection, see [`DisconnectCode`](crate::DisconnectCode).
+    pub const DISCONNECT_REASON: Self = Self(1 << 2);
+    /// Peers answer path MTU probes, see [`MtuDiscovery`](crate::MtuDiscovery).
+    pub const MTU_PROBE: Self = Self(1 << 3);
+    /// Peers answer time requests, see [`TimeSync`](crate::TimeSync).
+    pub const TIME_SYNC: Self = Self(1 << 4);
+    /// All optional features known by this version of the crate.
+    pub const ALL: Self =
+        Self(Self::CLIENT_ACTIVITY.0 | Self::KEEP_ALIVE.0 | Self::DISCONNECT_REASON.0 | Self::MTU_PROBE.0 | Self::TIME_SYNC.0);
+
+    /// Creates a feature set from its raw bits, unknown bits are discarded.
+    pub const fn from_bits_truncate(bits: u64) -> Self {
+        Self(bits & Self::ALL.0)
+    }
+
+    /// Returns the raw bits of the feature set.
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns whether all features in `other` are contained in this set.
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the features available in both sets.
+    pub const fn intersection(&self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    /// Returns the features available in either set.
+    pub const fn union(&self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Returns whether the set has no features.
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl fmt::Debug for ProtocolFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ProtocolFeatures({:#x})", self.0)
+    }
+}
+
+/// Sent by the client in the connection request of transports that support it,
+/// so the server can reject incompatible clients before adding their connection.
+///
+/// Encoded in [`Handshake::BYTES`] bytes, in big-endian: `wire_version: u16`, `version: u16`,
+/// `compatible_versions: u16..=u16`, `features: u64`, then zeros.
+/// The server learns the features of the client from it, so they are not sent after connecting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Handshake {
+    /// The [`WIRE_VERSION`](crate::packet::WIRE_VERSION) of the client.
+    pub wire_version: u16,
+    /// See [`ConnectionConfig::version`].
+    pub version: u16,
+    /// See [`ConnectionConfig::compatible_versions`].
+    pub compatible_versions: RangeInclusive<u16>,
+    /// See [`ConnectionConfig::features`].
+    pub features: ProtocolFeatures,
+}
+
+impl Handshake {
+    /// Size of the encoded handshake.
+    pub const BYTES: usize = 32;
+
+    pub fn to_bytes(&self) -> [u8; Self::BYTES] {
+        let mut bytes = [0u8; Self::BYTES];
+        bytes[0..2].copy_from_slice(&self.wire_version.to_be_bytes());
+        bytes[2..4].copy_from_slice(&self.version.to_be_bytes());
+        bytes[4..6].copy_from_slice(&self.compatible_versions.start().to_be_bytes());
+        bytes[6..8].copy_from_slice(&self.compatible_versions.end().to_be_bytes());
+        bytes[8..16].copy_from_slice(&self.features.bits().to_be_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::BYTES]) -> Self {
+        let read_u16 = |at: usize| u16::from_be_bytes([bytes[at], bytes[at + 1]]);
+        let mut features = [0u8; 8];
+        features.copy_from_slice(&bytes[8..16]);
+        Self {
+            wire_version: read_u16(0),
+            version: read_u16(2),
+            compatible_versions: read_u16(4)..=read_u16(6),
+            features: ProtocolFeatures::from_bits_truncate(u64::from_be_bytes(features)),
+        }
+    }
+
+    /// Checks that both sides accept the version of the other one.
+    pub(crate) fn check(&self, config: &ConnectionConfig) -> Result<(), DisconnectReason> {
+        if self.wire_version != WIRE_VERSION {
+            return Err(DisconnectReason::IncompatibleWireVersion {
+                remote_wire_version: self.wire_version,
+            });
+        }
+        if !compatible_versions(config).contains(&self.version) || !self.compatible_versions.contains(&config.version) {
+            return Err(DisconnectReason::IncompatibleVersion {
+                remote_version: self.version,
+            });
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn compatible_versions(config: &ConnectionConfig) -> RangeInclusive<u16> {
+    config.compatible_versions.clone().unwrap_or(config.version..=config.version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RenetClient;
+
+    #[test]
+    fn unknown_bits() {
+        let features = ProtocolFeatures::from_bits_truncate(u64::MAX);
+        assert_eq!(features, ProtocolFeatures::ALL);
+        assert!(ProtocolFeatures::ALL.contains(ProtocolFeatures::NONE));
+        assert_eq!(features.intersection(ProtocolFeatures::NONE), ProtocolFeatures::NONE);
+    }
+
+    #[test]
+    fn handshake() {
+        let config = |version: u16, compatible_versions: Option<RangeInclusive<u16>>| ConnectionConfig {
+            version,
+            compatible_versions,
+            ..Default::default()
+        };
+        let handshake = RenetClient::new(config(2, Some(1..=3))).start_handshake();
+        assert_eq!(Handshake::from_bytes(&handshake.to_bytes()), handshake);
+
+        assert_eq!(handshake.check(&config(3, Some(2..=3))), Ok(()));
+        assert_eq!(
+            handshake.check(&config(3, None)),
+            Err(DisconnectReason::IncompatibleVersion { remote_version: 2 })
+        );
+        // The server version must also be accepted by the client
+        assert_eq!(
+            handshake.check(&config(4, Some(2..=4))),
+            Err(DisconnectReason::IncompatibleVersion { remote_version: 2 })
+        );
+
+        let mismatched = Handshake {
+            wire_version: WIRE_VERSION + 1,
+            ..handshake
+        };
+        assert_eq!(
+            mismatched.check(&config(2, None)),
+            Err(DisconnectReason::IncompatibleWireVersion {
+                remote_wire_version: WIRE_VERSION + 1
+            })
+        );
+    }
+}
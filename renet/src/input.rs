@@ -0,0 +1,210 @@
+use std::collections::VecDeque;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::packet::SLICE_SIZE;
+
+// Header of an input message: sequence of the first input (8 bytes) and number of inputs (1 byte),
+// followed by the length (2 bytes) and bytes of each input
+const HEADER_BYTES: usize = 9;
+const INPUT_HEADER_BYTES: usize = 2;
+
+/// Returns the sequence of the last input of a message, used to track which packets carried it.
+pub(crate) fn last_input_sequence(message: &[u8]) -> Option<u64> {
+    let mut header = message.get(..HEADER_BYTES)?;
+    let first = header.get_u64();
+    let count = header.get_u8();
+    first.checked_add(count as u64)?.checked_sub(1)
+}
+
+#[derive(Debug)]
+pub(crate) struct InputSender {
+    max_inputs: usize,
+    next_sequence: u64,
+    // Inputs not acknowledged yet, with their sequence
+    unacked: VecDeque<(u64, Bytes)>,
+}
+
+impl InputSender {
+    pub fn new(max_inputs: usize) -> Self {
+        Self {
+            max_inputs: max_inputs.clamp(1, u8::MAX as usize),
+            next_sequence: 0,
+            unacked: VecDeque::new(),
+        }
+    }
+
+    /// Returns the message with the new input and the most recent unacked ones before it,
+    /// as many as fit in one packet.
+    /// Returns `None` if the input is larger than its 2 bytes length prefix allows.
+    pub fn encode(&mut self, input: Bytes) -> Option<Bytes> {
+        if u16::try_from(input.len()).is_err() {
+            return None;
+        }
+
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.unacked.push_back((sequence, input));
+        while self.unacked.len() > self.max_inputs {
+            self.unacked.pop_front();
+        }
+
+        // The new input is always sent, even if it's too large to fit in a packet
+        let mut count = 1;
+        let mut size = HEADER_BYTES + INPUT_HEADER_BYTES + self.unacked.back().unwrap().1.len();
+        for (_, input) in self.unacked.iter().rev().skip(1) {
+            if size + INPUT_HEADER_BYTES + input.len() > SLICE_SIZE {
+                break;
+            }
+            size += INPUT_HEADER_BYTES + input.len();
+            count += 1;
+        }
+
+        let inputs = self.unacked.range(self.unacked.len() - count..);
+        let mut message = BytesMut::with_capacity(size);
+        message.put_u64(sequence + 1 - count as u64);
+        message.put_u8(count as u8);
+        for (_, input) in inputs {
+            message.put_u16(input.len() as u16);
+            message.extend_from_slice(input);
+        }
+
+        // Sliced messages are not tracked, the input is only sent once
+        if size > SLICE_SIZE {
+            self.unacked.pop_back();
+        }
+
+        Some(message.freeze())
+    }
+
+    /// Called when a packet with a message with inputs up to this sequence is acknowledged.
+    pub fn acked(&mut self, last_sequence: u64) {
+        while self.unacked.front().is_some_and(|(sequence, _)| *sequence <= last_sequence) {
+            self.unacked.pop_front();
+        }
+    }
+
+    pub fn unacked_inputs(&self) -> usize {
+        self.unacked.len()
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct InputReceiver {
+    next_sequence: u64,
+}
+
+impl InputReceiver {
+    /// Adds the inputs of the message not received yet, in order, and returns how many were skipped
+    /// because they were lost in more consecutive packets than the redundancy covers.
+    /// Returns `None` if the message is malformed.
+    pub fn decode(&mut self, mut message: &[u8], inputs: &mut Vec<(u64, Bytes)>) -> Option<u64> {
+        if message.len() < HEADER_BYTES {
+            return None;
+        }
+        let first = message.get_u64();
+        let count = message.get_u8();
+
+        // The sequence is from the remote side, a message can't end after the last sequence
+        let end = first.checked_add(count as u64)?;
+
+        let mut skipped = 0;
+        for sequence in first..end {
+            if message.len() < INPUT_HEADER_BYTES {
+                return None;
+            }
+            let len = message.get_u16() as usize;
+            if message.len() < len {
+                return None;
+            }
+            let input = &message[..len];
+            message = &message[len..];
+
+            if sequence >= self.next_sequence {
+                skipped += sequence - self.next_sequence;
+                inputs.push((sequence, Bytes::copy_from_slice(input)));
+                self.next_sequence = sequence + 1;
+            }
+        }
+
+        Some(skipped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(receiver: &mut InputReceiver, message: &[u8]) -> Vec<u64> {
+        let mut inputs = vec![];
+        receiver.decode(message, &mut inputs).unwrap();
+        inputs.into_iter().map(|(sequence, _)| sequence).collect()
+    }
+
+    #[test]
+    fn redundancy() {
+        let mut sender = InputSender::new(4);
+        let mut receiver = InputReceiver::default();
+
+        let message = sender.encode(Bytes::from_static(b"a")).unwrap();
+        assert_eq!(last_input_sequence(&message), Some(0));
+        assert_eq!(decode(&mut receiver, &message), vec![0]);
+
+        // Lost
+        sender.encode(Bytes::from_static(b"b")).unwrap();
+        let message = sender.encode(Bytes::from_static(b"c")).unwrap();
+        let mut inputs = vec![];
+        receiver.decode(&message, &mut inputs).unwrap();
+        assert_eq!(inputs, vec![(1, Bytes::from_static(b"b")), (2, Bytes::from_static(b"c"))]);
+        // Duplicated
+        assert!(decode(&mut receiver, &message).is_empty());
+
+        sender.acked(1);
+        assert_eq!(sender.unacked_inputs(), 1);
+        let message = sender.encode(Bytes::from_static(b"d")).unwrap();
+        assert_eq!(decode(&mut receiver, &message), vec![3]);
+
+        // More lost inputs than the window
+        for _ in 0..5 {
+            sender.encode(Bytes::from_static(b"e")).unwrap();
+        }
+        let message = sender.encode(Bytes::from_static(b"f")).unwrap();
+        let mut inputs = vec![];
+        assert_eq!(receiver.decode(&message, &mut inputs), Some(2));
+        assert_eq!(inputs.first().unwrap().0, 6);
+
+        assert_eq!(receiver.decode(&message[..message.len() - 1], &mut inputs), None);
+
+        // Inputs that don't fit a packet are not resent
+        let message = sender.encode(vec![0; SLICE_SIZE].into()).unwrap();
+        assert_eq!(last_input_sequence(&message), Some(10));
+        assert_eq!(sender.unacked_inputs(), 3);
+    }
+
+    #[test]
+    fn input_too_large() {
+        let mut sender = InputSender::new(4);
+        assert!(sender.encode(vec![0; u16::MAX as usize + 1].into()).is_none());
+        assert_eq!(sender.unacked_inputs(), 0);
+
+        // The rejected input didn't use a sequence
+        let message = sender.encode(vec![0; u16::MAX as usize].into()).unwrap();
+        assert_eq!(last_input_sequence(&message), Some(0));
+    }
+
+    #[test]
+    fn sequence_overflow() {
+        let mut message = BytesMut::new();
+        message.put_u64(u64::MAX);
+        message.put_u8(2);
+        for _ in 0..2 {
+            message.put_u16(1);
+            message.put_u8(0);
+        }
+
+        assert_eq!(last_input_sequence(&message), None);
+        let mut inputs = vec![];
+        assert_eq!(InputReceiver::default().decode(&message, &mut inputs), None);
+        assert!(inputs.is_empty());
+    }
+}
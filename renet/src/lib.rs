@@ -1,20 +1,67 @@
+mod capture;
 mod channel;
+mod checksum;
+mod clock;
+#[cfg(any(feature = "lz4", feature = "snappy"))]
+mod compression;
+mod congestion;
 mod connection_stats;
+mod delta;
 mod error;
-mod packet;
+mod features;
+mod input;
+#[cfg(feature = "serde")]
+mod message;
+mod mtu;
+mod pacing;
+mod pool;
 mod remote_connection;
+mod replay;
 mod server;
+#[cfg(feature = "metrics")]
+mod telemetry;
+mod time_sync;
+mod varint;
+
+pub mod bits;
+pub mod packet;
+pub mod quantize;
+pub mod replication;
+pub mod sequence;
+pub mod snapshot;
+pub mod voice;
 
 #[cfg(feature = "transport")]
 pub mod transport;
 
-pub use channel::{ChannelConfig, DefaultChannel, SendType};
-pub use error::{ChannelError, ClientNotFound, DisconnectReason};
-pub use remote_connection::{ConnectionConfig, NetworkInfo, RenetClient, RenetConnectionStatus};
+pub use capture::{CaptureDirection, PacketCapture, PcapWriter};
+pub use channel::{ChannelConfig, ChannelConfigBuilder, ChannelId, DefaultChannel, FullQueuePolicy, MaxResends, ResendBackoff, SendType};
+pub use checksum::ChecksumPacketSerializer;
+pub use clock::{Clock, ManualClock, SystemClock};
+#[cfg(any(feature = "lz4", feature = "snappy"))]
+pub use compression::{CompressedPacketSerializer, CompressionAlgorithm};
+pub use congestion::{CongestionControl, CongestionMode};
+pub use connection_stats::{ChannelStats, ConnectionStats, NetworkHistory, NetworkSample, RttPercentiles};
+pub use delta::DeltaCompression;
+pub use error::{ChannelError, ClientNotFound, ConfigError, DisconnectCode, DisconnectReason, RenetError};
+pub use features::{Handshake, ProtocolFeatures};
+#[cfg(feature = "serde")]
+pub use message::{MessageError, MessageRegistry, NetworkMessage};
+pub use mtu::MtuDiscovery;
+pub use pacing::Pacing;
+pub use remote_connection::{
+    ChannelQueueInfo, ClientActivity, ConnectionConfig, ConnectionEvent, ConnectionState, NetworkInfo, RenetClient, RenetConnectionStatus,
+    SnapshotSendRate,
+};
+pub use replay::{RecordedEvent, SessionEvent, SessionRecorder, SessionRecording, SessionReplay};
 pub use server::{RenetServer, ServerEvent};
+pub use time_sync::TimeSync;
 
 pub use bytes::Bytes;
 
+#[cfg(feature = "derive")]
+pub use renet_derive::ChannelId;
+
 /// Unique identifier for clients.
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Ord, PartialOrd)]
 pub struct ClientId(u64);
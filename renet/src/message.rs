@@ -0,0 +1,313 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fmt,
+};
+
+use bytes::Bytes;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::bits::{bits_required, BitReader, BitWriter};
+
+/// Message that can be sent using a [`MessageRegistry`], implemented for all serde types.
+pub trait NetworkMessage: Serialize + DeserializeOwned + Send + Sync + 'static {}
+
+impl<T: Serialize + DeserializeOwned + Send + Sync + 'static> NetworkMessage for T {}
+
+type BoxedMessage = Box<dyn Any + Send + Sync>;
+type DecodeFn = fn(&[u8]) -> Result<BoxedMessage, bincode::Error>;
+
+struct RegisteredMessage {
+    channel_id: u8,
+    type_id: TypeId,
+    type_name: &'static str,
+    decode: DecodeFn,
+}
+
+/// Maps message types to the channels they are sent over, so typed messages can be sent and
+/// received with [`RenetClient::send`](crate::RenetClient::send) and [`RenetServer::send`](crate::RenetServer::send).
+///
+/// Messages are serialized with bincode, prefixed with the index of their type in the registry,
+/// so multiple types can share the same channel. The index only uses the bits needed for the
+/// registered types, padded to a byte: no bytes for a single type, one byte for up to 256 types.
+/// The client and the server must register the same types in the same order.
+///
+/// # Usage
+/// ```
+/// # use renet::{DefaultChannel, MessageRegistry};
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Serialize, Deserialize)]
+/// struct PlayerInput {
+///     up: bool,
+///     down: bool,
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Chat(String);
+///
+/// let registry = MessageRegistry::new()
+///     .register::<PlayerInput>(DefaultChannel::Unreliable)
+///     .register::<Chat>(DefaultChannel::ReliableOrdered);
+/// ```
+#[derive(Default)]
+pub struct MessageRegistry {
+    messages: Vec<RegisteredMessage>,
+    indices: HashMap<TypeId, u16>,
+}
+
+impl fmt::Debug for MessageRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.messages.iter().map(|message| (message.type_name, message.channel_id)))
+            .finish()
+    }
+}
+
+impl MessageRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the message type to be sent over the channel.
+    /// Panics if the type is already registered.
+    pub fn register<T: NetworkMessage>(mut self, channel_id: impl Into<u8>) -> Self {
+        let type_id = TypeId::of::<T>();
+        assert!(
+            !self.indices.contains_key(&type_id),
+            "message {} already registered",
+            type_name::<T>()
+        );
+        let index = u16::try_from(self.messages.len()).expect("too many message types registered");
+
+        self.indices.insert(type_id, index);
+        self.messages.push(RegisteredMessage {
+            channel_id: channel_id.into(),
+            type_id,
+            type_name: type_name::<T>(),
+            decode: |bytes| Ok(Box::new(bincode::deserialize::<T>(bytes)?)),
+        });
+        self
+    }
+
+    /// Returns the channel of the message type, if it's registered.
+    pub fn channel_id<T: NetworkMessage>(&self) -> Option<u8> {
+        let index = self.indices.get(&TypeId::of::<T>())?;
+        Some(self.messages[*index as usize].channel_id)
+    }
+
+    // Bits used by the message type index
+    fn tag_bits(&self) -> u32 {
+        bits_required(self.messages.len().saturating_sub(1) as u64)
+    }
+
+    // Returns the channel and the serialized message, panics if the type isn't registered.
+    pub(crate) fn serialize<T: NetworkMessage>(&self, message: &T) -> Result<(u8, Bytes), MessageError> {
+        let Some(&index) = self.indices.get(&TypeId::of::<T>()) else {
+            panic!("message {} is not registered", type_name::<T>());
+        };
+
+        let mut writer = BitWriter::new();
+        writer.write_bits(index as u64, self.tag_bits());
+        let mut bytes = writer.finish();
+        bincode::serialize_into(&mut bytes, message).map_err(|e| MessageError::Serialization {
+            type_name: type_name::<T>(),
+            error: e.to_string(),
+        })?;
+
+        Ok((self.messages[index as usize].channel_id, bytes.into()))
+    }
+
+    pub(crate) fn deserialize(&self, channel_id: u8, message: &[u8]) -> Result<(TypeId, BoxedMessage), MessageError> {
+        let mut reader = BitReader::new(message);
+        let tag = match reader.read_bits(self.tag_bits()) {
+            Some(tag) => tag as u16,
+            None => return Err(MessageError::UnknownType { channel_id, tag: None }),
+        };
+
+        let registered = match self.messages.get(tag as usize) {
+            Some(registered) if registered.channel_id == channel_id => registered,
+            _ => {
+                return Err(MessageError::UnknownType {
+                    channel_id,
+                    tag: Some(tag),
+                })
+            }
+        };
+
+        let value = (registered.decode)(reader.remaining_bytes()).map_err(|e| MessageError::Serialization {
+            type_name: registered.type_name,
+            error: e.to_string(),
+        })?;
+
+        Ok((registered.type_id, value))
+    }
+}
+
+/// Error while serializing or deserializing a typed message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageError {
+    /// A message was received without a type registered for its channel.
+    UnknownType {
+        channel_id: u8,
+        tag: Option<u16>,
+    },
+    Serialization {
+        type_name: &'static str,
+        error: String,
+    },
+}
+
+impl Error for MessageError {}
+
+impl fmt::Display for MessageError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use MessageError::*;
+
+        match *self {
+            UnknownType {
+                channel_id,
+                tag: Some(tag),
+            } => write!(fmt, "received message with unknown type {tag} in channel {channel_id}"),
+            UnknownType { channel_id, tag: None } => write!(fmt, "received message without type in channel {channel_id}"),
+            Serialization { type_name, ref error } => write!(fmt, "failed to serialize message {type_name}: {error}"),
+        }
+    }
+}
+
+// Messages of a type that are not read, and errors that are not checked, would grow forever
+// if the remote side keeps sending them, so the oldest ones are discarded.
+const MAX_QUEUED_MESSAGES_PER_TYPE: usize = 1024;
+const MAX_QUEUED_ERRORS: usize = 256;
+
+// Received typed messages, waiting to be read by their type.
+#[derive(Debug, Default)]
+pub(crate) struct TypedMessages {
+    queues: HashMap<TypeId, VecDeque<BoxedMessage>>,
+    errors: VecDeque<MessageError>,
+}
+
+impl TypedMessages {
+    // Decodes the messages from the channel of the type, and returns the first one with the type.
+    pub fn receive<T: NetworkMessage>(
+        &mut self,
+        registry: &MessageRegistry,
+        mut receive_message: impl FnMut(u8) -> Option<Bytes>,
+    ) -> Option<T> {
+        let Some(channel_id) = registry.channel_id::<T>() else {
+            panic!("message {} is not registered", type_name::<T>());
+        };
+
+        while let Some(message) = receive_message(channel_id) {
+            match registry.deserialize(channel_id, &message) {
+                Ok((type_id, value)) => {
+                    let queue = self.queues.entry(type_id).or_default();
+                    if queue.len() >= MAX_QUEUED_MESSAGES_PER_TYPE {
+                        log::warn!("dropped oldest typed message of channel {channel_id} because its queue is full");
+                        queue.pop_front();
+                    }
+                    queue.push_back(value);
+                }
+                Err(e) => self.push_error(e),
+            }
+        }
+
+        let value = self.queues.get_mut(&TypeId::of::<T>())?.pop_front()?;
+        Some(*value.downcast::<T>().expect("message queued with the wrong type"))
+    }
+
+    pub fn push_error(&mut self, error: MessageError) {
+        if self.errors.len() >= MAX_QUEUED_ERRORS {
+            self.errors.pop_front();
+        }
+        self.errors.push_back(error);
+    }
+
+    pub fn pop_error(&mut self) -> Option<MessageError> {
+        self.errors.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Position {
+        x: f32,
+        y: f32,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Chat(String);
+
+    #[test]
+    fn shared_channel() {
+        let registry = MessageRegistry::new().register::<Position>(0).register::<Chat>(0);
+
+        let mut channel = VecDeque::new();
+        for message in [
+            registry.serialize(&Chat("hi".to_string())),
+            registry.serialize(&Position { x: 1.0, y: 2.0 }),
+        ] {
+            let (channel_id, bytes) = message.unwrap();
+            assert_eq!(channel_id, 0);
+            channel.push_back(bytes);
+        }
+        // Corrupted message
+        channel.push_back(Bytes::from_static(&[0, 1]));
+
+        let mut typed_messages = TypedMessages::default();
+        let position = typed_messages.receive::<Position>(&registry, |_| channel.pop_front());
+        assert_eq!(position, Some(Position { x: 1.0, y: 2.0 }));
+        let chat = typed_messages.receive::<Chat>(&registry, |_| channel.pop_front());
+        assert_eq!(chat, Some(Chat("hi".to_string())));
+        assert!(typed_messages.receive::<Chat>(&registry, |_| channel.pop_front()).is_none());
+
+        assert!(matches!(typed_messages.pop_error(), Some(MessageError::Serialization { .. })));
+        assert!(typed_messages.pop_error().is_none());
+    }
+
+    #[test]
+    fn bounded_queues() {
+        let registry = MessageRegistry::new().register::<Position>(0).register::<Chat>(0);
+
+        let mut channel = VecDeque::new();
+        for i in 0..MAX_QUEUED_MESSAGES_PER_TYPE + 10 {
+            channel.push_back(registry.serialize(&Chat(i.to_string())).unwrap().1);
+        }
+        for _ in 0..MAX_QUEUED_ERRORS + 10 {
+            channel.push_back(Bytes::from_static(&[0, 1]));
+        }
+
+        // Only reads positions, the chat messages and errors are never read
+        let mut typed_messages = TypedMessages::default();
+        assert!(typed_messages.receive::<Position>(&registry, |_| channel.pop_front()).is_none());
+        assert_eq!(typed_messages.queues[&TypeId::of::<Chat>()].len(), MAX_QUEUED_MESSAGES_PER_TYPE);
+        assert_eq!(typed_messages.errors.len(), MAX_QUEUED_ERRORS);
+
+        // The oldest messages were dropped
+        let chat = typed_messages.receive::<Chat>(&registry, |_| channel.pop_front());
+        assert_eq!(chat, Some(Chat("10".to_string())));
+    }
+
+    #[test]
+    fn unknown_type() {
+        let registry = MessageRegistry::new().register::<Position>(0).register::<Chat>(1);
+        let (_, bytes) = registry.serialize(&Chat("hi".to_string())).unwrap();
+
+        // Sent over the wrong channel
+        let error = registry.deserialize(0, &bytes).unwrap_err();
+        assert_eq!(
+            error,
+            MessageError::UnknownType {
+                channel_id: 0,
+                tag: Some(1)
+            }
+        );
+        let error = registry.deserialize(0, &[]).unwrap_err();
+        assert_eq!(error, MessageError::UnknownType { channel_id: 0, tag: None });
+    }
+}
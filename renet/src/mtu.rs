@@ -0,0 +1,160 @@
+use std::time::Duration;
+
+// How many times a probe size is tried before considering it too big,
+// so a single lost packet doesn't reduce the discovered mtu.
+const PROBE_ATTEMPTS: usize = 3;
+
+/// Configuration for the path MTU discovery.
+///
+/// Padded probe packets are sent with a binary search between `min_size` and `max_size`,
+/// the largest acknowledged size is used as the maximum size for packets with aggregated messages.
+/// Sizes are of the packets given to the transport layer, so the transport overhead is already accounted for.
+///
+/// Sliced messages are always split in chunks of 1200 bytes,
+/// since the receiving side depends on it to reassemble them.
+#[derive(Debug, Clone)]
+pub struct MtuDiscovery {
+    /// Packet size assumed to work before probing.
+    /// Default: 1200
+    pub min_size: usize,
+    /// Largest packet size probed, must not be bigger than what the transport can send.
+    /// Probes are also limited by [`ConnectionConfig::max_packet_size`](crate::ConnectionConfig::max_packet_size).
+    /// Default: 1300 (the maximum payload of the netcode transport)
+    pub max_size: usize,
+    /// Time to wait for the acknowledgment of a probe before sending the next one.
+    /// Default: 500ms
+    pub probe_timeout: Duration,
+}
+
+impl Default for MtuDiscovery {
+    fn default() -> Self {
+        Self {
+            min_size: 1200,
+            max_size: 1300,
+            probe_timeout: Duration::from_millis(500),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Probe {
+    size: usize,
+    sent_at: Duration,
+}
+
+#[derive(Debug)]
+pub(crate) struct MtuProber {
+    probe_timeout: Duration,
+    // Largest size known to work
+    low: usize,
+    // Largest size that could work
+    high: usize,
+    probe: Option<Probe>,
+    attempts: usize,
+}
+
+impl MtuProber {
+    pub fn new(config: &MtuDiscovery, max_packet_size: usize) -> Self {
+        Self {
+            probe_timeout: config.probe_timeout,
+            low: config.min_size,
+            high: config.max_size.min(max_packet_size).max(config.min_size),
+            probe: None,
+            attempts: 0,
+        }
+    }
+
+    pub fn path_mtu(&self) -> usize {
+        self.low
+    }
+
+    /// Returns the size of the next probe to send, if any.
+    pub fn next_probe(&mut self, current_time: Duration) -> Option<usize> {
+        if let Some(probe) = &self.probe {
+            if current_time - probe.sent_at < self.probe_timeout {
+                return None;
+            }
+
+            if self.attempts >= PROBE_ATTEMPTS {
+                self.high = probe.size - 1;
+                self.attempts = 0;
+            }
+            self.probe = None;
+        }
+
+        if self.low >= self.high {
+            return None;
+        }
+
+        let size = self.low + (self.high - self.low).div_ceil(2);
+        self.attempts += 1;
+        self.probe = Some(Probe {
+            size,
+            sent_at: current_time,
+        });
+
+        Some(size)
+    }
+
+    pub fn probe_acked(&mut self, size: usize) {
+        if size > self.low {
+            self.low = size.min(self.high);
+        }
+
+        if matches!(&self.probe, Some(probe) if probe.size == size) {
+            self.probe = None;
+            self.attempts = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_search() {
+        let config = MtuDiscovery {
+            min_size: 500,
+            max_size: 1500,
+            probe_timeout: Duration::from_millis(100),
+        };
+        let mut prober = MtuProber::new(&config, 1500);
+        let mut current_time = Duration::ZERO;
+        let path_mtu = 1234;
+
+        loop {
+            let size = match prober.next_probe(current_time) {
+                Some(size) => size,
+                None => {
+                    // Wait for the probe to timeout
+                    current_time += config.probe_timeout;
+                    match prober.next_probe(current_time) {
+                        Some(size) => size,
+                        None => break,
+                    }
+                }
+            };
+            if size <= path_mtu {
+                prober.probe_acked(size);
+            }
+        }
+
+        assert_eq!(prober.path_mtu(), path_mtu);
+    }
+
+    #[test]
+    fn lost_probe_is_retried() {
+        let config = MtuDiscovery::default();
+        let mut prober = MtuProber::new(&config, 1300);
+
+        assert_eq!(prober.next_probe(Duration::ZERO), Some(1250));
+        // Waiting for the ack
+        assert_eq!(prober.next_probe(Duration::from_millis(100)), None);
+        // Lost, same size is tried again
+        assert_eq!(prober.next_probe(Duration::from_millis(500)), Some(1250));
+        prober.probe_acked(1250);
+        assert_eq!(prober.path_mtu(), 1250);
+        assert_eq!(prober.next_probe(Duration::from_millis(600)), Some(1275));
+    }
+}
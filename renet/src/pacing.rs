@@ -0,0 +1,144 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::packet::Payload;
+
+/// Configuration for the packet pacing.
+///
+/// Instead of sending all packets generated in a tick at once, packets are released with a token bucket:
+/// up to `burst_bytes` can be sent at once, and the bucket is refilled with `bytes_per_second` as the connection is updated.
+/// Packets that can't be sent yet are queued and returned in the next calls to [`RenetClient::get_packets_to_send`],
+/// so updating and sending more often than the game tick spreads the packets over the tick.
+///
+/// [`RenetClient::get_packets_to_send`]: crate::RenetClient::get_packets_to_send
+#[derive(Debug, Clone)]
+pub struct Pacing {
+    /// Rate at which packets are released.
+    /// Default: 1_250_000 (10 Mbps)
+    pub bytes_per_second: u64,
+    /// Bytes that can be sent at once.
+    /// Default: 5_200 (4 packets of 1300 bytes)
+    pub burst_bytes: u64,
+}
+
+impl Default for Pacing {
+    fn default() -> Self {
+        Self {
+            bytes_per_second: 1_250_000,
+            burst_bytes: 5_200,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Pacer {
+    config: Pacing,
+    tokens: f64,
+    queue: VecDeque<(u64, Payload)>,
+    queued_bytes: u64,
+}
+
+impl Pacer {
+    pub fn new(config: Pacing) -> Self {
+        Self {
+            tokens: config.burst_bytes as f64,
+            config,
+            queue: VecDeque::new(),
+            queued_bytes: 0,
+        }
+    }
+
+    pub fn update(&mut self, duration: Duration) {
+        let tokens = self.tokens + self.config.bytes_per_second as f64 * duration.as_secs_f64();
+        self.tokens = tokens.min(self.config.burst_bytes as f64);
+    }
+
+    pub fn queued_packets(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Sequences of the packets waiting to be sent.
+    pub fn queued_sequences(&self) -> impl Iterator<Item = u64> + '_ {
+        self.queue.iter().map(|(sequence, _)| *sequence)
+    }
+
+    /// Time until the next queued packet can be sent.
+    pub fn delay(&self) -> Option<Duration> {
+        if self.queue.is_empty() {
+            return None;
+        }
+        if self.tokens > 0.0 {
+            return Some(Duration::ZERO);
+        }
+
+        Some(Duration::from_secs_f64(-self.tokens / self.config.bytes_per_second as f64))
+    }
+
+    /// Queues the packets with their sequence and returns the ones that can be sent now.
+    pub fn pace(&mut self, packets: Vec<(u64, Payload)>) -> Vec<(u64, Payload)> {
+        for (sequence, packet) in packets {
+            // Keep at most one second of packets, like packets lost in the network
+            // the reliable messages are resent and the unreliable ones dropped.
+            if self.queued_bytes + packet.len() as u64 > self.config.bytes_per_second {
+                log::debug!("dropped packet because the pacing queue is full");
+                continue;
+            }
+            self.queued_bytes += packet.len() as u64;
+            self.queue.push_back((sequence, packet));
+        }
+
+        let mut packets = vec![];
+        while self.tokens > 0.0 {
+            let Some((sequence, packet)) = self.queue.pop_front() else {
+                break;
+            };
+            // Tokens can become negative, so packets bigger than the burst can still be sent
+            self.tokens -= packet.len() as f64;
+            self.queued_bytes -= packet.len() as u64;
+            packets.push((sequence, packet));
+        }
+
+        packets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pacing() {
+        let mut pacer = Pacer::new(Pacing {
+            bytes_per_second: 10_000,
+            burst_bytes: 2_000,
+        });
+
+        let packets = pacer.pace((0..5).map(|sequence| (sequence, vec![0; 1000])).collect());
+        assert_eq!(packets.len(), 2);
+        assert_eq!(pacer.queued_packets(), 3);
+        assert_eq!(pacer.queued_sequences().collect::<Vec<_>>(), vec![2, 3, 4]);
+        assert_eq!(pacer.delay(), Some(Duration::ZERO));
+        assert!(pacer.pace(vec![]).is_empty());
+
+        pacer.update(Duration::from_millis(50));
+        assert_eq!(pacer.pace(vec![])[0].0, 2);
+        assert_eq!(pacer.delay(), Some(Duration::from_millis(50)));
+
+        // The bucket is limited to the burst size
+        pacer.update(Duration::from_secs(10));
+        assert_eq!(pacer.pace(vec![]).len(), 2);
+        assert_eq!(pacer.delay(), None);
+    }
+
+    #[test]
+    fn full_queue() {
+        let mut pacer = Pacer::new(Pacing {
+            bytes_per_second: 10_000,
+            burst_bytes: 1_000,
+        });
+
+        let packets = pacer.pace((0..20).map(|sequence| (sequence, vec![0; 1000])).collect());
+        assert_eq!(packets.len(), 1);
+        assert_eq!(pacer.queued_packets(), 9);
+    }
+}
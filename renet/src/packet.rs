@@ -1,11 +1,90 @@
+//! Wire format of renet packets.
+//!
+//! Every packet is encoded by hand, without relying on any serialization library defaults,
+//! so other implementations (proxies, bots, dissectors) can read and write it.
+//! Fixed size integers are written in network byte order (big-endian), like in the other formats of this crate:
+//! the [`Handshake`](crate::Handshake) and the LAN discovery probes and responses of the transport.
+//! Fields marked as `varint` use the QUIC variable-length integer encoding (RFC 9000, section 16):
+//! the two most significant bits of the first byte are the length (1, 2, 4 or 8 bytes)
+//! and the remaining bits are the value in big-endian.
+//!
+//! The byte order is fixed, it's neither little-endian nor configurable: varints are big-endian by definition,
+//! so a little-endian layout would mix both orders in the same packet, and changing or configuring it would
+//! break compatibility with existing peers, that have no way to agree on the order before the first packet.
+//! The encryption layers below, like netcode, use their own formats (netcode is little-endian).
+//!
+//! All packets start with:
+//!
+//! | Field         | Type     |
+//! |---------------|----------|
+//! | packet type   | u8       |
+//! | sequence      | varint   |
+//!
+//! Followed by the packet specific fields:
+//!
+//! - `0` SmallReliable: `channel_id: varint`, `num_messages: varint`, `consecutive: (num_messages - 1) bits`
+//!   padded to whole bytes, then for each message `message_id: varint`, `len: varint`, `len` bytes of payload.
+//!   Only the first message id is written as is. The bits of `consecutive` are written with a
+//!   [`BitWriter`], one for each of the next messages, set when its id is the previous id plus one:
+//!   the id is then not written. Otherwise it's written as the zigzag encoded difference from the previous id plus one.
+//! - `1` SmallUnreliable: `channel_id: varint`, `num_messages: varint`, then for each message
+//!   `len: varint`, `len` bytes of payload.
+//! - `2` ReliableSlice and `3` UnreliableSlice: `channel_id: varint`, `message_id: varint`, `slice_index: varint`,
+//!   `num_slices: varint`, `len: varint`, `len` bytes of payload.
+//! - `4` Ack: `largest_acked: varint`, `first_range_size: varint`, `num_remaining_ranges: varint`, then for each
+//!   remaining range `gap: varint`, `range_size: varint`. Ranges go from the largest sequence down:
+//!   the first one is `largest_acked - first_range_size ..= largest_acked`, each next one ends at
+//!   `previous_start - gap - 2` and starts `range_size` sequences before its end.
+//! - `5` Features: `features: u64`, `version: u16`. The version was added later, peers that don't send it have version 0.
+//!   Servers only send it after learning the features of the client, from the [`Handshake`](crate::Handshake)
+//!   or from the client's own Features packet. Clients that sent a handshake don't send it.
+//! - `6` Activity: `activity: u8`. Only sent when both sides support [`ProtocolFeatures::CLIENT_ACTIVITY`].
+//! - `7` KeepAlive: no extra fields. Only sent when both sides support [`ProtocolFeatures::KEEP_ALIVE`].
+//! - `8` Disconnect: `code: u8`, `value: u8` (custom code, 0 otherwise). Sent a few times when disconnecting,
+//!   only when both sides support [`ProtocolFeatures::DISCONNECT_REASON`].
+//! - `9` MtuProbe: `size: u16`, then zeros until the packet is `size` bytes long.
+//!   Only sent when both sides support [`ProtocolFeatures::MTU_PROBE`].
+//! - `10` TimeRequest: `time: u64`, the microseconds of the sender's clock.
+//! - `11` TimeResponse: `request_time: u64` (the time of the request), `receive_time: u64`, `send_time: u64`,
+//!   all in microseconds. The last two are of the responder's clock.
+//!   Both are only sent when both sides support [`ProtocolFeatures::TIME_SYNC`].
+//!
+//! Packets with an unknown type are dropped, so newer versions can add packet types for optional features.
+//!
+//! [`ProtocolFeatures::CLIENT_ACTIVITY`]: crate::ProtocolFeatures::CLIENT_ACTIVITY
+//! [`ProtocolFeatures::KEEP_ALIVE`]: crate::ProtocolFeatures::KEEP_ALIVE
+//! [`ProtocolFeatures::DISCONNECT_REASON`]: crate::ProtocolFeatures::DISCONNECT_REASON
+//! [`ProtocolFeatures::MTU_PROBE`]: crate::ProtocolFeatures::MTU_PROBE
+//! [`ProtocolFeatures::TIME_SYNC`]: crate::ProtocolFeatures::TIME_SYNC
+//!
+//! # Acknowledgments
+//!
+//! Instead of a fixed `ack`/`ack_bits` header in each packet, the receiving side keeps the sequences it received
+//! as ranges and sends them in an Ack packet every time packets are generated, until an Ack packet containing
+//! them is itself acknowledged. Since sequences are u64 there is no wrapping, and a single Ack packet can
+//! acknowledge any number of consecutive packets. Acknowledged packets drive the acks of the reliable channels.
 use bytes::Bytes;
 use std::{fmt, ops::Range};
 
+use crate::bits::{BitReader, BitWriter};
+
 pub type Payload = Vec<u8>;
 
-// Sliced messages are split into SLICE_SIZE bytes chunks
+/// Version of the wire format described in this module, increased with every change that
+/// older builds can't parse. It's sent in the [`Handshake`](crate::Handshake) of transports that support it,
+/// so mismatched builds are rejected with [`DisconnectReason::IncompatibleWireVersion`](crate::DisconnectReason::IncompatibleWireVersion)
+/// instead of failing to parse each other's packets.
+///
+/// - `1`: fixed size channel ids and message counts.
+/// - `2`: channel ids and message counts are varints.
+/// - `3`: message ids in SmallReliable packets are delta encoded.
+/// - `4`: consecutive message ids in SmallReliable packets are flagged with a bit instead of a delta.
+pub const WIRE_VERSION: u16 = 4;
+
+/// Sliced messages are split into chunks of this many bytes.
 pub const SLICE_SIZE: usize = 1200;
 
+/// Part of a message that is too big to fit in a single packet.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Slice {
     pub message_id: u64,
@@ -14,40 +93,52 @@ pub struct Slice {
     pub payload: Bytes,
 }
 
+/// Packet sent between renet connections, see the [module documentation](self) for its wire format.
+///
+/// Packet sequences and message ids are u64, so they never wrap in practice.
 #[derive(Debug, PartialEq, Eq)]
 pub enum Packet {
-    // Small messages in a reliable channel are aggregated and sent in this packet
+    /// Small messages in a reliable channel are aggregated and sent in this packet
     SmallReliable {
         sequence: u64,
         channel_id: u8,
         messages: Vec<(u64, Bytes)>,
     },
-    // Small messages in a unreliable channel are aggregated and sent in this packet
+    /// Small messages in a unreliable channel are aggregated and sent in this packet
     SmallUnreliable {
         sequence: u64,
         channel_id: u8,
         messages: Vec<Bytes>,
     },
-    // A big unreliable message is sliced in multiples slice packets
-    UnreliableSlice {
-        sequence: u64,
-        channel_id: u8,
-        slice: Slice,
-    },
-    // A big reliable messages is sliced in multiples slice packets
-    ReliableSlice {
+    /// A big unreliable message is sliced in multiples slice packets
+    UnreliableSlice { sequence: u64, channel_id: u8, slice: Slice },
+    /// A big reliable messages is sliced in multiples slice packets
+    ReliableSlice { sequence: u64, channel_id: u8, slice: Slice },
+    /// Contains the packets that were acked
+    /// Acks are saved in multiples ranges, all values in the ranges are considered acked.
+    Ack { sequence: u64, ack_ranges: Vec<Range<u64>> },
+    /// Optional protocol features supported by the sender
+    Features { sequence: u64, features: u64, version: u16 },
+    /// Application activity of the sender (in game, loading, away)
+    Activity { sequence: u64, activity: u8 },
+    /// Sent when no other packet was sent for a while, keeps acks and rtt updated
+    KeepAlive { sequence: u64 },
+    /// The sender disconnected, with the reason why
+    Disconnect { sequence: u64, code: u8, value: u8 },
+    /// Padded packet used to discover the path mtu, must be serialized with `size` bytes.
+    MtuProbe { sequence: u64, size: u16 },
+    /// Time of the sender's clock, answered with a `TimeResponse`
+    TimeRequest { sequence: u64, time: u64 },
+    /// Answer to a `TimeRequest`, with the times the responder received it and sent the response
+    TimeResponse {
         sequence: u64,
-        channel_id: u8,
-        slice: Slice,
-    },
-    // Contains the packets that were acked
-    // Acks are saved in multiples ranges, all values in the ranges are considered acked.
-    Ack {
-        sequence: u64,
-        ack_ranges: Vec<Range<u64>>,
+        request_time: u64,
+        receive_time: u64,
+        send_time: u64,
     },
 }
 
+/// Error while serializing or deserializing a [`Packet`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SerializationError {
     BufferTooShort,
@@ -56,6 +147,9 @@ pub enum SerializationError {
     EmptySlice,
     InvalidAckRange,
     InvalidPacketType,
+    InvalidChannelId,
+    InvalidCompression,
+    InvalidChecksum,
 }
 
 impl std::error::Error for SerializationError {}
@@ -69,6 +163,9 @@ impl fmt::Display for SerializationError {
             InvalidNumSlices => write!(fmt, "invalid number of slices"),
             InvalidAckRange => write!(fmt, "invalid ack range"),
             InvalidPacketType => write!(fmt, "invalid packet type"),
+            InvalidChannelId => write!(fmt, "invalid channel id"),
+            InvalidCompression => write!(fmt, "invalid compressed packet"),
+            InvalidChecksum => write!(fmt, "invalid checksum"),
             SliceSizeAboveLimit => write!(fmt, "invalid slice size, it's above the limit of {} bytes", SLICE_SIZE),
             EmptySlice => write!(fmt, "invalid slice, slices cannot be empty"),
         }
@@ -81,6 +178,38 @@ impl From<octets::BufferTooShortError> for SerializationError {
     }
 }
 
+/// Encodes and decodes packets, so the wire format can be replaced without forking the crate.
+/// Both sides of a connection must use the same serializer.
+///
+/// The default is [`DefaultPacketSerializer`], the format described in the [module documentation](self).
+pub trait PacketSerializer: fmt::Debug + Send + Sync + 'static {
+    /// Writes the packet to the buffer and returns the number of bytes written.
+    fn serialize(&self, packet: &Packet, buffer: &mut [u8]) -> Result<usize, SerializationError>;
+
+    /// Reads a packet from a received buffer.
+    /// Packets that fail with [`SerializationError::InvalidChecksum`] are dropped, other errors disconnect.
+    fn deserialize(&self, buffer: &[u8]) -> Result<Packet, SerializationError>;
+
+    /// Bytes added to the packets by the serializer, removed from the space available for messages.
+    fn overhead(&self) -> usize {
+        0
+    }
+}
+
+/// Renet wire format, see the [module documentation](self).
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultPacketSerializer;
+
+impl PacketSerializer for DefaultPacketSerializer {
+    fn serialize(&self, packet: &Packet, buffer: &mut [u8]) -> Result<usize, SerializationError> {
+        packet.to_bytes(&mut octets::OctetsMut::with_slice(buffer))
+    }
+
+    fn deserialize(&self, buffer: &[u8]) -> Result<Packet, SerializationError> {
+        Packet::from_bytes(&mut octets::Octets::with_slice(buffer))
+    }
+}
+
 impl Packet {
     pub fn sequence(&self) -> u64 {
         match self {
@@ -88,11 +217,36 @@ impl Packet {
             | Packet::SmallUnreliable { sequence, .. }
             | Packet::UnreliableSlice { sequence, .. }
             | Packet::ReliableSlice { sequence, .. }
-            | Packet::Ack { sequence, .. } => *sequence,
+            | Packet::Ack { sequence, .. }
+            | Packet::Features { sequence, .. }
+            | Packet::Activity { sequence, .. }
+            | Packet::KeepAlive { sequence }
+            | Packet::Disconnect { sequence, .. }
+            | Packet::MtuProbe { sequence, .. }
+            | Packet::TimeRequest { sequence, .. }
+            | Packet::TimeResponse { sequence, .. } => *sequence,
         }
     }
 
-    pub fn to_bytes(&self, b: &mut octets::OctetsMut) -> Result<usize, SerializationError> {
+    /// Returns the name of the packet type, useful for logs and traces.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Packet::SmallReliable { .. } => "SmallReliable",
+            Packet::SmallUnreliable { .. } => "SmallUnreliable",
+            Packet::ReliableSlice { .. } => "ReliableSlice",
+            Packet::UnreliableSlice { .. } => "UnreliableSlice",
+            Packet::Ack { .. } => "Ack",
+            Packet::Features { .. } => "Features",
+            Packet::Activity { .. } => "Activity",
+            Packet::KeepAlive { .. } => "KeepAlive",
+            Packet::Disconnect { .. } => "Disconnect",
+            Packet::MtuProbe { .. } => "MtuProbe",
+            Packet::TimeRequest { .. } => "TimeRequest",
+            Packet::TimeResponse { .. } => "TimeResponse",
+        }
+    }
+
+    pub(crate) fn to_bytes(&self, b: &mut octets::OctetsMut) -> Result<usize, SerializationError> {
         let before = b.cap();
 
         match self {
@@ -103,10 +257,27 @@ impl Packet {
             } => {
                 b.put_u8(0)?;
                 b.put_varint(*sequence)?;
-                b.put_u8(*channel_id)?;
-                b.put_u16(messages.len() as u16)?;
+                b.put_varint(*channel_id as u64)?;
+                b.put_varint(messages.len() as u64)?;
+                let mut consecutive = BitWriter::with_capacity(consecutive_bytes(messages.len() as u64));
+                for pair in messages.windows(2) {
+                    consecutive.write_bool(pair[1].0 == pair[0].0.wrapping_add(1));
+                }
+                b.put_bytes(&consecutive.finish())?;
+
+                let mut next_message_id = None;
                 for (message_id, message) in messages {
-                    b.put_varint(*message_id)?;
+                    match next_message_id {
+                        None => {
+                            b.put_varint(*message_id)?;
+                        }
+                        // Flagged as consecutive
+                        Some(next) if next == *message_id => {}
+                        Some(next) => {
+                            b.put_varint(zigzag_encode(message_id.wrapping_sub(next) as i64))?;
+                        }
+                    }
+                    next_message_id = Some(message_id.wrapping_add(1));
                     b.put_varint(message.len() as u64)?;
                     b.put_bytes(message)?;
                 }
@@ -118,8 +289,8 @@ impl Packet {
             } => {
                 b.put_u8(1)?;
                 b.put_varint(*sequence)?;
-                b.put_u8(*channel_id)?;
-                b.put_u16(messages.len() as u16)?;
+                b.put_varint(*channel_id as u64)?;
+                b.put_varint(messages.len() as u64)?;
                 for message in messages {
                     b.put_varint(message.len() as u64)?;
                     b.put_bytes(message)?;
@@ -132,7 +303,7 @@ impl Packet {
             } => {
                 b.put_u8(2)?;
                 b.put_varint(*sequence)?;
-                b.put_u8(*channel_id)?;
+                b.put_varint(*channel_id as u64)?;
                 b.put_varint(slice.message_id)?;
                 b.put_varint(slice.slice_index as u64)?;
                 b.put_varint(slice.num_slices as u64)?;
@@ -146,7 +317,7 @@ impl Packet {
             } => {
                 b.put_u8(3)?;
                 b.put_varint(*sequence)?;
-                b.put_u8(*channel_id)?;
+                b.put_varint(*channel_id as u64)?;
                 b.put_varint(slice.message_id)?;
                 b.put_varint(slice.slice_index as u64)?;
                 b.put_varint(slice.num_slices as u64)?;
@@ -200,22 +371,78 @@ impl Packet {
                     previous_range_start = range.start;
                 }
             }
+            Packet::Features {
+                sequence,
+                features,
+                version,
+            } => {
+                b.put_u8(5)?;
+                b.put_varint(*sequence)?;
+                b.put_u64(*features)?;
+                b.put_u16(*version)?;
+            }
+            Packet::Activity { sequence, activity } => {
+                b.put_u8(6)?;
+                b.put_varint(*sequence)?;
+                b.put_u8(*activity)?;
+            }
+            Packet::KeepAlive { sequence } => {
+                b.put_u8(7)?;
+                b.put_varint(*sequence)?;
+            }
+            Packet::Disconnect { sequence, code, value } => {
+                b.put_u8(8)?;
+                b.put_varint(*sequence)?;
+                b.put_u8(*code)?;
+                b.put_u8(*value)?;
+            }
+            Packet::MtuProbe { sequence, size } => {
+                b.put_u8(9)?;
+                b.put_varint(*sequence)?;
+                b.put_u16(*size)?;
+                let padding = (*size as usize).saturating_sub(before - b.cap());
+                b.put_bytes(&vec![0; padding])?;
+            }
+            Packet::TimeRequest { sequence, time } => {
+                b.put_u8(10)?;
+                b.put_varint(*sequence)?;
+                b.put_u64(*time)?;
+            }
+            Packet::TimeResponse {
+                sequence,
+                request_time,
+                receive_time,
+                send_time,
+            } => {
+                b.put_u8(11)?;
+                b.put_varint(*sequence)?;
+                b.put_u64(*request_time)?;
+                b.put_u64(*receive_time)?;
+                b.put_u64(*send_time)?;
+            }
         }
 
         Ok(before - b.cap())
     }
 
-    pub fn from_bytes(b: &mut octets::Octets) -> Result<Packet, SerializationError> {
+    pub(crate) fn from_bytes(b: &mut octets::Octets) -> Result<Packet, SerializationError> {
         let packet_type = b.get_u8()?;
         match packet_type {
             0 => {
                 // SmallReliable
                 let sequence = b.get_varint()?;
-                let channel_id = b.get_u8()?;
-                let messages_len = b.get_u16()?;
+                let channel_id = get_channel_id(b)?;
+                let messages_len = b.get_varint()?;
+                let mut consecutive = BitReader::new(b.get_bytes(consecutive_bytes(messages_len))?.buf());
                 let mut messages: Vec<(u64, Bytes)> = Vec::with_capacity(64);
+                let mut next_message_id: Option<u64> = None;
                 for _ in 0..messages_len {
-                    let message_id = b.get_varint()?;
+                    let message_id = match next_message_id {
+                        None => b.get_varint()?,
+                        Some(next) if consecutive.read_bool() == Some(true) => next,
+                        Some(next) => next.wrapping_add(zigzag_decode(b.get_varint()?) as u64),
+                    };
+                    next_message_id = Some(message_id.wrapping_add(1));
                     let payload = b.get_bytes_with_varint_length()?;
 
                     messages.push((message_id, payload.to_vec().into()));
@@ -230,8 +457,8 @@ impl Packet {
             1 => {
                 // SmallUnreliable
                 let sequence = b.get_varint()?;
-                let channel_id = b.get_u8()?;
-                let messages_len = b.get_u16()?;
+                let channel_id = get_channel_id(b)?;
+                let messages_len = b.get_varint()?;
                 let mut messages: Vec<Bytes> = Vec::with_capacity(64);
                 for _ in 0..messages_len {
                     let payload = b.get_bytes_with_varint_length()?;
@@ -247,7 +474,7 @@ impl Packet {
             2 => {
                 // ReliableSlice
                 let sequence = b.get_varint()?;
-                let channel_id = b.get_u8()?;
+                let channel_id = get_channel_id(b)?;
                 let message_id = b.get_varint()?;
                 let slice_index = b.get_varint()? as usize;
                 let num_slices = b.get_varint()? as usize;
@@ -280,7 +507,7 @@ impl Packet {
             3 => {
                 // UnreliableSlice
                 let sequence = b.get_varint()?;
-                let channel_id = b.get_u8()?;
+                let channel_id = get_channel_id(b)?;
                 let message_id = b.get_varint()?;
                 let slice_index = b.get_varint()? as usize;
                 let num_slices = b.get_varint()? as usize;
@@ -346,11 +573,93 @@ impl Packet {
 
                 Ok(Packet::Ack { sequence, ack_ranges })
             }
+            5 => {
+                // Features
+                let sequence = b.get_varint()?;
+                let features = b.get_u64()?;
+                // Older versions don't send the version, and ignore it when received
+                let version = if b.cap() >= 2 { b.get_u16()? } else { 0 };
+
+                Ok(Packet::Features {
+                    sequence,
+                    features,
+                    version,
+                })
+            }
+            6 => {
+                // Activity
+                let sequence = b.get_varint()?;
+                let activity = b.get_u8()?;
+
+                Ok(Packet::Activity { sequence, activity })
+            }
+            7 => {
+                // KeepAlive
+                let sequence = b.get_varint()?;
+
+                Ok(Packet::KeepAlive { sequence })
+            }
+            8 => {
+                // Disconnect
+                let sequence = b.get_varint()?;
+                let code = b.get_u8()?;
+                let value = b.get_u8()?;
+
+                Ok(Packet::Disconnect { sequence, code, value })
+            }
+            9 => {
+                // MtuProbe
+                let sequence = b.get_varint()?;
+                let size = b.get_u16()?;
+                let padding = (size as usize).saturating_sub(b.off());
+                b.skip(padding)?;
+
+                Ok(Packet::MtuProbe { sequence, size })
+            }
+            10 => {
+                // TimeRequest
+                let sequence = b.get_varint()?;
+                let time = b.get_u64()?;
+
+                Ok(Packet::TimeRequest { sequence, time })
+            }
+            11 => {
+                // TimeResponse
+                let sequence = b.get_varint()?;
+                let request_time = b.get_u64()?;
+                let receive_time = b.get_u64()?;
+                let send_time = b.get_u64()?;
+
+                Ok(Packet::TimeResponse {
+                    sequence,
+                    request_time,
+                    receive_time,
+                    send_time,
+                })
+            }
             _ => Err(SerializationError::InvalidPacketType),
         }
     }
 }
 
+// Maps signed values to unsigned ones, so small negative values are also small varints
+fn zigzag_encode(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn zigzag_decode(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+// Bytes of the consecutive message ids flags, one bit for each message after the first
+fn consecutive_bytes(num_messages: u64) -> usize {
+    num_messages.saturating_sub(1).div_ceil(8) as usize
+}
+
+fn get_channel_id(b: &mut octets::Octets) -> Result<u8, SerializationError> {
+    u8::try_from(b.get_varint()?).map_err(|_| SerializationError::InvalidChannelId)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -435,6 +744,197 @@ mod tests {
         assert_eq!(packet, recv_packet);
     }
 
+    #[test]
+    fn wire_format() {
+        let mut buffer = [0u8; 1300];
+        let packet = Packet::SmallReliable {
+            sequence: 300,
+            channel_id: 2,
+            messages: vec![(64, vec![7, 8].into())],
+        };
+
+        let mut b = octets::OctetsMut::with_slice(&mut buffer);
+        let len = packet.to_bytes(&mut b).unwrap();
+
+        #[rustfmt::skip]
+        let expected = [
+            0,          // packet type
+            0x41, 0x2C, // sequence: 300 as 2 bytes varint
+            2,          // channel id
+            1,          // number of messages
+            0x40, 0x40, // message id: 64 as 2 bytes varint
+            2,          // message length
+            7, 8,       // message payload
+        ];
+        assert_eq!(&buffer[..len], &expected);
+    }
+
+    #[test]
+    fn delta_message_ids() {
+        let mut buffer = [0u8; 1300];
+        let packet = Packet::SmallReliable {
+            sequence: 0,
+            channel_id: 0,
+            messages: vec![
+                (1000, vec![0].into()),
+                (1001, vec![1].into()),
+                (1002, vec![2].into()),
+                (1005, vec![3].into()),
+                (3, vec![4].into()),
+                (4, vec![5].into()),
+            ],
+        };
+
+        let mut b = octets::OctetsMut::with_slice(&mut buffer);
+        let len = packet.to_bytes(&mut b).unwrap();
+        // Header, 1 byte for the consecutive flags, 2 bytes for the first id and the backwards delta,
+        // 1 byte for the forward delta, nothing for the consecutive ids, 2 bytes per message
+        assert_eq!(len, 4 + 1 + 2 + 2 + 1 + 6 * 2);
+        assert_eq!(buffer[4], 0b1100_1000);
+
+        let mut b = octets::Octets::with_slice(&buffer[..len]);
+        assert_eq!(Packet::from_bytes(&mut b).unwrap(), packet);
+    }
+
+    #[test]
+    fn zigzag() {
+        for value in [0, 1, -1, 63, -64, i64::MAX, i64::MIN] {
+            assert_eq!(zigzag_decode(zigzag_encode(value)), value);
+        }
+        assert_eq!(zigzag_encode(-1), 1);
+        assert_eq!(zigzag_encode(1), 2);
+    }
+
+    #[test]
+    fn invalid_channel_id() {
+        // SmallUnreliable packet with channel id 300
+        let buffer = [1, 0, 0x41, 0x2C, 0];
+        let mut b = octets::Octets::with_slice(&buffer);
+        assert_eq!(Packet::from_bytes(&mut b), Err(SerializationError::InvalidChannelId));
+    }
+
+    #[test]
+    fn serialize_features_packet() {
+        let mut buffer = [0u8; 1300];
+
+        let packet = Packet::Features {
+            sequence: 7,
+            features: 0b101,
+            version: 3,
+        };
+
+        let mut b = octets::OctetsMut::with_slice(&mut buffer);
+        let len = packet.to_bytes(&mut b).unwrap();
+
+        let mut b = octets::Octets::with_slice(&buffer[..len]);
+        let recv_packet = Packet::from_bytes(&mut b).unwrap();
+        assert_eq!(packet, recv_packet);
+
+        // Sent without version
+        let mut b = octets::Octets::with_slice(&buffer[..len - 2]);
+        let recv_packet = Packet::from_bytes(&mut b).unwrap();
+        assert_eq!(
+            recv_packet,
+            Packet::Features {
+                sequence: 7,
+                features: 0b101,
+                version: 0
+            }
+        );
+    }
+
+    #[test]
+    fn serialize_activity_packet() {
+        let mut buffer = [0u8; 1300];
+
+        let packet = Packet::Activity { sequence: 3, activity: 1 };
+
+        let mut b = octets::OctetsMut::with_slice(&mut buffer);
+        packet.to_bytes(&mut b).unwrap();
+
+        let mut b = octets::Octets::with_slice(&buffer);
+        let recv_packet = Packet::from_bytes(&mut b).unwrap();
+        assert_eq!(packet, recv_packet);
+    }
+
+    #[test]
+    fn serialize_disconnect_packet() {
+        let mut buffer = [0u8; 1300];
+
+        let packet = Packet::Disconnect {
+            sequence: 10,
+            code: 5,
+            value: 42,
+        };
+
+        let mut b = octets::OctetsMut::with_slice(&mut buffer);
+        packet.to_bytes(&mut b).unwrap();
+
+        let mut b = octets::Octets::with_slice(&buffer);
+        let recv_packet = Packet::from_bytes(&mut b).unwrap();
+        assert_eq!(packet, recv_packet);
+    }
+
+    #[test]
+    fn serialize_time_packets() {
+        let mut buffer = [0u8; 1300];
+
+        for packet in [
+            Packet::TimeRequest {
+                sequence: 4,
+                time: 1_500_000,
+            },
+            Packet::TimeResponse {
+                sequence: 5,
+                request_time: 1_500_000,
+                receive_time: 90_000_000,
+                send_time: 90_016_000,
+            },
+        ] {
+            let mut b = octets::OctetsMut::with_slice(&mut buffer);
+            packet.to_bytes(&mut b).unwrap();
+
+            let mut b = octets::Octets::with_slice(&buffer);
+            let recv_packet = Packet::from_bytes(&mut b).unwrap();
+            assert_eq!(packet, recv_packet);
+        }
+    }
+
+    #[test]
+    fn serialize_keep_alive_packet() {
+        let mut buffer = [0u8; 1300];
+
+        let packet = Packet::KeepAlive { sequence: 300 };
+
+        let mut b = octets::OctetsMut::with_slice(&mut buffer);
+        let len = packet.to_bytes(&mut b).unwrap();
+        assert_eq!(&buffer[..len], &[7, 0x41, 0x2C]);
+
+        let mut b = octets::Octets::with_slice(&buffer);
+        let recv_packet = Packet::from_bytes(&mut b).unwrap();
+        assert_eq!(packet, recv_packet);
+    }
+
+    #[test]
+    fn serialize_mtu_probe_packet() {
+        let mut buffer = [0u8; 1300];
+
+        let packet = Packet::MtuProbe { sequence: 300, size: 1250 };
+
+        let mut b = octets::OctetsMut::with_slice(&mut buffer);
+        let len = packet.to_bytes(&mut b).unwrap();
+        assert_eq!(len, 1250);
+        assert_eq!(&buffer[..5], &[9, 0x41, 0x2C, 0x04, 0xE2]);
+
+        let mut b = octets::Octets::with_slice(&buffer[..len]);
+        let recv_packet = Packet::from_bytes(&mut b).unwrap();
+        assert_eq!(packet, recv_packet);
+
+        // Truncated probe
+        let mut b = octets::Octets::with_slice(&buffer[..1000]);
+        assert!(Packet::from_bytes(&mut b).is_err());
+    }
+
     #[test]
     fn serialize_ack_packet() {
         let mut buffer = [0u8; 1300];
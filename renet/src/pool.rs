@@ -0,0 +1,101 @@
+use bytes::{Bytes, BytesMut};
+
+use crate::packet::Payload;
+use crate::remote_connection::MAX_PACKET_BYTES;
+
+const MAX_POOLED_BUFFERS: usize = 256;
+const MAX_POOLED_PAYLOADS: usize = 64;
+
+/// Reusable message buffers and packet payloads of a connection.
+///
+/// Message buffers are acquired with [`RenetClient::acquire_buffer`](crate::RenetClient::acquire_buffer),
+/// and return to the pool when renet drops the message: once reliable messages are acknowledged,
+/// and once unreliable messages are sent. Buffers still referenced elsewhere are not reclaimed.
+/// Payloads returned by `get_packets_to_send` are reused when given back with `recycle_payloads`.
+#[derive(Debug, Default)]
+pub(crate) struct BufferPool {
+    buffers: Vec<BytesMut>,
+    payloads: Vec<Payload>,
+}
+
+impl BufferPool {
+    pub fn acquire(&mut self) -> BytesMut {
+        self.buffers.pop().unwrap_or_default()
+    }
+
+    /// Returns the buffer of the message to the pool, if it's the only reference to it.
+    pub fn reclaim(&mut self, message: Bytes) {
+        if self.buffers.len() >= MAX_POOLED_BUFFERS {
+            return;
+        }
+
+        if let Ok(mut buffer) = message.try_into_mut() {
+            // Static messages have no capacity and are not worth pooling
+            if buffer.capacity() > 0 {
+                buffer.clear();
+                self.buffers.push(buffer);
+            }
+        }
+    }
+
+    pub fn payload(&mut self, bytes: &[u8]) -> Payload {
+        let mut payload = self.payloads.pop().unwrap_or_else(|| Vec::with_capacity(MAX_PACKET_BYTES));
+        payload.extend_from_slice(bytes);
+        payload
+    }
+
+    pub fn recycle_payload(&mut self, mut payload: Payload) {
+        if self.payloads.len() < MAX_POOLED_PAYLOADS {
+            payload.clear();
+            self.payloads.push(payload);
+        }
+    }
+
+    /// Moves the buffers of the other pool into this one, up to the limit.
+    pub fn take_buffers(&mut self, other: &mut BufferPool) {
+        let count = MAX_POOLED_BUFFERS.saturating_sub(self.buffers.len()).min(other.buffers.len());
+        let start = other.buffers.len() - count;
+        self.buffers.extend(other.buffers.drain(start..));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reclaim_unique_buffers() {
+        let mut pool = BufferPool::default();
+        let mut buffer = pool.acquire();
+        buffer.extend_from_slice(&[1; 100]);
+        let pointer = buffer.as_ptr();
+        let message = buffer.freeze();
+
+        // Still referenced
+        let clone = message.clone();
+        pool.reclaim(message);
+        assert!(pool.buffers.is_empty());
+
+        pool.reclaim(clone);
+        pool.reclaim(Bytes::from_static(b"static"));
+        assert_eq!(pool.buffers.len(), 1);
+
+        let buffer = pool.acquire();
+        assert!(buffer.is_empty());
+        assert!(buffer.capacity() >= 100);
+        assert_eq!(buffer.as_ptr(), pointer);
+    }
+
+    #[test]
+    fn recycle_payloads() {
+        let mut pool = BufferPool::default();
+        let payload = pool.payload(&[1, 2, 3]);
+        assert_eq!(payload, [1, 2, 3]);
+        let pointer = payload.as_ptr();
+
+        pool.recycle_payload(payload);
+        let payload = pool.payload(&[4]);
+        assert_eq!(payload, [4]);
+        assert_eq!(payload.as_ptr(), pointer);
+    }
+}
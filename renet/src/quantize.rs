@@ -0,0 +1,233 @@
+//! Lossy compression of floats, unit vectors and rotations to a fixed number of bits,
+//! written with the [`bits`](crate::bits) writer and reader.
+//!
+//! ```
+//! use renet::bits::{BitReader, BitWriter};
+//! use renet::quantize::{FloatQuantizer, QuaternionQuantizer};
+//!
+//! // Positions between -500 and 500 with a precision of about 1mm
+//! let position = FloatQuantizer::new(-500.0, 500.0, 20);
+//! let rotation = QuaternionQuantizer::new(10);
+//!
+//! let mut writer = BitWriter::new();
+//! position.write(&mut writer, 123.456);
+//! rotation.write(&mut writer, [0.0, 0.0, 0.0, 1.0]);
+//! let bytes = writer.finish();
+//! assert_eq!(bytes.len(), 7);
+//!
+//! let mut reader = BitReader::new(&bytes);
+//! assert!((position.read(&mut reader).unwrap() - 123.456).abs() < 0.001);
+//! assert!(rotation.read(&mut reader).unwrap()[3] > 0.999);
+//! ```
+
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::bits::{BitReader, BitWriter};
+
+/// Maps floats in a range to integers with a fixed number of bits.
+/// Values outside of the range are clamped, NaN is written as the minimum.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FloatQuantizer {
+    min: f32,
+    max: f32,
+    bits: u32,
+}
+
+impl FloatQuantizer {
+    /// Panics if `min` is not less than `max`, or `bits` is not between 1 and 32.
+    pub fn new(min: f32, max: f32, bits: u32) -> Self {
+        assert!(min < max, "quantization range {min}..{max} is empty");
+        assert!((1..=32).contains(&bits), "floats must be quantized with 1 to 32 bits, got {bits}");
+        Self { min, max, bits }
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// Returns the largest difference between a value in the range and its dequantized value.
+    pub fn precision(&self) -> f32 {
+        (self.max - self.min) / self.max_value() as f32 / 2.0
+    }
+
+    fn max_value(&self) -> u64 {
+        (1 << self.bits) - 1
+    }
+
+    pub fn quantize(&self, value: f32) -> u64 {
+        let normalized = ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0);
+        // NaN is mapped to 0 by the cast
+        (normalized as f64 * self.max_value() as f64).round() as u64
+    }
+
+    pub fn dequantize(&self, quantized: u64) -> f32 {
+        let normalized = quantized.min(self.max_value()) as f64 / self.max_value() as f64;
+        (self.min as f64 + normalized * (self.max - self.min) as f64) as f32
+    }
+
+    pub fn write(&self, writer: &mut BitWriter, value: f32) {
+        writer.write_bits(self.quantize(value), self.bits);
+    }
+
+    pub fn read(&self, reader: &mut BitReader) -> Option<f32> {
+        Some(self.dequantize(reader.read_bits(self.bits)?))
+    }
+}
+
+/// Writes unit vectors with two components using the octahedral encoding.
+/// Vectors don't need to be normalized when written, but are always normalized when read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnitVectorQuantizer {
+    component: FloatQuantizer,
+}
+
+impl UnitVectorQuantizer {
+    /// Each of the two components is written with `bits` bits.
+    pub fn new(bits: u32) -> Self {
+        Self {
+            component: FloatQuantizer::new(-1.0, 1.0, bits),
+        }
+    }
+
+    pub fn write(&self, writer: &mut BitWriter, vector: [f32; 3]) {
+        let [x, y, z] = vector;
+        let length = x.abs() + y.abs() + z.abs();
+        let (mut u, mut v) = if length > 0.0 { (x / length, y / length) } else { (0.0, 0.0) };
+        // Fold the lower hemisphere over the upper one
+        if z < 0.0 {
+            (u, v) = ((1.0 - v.abs()) * u.signum(), (1.0 - u.abs()) * v.signum());
+        }
+
+        self.component.write(writer, u);
+        self.component.write(writer, v);
+    }
+
+    pub fn read(&self, reader: &mut BitReader) -> Option<[f32; 3]> {
+        let mut u = self.component.read(reader)?;
+        let mut v = self.component.read(reader)?;
+        let z = 1.0 - u.abs() - v.abs();
+        if z < 0.0 {
+            (u, v) = ((1.0 - v.abs()) * u.signum(), (1.0 - u.abs()) * v.signum());
+        }
+
+        Some(normalize([u, v, z]))
+    }
+}
+
+/// Writes rotations with the smallest three encoding: the index of the largest component of the quaternion
+/// in 2 bits, and the three other components. The largest component is recomputed when reading.
+/// Quaternions are in the `[x, y, z, w]` order, and must be normalized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuaternionQuantizer {
+    component: FloatQuantizer,
+}
+
+impl QuaternionQuantizer {
+    /// Each of the three smallest components is written with `bits` bits.
+    pub fn new(bits: u32) -> Self {
+        Self {
+            // The smallest components of a normalized quaternion are never above 1/sqrt(2)
+            component: FloatQuantizer::new(-FRAC_1_SQRT_2, FRAC_1_SQRT_2, bits),
+        }
+    }
+
+    pub fn write(&self, writer: &mut BitWriter, quaternion: [f32; 4]) {
+        let mut largest = 0;
+        for i in 1..4 {
+            if quaternion[i].abs() > quaternion[largest].abs() {
+                largest = i;
+            }
+        }
+
+        // q and -q are the same rotation, so the largest component is always positive
+        let sign = if quaternion[largest] < 0.0 { -1.0 } else { 1.0 };
+        writer.write_bits(largest as u64, 2);
+        for (i, component) in quaternion.iter().enumerate() {
+            if i != largest {
+                self.component.write(writer, component * sign);
+            }
+        }
+    }
+
+    pub fn read(&self, reader: &mut BitReader) -> Option<[f32; 4]> {
+        let largest = reader.read_bits(2)? as usize;
+        let mut quaternion = [0.0; 4];
+        let mut sum_squares = 0.0;
+        for (i, component) in quaternion.iter_mut().enumerate() {
+            if i != largest {
+                *component = self.component.read(reader)?;
+                sum_squares += *component * *component;
+            }
+        }
+        quaternion[largest] = (1.0 - sum_squares).max(0.0).sqrt();
+
+        Some(quaternion)
+    }
+}
+
+fn normalize<const N: usize>(vector: [f32; N]) -> [f32; N] {
+    let length = vector.iter().map(|c| c * c).sum::<f32>().sqrt();
+    vector.map(|c| c / length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T>(write: impl FnOnce(&mut BitWriter), read: impl FnOnce(&mut BitReader) -> Option<T>) -> T {
+        let mut writer = BitWriter::new();
+        write(&mut writer);
+        let bytes = writer.finish();
+        read(&mut BitReader::new(&bytes)).unwrap()
+    }
+
+    #[test]
+    fn float() {
+        let quantizer = FloatQuantizer::new(-10.0, 10.0, 16);
+        for value in [-10.0, -3.3, 0.0, 0.001, 7.5, 10.0] {
+            let result = round_trip(|w| quantizer.write(w, value), |r| quantizer.read(r));
+            assert!((result - value).abs() <= quantizer.precision(), "{value} became {result}");
+        }
+
+        assert_eq!(quantizer.quantize(-20.0), 0);
+        assert_eq!(quantizer.quantize(20.0), u16::MAX as u64);
+        assert_eq!(quantizer.quantize(f32::NAN), 0);
+        assert_eq!(quantizer.dequantize(0), -10.0);
+        assert_eq!(quantizer.dequantize(u16::MAX as u64), 10.0);
+    }
+
+    #[test]
+    fn unit_vector() {
+        let quantizer = UnitVectorQuantizer::new(12);
+        let vectors = [
+            [1.0, 0.0, 0.0],
+            [0.0, -1.0, 0.0],
+            [0.0, 0.0, -1.0],
+            normalize([1.0, 2.0, 3.0]),
+            normalize([-0.3, 0.5, -0.8]),
+        ];
+        for vector in vectors {
+            let result = round_trip(|w| quantizer.write(w, vector), |r| quantizer.read(r));
+            for i in 0..3 {
+                assert!((result[i] - vector[i]).abs() < 0.002, "{vector:?} became {result:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn quaternion() {
+        let quantizer = QuaternionQuantizer::new(12);
+        let quaternions = [
+            [0.0, 0.0, 0.0, 1.0],
+            [0.0, 0.0, 0.0, -1.0],
+            normalize([0.1, -0.7, 0.2, 0.5]),
+            normalize([-0.5, 0.5, -0.5, 0.5]),
+        ];
+        for quaternion in quaternions {
+            let result = round_trip(|w| quantizer.write(w, quaternion), |r| quantizer.read(r));
+            // q and -q are the same rotation
+            let dot: f32 = (0..4).map(|i| result[i] * quaternion[i]).sum();
+            assert!(dot.abs() > 0.9999, "{quaternion:?} became {result:?}");
+        }
+    }
+}
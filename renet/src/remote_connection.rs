@@ -1,16 +1,44 @@
+use crate::capture::{CaptureDirection, PacketCapture};
 use crate::channel::reliable::{ReceiveChannelReliable, SendChannelReliable};
 use crate::channel::unreliable::{ReceiveChannelUnreliable, SendChannelUnreliable};
 use crate::channel::{ChannelConfig, DefaultChannel, SendType};
-use crate::connection_stats::ConnectionStats;
-use crate::error::DisconnectReason;
-use crate::packet::{Packet, Payload};
-use bytes::Bytes;
-use octets::OctetsMut;
-
-use std::collections::{BTreeMap, HashMap};
-use std::ops::Range;
+use crate::congestion::{CongestionControl, CongestionController, CongestionMode};
+use crate::connection_stats::{
+    BandwidthWindow, ConnectionStats, NetworkHistory, RttPercentiles, RttSamples, StatsWindow, DEFAULT_WINDOW as DEFAULT_STATS_WINDOW,
+};
+use crate::delta::{state_tick, DeltaCompression, DeltaReceiver, DeltaSender};
+use crate::error::{ChannelError, ConfigError, DisconnectCode, DisconnectReason, RenetError};
+use crate::features::{compatible_versions, Handshake, ProtocolFeatures};
+use crate::input::{last_input_sequence, InputReceiver, InputSender};
+#[cfg(feature = "serde")]
+use crate::message::{MessageError, MessageRegistry, NetworkMessage, TypedMessages};
+use crate::mtu::{MtuDiscovery, MtuProber};
+use crate::pacing::{Pacer, Pacing};
+use crate::packet::{DefaultPacketSerializer, Packet, PacketSerializer, Payload, SerializationError, SLICE_SIZE};
+use crate::pool::BufferPool;
+use crate::replay::{SessionEvent, SessionRecorder};
+use crate::time_sync::{TimeSync, TimeSyncEstimator};
+use crate::voice::VoiceFrame;
+use crate::ClientId;
+use bytes::{Buf, Bytes, BytesMut};
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::ops::{Range, RangeInclusive};
+use std::sync::Arc;
 use std::time::Duration;
 
+// Received sequences are acked until an ack packet containing them is acknowledged,
+// so lost ack packets don't cause retransmissions. Only this many non-contiguous ranges are kept.
+const MAX_PENDING_ACK_RANGES: usize = 64;
+
+// Largest header of a packet with aggregated messages, removed from the packet size
+// to get how many bytes of messages can be aggregated in a packet.
+const SMALL_MESSAGES_PACKET_HEADER_BYTES: usize = 13;
+// Largest header of a slice packet, slices are not split further so packets must fit them.
+const SLICE_PACKET_HEADER_BYTES: usize = 29;
+// Size of the buffer packets are serialized into.
+pub(crate) const MAX_PACKET_BYTES: usize = 1400;
+
 /// Configuration for a renet connection and its channels.
 #[derive(Debug, Clone)]
 pub struct ConnectionConfig {
@@ -27,11 +55,89 @@ pub struct ConnectionConfig {
     /// Each tick, the first channel can consume up to `available_bytes_per_tick`,
     /// used bytes are removed from it and passed to the next channel
     pub client_channels_config: Vec<ChannelConfig>,
+    /// Optional protocol features supported by this side of the connection.
+    /// Only the features also supported by the remote side are used.
+    /// Default: all features known by this version of the crate.
+    pub features: ProtocolFeatures,
+    /// How often [`RenetClient::send_snapshot`] sends snapshots, depending on the connection quality.
+    pub snapshot_send_rate: SnapshotSendRate,
+    /// A keep-alive packet is sent when no other packet was sent for this duration,
+    /// so idle connections keep updating their acks and rtt.
+    /// Default: 100ms
+    pub keep_alive_interval: Duration,
+    /// A connected client is disconnected with [`DisconnectReason::TimedOut`]
+    /// if no packet is received from the remote side for this duration.
+    /// Default: 15 seconds
+    pub timeout: Duration,
+    /// Timeout used instead of `timeout` while the remote side is [`ClientActivity::Loading`].
+    /// Default: 60 seconds
+    pub loading_timeout: Duration,
+    /// Duration of the rolling window used for the packet loss and bandwidth stats.
+    /// Rounded to multiples of 300ms, with a minimum of 1.2 seconds.
+    /// Default: 6 seconds
+    pub stats_window: Duration,
+    /// Reduces the bytes sent per tick while the connection has high rtt or packet loss.
+    /// Default: None (disabled)
+    pub congestion_control: Option<CongestionControl>,
+    /// Maximum size in bytes of the packets generated by the connection.
+    /// Must be at least 1229 bytes to fit message slices and at most 1400 bytes.
+    /// Default: 1300 (the maximum payload of the netcode transport)
+    pub max_packet_size: usize,
+    /// Probes the path mtu to choose the size of packets with aggregated messages,
+    /// the packets are still limited by `max_packet_size`.
+    /// Default: None (disabled)
+    pub mtu_discovery: Option<MtuDiscovery>,
+    /// Spreads the sent packets over time, instead of sending all packets of a tick at once.
+    /// Default: None (disabled)
+    pub pacing: Option<Pacing>,
+    /// Synchronizes the clock of clients with the server, see [`RenetClient::server_time_estimate`].
+    /// Only used by clients, servers always answer the time requests.
+    /// Default: None (disabled)
+    pub time_sync: Option<TimeSync>,
+    /// Version of the application protocol. Transports with a handshake, like the netcode transport,
+    /// send it in the connection request so the server rejects incompatible clients before accepting them,
+    /// other transports send it when the connection is established.
+    /// The version of the renet wire format is checked separately, see [`WIRE_VERSION`](crate::packet::WIRE_VERSION).
+    /// Default: 0
+    pub version: u16,
+    /// Versions of the remote side that are accepted, the connection is terminated with
+    /// [`DisconnectReason::IncompatibleVersion`] otherwise. If `None`, only the same `version` is accepted.
+    /// Default: None
+    pub compatible_versions: Option<RangeInclusive<u16>>,
+    /// Format used to encode and decode packets, both sides must use the same one.
+    /// Default: [`DefaultPacketSerializer`]
+    pub packet_serializer: Arc<dyn PacketSerializer>,
+    /// Baselines and keyframes of the states sent with [`RenetClient::send_state`], both sides should use the same one.
+    pub delta_compression: DeltaCompression,
+    /// Maximum number of unacknowledged inputs carried by each message sent with [`RenetClient::send_input`],
+    /// inputs lost in fewer consecutive packets are still received. Default: 8
+    pub input_redundancy: usize,
+}
+
+/// Minimum interval between snapshots sent with [`RenetClient::send_snapshot`].
+///
+/// Connections with packet loss or rtt above the thresholds are considered poor,
+/// and receive snapshots less often to avoid congesting them further.
+#[derive(Debug, Clone)]
+pub struct SnapshotSendRate {
+    /// Interval between snapshots for good connections.
+    /// Default: 0 (a snapshot every time one is sent)
+    pub good_interval: Duration,
+    /// Interval between snapshots for poor connections.
+    /// Default: 50ms (20hz)
+    pub poor_interval: Duration,
+    /// Packet loss above which a connection is considered poor.
+    /// Default: 0.1 (10%)
+    pub poor_packet_loss: f64,
+    /// Round-trip time, in seconds, above which a connection is considered poor.
+    /// Default: 0.25
+    pub poor_rtt: f64,
 }
 
 #[derive(Debug, Clone)]
 struct PacketSent {
     sent_at: Duration,
+    size: usize,
     info: PacketSentInfo,
 }
 
@@ -53,6 +159,22 @@ enum PacketSentInfo {
     Ack {
         largest_acked_packet: u64,
     },
+    // The remote side knows our supported features
+    Features,
+    // The remote side knows our activity
+    Activity(ClientActivity),
+    // The path supports packets of this size
+    MtuProbe(usize),
+    // Messages or a slice of a message of a channel used with `send_state`
+    States {
+        channel_id: u8,
+        ticks: Vec<u64>,
+    },
+    // Messages of a channel used with `send_input`, with the sequence of their last input
+    Inputs {
+        channel_id: u8,
+        last_sequences: Vec<u64>,
+    },
 }
 
 #[derive(Debug)]
@@ -62,12 +184,37 @@ enum ChannelOrder {
 }
 
 /// Describes the stats of a connection.
+#[derive(Debug, Clone)]
 pub struct NetworkInfo {
     /// Round-trip Time
     pub rtt: f64,
+    /// Percentiles and jitter of the recent round-trip times.
+    pub rtt_percentiles: RttPercentiles,
     pub packet_loss: f64,
     pub bytes_sent_per_second: f64,
     pub bytes_received_per_second: f64,
+    /// Kilobits sent per second over the last second, see [`RenetClient::sent_kbps`].
+    pub sent_kbps: f64,
+    /// Kilobits received per second over the last second, see [`RenetClient::received_kbps`].
+    pub received_kbps: f64,
+    /// Bytes of the sent packets that were not acknowledged or considered lost yet.
+    pub bytes_in_flight: u64,
+    /// Number of messages queued in each channel.
+    pub channels: Vec<ChannelQueueInfo>,
+    /// Optional protocol features supported by both sides of the connection.
+    pub negotiated_features: ProtocolFeatures,
+    /// Current congestion avoidance mode, always good when disabled.
+    pub congestion_mode: CongestionMode,
+}
+
+/// Number of messages queued in a channel, see [`NetworkInfo::channels`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelQueueInfo {
+    pub channel_id: u8,
+    /// Messages waiting to be sent, for reliable channels also the ones waiting to be acknowledged.
+    pub send_queue: usize,
+    /// Messages received that were not read yet.
+    pub receive_queue: usize,
 }
 
 /// The connection status of a [`RenetClient`].
@@ -78,6 +225,106 @@ pub enum RenetConnectionStatus {
     Disconnected { reason: DisconnectReason },
 }
 
+/// Detailed state of a [`RenetClient`] connection, see [`RenetClient::state`].
+///
+/// Changes of state can be received with [`RenetClient::next_state_change`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// The connection is terminated, the disconnect reason can be checked with [`RenetClient::disconnect_reason`].
+    Disconnected,
+    /// The transport is establishing the connection.
+    Connecting,
+    /// The transport received the challenge from the server and is waiting for the connection to be accepted.
+    Challenged,
+    /// The connection is established, messages can be exchanged.
+    Connected,
+    /// The connection was terminated, the reason returned by [`RenetClient::disconnect_reason`]
+    /// is still being sent to the remote side.
+    Disconnecting,
+}
+
+impl ConnectionState {
+    /// All the states of the connection state machine.
+    pub const STATES: [ConnectionState; 5] = [
+        ConnectionState::Connecting,
+        ConnectionState::Challenged,
+        ConnectionState::Connected,
+        ConnectionState::Disconnecting,
+        ConnectionState::Disconnected,
+    ];
+
+    /// Returns all possible transitions of the connection state machine, as `(from, to)` states.
+    ///
+    /// Useful for tools that render the state machine, together with [`RenetClient::state`]
+    /// to highlight the current state. The disconnected state is final, a new client must be created.
+    pub const fn transitions() -> &'static [(ConnectionState, ConnectionState)] {
+        use ConnectionState::*;
+
+        &[
+            (Connecting, Challenged),
+            (Connecting, Connected),
+            (Challenged, Connecting),
+            (Challenged, Connected),
+            (Connected, Connecting),
+            (Connecting, Disconnecting),
+            (Connected, Disconnecting),
+            (Disconnecting, Disconnected),
+            (Connecting, Disconnected),
+            (Challenged, Disconnected),
+            (Connected, Disconnected),
+        ]
+    }
+}
+
+/// Events of a [`RenetClient`] connection, see [`RenetClient::next_event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Event))]
+pub enum ConnectionEvent {
+    /// The connection was established.
+    Connected,
+    /// The connection was terminated.
+    Disconnected(DisconnectReason),
+    /// A message can be received from the channel, one event is generated for each message.
+    MessageReceived { channel_id: u8 },
+    /// A sent packet was acknowledged by the remote side.
+    PacketAcked { sequence: u64 },
+    /// A reliable message reached the max resends of its channel without being acknowledged, and is no longer sent.
+    /// See [`MaxResends`](crate::MaxResends).
+    DeliveryFailed { channel_id: u8 },
+}
+
+/// Application activity of a connected peer, visible to the other side of the connection.
+///
+/// Servers can use it to relax timeouts or stop sending snapshots to clients that are loading.
+/// Only exchanged when both sides support [`ProtocolFeatures::CLIENT_ACTIVITY`],
+/// otherwise the remote activity is always [`ClientActivity::InGame`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClientActivity {
+    #[default]
+    InGame,
+    Loading,
+    Afk,
+}
+
+impl ClientActivity {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::InGame),
+            1 => Some(Self::Loading),
+            2 => Some(Self::Afk),
+            _ => None,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::InGame => 0,
+            Self::Loading => 1,
+            Self::Afk => 2,
+        }
+    }
+}
+
 #[derive(Debug)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
 pub struct RenetClient {
@@ -90,10 +337,68 @@ pub struct RenetClient {
     receive_unreliable_channels: HashMap<u8, ReceiveChannelUnreliable>,
     send_reliable_channels: HashMap<u8, SendChannelReliable>,
     receive_reliable_channels: HashMap<u8, ReceiveChannelReliable>,
-    stats: ConnectionStats,
+    // Partially read messages from channels used with `read_stream`
+    stream_read_buffers: HashMap<u8, Bytes>,
+    stats: StatsWindow,
+    bandwidth: BandwidthWindow,
+    connection_stats: ConnectionStats,
+    network_history: NetworkHistory,
+    buffer_pool: BufferPool,
+    // Connections of the server are recorded by the server, labeled with the client id
+    #[cfg(feature = "metrics")]
+    record_metrics: bool,
     available_bytes_per_tick: u64,
     connection_status: RenetConnectionStatus,
+    challenged: bool,
+    state: ConnectionState,
+    state_changes: VecDeque<ConnectionState>,
+    events: VecDeque<ConnectionEvent>,
     rtt: f64,
+    rtt_samples: RttSamples,
+    supported_features: ProtocolFeatures,
+    remote_features: Option<ProtocolFeatures>,
+    features_acked: bool,
+    // Servers only send their features to clients that sent theirs,
+    // clients built before the features packet existed would reject it.
+    wait_for_remote_features: bool,
+    snapshot_send_rate: SnapshotSendRate,
+    last_snapshot_sent_at: Option<Duration>,
+    activity: ClientActivity,
+    activity_acked: bool,
+    remote_activity: ClientActivity,
+    keep_alive_interval: Duration,
+    last_packet_sent_at: Duration,
+    last_packet_received_at: Duration,
+    timeout: Duration,
+    loading_timeout: Duration,
+    disconnect_packets_sent: bool,
+    congestion: Option<CongestionController>,
+    max_packet_size: usize,
+    mtu_prober: Option<MtuProber>,
+    time_sync: Option<TimeSyncEstimator>,
+    // Local time when the connection was created, the time of the server for its connections
+    time_base: Duration,
+    // Time requests received and the time they were received
+    pending_time_responses: Vec<(u64, u64)>,
+    pacer: Option<Pacer>,
+    version: u16,
+    compatible_versions: RangeInclusive<u16>,
+    remote_version: Option<u16>,
+    packet_serializer: Arc<dyn PacketSerializer>,
+    delta_compression: DeltaCompression,
+    delta_senders: HashMap<u8, DeltaSender>,
+    delta_receivers: HashMap<u8, DeltaReceiver>,
+    input_redundancy: usize,
+    input_senders: HashMap<u8, InputSender>,
+    input_receivers: HashMap<u8, InputReceiver>,
+    voice_sequences: HashMap<u8, u64>,
+    // Capture hook and the client id of server connections
+    packet_capture: Option<(Arc<dyn PacketCapture>, Option<ClientId>)>,
+    session_recorder: Option<Arc<SessionRecorder>>,
+    #[cfg(feature = "serde")]
+    message_registry: Option<Arc<MessageRegistry>>,
+    #[cfg(feature = "serde")]
+    typed_messages: TypedMessages,
 }
 
 impl Default for ConnectionConfig {
@@ -103,48 +408,142 @@ impl Default for ConnectionConfig {
             available_bytes_per_tick: 60_000,
             server_channels_config: DefaultChannel::config(),
             client_channels_config: DefaultChannel::config(),
+            features: ProtocolFeatures::ALL,
+            snapshot_send_rate: SnapshotSendRate::default(),
+            keep_alive_interval: Duration::from_millis(100),
+            timeout: Duration::from_secs(15),
+            loading_timeout: Duration::from_secs(60),
+            stats_window: DEFAULT_STATS_WINDOW,
+            congestion_control: None,
+            max_packet_size: 1300,
+            mtu_discovery: None,
+            pacing: None,
+            time_sync: None,
+            version: 0,
+            compatible_versions: None,
+            packet_serializer: Arc::new(DefaultPacketSerializer),
+            delta_compression: DeltaCompression::default(),
+            input_redundancy: 8,
+        }
+    }
+}
+
+impl ConnectionConfig {
+    /// Checks the packet size and channels of the config, returning the first invariant it breaks.
+    /// Connections panic on an invalid packet size, the other errors would only misbehave at runtime.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let min = SLICE_SIZE + SLICE_PACKET_HEADER_BYTES + self.packet_serializer.overhead();
+        if self.max_packet_size < min {
+            return Err(ConfigError::MaxPacketSizeTooSmall {
+                size: self.max_packet_size,
+                min,
+            });
+        }
+        if self.max_packet_size > MAX_PACKET_BYTES {
+            return Err(ConfigError::MaxPacketSizeTooLarge {
+                size: self.max_packet_size,
+                max: MAX_PACKET_BYTES,
+            });
+        }
+
+        for channels in [&self.server_channels_config, &self.client_channels_config] {
+            for (i, channel) in channels.iter().enumerate() {
+                channel.validate()?;
+                if channels[..i].iter().any(|other| other.channel_id == channel.channel_id) {
+                    return Err(ConfigError::DuplicateChannelId(channel.channel_id));
+                }
+                if let Some(resend_time) = channel.resend_time() {
+                    if resend_time >= self.timeout {
+                        return Err(ConfigError::ResendTimeAboveTimeout {
+                            channel_id: channel.channel_id,
+                            resend_time,
+                            timeout: self.timeout,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for SnapshotSendRate {
+    fn default() -> Self {
+        Self {
+            good_interval: Duration::ZERO,
+            poor_interval: Duration::from_millis(50),
+            poor_packet_loss: 0.1,
+            poor_rtt: 0.25,
         }
     }
 }
 
 impl RenetClient {
     pub fn new(config: ConnectionConfig) -> Self {
-        Self::from_channels(
-            config.available_bytes_per_tick,
-            config.client_channels_config,
-            config.server_channels_config,
-        )
+        Self::from_channels(&config, &config.client_channels_config, &config.server_channels_config)
+    }
+
+    /// Creates the client after validating the config, instead of panicking or misbehaving with an invalid one.
+    pub fn try_new(config: ConnectionConfig) -> Result<Self, RenetError> {
+        config.validate()?;
+        Ok(Self::new(config))
     }
 
     // When creating a client from the server, the server_channels_config are used as send channels,
     // and the client_channels_config is used as recv channels.
     pub(crate) fn new_from_server(config: ConnectionConfig) -> Self {
-        Self::from_channels(
-            config.available_bytes_per_tick,
-            config.server_channels_config,
-            config.client_channels_config,
-        )
-    }
-
-    fn from_channels(
-        available_bytes_per_tick: u64,
-        send_channels_config: Vec<ChannelConfig>,
-        receive_channels_config: Vec<ChannelConfig>,
-    ) -> Self {
+        #[allow(unused_mut)]
+        let mut connection = Self::from_channels(&config, &config.server_channels_config, &config.client_channels_config);
+        connection.time_sync = None;
+        connection.wait_for_remote_features = true;
+        #[cfg(feature = "metrics")]
+        {
+            connection.record_metrics = false;
+        }
+        connection
+    }
+
+    fn from_channels(config: &ConnectionConfig, send_channels_config: &[ChannelConfig], receive_channels_config: &[ChannelConfig]) -> Self {
+        assert!(
+            config.max_packet_size >= SLICE_SIZE + SLICE_PACKET_HEADER_BYTES + config.packet_serializer.overhead(),
+            "max packet size {} is too small to fit message slices, must be at least {}",
+            config.max_packet_size,
+            SLICE_SIZE + SLICE_PACKET_HEADER_BYTES + config.packet_serializer.overhead()
+        );
+        assert!(
+            config.max_packet_size <= MAX_PACKET_BYTES,
+            "max packet size {} is above the limit of {} bytes",
+            config.max_packet_size,
+            MAX_PACKET_BYTES
+        );
+
         let mut send_unreliable_channels = HashMap::new();
         let mut send_reliable_channels = HashMap::new();
         let mut channel_send_order: Vec<ChannelOrder> = Vec::with_capacity(send_channels_config.len());
         for channel_config in send_channels_config.iter() {
             match channel_config.send_type {
                 SendType::Unreliable => {
-                    let channel = SendChannelUnreliable::new(channel_config.channel_id, channel_config.max_memory_usage_bytes);
+                    let channel = SendChannelUnreliable::new(
+                        channel_config.channel_id,
+                        channel_config.max_memory_usage_bytes,
+                        channel_config.full_queue_policy,
+                    );
                     let old = send_unreliable_channels.insert(channel_config.channel_id, channel);
                     assert!(old.is_none(), "already exists send channel {}", channel_config.channel_id);
 
                     channel_send_order.push(ChannelOrder::Unreliable(channel_config.channel_id));
                 }
                 SendType::ReliableOrdered { resend_time } | SendType::ReliableUnordered { resend_time } => {
-                    let channel = SendChannelReliable::new(channel_config.channel_id, resend_time, channel_config.max_memory_usage_bytes);
+                    let channel = SendChannelReliable::new(
+                        channel_config.channel_id,
+                        resend_time,
+                        channel_config.resend_backoff,
+                        channel_config.max_resends,
+                        channel_config.max_memory_usage_bytes,
+                        channel_config.full_queue_policy,
+                        matches!(channel_config.send_type, SendType::ReliableOrdered { .. }),
+                    );
                     let old = send_reliable_channels.insert(channel_config.channel_id, channel);
                     assert!(old.is_none(), "already exists send channel {}", channel_config.channel_id);
 
@@ -185,10 +584,62 @@ impl RenetClient {
             receive_unreliable_channels,
             send_reliable_channels,
             receive_reliable_channels,
-            stats: ConnectionStats::new(),
+            stream_read_buffers: HashMap::new(),
+            stats: StatsWindow::new(config.stats_window),
+            bandwidth: BandwidthWindow::default(),
+            connection_stats: ConnectionStats::default(),
+            network_history: NetworkHistory::default(),
+            buffer_pool: BufferPool::default(),
+            #[cfg(feature = "metrics")]
+            record_metrics: true,
             rtt: 0.0,
-            available_bytes_per_tick,
+            rtt_samples: RttSamples::default(),
+            available_bytes_per_tick: config.available_bytes_per_tick,
             connection_status: RenetConnectionStatus::Connecting,
+            challenged: false,
+            state: ConnectionState::Connecting,
+            state_changes: VecDeque::new(),
+            events: VecDeque::new(),
+            supported_features: config.features,
+            remote_features: None,
+            features_acked: false,
+            wait_for_remote_features: false,
+            snapshot_send_rate: config.snapshot_send_rate.clone(),
+            last_snapshot_sent_at: None,
+            // Both sides start as in game, no need to send it
+            activity: ClientActivity::InGame,
+            activity_acked: true,
+            remote_activity: ClientActivity::InGame,
+            keep_alive_interval: config.keep_alive_interval,
+            last_packet_sent_at: Duration::ZERO,
+            last_packet_received_at: Duration::ZERO,
+            timeout: config.timeout,
+            loading_timeout: config.loading_timeout,
+            disconnect_packets_sent: false,
+            congestion: config.congestion_control.clone().map(CongestionController::new),
+            max_packet_size: config.max_packet_size,
+            mtu_prober: config.mtu_discovery.as_ref().map(|mtu| MtuProber::new(mtu, config.max_packet_size)),
+            time_sync: config.time_sync.clone().map(TimeSyncEstimator::new),
+            time_base: Duration::ZERO,
+            pending_time_responses: Vec::new(),
+            pacer: config.pacing.clone().map(Pacer::new),
+            version: config.version,
+            compatible_versions: compatible_versions(config),
+            remote_version: None,
+            packet_serializer: config.packet_serializer.clone(),
+            delta_compression: config.delta_compression.clone(),
+            delta_senders: HashMap::new(),
+            delta_receivers: HashMap::new(),
+            input_redundancy: config.input_redundancy,
+            input_senders: HashMap::new(),
+            input_receivers: HashMap::new(),
+            voice_sequences: HashMap::new(),
+            packet_capture: None,
+            session_recorder: None,
+            #[cfg(feature = "serde")]
+            message_registry: None,
+            #[cfg(feature = "serde")]
+            typed_messages: TypedMessages::default(),
         }
     }
 
@@ -197,6 +648,11 @@ impl RenetClient {
         self.rtt
     }
 
+    /// Returns the percentiles and jitter of the round-trip time of the last 128 acknowledged packets.
+    pub fn rtt_percentiles(&self) -> RttPercentiles {
+        self.rtt_samples.percentiles()
+    }
+
     /// Returns the packet loss for the connection.
     pub fn packet_loss(&self) -> f64 {
         self.stats.packet_loss()
@@ -212,13 +668,254 @@ impl RenetClient {
         self.stats.bytes_received_per_second(self.current_time)
     }
 
+    /// Returns the kilobits sent per second in the connection, over the last second.
+    /// Unlike [`RenetClient::bytes_sent_per_sec`], it doesn't use the [`ConnectionConfig::stats_window`].
+    pub fn sent_kbps(&self) -> f64 {
+        self.bandwidth.bytes_sent_per_second(self.current_time) * 8.0 / 1000.0
+    }
+
+    /// Returns the kilobits received per second in the connection, over the last second.
+    /// Unlike [`RenetClient::bytes_received_per_sec`], it doesn't use the [`ConnectionConfig::stats_window`].
+    pub fn received_kbps(&self) -> f64 {
+        self.bandwidth.bytes_received_per_second(self.current_time) * 8.0 / 1000.0
+    }
+
+    /// Returns the totals of packets, bytes and messages since the connection was created.
+    pub fn connection_stats(&self) -> &ConnectionStats {
+        &self.connection_stats
+    }
+
+    /// Returns the samples of the network conditions of the last minute, one per second.
+    pub fn network_history(&self) -> &NetworkHistory {
+        &self.network_history
+    }
+
     /// Returns all network informations for the connection.
     pub fn network_info(&self) -> NetworkInfo {
         NetworkInfo {
             rtt: self.rtt,
+            rtt_percentiles: self.rtt_percentiles(),
             packet_loss: self.stats.packet_loss(),
             bytes_sent_per_second: self.stats.bytes_sent_per_second(self.current_time),
             bytes_received_per_second: self.stats.bytes_received_per_second(self.current_time),
+            sent_kbps: self.sent_kbps(),
+            received_kbps: self.received_kbps(),
+            bytes_in_flight: self.bytes_in_flight(),
+            channels: self.channel_queues(),
+            negotiated_features: self.negotiated_features(),
+            congestion_mode: self.congestion_mode(),
+        }
+    }
+
+    /// Returns the bytes of the sent packets that were not acknowledged or considered lost yet.
+    pub fn bytes_in_flight(&self) -> u64 {
+        self.sent_packets.values().map(|packet| packet.size as u64).sum()
+    }
+
+    pub(crate) fn channel_queues(&self) -> Vec<ChannelQueueInfo> {
+        let mut channels: Vec<ChannelQueueInfo> = Vec::with_capacity(self.channel_send_order.len());
+        for order in self.channel_send_order.iter() {
+            let (channel_id, send_queue) = match order {
+                ChannelOrder::Reliable(channel_id) => (*channel_id, self.send_reliable_channels[channel_id].queued_messages()),
+                ChannelOrder::Unreliable(channel_id) => (*channel_id, self.send_unreliable_channels[channel_id].queued_messages()),
+            };
+            channels.push(ChannelQueueInfo {
+                channel_id,
+                send_queue,
+                receive_queue: 0,
+            });
+        }
+        for (&channel_id, channel) in self.receive_reliable_channels.iter() {
+            Self::channel_queue_info(&mut channels, channel_id).receive_queue = channel.available_messages();
+        }
+        for (&channel_id, channel) in self.receive_unreliable_channels.iter() {
+            Self::channel_queue_info(&mut channels, channel_id).receive_queue = channel.available_messages();
+        }
+        channels.sort_by_key(|info| info.channel_id);
+
+        channels
+    }
+
+    fn channel_queue_info(channels: &mut Vec<ChannelQueueInfo>, channel_id: u8) -> &mut ChannelQueueInfo {
+        let index = match channels.iter().position(|info| info.channel_id == channel_id) {
+            Some(index) => index,
+            None => {
+                channels.push(ChannelQueueInfo {
+                    channel_id,
+                    send_queue: 0,
+                    receive_queue: 0,
+                });
+                channels.len() - 1
+            }
+        };
+
+        &mut channels[index]
+    }
+
+    /// Returns the estimated time of the server clock, the time it was advanced with [`RenetServer::update`](crate::RenetServer::update).
+    /// Returns `None` if [`ConnectionConfig::time_sync`] is disabled or no time response was received yet.
+    ///
+    /// Useful to render snapshots at the server time minus the interpolation delay, or to stamp inputs for lag compensation.
+    pub fn server_time_estimate(&self) -> Option<Duration> {
+        self.time_sync.as_ref()?.server_time(self.current_time)
+    }
+
+    pub(crate) fn set_time_base(&mut self, time_base: Duration) {
+        self.time_base = time_base;
+    }
+
+    /// Returns the largest packet size acknowledged by the path mtu discovery.
+    /// Returns `None` if the discovery is disabled.
+    pub fn path_mtu(&self) -> Option<usize> {
+        self.mtu_prober.as_ref().map(|prober| prober.path_mtu())
+    }
+
+    fn max_small_messages_bytes(&self) -> usize {
+        let packet_size = match &self.mtu_prober {
+            Some(prober) => prober.path_mtu().min(self.max_packet_size),
+            None => self.max_packet_size,
+        };
+        packet_size.saturating_sub(SMALL_MESSAGES_PACKET_HEADER_BYTES + self.packet_serializer.overhead())
+    }
+
+    /// Returns the number of packets waiting to be sent by the pacing.
+    pub fn paced_packets(&self) -> usize {
+        self.pacer.as_ref().map_or(0, |pacer| pacer.queued_packets())
+    }
+
+    /// Returns the time until the next packet waiting for the pacing can be sent,
+    /// or `None` if there are no packets waiting.
+    pub fn pacing_delay(&self) -> Option<Duration> {
+        self.pacer.as_ref().and_then(|pacer| pacer.delay())
+    }
+
+    /// Returns the current congestion avoidance mode.
+    /// Always [`CongestionMode::Good`] if congestion control is disabled.
+    pub fn congestion_mode(&self) -> CongestionMode {
+        match &self.congestion {
+            Some(congestion) => congestion.mode(),
+            None => CongestionMode::Good,
+        }
+    }
+
+    /// Returns the application protocol version of the remote side,
+    /// or `None` if it was not received yet.
+    pub fn remote_version(&self) -> Option<u16> {
+        self.remote_version
+    }
+
+    /// Returns the handshake the transport sends in the connection request, see [`Handshake`].
+    /// The server learns the features of the client from it, so they are not sent again after connecting.
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn start_handshake(&mut self) -> Handshake {
+        self.features_acked = true;
+        Handshake {
+            wire_version: crate::packet::WIRE_VERSION,
+            version: self.version,
+            compatible_versions: self.compatible_versions.clone(),
+            features: self.supported_features,
+        }
+    }
+
+    // Applies the handshake of a client that was already checked by the server.
+    pub(crate) fn set_remote_handshake(&mut self, handshake: &Handshake) {
+        self.remote_version = Some(handshake.version);
+        self.remote_features = Some(handshake.features);
+    }
+
+    // Disconnects a connection that was refused before being established, returning the packets that tell
+    // the remote side why. It sent a handshake, so it understands disconnect packets without negotiating features.
+    pub(crate) fn reject(&mut self, reason: DisconnectReason) -> Vec<Payload> {
+        self.remote_features = Some(ProtocolFeatures::DISCONNECT_REASON);
+        self.disconnect_with_reason(reason);
+        self.get_disconnect_packets()
+    }
+
+    /// Returns the optional protocol features supported by both sides of the connection.
+    /// Returns [`ProtocolFeatures::NONE`] until the features from the remote side are received.
+    pub fn negotiated_features(&self) -> ProtocolFeatures {
+        match self.remote_features {
+            Some(remote_features) => self.supported_features.intersection(remote_features),
+            None => ProtocolFeatures::NONE,
+        }
+    }
+
+    /// Returns the activity of this side of the connection.
+    pub fn activity(&self) -> ClientActivity {
+        self.activity
+    }
+
+    /// Set the activity of this side of the connection, it will be sent to the remote side.
+    pub fn set_activity(&mut self, activity: ClientActivity) {
+        if self.activity != activity {
+            self.activity = activity;
+            self.activity_acked = false;
+        }
+    }
+
+    /// Returns the last activity received from the remote side of the connection.
+    pub fn remote_activity(&self) -> ClientActivity {
+        self.remote_activity
+    }
+
+    /// Returns the detailed state of the connection.
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    /// Returns the next state the connection changed to, in order.
+    /// Should be called until it returns `None` every tick, so changes are not accumulated.
+    pub fn next_state_change(&mut self) -> Option<ConnectionState> {
+        self.state_changes.pop_front()
+    }
+
+    /// Returns the next connection event.
+    ///
+    /// Events are discarded when the client is updated,
+    /// so they should be read every tick after the transport receives the packets.
+    ///
+    /// # Usage
+    /// ```
+    /// # use renet::{ConnectionConfig, ConnectionEvent, RenetClient};
+    /// # let mut client = RenetClient::new(ConnectionConfig::default());
+    /// while let Some(event) = client.next_event() {
+    ///     match event {
+    ///         ConnectionEvent::MessageReceived { channel_id } => {
+    ///             let message = client.receive_message(channel_id).unwrap();
+    ///         }
+    ///         ConnectionEvent::Disconnected(reason) => println!("Disconnected: {reason}"),
+    ///         _ => {}
+    ///     }
+    /// }
+    /// ```
+    pub fn next_event(&mut self) -> Option<ConnectionEvent> {
+        self.events.pop_front()
+    }
+
+    fn update_state(&mut self) {
+        let state = match self.connection_status {
+            RenetConnectionStatus::Connected => ConnectionState::Connected,
+            RenetConnectionStatus::Connecting if self.challenged => ConnectionState::Challenged,
+            RenetConnectionStatus::Connecting => ConnectionState::Connecting,
+            RenetConnectionStatus::Disconnected { reason } => {
+                if !self.disconnect_packets_sent
+                    && reason.code().is_some()
+                    && self.negotiated_features().contains(ProtocolFeatures::DISCONNECT_REASON)
+                {
+                    ConnectionState::Disconnecting
+                } else {
+                    ConnectionState::Disconnected
+                }
+            }
+        };
+
+        if state != self.state {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(from = ?self.state, to = ?state, "connection state changed");
+            self.state = state;
+            self.state_changes.push_back(state);
         }
     }
 
@@ -257,8 +954,30 @@ impl RenetClient {
     /// <strong>Note:</strong> This should only be called by the transport layer.
     /// </p>
     pub fn set_connected(&mut self) {
-        if !self.is_disconnected() {
+        if self.is_connecting() {
+            self.record(SessionEvent::Connected {
+                client_id: None,
+                user_data: None,
+            });
+            // Start the timeout from when the connection was established
+            self.last_packet_received_at = self.current_time;
             self.connection_status = RenetConnectionStatus::Connected;
+            self.events.push_back(ConnectionEvent::Connected);
+            self.update_state();
+        }
+    }
+
+    /// Set the client connection status to connecting, after receiving the challenge from the server.
+    ///
+    /// Does nothing if the client is not connecting.
+    ///
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn set_challenged(&mut self) {
+        if self.is_connecting() {
+            self.challenged = true;
+            self.update_state();
         }
     }
 
@@ -272,6 +991,8 @@ impl RenetClient {
     pub fn set_connecting(&mut self) {
         if !self.is_disconnected() {
             self.connection_status = RenetConnectionStatus::Connecting;
+            self.challenged = false;
+            self.update_state();
         }
     }
 
@@ -282,6 +1003,13 @@ impl RenetClient {
         self.disconnect_with_reason(DisconnectReason::DisconnectedByClient);
     }
 
+    /// Disconnect the client with an application specific code, it's sent to the remote side.
+    ///
+    /// If the client is already disconnected, it does nothing.
+    pub fn disconnect_with_code(&mut self, code: u8) {
+        self.disconnect_with_reason(DisconnectReason::Custom(code));
+    }
+
     /// Disconnect the client because an error occurred in the transport layer.
     ///
     /// If the client is already disconnected, it does nothing.
@@ -289,6 +1017,9 @@ impl RenetClient {
     /// <strong>Note:</strong> This should only be called by the transport layer.
     /// </p>
     pub fn disconnect_due_to_transport(&mut self) {
+        if !self.is_disconnected() {
+            self.record(SessionEvent::Disconnected { client_id: None });
+        }
         self.disconnect_with_reason(DisconnectReason::Transport);
     }
 
@@ -317,121 +1048,563 @@ impl RenetClient {
     }
 
     /// Send a message to the server over a channel.
+    /// A full reliable channel disconnects the client, and a full unreliable channel drops the message,
+    /// use [`RenetClient::try_send_message`] to handle these cases instead.
     pub fn send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) {
-        if self.is_disconnected() {
-            return;
+        match self.try_send_message(channel_id, message) {
+            Ok(()) | Err(RenetError::Disconnected(_)) => {}
+            Err(RenetError::InvalidChannelId(channel_id)) => panic!("Called 'send_message' with invalid channel {channel_id}"),
+            Err(RenetError::Channel {
+                channel_id,
+                error: ChannelError::UnreliableChannelMaxMemoryReached,
+            }) => log::warn!("dropped unreliable message sent because channel {channel_id} is memory limited"),
+            Err(RenetError::Channel { channel_id, error }) => {
+                self.disconnect_with_reason(DisconnectReason::SendChannelError { channel_id, error })
+            }
+            Err(error) => unreachable!("unexpected error when sending a message: {error}"),
+        }
+    }
+
+    /// Send a message to the server over a channel, returning an error if the client is disconnected,
+    /// the channel doesn't exist or doesn't have enough memory left for the message and its
+    /// [`FullQueuePolicy`](crate::FullQueuePolicy) is to return an error. Unlike [`RenetClient::send_message`], a full channel doesn't disconnect the client.
+    pub fn try_send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) -> Result<(), RenetError> {
+        if let Some(reason) = self.disconnect_reason() {
+            return Err(RenetError::Disconnected(reason));
         }
 
         let channel_id = channel_id.into();
-        if let Some(reliable_channel) = self.send_reliable_channels.get_mut(&channel_id) {
-            if let Err(error) = reliable_channel.send_message(message.into()) {
-                self.disconnect_with_reason(DisconnectReason::SendChannelError { channel_id, error });
-            }
+        let message = message.into();
+        let len = message.len();
+        let queued = if let Some(reliable_channel) = self.send_reliable_channels.get_mut(&channel_id) {
+            reliable_channel.send_message(message)
         } else if let Some(unreliable_channel) = self.send_unreliable_channels.get_mut(&channel_id) {
-            unreliable_channel.send_message(message.into());
+            unreliable_channel.send_message(message)
         } else {
-            panic!("Called 'send_message' with invalid channel {channel_id}");
+            return Err(RenetError::InvalidChannelId(channel_id));
+        };
+        match queued.map_err(|error| RenetError::Channel { channel_id, error })? {
+            true => self.connection_stats.sent_message(channel_id, len),
+            false => self.connection_stats.dropped_message(channel_id),
         }
+
+        Ok(())
     }
 
     /// Receive a message from the server over a channel.
     pub fn receive_message<I: Into<u8>>(&mut self, channel_id: I) -> Option<Bytes> {
-        if self.is_disconnected() {
-            return None;
+        match self.try_receive_message(channel_id) {
+            Ok(message) => message,
+            Err(RenetError::InvalidChannelId(channel_id)) => panic!("Called 'receive_message' with invalid channel {channel_id}"),
+            Err(_) => None,
+        }
+    }
+
+    /// Receive a message from the server over a channel,
+    /// returning an error if the client is disconnected or the channel doesn't exist.
+    pub fn try_receive_message<I: Into<u8>>(&mut self, channel_id: I) -> Result<Option<Bytes>, RenetError> {
+        if let Some(reason) = self.disconnect_reason() {
+            return Err(RenetError::Disconnected(reason));
         }
 
         let channel_id = channel_id.into();
-        if let Some(reliable_channel) = self.receive_reliable_channels.get_mut(&channel_id) {
+        let message = if let Some(reliable_channel) = self.receive_reliable_channels.get_mut(&channel_id) {
             reliable_channel.receive_message()
         } else if let Some(unreliable_channel) = self.receive_unreliable_channels.get_mut(&channel_id) {
             unreliable_channel.receive_message()
         } else {
-            panic!("Called 'receive_message' with invalid channel {channel_id}");
+            return Err(RenetError::InvalidChannelId(channel_id));
+        };
+
+        if let Some(message) = &message {
+            self.connection_stats.received_message(channel_id, message.len());
         }
+        Ok(message)
     }
 
-    /// Advances the client by the duration.
-    /// Should be called every tick
-    pub fn update(&mut self, duration: Duration) {
-        self.current_time += duration;
-        self.stats.update(self.current_time);
+    /// Returns an empty buffer from the pool of the connection, to write a message into.
+    ///
+    /// Buffers of sent messages return to the pool once they are not needed anymore,
+    /// so steady streams of messages written into acquired buffers don't allocate.
+    ///
+    /// # Usage
+    /// ```
+    /// # use renet::{ConnectionConfig, DefaultChannel, RenetClient};
+    /// # let mut client = RenetClient::new(ConnectionConfig::default());
+    /// let mut buffer = client.acquire_buffer();
+    /// buffer.extend_from_slice(b"position");
+    /// client.send_message(DefaultChannel::Unreliable, buffer.freeze());
+    /// ```
+    pub fn acquire_buffer(&mut self) -> BytesMut {
+        self.buffer_pool.acquire()
+    }
 
-        for unreliable_channel in self.receive_unreliable_channels.values_mut() {
-            unreliable_channel.discard_incomplete_old_slices(self.current_time);
+    /// Returns payloads from [`RenetClient::get_packets_to_send`] after they were sent,
+    /// so they are reused for the next packets.
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn recycle_payloads(&mut self, payloads: impl IntoIterator<Item = Payload>) {
+        for payload in payloads {
+            self.buffer_pool.recycle_payload(payload);
         }
+    }
 
-        // Discard lost packets
-        let mut lost_packets: Vec<u64> = Vec::new();
-        for (&sequence, sent_packet) in self.sent_packets.iter() {
-            const DISCARD_AFTER: Duration = Duration::from_secs(3);
-            if self.current_time - sent_packet.sent_at >= DISCARD_AFTER {
-                lost_packets.push(sequence);
-            } else {
-                // If the current packet is not lost, the next ones will not be lost
-                // since all the next packets were sent after this one.
-                break;
+    pub(crate) fn buffer_pool(&mut self) -> &mut BufferPool {
+        &mut self.buffer_pool
+    }
+
+    /// Sets the hook called with every packet sent and received, see [`PcapWriter`](crate::PcapWriter).
+    pub fn set_packet_capture(&mut self, capture: Arc<dyn PacketCapture>) {
+        self.packet_capture = Some((capture, None));
+    }
+
+    pub(crate) fn set_packet_capture_for(&mut self, capture: Arc<dyn PacketCapture>, client_id: ClientId) {
+        self.packet_capture = Some((capture, Some(client_id)));
+    }
+
+    fn capture(&self, direction: CaptureDirection, packets: &[impl AsRef<[u8]>]) {
+        if let Some((capture, client_id)) = &self.packet_capture {
+            for packet in packets {
+                capture.capture(*client_id, direction, packet.as_ref());
             }
         }
+    }
 
-        for sequence in lost_packets.iter() {
-            self.sent_packets.remove(sequence);
-        }
+    /// Records the updates, transport state changes and received packets of the client, see [`SessionRecorder`].
+    pub fn set_session_recorder(&mut self, recorder: Arc<SessionRecorder>) {
+        self.session_recorder = Some(recorder);
     }
 
-    /// Process a packet received from the server.
-    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
-    /// <strong>Note:</strong> This should only be called by the transport layer.
-    /// </p>
-    pub fn process_packet(&mut self, packet: &[u8]) {
-        if self.is_disconnected() {
-            return;
+    fn record(&self, event: SessionEvent) {
+        if let Some(recorder) = &self.session_recorder {
+            recorder.record(event);
         }
+    }
 
-        self.stats.received_packet(packet.len() as u64);
-        let mut octets = octets::Octets::with_slice(packet);
-        let packet = match Packet::from_bytes(&mut octets) {
-            Err(err) => {
-                self.disconnect_with_reason(DisconnectReason::PacketDeserialization(err));
-                return;
+    /// Sets the registry used to send and receive typed messages with [`RenetClient::send`] and [`RenetClient::receive`].
+    #[cfg(feature = "serde")]
+    pub fn set_message_registry(&mut self, registry: Arc<MessageRegistry>) {
+        self.message_registry = Some(registry);
+    }
+
+    /// Serializes and sends a typed message over the channel registered for its type.
+    /// Panics if no registry is set or the type is not registered.
+    #[cfg(feature = "serde")]
+    pub fn send<T: NetworkMessage>(&mut self, message: T) {
+        let registry = self.message_registry.as_ref().expect("Called 'send' without a message registry");
+        match registry.serialize(&message) {
+            Ok((channel_id, message)) => self.send_message(channel_id, message),
+            Err(e) => {
+                log::error!("{}", e);
+                self.typed_messages.push_error(e);
             }
-            Ok(packet) => packet,
-        };
+        }
+    }
 
-        self.add_pending_ack(packet.sequence());
+    /// Receive a typed message over the channel registered for its type.
+    /// Messages that fail to deserialize are skipped and available with [`RenetClient::get_message_error`].
+    /// Messages of other types sharing the channel are kept until read, up to 1024 per type, then the oldest are dropped.
+    /// Panics if no registry is set or the type is not registered.
+    #[cfg(feature = "serde")]
+    pub fn receive<T: NetworkMessage>(&mut self) -> Option<T> {
+        let registry = self.message_registry.clone().expect("Called 'receive' without a message registry");
+        let mut typed_messages = std::mem::take(&mut self.typed_messages);
+        let message = typed_messages.receive::<T>(&registry, |channel_id| self.receive_message(channel_id));
+        self.typed_messages = typed_messages;
+        message
+    }
 
-        match packet {
-            Packet::SmallReliable { channel_id, messages, .. } => {
-                let Some(channel) = self.receive_reliable_channels.get_mut(&channel_id) else {
-                    self.disconnect_with_reason(DisconnectReason::ReceivedInvalidChannelId(channel_id));
-                    return;
-                };
+    /// Returns an error that occurred while sending or receiving typed messages.
+    /// Only the last 256 errors are kept.
+    #[cfg(feature = "serde")]
+    pub fn get_message_error(&mut self) -> Option<MessageError> {
+        self.typed_messages.pop_error()
+    }
 
-                for (message_id, message) in messages {
-                    if let Err(error) = channel.process_message(message, message_id) {
-                        self.disconnect_with_reason(DisconnectReason::ReceiveChannelError { channel_id, error });
-                        return;
-                    }
-                }
-            }
-            Packet::SmallUnreliable { channel_id, messages, .. } => {
-                let Some(channel) = self.receive_unreliable_channels.get_mut(&channel_id) else {
-                    self.disconnect_with_reason(DisconnectReason::ReceivedInvalidChannelId(channel_id));
-                    return;
-                };
+    /// Returns the current minimum interval between snapshots, based on the connection quality.
+    pub fn snapshot_interval(&self) -> Duration {
+        let rate = &self.snapshot_send_rate;
+        if self.packet_loss() > rate.poor_packet_loss || self.rtt > rate.poor_rtt {
+            rate.poor_interval
+        } else {
+            rate.good_interval
+        }
+    }
 
-                for message in messages {
-                    channel.process_message(message);
-                }
+    /// Send a snapshot message over a channel, if it's time to send a new snapshot.
+    ///
+    /// Can be called every tick, the message is discarded when the last snapshot was sent
+    /// more recently than [`RenetClient::snapshot_interval`], or when the remote side is loading.
+    /// Returns whether the message was sent.
+    pub fn send_snapshot<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) -> bool {
+        if self.remote_activity == ClientActivity::Loading {
+            return false;
+        }
+
+        if let Some(last_sent_at) = self.last_snapshot_sent_at {
+            if self.current_time - last_sent_at < self.snapshot_interval() {
+                return false;
             }
-            Packet::ReliableSlice { channel_id, slice, .. } => {
-                let Some(channel) = self.receive_reliable_channels.get_mut(&channel_id) else {
-                    self.disconnect_with_reason(DisconnectReason::ReceivedInvalidChannelId(channel_id));
-                    return;
-                };
+        }
 
+        self.last_snapshot_sent_at = Some(self.current_time);
+        self.send_message(channel_id, message);
+        true
+    }
+
+    /// Send the state of a tick over an unreliable channel, as a diff against the most recent state
+    /// acknowledged by the remote side, see [`DeltaCompression`].
+    ///
+    /// The channel should only be used for states, and they must be received with [`RenetClient::receive_state`].
+    /// Ticks must increase, usually the simulation tick of the state.
+    pub fn send_state<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, tick: u64, state: B) {
+        let channel_id = channel_id.into();
+        if !self.send_unreliable_channels.contains_key(&channel_id) {
+            panic!("Called 'send_state' with invalid unreliable channel {channel_id}");
+        }
+
+        let config = &self.delta_compression;
+        let sender = self
+            .delta_senders
+            .entry(channel_id)
+            .or_insert_with(|| DeltaSender::new(config.clone()));
+        let message = sender.encode(tick, state.into());
+        self.send_message(channel_id, message);
+    }
+
+    /// Receive the most recent state sent with [`RenetClient::send_state`] over a channel.
+    ///
+    /// Older states received at the same time are skipped, as well as states older than the last one returned.
+    pub fn receive_state<I: Into<u8>>(&mut self, channel_id: I) -> Option<(u64, Bytes)> {
+        let channel_id = channel_id.into();
+        let mut latest = None;
+        while let Some(message) = self.receive_message(channel_id) {
+            let config = &self.delta_compression;
+            let receiver = self.delta_receivers.entry(channel_id).or_insert_with(|| DeltaReceiver::new(config));
+            match receiver.decode(&message) {
+                Some(state) => latest = Some(state),
+                None => log::debug!("Discarded state message from channel {channel_id}, it's old or its baseline is missing"),
+            }
+        }
+        latest
+    }
+
+    /// Returns the most recent tick sent with [`RenetClient::send_state`] that was acknowledged by the remote side.
+    pub fn acked_state_tick<I: Into<u8>>(&self, channel_id: I) -> Option<u64> {
+        self.delta_senders.get(&channel_id.into()).and_then(|sender| sender.acked())
+    }
+
+    /// Send an input over an unreliable channel, together with the previous inputs not acknowledged yet,
+    /// up to [`ConnectionConfig::input_redundancy`], so a lost packet doesn't lose its inputs.
+    ///
+    /// The channel should only be used for inputs, and they must be received with [`RenetClient::receive_inputs`].
+    /// Inputs should be small, the previous ones are only included while the message fits in one packet.
+    /// Inputs larger than 65535 bytes are rejected and not sent.
+    pub fn send_input<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, input: B) {
+        let channel_id = channel_id.into();
+        if !self.send_unreliable_channels.contains_key(&channel_id) {
+            panic!("Called 'send_input' with invalid unreliable channel {channel_id}");
+        }
+
+        let redundancy = self.input_redundancy;
+        let sender = self.input_senders.entry(channel_id).or_insert_with(|| InputSender::new(redundancy));
+        match sender.encode(input.into()) {
+            Some(message) => self.send_message(channel_id, message),
+            None => log::error!("Discarded input larger than {} bytes for channel {channel_id}", u16::MAX),
+        }
+    }
+
+    /// Receive the inputs sent with [`RenetClient::send_input`] over a channel, in order and without duplicates,
+    /// together with their sequence. Inputs lost in more consecutive packets than the redundancy are skipped.
+    pub fn receive_inputs<I: Into<u8>>(&mut self, channel_id: I) -> Vec<(u64, Bytes)> {
+        let channel_id = channel_id.into();
+        let mut inputs = vec![];
+        while let Some(message) = self.receive_message(channel_id) {
+            let receiver = self.input_receivers.entry(channel_id).or_default();
+            match receiver.decode(&message, &mut inputs) {
+                Some(0) => {}
+                Some(skipped) => log::debug!("Lost {skipped} inputs from channel {channel_id}"),
+                None => log::debug!("Discarded malformed input message from channel {channel_id}"),
+            }
+        }
+        inputs
+    }
+
+    /// Send an encoded audio frame over an unreliable channel, with its sequence so the receiver can
+    /// reorder the frames and detect the lost ones, see the [`voice`](crate::voice) module.
+    pub fn send_voice_frame<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, frame: B) {
+        let channel_id = channel_id.into();
+        if !self.send_unreliable_channels.contains_key(&channel_id) {
+            panic!("Called 'send_voice_frame' with invalid unreliable channel {channel_id}");
+        }
+
+        let sequence = self.voice_sequences.entry(channel_id).or_default();
+        let frame = VoiceFrame {
+            // Set by the server when relaying the frame
+            talker: ClientId::from_raw(0),
+            sequence: *sequence,
+            data: frame.into(),
+        };
+        *sequence += 1;
+        self.send_message(channel_id, frame.to_bytes());
+    }
+
+    /// Receive the audio frames sent over a channel, to be pushed to a [`VoiceReceiver`](crate::voice::VoiceReceiver).
+    pub fn receive_voice_frames<I: Into<u8>>(&mut self, channel_id: I) -> Vec<VoiceFrame> {
+        let channel_id = channel_id.into();
+        let mut frames = vec![];
+        while let Some(message) = self.receive_message(channel_id) {
+            match VoiceFrame::from_bytes(&message) {
+                Some(frame) => frames.push(frame),
+                None => log::debug!("Discarded malformed voice frame from channel {channel_id}"),
+            }
+        }
+        frames
+    }
+
+    /// Returns the number of inputs sent with [`RenetClient::send_input`] not acknowledged yet by the remote side.
+    pub fn unacked_inputs<I: Into<u8>>(&self, channel_id: I) -> usize {
+        self.input_senders
+            .get(&channel_id.into())
+            .map_or(0, |sender| sender.unacked_inputs())
+    }
+
+    /// Writes bytes to a reliable ordered channel as a continuous stream.
+    ///
+    /// The data is split in segments internally, the receiving side should use [`RenetClient::read_stream`]
+    /// to read the bytes in the same order they were written, without any message boundary.
+    /// Returns an error without writing anything if the channel is not reliable ordered, the client is disconnected
+    /// or the channel doesn't have enough memory left for all the data, whatever its [`FullQueuePolicy`](crate::FullQueuePolicy) is.
+    pub fn write_stream<I: Into<u8>>(&mut self, channel_id: I, data: &[u8]) -> Result<(), RenetError> {
+        let channel_id = channel_id.into();
+        let channel = match self.send_reliable_channels.get(&channel_id) {
+            Some(channel) if channel.is_ordered() => channel,
+            Some(_) => return Err(RenetError::UnorderedChannel(channel_id)),
+            None if self.send_unreliable_channels.contains_key(&channel_id) => return Err(RenetError::UnorderedChannel(channel_id)),
+            None => return Err(RenetError::InvalidChannelId(channel_id)),
+        };
+
+        if let Some(reason) = self.disconnect_reason() {
+            return Err(RenetError::Disconnected(reason));
+        }
+        // A dropped segment would corrupt the stream, so it is written entirely or not at all
+        if !channel.can_send_message(data.len()) {
+            return Err(RenetError::Channel {
+                channel_id,
+                error: ChannelError::ReliableChannelMaxMemoryReached,
+            });
+        }
+
+        for segment in data.chunks(SLICE_SIZE) {
+            self.try_send_message(channel_id, Bytes::copy_from_slice(segment))?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads bytes written with [`RenetClient::write_stream`] from a reliable ordered channel.
+    ///
+    /// Returns the number of bytes written to the buffer, 0 if no data is available,
+    /// or an error if the channel is not reliable ordered.
+    pub fn read_stream<I: Into<u8>>(&mut self, channel_id: I, buf: &mut [u8]) -> Result<usize, RenetError> {
+        let channel_id = channel_id.into();
+        match self.receive_reliable_channels.get(&channel_id) {
+            Some(channel) if channel.is_ordered() => {}
+            Some(_) => return Err(RenetError::UnorderedChannel(channel_id)),
+            None if self.receive_unreliable_channels.contains_key(&channel_id) => return Err(RenetError::UnorderedChannel(channel_id)),
+            None => return Err(RenetError::InvalidChannelId(channel_id)),
+        }
+
+        let mut read = 0;
+        while read < buf.len() {
+            let mut segment = match self.stream_read_buffers.remove(&channel_id) {
+                Some(segment) => segment,
+                None => match self.receive_message(channel_id) {
+                    Some(message) => message,
+                    None => break,
+                },
+            };
+
+            let len = segment.len().min(buf.len() - read);
+            buf[read..read + len].copy_from_slice(&segment[..len]);
+            segment.advance(len);
+            read += len;
+
+            if !segment.is_empty() {
+                self.stream_read_buffers.insert(channel_id, segment);
+            }
+        }
+
+        Ok(read)
+    }
+
+    /// Returns the duration since the last packet was received from the remote side.
+    pub fn time_since_last_received_packet(&self) -> Duration {
+        self.current_time - self.last_packet_received_at
+    }
+
+    /// Advances the client by the duration.
+    /// Should be called every tick
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "renet_update", level = "trace", skip_all))]
+    pub fn update(&mut self, duration: Duration) {
+        self.record(SessionEvent::Update(duration));
+        self.events.clear();
+        self.current_time += duration;
+        self.stats.update(self.current_time);
+        self.bandwidth.update(self.current_time);
+        self.network_history
+            .update(self.current_time, &self.connection_stats, self.stats.packet_loss(), self.rtt);
+        #[cfg(feature = "metrics")]
+        if self.record_metrics {
+            crate::telemetry::record_connection(self, None);
+        }
+        if let Some(pacer) = &mut self.pacer {
+            pacer.update(duration);
+        }
+        if let Some(congestion) = &mut self.congestion {
+            congestion.update(self.current_time, self.rtt, self.stats.packet_loss());
+        }
+
+        if self.is_connected() {
+            let timeout = match self.remote_activity {
+                ClientActivity::Loading => self.loading_timeout,
+                _ => self.timeout,
+            };
+            if self.time_since_last_received_packet() >= timeout {
+                self.disconnect_with_reason(DisconnectReason::TimedOut);
+                return;
+            }
+        }
+
+        for unreliable_channel in self.receive_unreliable_channels.values_mut() {
+            unreliable_channel.discard_incomplete_old_slices(self.current_time);
+        }
+
+        // Discard lost packets
+        let mut lost_packets: Vec<u64> = Vec::new();
+        for (&sequence, sent_packet) in self.sent_packets.iter() {
+            const DISCARD_AFTER: Duration = Duration::from_secs(3);
+            if self.current_time - sent_packet.sent_at >= DISCARD_AFTER {
+                lost_packets.push(sequence);
+            } else {
+                // If the current packet is not lost, the next ones will not be lost
+                // since all the next packets were sent after this one.
+                break;
+            }
+        }
+
+        for sequence in lost_packets.iter() {
+            self.sent_packets.remove(sequence);
+        }
+        self.connection_stats.packets_lost += lost_packets.len() as u64;
+    }
+
+    /// Process a packet received from the server.
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "renet_process_packet", level = "trace", skip_all, fields(len = packet.len())))]
+    pub fn process_packet(&mut self, packet: &[u8]) {
+        self.capture(CaptureDirection::Received, &[packet]);
+        self.record(SessionEvent::Packet {
+            client_id: None,
+            payload: packet.to_vec(),
+        });
+        if self.is_disconnected() {
+            return;
+        }
+
+        self.stats.received_packet(packet.len() as u64);
+        self.bandwidth.received_packet(packet.len() as u64);
+        self.connection_stats.bytes_received += packet.len() as u64;
+        let packet = match self.packet_serializer.deserialize(packet) {
+            // Corrupted packets are dropped like lost packets
+            Err(SerializationError::InvalidChecksum) => {
+                log::debug!("Dropped packet with invalid checksum");
+                #[cfg(feature = "tracing")]
+                tracing::debug!("dropped packet with invalid checksum");
+                return;
+            }
+            // Newer versions may send packet types introduced after this one, they are optional
+            Err(SerializationError::InvalidPacketType) => {
+                log::debug!("Dropped packet with unknown type");
+                #[cfg(feature = "tracing")]
+                tracing::debug!("dropped packet with unknown type");
+                return;
+            }
+            Err(err) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(error = %err, "failed to parse packet");
+                self.disconnect_with_reason(DisconnectReason::PacketDeserialization(err));
+                return;
+            }
+            Ok(packet) => packet,
+        };
+        #[cfg(feature = "tracing")]
+        tracing::trace!(packet_type = packet.name(), sequence = packet.sequence(), "parsed packet");
+
+        self.handle_packet(packet);
+    }
+
+    // Processes a packet from a local connection in the same process, without parsing it.
+    // See `RenetServer::process_local_client`.
+    pub(crate) fn process_local_packet(&mut self, packet: Packet) {
+        if self.is_disconnected() {
+            return;
+        }
+
+        self.handle_packet(packet);
+    }
+
+    fn handle_packet(&mut self, packet: Packet) {
+        self.last_packet_received_at = self.current_time;
+        self.connection_stats.packets_received += 1;
+        self.add_pending_ack(packet.sequence());
+
+        match packet {
+            Packet::SmallReliable { channel_id, messages, .. } => {
+                let Some(channel) = self.receive_reliable_channels.get_mut(&channel_id) else {
+                    self.disconnect_with_reason(DisconnectReason::ReceivedInvalidChannelId(channel_id));
+                    return;
+                };
+
+                let available_messages = channel.available_messages();
+                for (message_id, message) in messages {
+                    if let Err(error) = channel.process_message(message, message_id) {
+                        self.disconnect_with_reason(DisconnectReason::ReceiveChannelError { channel_id, error });
+                        return;
+                    }
+                }
+                let received = channel.available_messages() - available_messages;
+                self.push_received_events(channel_id, received);
+            }
+            Packet::SmallUnreliable { channel_id, messages, .. } => {
+                let Some(channel) = self.receive_unreliable_channels.get_mut(&channel_id) else {
+                    self.disconnect_with_reason(DisconnectReason::ReceivedInvalidChannelId(channel_id));
+                    return;
+                };
+
+                let available_messages = channel.available_messages();
+                for message in messages {
+                    channel.process_message(message);
+                }
+                let received = channel.available_messages() - available_messages;
+                self.push_received_events(channel_id, received);
+            }
+            Packet::ReliableSlice { channel_id, slice, .. } => {
+                let Some(channel) = self.receive_reliable_channels.get_mut(&channel_id) else {
+                    self.disconnect_with_reason(DisconnectReason::ReceivedInvalidChannelId(channel_id));
+                    return;
+                };
+
+                let available_messages = channel.available_messages();
                 if let Err(error) = channel.process_slice(slice) {
                     self.disconnect_with_reason(DisconnectReason::ReceiveChannelError { channel_id, error });
+                    return;
                 }
+                let received = channel.available_messages() - available_messages;
+                self.push_received_events(channel_id, received);
             }
             Packet::UnreliableSlice { channel_id, slice, .. } => {
                 let Some(channel) = self.receive_unreliable_channels.get_mut(&channel_id) else {
@@ -439,9 +1612,13 @@ impl RenetClient {
                     return;
                 };
 
+                let available_messages = channel.available_messages();
                 if let Err(error) = channel.process_slice(slice, self.current_time) {
                     self.disconnect_with_reason(DisconnectReason::ReceiveChannelError { channel_id, error });
+                    return;
                 }
+                let received = channel.available_messages() - available_messages;
+                self.push_received_events(channel_id, received);
             }
             Packet::Ack { ack_ranges, .. } => {
                 // Create list with just new acks
@@ -456,9 +1633,12 @@ impl RenetClient {
                 for packet_sequence in new_acks {
                     let sent_packet = self.sent_packets.remove(&packet_sequence).unwrap();
                     self.stats.acked_packet(sent_packet.sent_at, self.current_time);
+                    self.connection_stats.packets_acked += 1;
+                    self.events.push_back(ConnectionEvent::PacketAcked { sequence: packet_sequence });
 
                     // Update rtt
                     let rtt = (self.current_time - sent_packet.sent_at).as_secs_f64();
+                    self.rtt_samples.push(rtt);
                     if self.rtt < f64::EPSILON {
                         self.rtt = rtt;
                     } else {
@@ -469,7 +1649,9 @@ impl RenetClient {
                         PacketSentInfo::ReliableMessages { channel_id, message_ids } => {
                             let reliable_channel = self.send_reliable_channels.get_mut(&channel_id).unwrap();
                             for message_id in message_ids {
-                                reliable_channel.process_message_ack(message_id);
+                                if let Some(message) = reliable_channel.process_message_ack(message_id) {
+                                    self.buffer_pool.reclaim(message);
+                                }
                             }
                         }
                         PacketSentInfo::ReliableSliceMessage {
@@ -478,15 +1660,83 @@ impl RenetClient {
                             slice_index,
                         } => {
                             let reliable_channel = self.send_reliable_channels.get_mut(&channel_id).unwrap();
-                            reliable_channel.process_slice_message_ack(message_id, slice_index);
+                            if let Some(message) = reliable_channel.process_slice_message_ack(message_id, slice_index) {
+                                self.buffer_pool.reclaim(message);
+                            }
                         }
                         PacketSentInfo::Ack { largest_acked_packet } => {
                             self.acked_largest(largest_acked_packet);
                         }
+                        PacketSentInfo::Features => {
+                            self.features_acked = true;
+                        }
+                        PacketSentInfo::Activity(activity) => {
+                            // Only acked if it's still the current activity
+                            if activity == self.activity {
+                                self.activity_acked = true;
+                            }
+                        }
+                        PacketSentInfo::MtuProbe(size) => {
+                            if let Some(prober) = &mut self.mtu_prober {
+                                prober.probe_acked(size);
+                            }
+                        }
+                        PacketSentInfo::States { channel_id, ticks } => {
+                            if let Some(sender) = self.delta_senders.get_mut(&channel_id) {
+                                for tick in ticks {
+                                    sender.packet_acked(tick);
+                                }
+                            }
+                        }
+                        PacketSentInfo::Inputs {
+                            channel_id,
+                            last_sequences,
+                        } => {
+                            if let Some(sender) = self.input_senders.get_mut(&channel_id) {
+                                for sequence in last_sequences {
+                                    sender.acked(sequence);
+                                }
+                            }
+                        }
                         PacketSentInfo::None => {}
                     }
                 }
             }
+            Packet::Features { features, version, .. } => {
+                self.remote_features = Some(ProtocolFeatures::from_bits_truncate(features));
+                self.remote_version = Some(version);
+                if !self.compatible_versions.contains(&version) {
+                    self.disconnect_with_reason(DisconnectReason::IncompatibleVersion { remote_version: version });
+                }
+            }
+            Packet::KeepAlive { .. } | Packet::MtuProbe { .. } => {}
+            Packet::TimeRequest { time, .. } => {
+                let receive_time = (self.time_base + self.current_time).as_micros() as u64;
+                self.pending_time_responses.push((time, receive_time));
+            }
+            Packet::TimeResponse {
+                request_time,
+                receive_time,
+                send_time,
+                ..
+            } => {
+                if let Some(time_sync) = &mut self.time_sync {
+                    time_sync.add_sample(
+                        Duration::from_micros(request_time),
+                        Duration::from_micros(receive_time),
+                        Duration::from_micros(send_time),
+                        self.current_time,
+                    );
+                }
+            }
+            Packet::Disconnect { code, value, .. } => match DisconnectCode::from_bytes(code, value) {
+                Some(code) => self.disconnect_with_reason(DisconnectReason::DisconnectedByRemote(code)),
+                None => self.disconnect_with_reason(DisconnectReason::Transport),
+            },
+            Packet::Activity { activity, .. } => match ClientActivity::from_u8(activity) {
+                Some(activity) => self.remote_activity = activity,
+                None => log::debug!("Received unknown client activity {activity}"),
+            },
         }
     }
 
@@ -494,26 +1744,214 @@ impl RenetClient {
     /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
     /// <strong>Note:</strong> This should only be called by the transport layer.
     /// </p>
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "renet_build_packets", level = "trace", skip_all))]
     pub fn get_packets_to_send(&mut self) -> Vec<Payload> {
+        let packets = self.generate_packets();
+        if self.is_disconnected() {
+            let packets = self.get_disconnect_packets();
+            self.capture(CaptureDirection::Sent, &packets);
+            return packets;
+        }
+
+        let mut buffer = [0u8; MAX_PACKET_BYTES];
+        let mut serialized_packets = Vec::with_capacity(packets.len());
+        for packet in packets.iter() {
+            let len = match self.packet_serializer.serialize(packet, &mut buffer) {
+                Err(err) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(error = %err, packet_type = packet.name(), "failed to build packet");
+                    self.disconnect_with_reason(DisconnectReason::PacketSerialization(err));
+                    return vec![];
+                }
+                Ok(len) => len,
+            };
+            #[cfg(feature = "tracing")]
+            tracing::trace!(packet_type = packet.name(), sequence = packet.sequence(), len, "built packet");
+
+            serialized_packets.push(self.buffer_pool.payload(&buffer[..len]));
+        }
+
+        self.record_sent_packets(&packets, serialized_packets.iter().map(|payload| payload.len()));
+        if !serialized_packets.is_empty() {
+            self.last_packet_sent_at = self.current_time;
+        }
+
+        if let Some(pacer) = &mut self.pacer {
+            let sequences = packets.iter().map(Packet::sequence);
+            let released = pacer.pace(sequences.zip(serialized_packets).collect());
+            let sequences: Vec<u64>;
+            (sequences, serialized_packets) = released.into_iter().unzip();
+            for sequence in sequences {
+                self.packet_released(sequence);
+            }
+        }
+
+        // Unreliable messages are not needed after being sent
+        for packet in packets {
+            if let Packet::SmallUnreliable { messages, .. } = packet {
+                for message in messages {
+                    self.buffer_pool.reclaim(message);
+                }
+            }
+        }
+
+        let bytes_sent: u64 = serialized_packets.iter().map(|packet| packet.len() as u64).sum();
+        self.stats.sent_packets(serialized_packets.len() as u64, bytes_sent);
+        self.bandwidth.sent_packets(bytes_sent);
+        self.connection_stats.packets_sent += serialized_packets.len() as u64;
+        self.connection_stats.bytes_sent += bytes_sent;
+        self.capture(CaptureDirection::Sent, &serialized_packets);
+
+        serialized_packets
+    }
+
+    // Returns the packets for a local connection in the same process, without serializing them.
+    // They don't use any bandwidth, so they are not paced and their size is 0.
+    // See `RenetServer::process_local_client`.
+    pub(crate) fn get_local_packets(&mut self) -> Vec<Packet> {
+        let packets = self.generate_packets();
+        if self.is_disconnected() {
+            return self.disconnect_packets();
+        }
+
+        self.record_sent_packets(&packets, std::iter::repeat(0));
+        if !packets.is_empty() {
+            self.last_packet_sent_at = self.current_time;
+        }
+        self.connection_stats.packets_sent += packets.len() as u64;
+
+        packets
+    }
+
+    // Returns the packets with the messages, acks and protocol data due this tick.
+    // Returns no packets if the connection is disconnected, or disconnects because a delivery failed.
+    fn generate_packets(&mut self) -> Vec<Packet> {
         let mut packets: Vec<Packet> = vec![];
         if self.is_disconnected() {
-            return vec![];
+            return packets;
+        }
+
+        // Packets waiting in the pacing queue are not in flight yet,
+        // their messages must not be resent before they are released
+        if let Some(pacer) = &self.pacer {
+            let queued: Vec<u64> = pacer.queued_sequences().collect();
+            for sequence in queued {
+                self.packet_released(sequence);
+            }
         }
 
-        let mut available_bytes = self.available_bytes_per_tick;
+        let mut available_bytes = match &self.congestion {
+            Some(congestion) => congestion.available_bytes(self.available_bytes_per_tick),
+            None => self.available_bytes_per_tick,
+        };
+        let max_small_messages_bytes = self.max_small_messages_bytes();
+        let mut delivery_failed = None;
         for order in self.channel_send_order.iter() {
             match order {
                 ChannelOrder::Reliable(channel_id) => {
                     let channel = self.send_reliable_channels.get_mut(channel_id).unwrap();
-                    packets.append(&mut channel.get_packets_to_send(&mut self.packet_sequence, &mut available_bytes, self.current_time));
+                    packets.append(&mut channel.get_packets_to_send(
+                        &mut self.packet_sequence,
+                        &mut available_bytes,
+                        max_small_messages_bytes,
+                        self.current_time,
+                    ));
+                    if channel.resent() > 0 {
+                        self.connection_stats.channels.entry(*channel_id).or_default().messages_resent = channel.resent();
+                    }
+                    let (failed, disconnect) = channel.take_failed_deliveries();
+                    for _ in 0..failed {
+                        self.events.push_back(ConnectionEvent::DeliveryFailed { channel_id: *channel_id });
+                    }
+                    if disconnect {
+                        delivery_failed = Some(*channel_id);
+                    }
                 }
                 ChannelOrder::Unreliable(channel_id) => {
                     let channel = self.send_unreliable_channels.get_mut(channel_id).unwrap();
-                    packets.append(&mut channel.get_packets_to_send(&mut self.packet_sequence, &mut available_bytes));
+                    packets.append(&mut channel.get_packets_to_send(
+                        &mut self.packet_sequence,
+                        &mut available_bytes,
+                        max_small_messages_bytes,
+                    ));
                 }
             }
         }
 
+        if let Some(channel_id) = delivery_failed {
+            self.disconnect_with_reason(DisconnectReason::SendChannelError {
+                channel_id,
+                error: ChannelError::DeliveryFailed,
+            });
+            return vec![];
+        }
+
+        // Advertise our features until the remote side acknowledges them
+        let remote_understands_features = !self.wait_for_remote_features || self.remote_features.is_some();
+        if !self.features_acked && self.is_connected() && remote_understands_features {
+            packets.push(Packet::Features {
+                sequence: self.packet_sequence,
+                features: self.supported_features.bits(),
+                version: self.version,
+            });
+            self.packet_sequence += 1;
+        }
+
+        // Send our activity until acked, the remote side only understands it if it supports the feature
+        if !self.activity_acked && self.is_connected() && self.negotiated_features().contains(ProtocolFeatures::CLIENT_ACTIVITY) {
+            packets.push(Packet::Activity {
+                sequence: self.packet_sequence,
+                activity: self.activity.to_u8(),
+            });
+            self.packet_sequence += 1;
+        }
+
+        if self.is_connected() && self.negotiated_features().contains(ProtocolFeatures::MTU_PROBE) {
+            if let Some(size) = self.mtu_prober.as_mut().and_then(|prober| prober.next_probe(self.current_time)) {
+                packets.push(Packet::MtuProbe {
+                    sequence: self.packet_sequence,
+                    size: size as u16,
+                });
+                self.packet_sequence += 1;
+            }
+        }
+
+        if self.is_connected() && self.negotiated_features().contains(ProtocolFeatures::TIME_SYNC) {
+            if self
+                .time_sync
+                .as_mut()
+                .is_some_and(|time_sync| time_sync.request_due(self.current_time))
+            {
+                packets.push(Packet::TimeRequest {
+                    sequence: self.packet_sequence,
+                    time: self.current_time.as_micros() as u64,
+                });
+                self.packet_sequence += 1;
+            }
+
+            let send_time = (self.time_base + self.current_time).as_micros() as u64;
+            for (request_time, receive_time) in self.pending_time_responses.drain(..) {
+                packets.push(Packet::TimeResponse {
+                    sequence: self.packet_sequence,
+                    request_time,
+                    receive_time,
+                    send_time,
+                });
+                self.packet_sequence += 1;
+            }
+        }
+
+        if packets.is_empty()
+            && self.is_connected()
+            && self.current_time - self.last_packet_sent_at >= self.keep_alive_interval
+            && self.negotiated_features().contains(ProtocolFeatures::KEEP_ALIVE)
+        {
+            packets.push(Packet::KeepAlive {
+                sequence: self.packet_sequence,
+            });
+            self.packet_sequence += 1;
+        }
+
         if !self.pending_acks.is_empty() {
             let ack_packet = Packet::Ack {
                 sequence: self.packet_sequence,
@@ -523,8 +1961,15 @@ impl RenetClient {
             packets.push(ack_packet);
         }
 
+        packets
+    }
+
+    // Keeps the sent packets until they are acked or lost, with their size on the wire
+    fn record_sent_packets(&mut self, packets: &[Packet], sizes: impl Iterator<Item = usize>) {
         let sent_at = self.current_time;
-        for packet in packets.iter() {
+        // Slices of a message are generated together, only the first one has the tick of a state
+        let mut sliced_state: Option<(u8, u64, u64)> = None;
+        for (packet, size) in packets.iter().zip(sizes) {
             match packet {
                 Packet::SmallReliable {
                     sequence,
@@ -535,6 +1980,7 @@ impl RenetClient {
                         *sequence,
                         PacketSent {
                             sent_at,
+                            size,
                             info: PacketSentInfo::ReliableMessages {
                                 channel_id: *channel_id,
                                 message_ids: messages.iter().map(|(id, _)| *id).collect(),
@@ -551,6 +1997,7 @@ impl RenetClient {
                         *sequence,
                         PacketSent {
                             sent_at,
+                            size,
                             info: PacketSentInfo::ReliableSliceMessage {
                                 channel_id: *channel_id,
                                 message_id: slice.message_id,
@@ -559,21 +2006,90 @@ impl RenetClient {
                         },
                     );
                 }
-                Packet::SmallUnreliable { sequence, .. } => {
+                Packet::SmallUnreliable {
+                    sequence,
+                    channel_id,
+                    messages,
+                } => {
+                    let info = if self.delta_senders.contains_key(channel_id) {
+                        PacketSentInfo::States {
+                            channel_id: *channel_id,
+                            ticks: messages.iter().filter_map(|message| state_tick(message)).collect(),
+                        }
+                    } else if self.input_senders.contains_key(channel_id) {
+                        PacketSentInfo::Inputs {
+                            channel_id: *channel_id,
+                            last_sequences: messages.iter().filter_map(|message| last_input_sequence(message)).collect(),
+                        }
+                    } else {
+                        PacketSentInfo::None
+                    };
+                    self.sent_packets.insert(*sequence, PacketSent { sent_at, size, info });
+                }
+                Packet::UnreliableSlice {
+                    sequence,
+                    channel_id,
+                    slice,
+                } => {
+                    let mut info = PacketSentInfo::None;
+                    if self.delta_senders.contains_key(channel_id) {
+                        if slice.slice_index == 0 {
+                            sliced_state = state_tick(&slice.payload).map(|tick| (*channel_id, slice.message_id, tick));
+                        }
+                        if let Some((state_channel_id, message_id, tick)) = sliced_state {
+                            if state_channel_id == *channel_id && message_id == slice.message_id {
+                                info = PacketSentInfo::States {
+                                    channel_id: *channel_id,
+                                    ticks: vec![tick],
+                                };
+                            }
+                        }
+                    }
+                    self.sent_packets.insert(*sequence, PacketSent { sent_at, size, info });
+                }
+                Packet::KeepAlive { sequence }
+                | Packet::Disconnect { sequence, .. }
+                | Packet::TimeRequest { sequence, .. }
+                | Packet::TimeResponse { sequence, .. } => {
                     self.sent_packets.insert(
                         *sequence,
                         PacketSent {
                             sent_at,
+                            size,
                             info: PacketSentInfo::None,
                         },
                     );
                 }
-                Packet::UnreliableSlice { sequence, .. } => {
+                Packet::Features { sequence, .. } => {
                     self.sent_packets.insert(
                         *sequence,
                         PacketSent {
                             sent_at,
-                            info: PacketSentInfo::None,
+                            size,
+                            info: PacketSentInfo::Features,
+                        },
+                    );
+                }
+                Packet::MtuProbe {
+                    sequence,
+                    size: probe_size,
+                } => {
+                    self.sent_packets.insert(
+                        *sequence,
+                        PacketSent {
+                            sent_at,
+                            size,
+                            info: PacketSentInfo::MtuProbe(*probe_size as usize),
+                        },
+                    );
+                }
+                Packet::Activity { sequence, activity } => {
+                    self.sent_packets.insert(
+                        *sequence,
+                        PacketSent {
+                            sent_at,
+                            size,
+                            info: PacketSentInfo::Activity(ClientActivity::from_u8(*activity).unwrap()),
                         },
                     );
                 }
@@ -584,33 +2100,88 @@ impl RenetClient {
                         *sequence,
                         PacketSent {
                             sent_at,
+                            size,
                             info: PacketSentInfo::Ack { largest_acked_packet },
                         },
                     );
                 }
             }
         }
+    }
+
+    // Packets are timed from when they leave the pacing queue, so the rtt doesn't include
+    // the time spent queued and the resend timer of their messages starts on release.
+    fn packet_released(&mut self, sequence: u64) {
+        let Some(sent) = self.sent_packets.get_mut(&sequence) else {
+            return;
+        };
+        sent.sent_at = self.current_time;
+        match &sent.info {
+            PacketSentInfo::ReliableMessages { channel_id, message_ids } => {
+                let channel = self.send_reliable_channels.get_mut(channel_id).unwrap();
+                for &message_id in message_ids {
+                    channel.message_sent(message_id, self.current_time);
+                }
+            }
+            PacketSentInfo::ReliableSliceMessage {
+                channel_id,
+                message_id,
+                slice_index,
+            } => {
+                let channel = self.send_reliable_channels.get_mut(channel_id).unwrap();
+                channel.slice_sent(*message_id, *slice_index, self.current_time);
+            }
+            _ => {}
+        }
+    }
+
+    fn push_received_events(&mut self, channel_id: u8, received: usize) {
+        for _ in 0..received {
+            self.events.push_back(ConnectionEvent::MessageReceived { channel_id });
+        }
+    }
+
+    // Returns the packets telling the remote side why the connection was terminated, only on the first call.
+    // They are never resent since the transport terminates the connection right after, so the disconnect
+    // packet is sent `REDUNDANT_DISCONNECT_PACKETS` times in a row, in case some of the copies are lost.
+    fn get_disconnect_packets(&mut self) -> Vec<Payload> {
+        let mut buffer = [0u8; MAX_PACKET_BYTES];
+        let mut packets = vec![];
+        for packet in self.disconnect_packets() {
+            match self.packet_serializer.serialize(&packet, &mut buffer) {
+                Ok(len) => packets.push(buffer[..len].to_vec()),
+                Err(e) => log::error!("Failed to serialize disconnect packet: {}", e),
+            }
+        }
 
-        let mut buffer = [0u8; 1400];
-        let mut serialized_packets = Vec::with_capacity(packets.len());
-        let mut bytes_sent: u64 = 0;
-        for packet in packets {
-            let mut oct = OctetsMut::with_slice(&mut buffer);
-            let len = match packet.to_bytes(&mut oct) {
-                Err(err) => {
-                    self.disconnect_with_reason(DisconnectReason::PacketSerialization(err));
-                    return vec![];
-                }
-                Ok(len) => len,
-            };
+        packets
+    }
 
-            bytes_sent += len as u64;
-            serialized_packets.push(buffer[..len].to_vec());
+    fn disconnect_packets(&mut self) -> Vec<Packet> {
+        const REDUNDANT_DISCONNECT_PACKETS: usize = 3;
+
+        if self.disconnect_packets_sent || !self.negotiated_features().contains(ProtocolFeatures::DISCONNECT_REASON) {
+            return vec![];
         }
+        self.disconnect_packets_sent = true;
+        self.update_state();
 
-        self.stats.sent_packets(serialized_packets.len() as u64, bytes_sent);
+        let Some(code) = self.disconnect_reason().and_then(|reason| reason.code()) else {
+            return vec![];
+        };
 
-        serialized_packets
+        let (code, value) = code.to_bytes();
+        let mut packets = Vec::with_capacity(REDUNDANT_DISCONNECT_PACKETS);
+        for _ in 0..REDUNDANT_DISCONNECT_PACKETS {
+            packets.push(Packet::Disconnect {
+                sequence: self.packet_sequence,
+                code,
+                value,
+            });
+            self.packet_sequence += 1;
+        }
+
+        packets
     }
 
     fn add_pending_ack(&mut self, sequence: u64) {
@@ -655,8 +2226,8 @@ impl RenetClient {
         // Add new range with only this sequence at the end
         self.pending_acks.push(sequence..sequence + 1);
 
-        // Limit to 64 pending ranges
-        if self.pending_acks.len() > 64 {
+        // Limit the pending ranges, the oldest ones were most likely already received by the remote side
+        if self.pending_acks.len() > MAX_PENDING_ACK_RANGES {
             self.pending_acks.remove(0);
         }
     }
@@ -689,7 +2260,11 @@ impl RenetClient {
 
     pub(crate) fn disconnect_with_reason(&mut self, reason: DisconnectReason) {
         if !self.is_disconnected() {
+            #[cfg(feature = "tracing")]
+            tracing::info!(%reason, "disconnected");
             self.connection_status = RenetConnectionStatus::Disconnected { reason };
+            self.events.push_back(ConnectionEvent::Disconnected(reason));
+            self.update_state();
         }
     }
 }
@@ -697,6 +2272,7 @@ impl RenetClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::channel::{FullQueuePolicy, ResendBackoff};
 
     #[test]
     fn pending_acks() {
@@ -752,6 +2328,1039 @@ mod tests {
         assert_eq!(connection.pending_acks, vec![]);
     }
 
+    #[test]
+    fn acks_from_received_packets() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+        client.set_connected();
+        server.set_connected();
+
+        let available_memory = client.channel_available_memory(DefaultChannel::ReliableOrdered);
+        client.send_message(DefaultChannel::ReliableOrdered, vec![0; 100]);
+        client.send_message(DefaultChannel::Unreliable, vec![0; 100]);
+        for packet in client.get_packets_to_send() {
+            server.process_packet(&packet);
+        }
+        // Reliable, unreliable and features packets
+        assert_eq!(server.pending_acks, vec![0..3]);
+
+        for packet in server.get_packets_to_send() {
+            client.process_packet(&packet);
+        }
+        assert!(client.sent_packets.is_empty());
+        assert!(client.features_acked);
+        assert_eq!(client.channel_available_memory(DefaultChannel::ReliableOrdered), available_memory);
+    }
+
+    #[test]
+    fn lost_ack_packet() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+
+        client.send_message(DefaultChannel::ReliableOrdered, vec![0; 100]);
+        for packet in client.get_packets_to_send() {
+            server.process_packet(&packet);
+        }
+
+        // First ack packet is lost, the next one still contains all the pending acks
+        assert_eq!(server.get_packets_to_send().len(), 1);
+        server.update(Duration::from_millis(10));
+        for packet in server.get_packets_to_send() {
+            client.process_packet(&packet);
+        }
+
+        assert!(client.sent_packets.is_empty());
+        client.update(Duration::from_secs(1));
+        assert!(
+            client.get_packets_to_send().iter().all(|p| p[0] == 4),
+            "only ack packets, no resend"
+        );
+    }
+
+    #[test]
+    fn snapshot_send_rate() {
+        let mut connection = RenetClient::new(ConnectionConfig::default());
+        let tick = Duration::from_secs_f64(1.0 / 60.0);
+        let count_sent = |connection: &mut RenetClient| {
+            let mut sent = 0;
+            for _ in 0..60 {
+                connection.update(tick);
+                if connection.send_snapshot(DefaultChannel::Unreliable, vec![0; 10]) {
+                    sent += 1;
+                }
+            }
+            sent
+        };
+
+        assert_eq!(count_sent(&mut connection), 60);
+
+        connection.rtt = 0.5;
+        assert_eq!(connection.snapshot_interval(), Duration::from_millis(50));
+        assert_eq!(count_sent(&mut connection), 20);
+    }
+
+    #[test]
+    fn client_activity() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+        client.set_connected();
+        server.set_connected();
+
+        client.set_activity(ClientActivity::Loading);
+        for _ in 0..3 {
+            for packet in client.get_packets_to_send() {
+                server.process_packet(&packet);
+            }
+            for packet in server.get_packets_to_send() {
+                client.process_packet(&packet);
+            }
+        }
+
+        assert!(client.activity_acked);
+        assert_eq!(server.remote_activity(), ClientActivity::Loading);
+        assert!(!server.send_snapshot(DefaultChannel::Unreliable, vec![0; 10]));
+
+        client.set_activity(ClientActivity::InGame);
+        for packet in client.get_packets_to_send() {
+            server.process_packet(&packet);
+        }
+        assert_eq!(server.remote_activity(), ClientActivity::InGame);
+        assert!(server.send_snapshot(DefaultChannel::Unreliable, vec![0; 10]));
+    }
+
+    #[test]
+    fn client_activity_unsupported() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut server = RenetClient::new_from_server(ConnectionConfig {
+            features: ProtocolFeatures::NONE,
+            ..Default::default()
+        });
+        client.set_connected();
+        server.set_connected();
+
+        client.set_activity(ClientActivity::Afk);
+        for _ in 0..3 {
+            for packet in client.get_packets_to_send() {
+                server.process_packet(&packet);
+            }
+            for packet in server.get_packets_to_send() {
+                client.process_packet(&packet);
+            }
+        }
+
+        assert!(!client.activity_acked);
+        assert!(server.is_connected());
+        assert_eq!(server.remote_activity(), ClientActivity::InGame);
+    }
+
+    #[test]
+    fn keep_alive() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+        client.set_connected();
+        server.set_connected();
+
+        // Features already exchanged and nothing to ack, the client is idle
+        client.remote_features = Some(ProtocolFeatures::ALL);
+        client.features_acked = true;
+        assert!(client.get_packets_to_send().is_empty());
+
+        client.update(Duration::from_millis(50));
+        assert!(client.get_packets_to_send().is_empty());
+
+        client.update(Duration::from_millis(50));
+        let packets = client.get_packets_to_send();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0][0], 7);
+        server.process_packet(&packets[0]);
+
+        client.update(Duration::from_millis(20));
+        for packet in server.get_packets_to_send() {
+            client.process_packet(&packet);
+        }
+        assert!(client.sent_packets.is_empty());
+        assert!((client.rtt() - 0.02).abs() < 1e-6);
+    }
+
+    #[test]
+    fn max_packet_size() {
+        let config = ConnectionConfig {
+            max_packet_size: 1250,
+            ..Default::default()
+        };
+        let mut client = RenetClient::new(config);
+        client.set_connected();
+
+        for _ in 0..100 {
+            client.send_message(DefaultChannel::Unreliable, vec![0; 100]);
+            client.send_message(DefaultChannel::ReliableOrdered, vec![0; 100]);
+        }
+        client.send_message(DefaultChannel::ReliableOrdered, vec![0; 5000]);
+        let packets = client.get_packets_to_send();
+        assert!(packets.iter().all(|packet| packet.len() <= 1250));
+        assert!(packets.iter().any(|packet| packet.len() > 1200));
+    }
+
+    #[test]
+    #[should_panic]
+    fn max_packet_size_below_slice_size() {
+        let config = ConnectionConfig {
+            max_packet_size: 1000,
+            ..Default::default()
+        };
+        RenetClient::new(config);
+    }
+
+    #[test]
+    fn pacing() {
+        let config = ConnectionConfig {
+            pacing: Some(Pacing {
+                bytes_per_second: 100_000,
+                burst_bytes: 2_000,
+            }),
+            ..Default::default()
+        };
+        let mut client = RenetClient::new(config);
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+        client.set_connected();
+        server.set_connected();
+
+        for _ in 0..10 {
+            client.send_message(DefaultChannel::ReliableOrdered, vec![0; 1000]);
+        }
+        let packets = client.get_packets_to_send();
+        assert!(packets.len() < 10);
+        assert!(client.paced_packets() > 0);
+        assert!(client.pacing_delay().is_some());
+
+        let mut received = 0;
+        for _ in 0..10 {
+            for packet in client.get_packets_to_send() {
+                server.process_packet(&packet);
+            }
+            client.update(Duration::from_millis(10));
+        }
+        for packet in packets {
+            server.process_packet(&packet);
+        }
+        while server.receive_message(DefaultChannel::ReliableOrdered).is_some() {
+            received += 1;
+        }
+        assert_eq!(received, 10);
+        assert_eq!(client.paced_packets(), 0);
+    }
+
+    #[test]
+    fn pacing_queue_time() {
+        let config = ConnectionConfig {
+            pacing: Some(Pacing {
+                bytes_per_second: 2_500,
+                burst_bytes: 1,
+            }),
+            ..Default::default()
+        };
+        let mut client = RenetClient::new(config);
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+        client.set_connected();
+        server.set_connected();
+
+        client.send_message(DefaultChannel::ReliableOrdered, vec![0; 1000]);
+        client.send_message(DefaultChannel::ReliableOrdered, vec![1; 1000]);
+        let packets = client.get_packets_to_send();
+        assert_eq!(packets.len(), 1);
+        assert!(client.paced_packets() > 0);
+        for packet in packets {
+            server.process_packet(&packet);
+        }
+        for packet in server.get_packets_to_send() {
+            client.process_packet(&packet);
+        }
+
+        // The second message waits for the pacing longer than the resend time
+        let mut packets = vec![];
+        while packets.iter().all(|packet: &Payload| packet.len() < 1000) {
+            client.update(Duration::from_millis(100));
+            packets = client.get_packets_to_send();
+        }
+        assert!(client.current_time > Duration::from_millis(300));
+        // The queued messages were not resent while waiting for the pacing
+        assert!(client
+            .connection_stats()
+            .channels
+            .values()
+            .all(|channel| channel.messages_resent == 0));
+
+        // The rtt doesn't include the time spent in the queue
+        for packet in packets {
+            server.process_packet(&packet);
+        }
+        assert_eq!(server.receive_message(DefaultChannel::ReliableOrdered).unwrap()[0], 0);
+        assert_eq!(server.receive_message(DefaultChannel::ReliableOrdered).unwrap()[0], 1);
+        server.update(Duration::from_millis(50));
+        client.update(Duration::from_millis(50));
+        for packet in server.get_packets_to_send() {
+            client.process_packet(&packet);
+        }
+        assert!((client.rtt() - 0.05).abs() < 0.001);
+    }
+
+    #[test]
+    fn mtu_discovery() {
+        let config = ConnectionConfig {
+            mtu_discovery: Some(MtuDiscovery::default()),
+            ..Default::default()
+        };
+        let mut client = RenetClient::new(config);
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+        client.set_connected();
+        server.set_connected();
+        assert_eq!(client.path_mtu(), Some(1200));
+        assert_eq!(server.path_mtu(), None);
+
+        // Packets bigger than the path mtu are dropped
+        const PATH_MTU: usize = 1260;
+        for _ in 0..200 {
+            client.update(Duration::from_millis(100));
+            server.update(Duration::from_millis(100));
+            for packet in client.get_packets_to_send() {
+                if packet.len() <= PATH_MTU {
+                    server.process_packet(&packet);
+                }
+            }
+            for packet in server.get_packets_to_send() {
+                client.process_packet(&packet);
+            }
+        }
+        assert_eq!(client.path_mtu(), Some(PATH_MTU));
+
+        for _ in 0..100 {
+            client.send_message(DefaultChannel::Unreliable, vec![0; 100]);
+        }
+        let packets = client.get_packets_to_send();
+        assert!(packets.iter().all(|packet| packet.len() <= PATH_MTU));
+        assert!(packets.iter().any(|packet| packet.len() > 1200));
+    }
+
+    #[test]
+    fn connection_timeout() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+
+        // No timeout while connecting
+        client.update(Duration::from_secs(20));
+        assert!(client.is_connecting());
+
+        client.set_connected();
+        server.set_connected();
+        client.update(Duration::from_secs(10));
+        server.send_message(DefaultChannel::Unreliable, vec![1]);
+        for packet in server.get_packets_to_send() {
+            client.process_packet(&packet);
+        }
+        client.update(Duration::from_secs(10));
+        assert!(client.is_connected());
+
+        client.update(Duration::from_secs(5));
+        assert_eq!(client.disconnect_reason(), Some(DisconnectReason::TimedOut));
+    }
+
+    #[test]
+    fn loading_timeout() {
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+        server.set_connected();
+        server.remote_activity = ClientActivity::Loading;
+
+        server.update(Duration::from_secs(30));
+        assert!(server.is_connected());
+
+        server.remote_activity = ClientActivity::InGame;
+        server.update(Duration::ZERO);
+        assert_eq!(server.disconnect_reason(), Some(DisconnectReason::TimedOut));
+    }
+
+    #[test]
+    fn connection_state_transitions() {
+        let transitions = ConnectionState::transitions();
+        for (from, to) in transitions {
+            assert!(ConnectionState::STATES.contains(from));
+            assert!(ConnectionState::STATES.contains(to));
+        }
+
+        let check_transitions = |client: &mut RenetClient| {
+            let mut previous = ConnectionState::Connecting;
+            while let Some(current) = client.next_state_change() {
+                assert!(transitions.contains(&(previous, current)), "{previous:?} -> {current:?}");
+                previous = current;
+            }
+        };
+
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        client.set_challenged();
+        client.set_connecting();
+        client.set_challenged();
+        client.set_connected();
+        client.set_connecting();
+        client.set_connected();
+        client.remote_features = Some(ProtocolFeatures::ALL);
+        client.disconnect();
+        client.get_packets_to_send();
+        assert_eq!(client.state(), ConnectionState::Disconnected);
+        check_transitions(&mut client);
+
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        client.set_challenged();
+        client.disconnect_due_to_transport();
+        check_transitions(&mut client);
+
+        // Rejected by the server before being connected
+        let mut client = RenetClient::new_from_server(ConnectionConfig::default());
+        client.reject(DisconnectReason::DisconnectedByServer);
+        check_transitions(&mut client);
+    }
+
+    #[test]
+    fn connection_state() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        assert_eq!(client.state(), ConnectionState::Connecting);
+        assert_eq!(client.next_state_change(), None);
+
+        client.set_challenged();
+        client.set_challenged();
+        client.set_connected();
+        assert_eq!(client.state(), ConnectionState::Connected);
+        assert_eq!(client.next_state_change(), Some(ConnectionState::Challenged));
+        assert_eq!(client.next_state_change(), Some(ConnectionState::Connected));
+        assert_eq!(client.next_state_change(), None);
+
+        // The disconnect reason is sent before the connection is fully disconnected
+        client.remote_features = Some(ProtocolFeatures::ALL);
+        client.disconnect();
+        assert_eq!(client.state(), ConnectionState::Disconnecting);
+        assert_eq!(client.disconnect_reason(), Some(DisconnectReason::DisconnectedByClient));
+        assert_eq!(client.get_packets_to_send().len(), 3);
+        assert_eq!(client.state(), ConnectionState::Disconnected);
+        assert_eq!(client.next_state_change(), Some(ConnectionState::Disconnecting));
+        assert_eq!(client.next_state_change(), Some(ConnectionState::Disconnected));
+
+        // Nothing to send to the remote side
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        client.disconnect_due_to_transport();
+        assert_eq!(client.state(), ConnectionState::Disconnected);
+        assert_eq!(client.next_state_change(), Some(ConnectionState::Disconnected));
+    }
+
+    #[test]
+    fn max_resends() {
+        let resend_time = Duration::from_millis(100);
+        let config = ConnectionConfig {
+            client_channels_config: vec![
+                ChannelConfig::reliable_ordered(0)
+                    .resend_time(resend_time)
+                    .max_resends(2, false)
+                    .build()
+                    .unwrap(),
+                ChannelConfig::reliable_ordered(1)
+                    .resend_time(resend_time)
+                    .max_resends(2, true)
+                    .build()
+                    .unwrap(),
+            ],
+            ..Default::default()
+        };
+        let mut client = RenetClient::new(config);
+        client.set_connected();
+
+        // No packet reaches the server
+        let mut failed = vec![];
+        let mut send_packets = |client: &mut RenetClient| {
+            client.update(resend_time);
+            client.get_packets_to_send();
+            while let Some(event) = client.next_event() {
+                if let ConnectionEvent::DeliveryFailed { channel_id } = event {
+                    failed.push(channel_id);
+                }
+            }
+        };
+
+        client.send_message(0, vec![0; 10]);
+        client.send_message(0, vec![0; 10]);
+        for _ in 0..4 {
+            send_packets(&mut client);
+        }
+        assert!(client.is_connected());
+
+        client.send_message(1, vec![0; 10]);
+        for _ in 0..4 {
+            send_packets(&mut client);
+        }
+        assert_eq!(failed, vec![0, 0, 1]);
+        assert_eq!(
+            client.disconnect_reason(),
+            Some(DisconnectReason::SendChannelError {
+                channel_id: 1,
+                error: ChannelError::DeliveryFailed
+            })
+        );
+    }
+
+    #[test]
+    fn connection_events() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+        client.set_connected();
+        server.set_connected();
+        assert_eq!(client.next_event(), Some(ConnectionEvent::Connected));
+
+        client.send_message(DefaultChannel::ReliableOrdered, vec![1]);
+        client.send_message(DefaultChannel::ReliableOrdered, vec![2]);
+        client.send_message(DefaultChannel::Unreliable, vec![0; 3000]);
+        for packet in client.get_packets_to_send() {
+            server.process_packet(&packet);
+        }
+
+        let mut received = vec![];
+        while let Some(event) = server.next_event() {
+            if let ConnectionEvent::MessageReceived { channel_id } = event {
+                received.push(channel_id);
+                assert!(server.receive_message(channel_id).is_some());
+            }
+        }
+        let reliable = DefaultChannel::ReliableOrdered.into();
+        let unreliable = DefaultChannel::Unreliable.into();
+        assert_eq!(received, [unreliable, reliable, reliable]);
+
+        for packet in server.get_packets_to_send() {
+            client.process_packet(&packet);
+        }
+        let mut acked = vec![];
+        while let Some(event) = client.next_event() {
+            if let ConnectionEvent::PacketAcked { sequence } = event {
+                acked.push(sequence);
+            }
+        }
+        assert!(!acked.is_empty());
+
+        // Events are discarded on update
+        server.send_message(DefaultChannel::Unreliable, vec![1]);
+        for packet in server.get_packets_to_send() {
+            client.process_packet(&packet);
+        }
+        client.update(Duration::ZERO);
+        assert_eq!(client.next_event(), None);
+
+        client.disconnect();
+        assert_eq!(
+            client.next_event(),
+            Some(ConnectionEvent::Disconnected(DisconnectReason::DisconnectedByClient))
+        );
+    }
+
+    #[test]
+    fn disconnect_packets() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+        client.set_connected();
+        server.set_connected();
+        client.remote_features = Some(ProtocolFeatures::ALL);
+        server.remote_features = Some(ProtocolFeatures::ALL);
+
+        server.disconnect_with_code(7);
+        let packets = server.get_packets_to_send();
+        assert_eq!(packets.len(), 3);
+        assert!(server.get_packets_to_send().is_empty());
+
+        // Any of the copies is enough, even if the others are lost
+        client.process_packet(&packets[1]);
+        assert_eq!(
+            client.disconnect_reason(),
+            Some(DisconnectReason::DisconnectedByRemote(DisconnectCode::Custom(7)))
+        );
+        // No need to notify a side that disconnected us
+        assert!(client.get_packets_to_send().is_empty());
+    }
+
+    #[test]
+    fn rtt() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+        assert_eq!(client.rtt(), 0.0);
+
+        let mut exchange = |client: &mut RenetClient, rtt: Duration| {
+            client.send_message(DefaultChannel::Unreliable, vec![0; 10]);
+            let packets = client.get_packets_to_send();
+            client.update(rtt);
+            for packet in packets {
+                server.process_packet(&packet);
+            }
+            for packet in server.get_packets_to_send() {
+                client.process_packet(&packet);
+            }
+        };
+
+        // First sample is used as is
+        exchange(&mut client, Duration::from_millis(100));
+        assert!((client.rtt() - 0.1).abs() < 1e-9);
+
+        // Following samples are smoothed
+        exchange(&mut client, Duration::from_millis(200));
+        assert!(client.rtt() > 0.1 && client.rtt() < 0.15);
+        assert_eq!(client.network_info().rtt, client.rtt());
+    }
+
+    #[test]
+    fn network_info() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+        client.set_connected();
+        server.set_connected();
+
+        client.send_message(DefaultChannel::ReliableOrdered, vec![0; 100]);
+        client.send_message(DefaultChannel::ReliableOrdered, vec![0; 100]);
+        let packets = client.get_packets_to_send();
+        let sent_bytes: usize = packets.iter().map(|packet| packet.len()).sum();
+        for packet in packets {
+            server.process_packet(&packet);
+        }
+
+        let info = client.network_info();
+        assert_eq!(info.bytes_in_flight, sent_bytes as u64);
+        let reliable = DefaultChannel::ReliableOrdered.into();
+        let channel = info.channels.iter().find(|info| info.channel_id == reliable).unwrap();
+        assert_eq!(channel.send_queue, 2);
+        assert_eq!(info.channels.len(), 3);
+
+        let info = server.network_info();
+        let channel = info.channels.iter().find(|info| info.channel_id == reliable).unwrap();
+        assert_eq!(channel.receive_queue, 2);
+
+        for packet in server.get_packets_to_send() {
+            client.process_packet(&packet);
+        }
+        let info = client.network_info();
+        assert!(info.channels.iter().all(|channel| channel.send_queue == 0));
+        // Only the packet with the ack of the server packets is in flight
+        assert!(info.bytes_in_flight < sent_bytes as u64);
+    }
+
+    #[test]
+    fn connection_stats() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+        client.set_connected();
+        server.set_connected();
+
+        client.send_message(DefaultChannel::ReliableOrdered, vec![0; 100]);
+        client.send_message(DefaultChannel::Unreliable, vec![0; 50]);
+        let packets = client.get_packets_to_send();
+        let sent_bytes: u64 = packets.iter().map(|packet| packet.len() as u64).sum();
+        for packet in packets.iter() {
+            server.process_packet(packet);
+        }
+        while server.receive_message(DefaultChannel::ReliableOrdered).is_some() {}
+
+        let stats = client.connection_stats();
+        assert_eq!(stats.packets_sent, packets.len() as u64);
+        assert_eq!(stats.bytes_sent, sent_bytes);
+        let reliable = stats.channels[&DefaultChannel::ReliableOrdered.into()].clone();
+        assert_eq!((reliable.messages_sent, reliable.bytes_sent), (1, 100));
+        let unreliable = stats.channels[&DefaultChannel::Unreliable.into()].clone();
+        assert_eq!((unreliable.messages_sent, unreliable.bytes_sent), (1, 50));
+
+        let stats = server.connection_stats();
+        assert_eq!(stats.packets_received, packets.len() as u64);
+        assert_eq!(stats.bytes_received, sent_bytes);
+        let reliable = stats.channels[&DefaultChannel::ReliableOrdered.into()].clone();
+        assert_eq!((reliable.messages_received, reliable.bytes_received), (1, 100));
+        assert!(!stats.channels.contains_key(&DefaultChannel::Unreliable.into()));
+
+        for packet in server.get_packets_to_send() {
+            client.process_packet(&packet);
+        }
+        assert_eq!(client.connection_stats().packets_acked, packets.len() as u64);
+
+        // Packets not acked are lost after 3 seconds
+        client.send_message(DefaultChannel::Unreliable, vec![0; 50]);
+        let lost = client.get_packets_to_send().len() as u64;
+        client.update(Duration::from_secs(3));
+        assert_eq!(client.connection_stats().packets_lost, lost);
+    }
+
+    #[test]
+    fn validate_config() {
+        assert_eq!(ConnectionConfig::default().validate(), Ok(()));
+
+        let channel = ChannelConfig::reliable_ordered(3)
+            .resend_time(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        assert!(matches!(channel.send_type, SendType::ReliableOrdered { resend_time } if resend_time == Duration::from_millis(50)));
+        assert_eq!(
+            ChannelConfig::unreliable(0).max_memory_usage_bytes(0).build().unwrap_err(),
+            ConfigError::ZeroMemoryUsage { channel_id: 0 }
+        );
+        // Resending every update is allowed
+        assert!(ChannelConfig::reliable_unordered(1).resend_time(Duration::ZERO).build().is_ok());
+        assert_eq!(
+            ChannelConfig::reliable_ordered(2)
+                .full_queue_policy(FullQueuePolicy::DropOldest)
+                .build()
+                .unwrap_err(),
+            ConfigError::DropOldestOnReliableChannel { channel_id: 2 }
+        );
+        assert_eq!(
+            ChannelConfig::reliable_ordered(2)
+                .resend_backoff(ResendBackoff {
+                    multiplier: 0.5,
+                    ..Default::default()
+                })
+                .build()
+                .unwrap_err(),
+            ConfigError::InvalidResendBackoff { channel_id: 2 }
+        );
+        assert!(ChannelConfig::unreliable(0)
+            .full_queue_policy(FullQueuePolicy::DropOldest)
+            .build()
+            .is_ok());
+
+        let mut config = ConnectionConfig::default();
+        config.client_channels_config.push(ChannelConfig::unreliable(1).build().unwrap());
+        assert_eq!(config.validate(), Err(ConfigError::DuplicateChannelId(1)));
+
+        let config = ConnectionConfig {
+            timeout: Duration::from_millis(300),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::ResendTimeAboveTimeout {
+                channel_id: 1,
+                resend_time: Duration::from_millis(300),
+                timeout: Duration::from_millis(300),
+            })
+        );
+
+        let config = ConnectionConfig {
+            max_packet_size: 1000,
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::MaxPacketSizeTooSmall { size: 1000, .. })
+        ));
+    }
+
+    #[test]
+    fn try_send_message() {
+        let config = ConnectionConfig {
+            client_channels_config: vec![
+                ChannelConfig::unreliable(0).max_memory_usage_bytes(100).build().unwrap(),
+                ChannelConfig::reliable_ordered(1).max_memory_usage_bytes(100).build().unwrap(),
+            ],
+            ..Default::default()
+        };
+        let mut client = RenetClient::new(config);
+        client.set_connected();
+
+        assert!(matches!(
+            client.try_send_message(5, vec![0; 10]),
+            Err(RenetError::InvalidChannelId(5))
+        ));
+        assert!(matches!(client.try_receive_message(5), Err(RenetError::InvalidChannelId(5))));
+        assert!(client.try_send_message(0, vec![0; 100]).is_ok());
+        assert!(matches!(
+            client.try_send_message(0, vec![0; 1]),
+            Err(RenetError::Channel {
+                channel_id: 0,
+                error: ChannelError::UnreliableChannelMaxMemoryReached
+            })
+        ));
+        assert!(matches!(
+            client.try_send_message(1, vec![0; 101]),
+            Err(RenetError::Channel {
+                channel_id: 1,
+                error: ChannelError::ReliableChannelMaxMemoryReached
+            })
+        ));
+        assert!(client.is_connected());
+
+        client.send_message(1, vec![0; 101]);
+        assert!(matches!(client.try_send_message(0, vec![0; 1]), Err(RenetError::Disconnected(_))));
+
+        let config = ConnectionConfig {
+            max_packet_size: 2000,
+            ..Default::default()
+        };
+        assert!(matches!(RenetClient::try_new(config), Err(RenetError::Config(_))));
+    }
+
+    #[test]
+    fn pooled_buffers() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+        client.set_connected();
+        server.set_connected();
+
+        let mut reliable = client.acquire_buffer();
+        reliable.extend_from_slice(&[1; 100]);
+        let reliable_pointer = reliable.as_ptr();
+        client.send_message(DefaultChannel::ReliableOrdered, reliable.freeze());
+        let mut unreliable = client.acquire_buffer();
+        unreliable.extend_from_slice(&[2; 100]);
+        let unreliable_pointer = unreliable.as_ptr();
+        client.send_message(DefaultChannel::Unreliable, unreliable.freeze());
+
+        let packets = client.get_packets_to_send();
+        let payload_pointers: Vec<*const u8> = packets.iter().map(|packet| packet.as_ptr()).collect();
+        for packet in packets.iter() {
+            server.process_packet(packet);
+        }
+        client.recycle_payloads(packets);
+
+        // The unreliable message was reclaimed after being sent, the reliable one after being acked
+        assert_eq!(client.acquire_buffer().as_ptr(), unreliable_pointer);
+        for packet in server.get_packets_to_send() {
+            client.process_packet(&packet);
+        }
+        assert_eq!(client.acquire_buffer().as_ptr(), reliable_pointer);
+
+        // The message is still referenced by the server
+        assert_eq!(
+            server.receive_message(DefaultChannel::ReliableOrdered).unwrap(),
+            [1; 100].as_slice()
+        );
+
+        client.send_message(DefaultChannel::Unreliable, vec![0; 10]);
+        assert!(payload_pointers.contains(&client.get_packets_to_send()[0].as_ptr()));
+    }
+
+    #[test]
+    fn kbps() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut bytes_sent = 0;
+        for _ in 0..10 {
+            client.update(Duration::from_millis(100));
+            client.send_message(DefaultChannel::Unreliable, vec![0; 122]);
+            bytes_sent += client.get_packets_to_send().iter().map(|p| p.len()).sum::<usize>();
+        }
+
+        // Sent during 1 second
+        assert!((client.sent_kbps() - bytes_sent as f64 * 8.0 / 1000.0).abs() < 1e-9);
+        assert_eq!(client.received_kbps(), 0.0);
+
+        // Nothing sent in the last second, the stats window still has the older packets
+        for _ in 0..15 {
+            client.update(Duration::from_millis(100));
+        }
+        assert_eq!(client.sent_kbps(), 0.0);
+        assert!(client.bytes_sent_per_sec() > 0.0);
+    }
+
+    #[test]
+    fn stream_channel() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+        client.set_connected();
+        server.set_connected();
+
+        let data: Vec<u8> = (0..SLICE_SIZE * 3).map(|i| i as u8).collect();
+        client.write_stream(DefaultChannel::ReliableOrdered, &data[..10]).unwrap();
+        client.write_stream(DefaultChannel::ReliableOrdered, &data[10..]).unwrap();
+
+        for packet in client.get_packets_to_send() {
+            server.process_packet(&packet);
+        }
+
+        let mut received = vec![];
+        let mut buf = [0u8; 700];
+        loop {
+            let read = server.read_stream(DefaultChannel::ReliableOrdered, &mut buf).unwrap();
+            if read == 0 {
+                break;
+            }
+            received.extend_from_slice(&buf[..read]);
+        }
+
+        assert_eq!(data, received);
+    }
+
+    #[test]
+    fn dropped_messages_stats() {
+        let channel = ChannelConfig::unreliable(0)
+            .max_memory_usage_bytes(100)
+            .full_queue_policy(FullQueuePolicy::DropNewest)
+            .build()
+            .unwrap();
+        let config = ConnectionConfig {
+            client_channels_config: vec![channel.clone()],
+            server_channels_config: vec![channel],
+            ..Default::default()
+        };
+        let mut client = RenetClient::new(config);
+        client.set_connected();
+
+        for _ in 0..3 {
+            client.try_send_message(0, vec![0; 40]).unwrap();
+        }
+        let stats = &client.connection_stats().channels[&0];
+        assert_eq!(stats.messages_sent, 2);
+        assert_eq!(stats.bytes_sent, 80);
+        assert_eq!(stats.messages_dropped, 1);
+    }
+
+    #[test]
+    fn stream_channel_full() {
+        let channel = ChannelConfig::reliable_ordered(0)
+            .max_memory_usage_bytes(1000)
+            .full_queue_policy(FullQueuePolicy::DropNewest)
+            .build()
+            .unwrap();
+        let config = ConnectionConfig {
+            client_channels_config: vec![channel.clone()],
+            server_channels_config: vec![channel],
+            ..Default::default()
+        };
+        let mut client = RenetClient::new(config);
+        client.set_connected();
+
+        client.write_stream(0, &[0; 600]).unwrap();
+        let error = client.write_stream(0, &[0; 600]).unwrap_err();
+        assert!(matches!(
+            error,
+            RenetError::Channel {
+                channel_id: 0,
+                error: ChannelError::ReliableChannelMaxMemoryReached
+            }
+        ));
+        // Nothing of the rejected data was written
+        assert_eq!(client.channel_available_memory(0), 400);
+        assert!(client.is_connected());
+    }
+
+    #[test]
+    fn stream_channel_invalid() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        client.set_connected();
+        let mut buf = [0u8; 10];
+
+        for channel_id in [DefaultChannel::Unreliable, DefaultChannel::ReliableUnordered] {
+            let channel_id = channel_id.into();
+            assert!(matches!(
+                client.write_stream(channel_id, &[0; 10]),
+                Err(RenetError::UnorderedChannel(id)) if id == channel_id
+            ));
+            assert!(matches!(
+                client.read_stream(channel_id, &mut buf),
+                Err(RenetError::UnorderedChannel(id)) if id == channel_id
+            ));
+        }
+        assert!(matches!(client.write_stream(10, &[0; 10]), Err(RenetError::InvalidChannelId(10))));
+        assert!(matches!(client.read_stream(10, &mut buf), Err(RenetError::InvalidChannelId(10))));
+        assert_eq!(client.channel_available_memory(DefaultChannel::ReliableUnordered), 5 * 1024 * 1024);
+    }
+
+    #[test]
+    fn version_negotiation() {
+        let connect = |client_config: ConnectionConfig, server_config: ConnectionConfig| {
+            let mut client = RenetClient::new(client_config);
+            let mut server = RenetClient::new_from_server(server_config);
+            client.set_connected();
+            server.set_connected();
+            for _ in 0..3 {
+                for packet in client.get_packets_to_send() {
+                    server.process_packet(&packet);
+                }
+                for packet in server.get_packets_to_send() {
+                    client.process_packet(&packet);
+                }
+            }
+            (client, server)
+        };
+        let config = |version: u16, compatible_versions: Option<RangeInclusive<u16>>| ConnectionConfig {
+            version,
+            compatible_versions,
+            ..Default::default()
+        };
+
+        let (client, server) = connect(config(1, None), config(1, None));
+        assert!(client.is_connected());
+        assert!(server.is_connected());
+        assert_eq!(server.remote_version(), Some(1));
+
+        // The server detects it first and notifies the client
+        let (client, server) = connect(config(1, None), config(2, None));
+        assert_eq!(
+            client.disconnect_reason(),
+            Some(DisconnectReason::DisconnectedByRemote(DisconnectCode::IncompatibleVersion))
+        );
+        assert_eq!(
+            server.disconnect_reason(),
+            Some(DisconnectReason::IncompatibleVersion { remote_version: 1 })
+        );
+
+        let (client, server) = connect(config(2, Some(2..=3)), config(3, Some(2..=3)));
+        assert!(client.is_connected());
+        assert!(server.is_connected());
+    }
+
+    #[test]
+    fn features_negotiation() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut server = RenetClient::new_from_server(ConnectionConfig {
+            features: ProtocolFeatures::NONE,
+            ..Default::default()
+        });
+        assert!(server.network_info().negotiated_features.is_empty());
+
+        // Features are only sent when connected
+        assert!(client.get_packets_to_send().is_empty());
+        client.set_connected();
+        server.set_connected();
+
+        for _ in 0..2 {
+            for packet in client.get_packets_to_send() {
+                server.process_packet(&packet);
+            }
+            for packet in server.get_packets_to_send() {
+                client.process_packet(&packet);
+            }
+        }
+
+        assert_eq!(client.negotiated_features(), ProtocolFeatures::NONE);
+        assert_eq!(server.negotiated_features(), ProtocolFeatures::NONE);
+        assert!(client.features_acked);
+        assert!(server.features_acked);
+    }
+
+    #[test]
+    fn features_from_handshake() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+        client.set_connected();
+        server.set_connected();
+
+        // Servers don't send their features to clients that may not understand them
+        assert!(server.get_packets_to_send().is_empty());
+
+        let handshake = client.start_handshake();
+        server.set_remote_handshake(&handshake);
+        assert_eq!(server.negotiated_features(), ProtocolFeatures::ALL);
+
+        // The client features are known from the handshake, only the server sends its features
+        assert!(client.get_packets_to_send().is_empty());
+        for packet in server.get_packets_to_send() {
+            client.process_packet(&packet);
+        }
+        assert_eq!(client.negotiated_features(), ProtocolFeatures::ALL);
+    }
+
+    #[test]
+    fn unknown_packet_type() {
+        let mut connection = RenetClient::new(ConnectionConfig::default());
+        connection.set_connected();
+        connection.process_packet(&[200, 0]);
+        assert!(connection.is_connected());
+    }
+
     #[test]
     fn discard_old_packets() {
         let mut connection = RenetClient::new(ConnectionConfig::default());
@@ -0,0 +1,401 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::Mutex,
+    time::Duration,
+};
+
+use bytes::Bytes;
+
+use crate::{ClientId, RenetClient, RenetServer};
+
+const RECORDING_MAGIC: &[u8; 4] = b"RNRC";
+const RECORDING_VERSION: u8 = 1;
+
+/// Input of a client or server, from the transport or the application loop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionEvent {
+    /// The client or server was updated with the duration.
+    Update(Duration),
+    /// A packet was received, `client_id` is the sender for servers and `None` for clients.
+    Packet { client_id: Option<ClientId>, payload: Vec<u8> },
+    /// The server added a connection, or the client was set as connected by its transport.
+    Connected {
+        client_id: Option<ClientId>,
+        user_data: Option<Bytes>,
+    },
+    /// The server removed a connection, or the client was disconnected by its transport.
+    Disconnected { client_id: Option<ClientId> },
+}
+
+/// Event with the time it happened, the sum of the updates before it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedEvent {
+    pub time: Duration,
+    pub event: SessionEvent,
+}
+
+/// Events recorded by a [`SessionRecorder`], that can be saved to a file and replayed with a [`SessionReplay`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionRecording {
+    events: Vec<RecordedEvent>,
+}
+
+impl SessionRecording {
+    pub fn events(&self) -> &[RecordedEvent] {
+        &self.events
+    }
+
+    /// Returns the time of the last event.
+    pub fn duration(&self) -> Duration {
+        self.events.last().map_or(Duration::ZERO, |event| event.time)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Writes the recording in a compact binary format, integers are big-endian.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(RECORDING_MAGIC)?;
+        writer.write_all(&[RECORDING_VERSION])?;
+        for RecordedEvent { time, event } in self.events.iter() {
+            writer.write_all(&(time.as_nanos() as u64).to_be_bytes())?;
+            match event {
+                SessionEvent::Update(duration) => {
+                    writer.write_all(&[0])?;
+                    writer.write_all(&(duration.as_nanos() as u64).to_be_bytes())?;
+                }
+                SessionEvent::Packet { client_id, payload } => {
+                    writer.write_all(&[1])?;
+                    write_client_id(&mut writer, *client_id)?;
+                    write_bytes(&mut writer, payload)?;
+                }
+                SessionEvent::Connected { client_id, user_data } => {
+                    writer.write_all(&[2])?;
+                    write_client_id(&mut writer, *client_id)?;
+                    match user_data {
+                        Some(user_data) => {
+                            writer.write_all(&[1])?;
+                            write_bytes(&mut writer, user_data)?;
+                        }
+                        None => writer.write_all(&[0])?,
+                    }
+                }
+                SessionEvent::Disconnected { client_id } => {
+                    writer.write_all(&[3])?;
+                    write_client_id(&mut writer, *client_id)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn read_from(mut reader: impl Read) -> io::Result<Self> {
+        let mut header = [0u8; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != RECORDING_MAGIC || header[4] != RECORDING_VERSION {
+            return Err(invalid_data("not a renet session recording"));
+        }
+
+        let mut events = vec![];
+        loop {
+            let mut time = [0u8; 8];
+            // The recording can only end at the start of an event
+            if reader.read(&mut time[..1])? == 0 {
+                break;
+            }
+            reader.read_exact(&mut time[1..])?;
+            let time = Duration::from_nanos(u64::from_be_bytes(time));
+
+            let event = match read_u8(&mut reader)? {
+                0 => SessionEvent::Update(Duration::from_nanos(read_u64(&mut reader)?)),
+                1 => SessionEvent::Packet {
+                    client_id: read_client_id(&mut reader)?,
+                    payload: read_bytes(&mut reader)?,
+                },
+                2 => {
+                    let client_id = read_client_id(&mut reader)?;
+                    let user_data = match read_u8(&mut reader)? {
+                        0 => None,
+                        1 => Some(read_bytes(&mut reader)?.into()),
+                        _ => return Err(invalid_data("invalid user data flag")),
+                    };
+                    SessionEvent::Connected { client_id, user_data }
+                }
+                3 => SessionEvent::Disconnected {
+                    client_id: read_client_id(&mut reader)?,
+                },
+                _ => return Err(invalid_data("invalid event type")),
+            };
+            events.push(RecordedEvent { time, event });
+        }
+
+        Ok(Self { events })
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_client_id(writer: &mut impl Write, client_id: Option<ClientId>) -> io::Result<()> {
+    match client_id {
+        Some(client_id) => {
+            writer.write_all(&[1])?;
+            writer.write_all(&client_id.raw().to_be_bytes())
+        }
+        None => writer.write_all(&[0]),
+    }
+}
+
+fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(bytes.len()).map_err(|_| invalid_data("event too big to be recorded"))?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(bytes)
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut value = [0u8; 1];
+    reader.read_exact(&mut value)?;
+    Ok(value[0])
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut value = [0u8; 8];
+    reader.read_exact(&mut value)?;
+    Ok(u64::from_be_bytes(value))
+}
+
+fn read_client_id(reader: &mut impl Read) -> io::Result<Option<ClientId>> {
+    match read_u8(reader)? {
+        0 => Ok(None),
+        1 => Ok(Some(ClientId::from_raw(read_u64(reader)?))),
+        _ => Err(invalid_data("invalid client id flag")),
+    }
+}
+
+fn read_bytes(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let mut bytes = vec![];
+    reader.by_ref().take(u32::from_be_bytes(len) as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != u32::from_be_bytes(len) as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+/// Records the inputs of a client or server, so the session can be replayed offline.
+///
+/// # Usage
+/// ```
+/// # use renet::{ConnectionConfig, RenetServer, SessionRecorder};
+/// # use std::sync::Arc;
+/// let recorder = Arc::new(SessionRecorder::new());
+/// let mut server = RenetServer::new(ConnectionConfig::default());
+/// server.set_session_recorder(recorder.clone());
+///
+/// // Run the server...
+///
+/// let recording = recorder.recording();
+/// # let path = std::env::temp_dir().join("renet_doc_session.rec");
+/// recording.save(&path).unwrap();
+/// ```
+#[derive(Debug, Default)]
+pub struct SessionRecorder {
+    recording: Mutex<SessionRecording>,
+    time: Mutex<Duration>,
+}
+
+impl SessionRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a copy of the events recorded so far.
+    pub fn recording(&self) -> SessionRecording {
+        self.recording.lock().unwrap().clone()
+    }
+
+    pub(crate) fn record(&self, event: SessionEvent) {
+        let mut time = self.time.lock().unwrap();
+        if let SessionEvent::Update(duration) = event {
+            *time += duration;
+        }
+        self.recording.lock().unwrap().events.push(RecordedEvent { time: *time, event });
+    }
+}
+
+/// Feeds a recording back into a client or server, one update at a time.
+///
+/// Each step applies an update and the events that followed it until the next update,
+/// like the transport does in a game loop. The game logic should run between the steps
+/// like it did in the recorded session, and the packets to send should still be generated,
+/// then discarded, so acks and resends are replayed identically.
+///
+/// # Usage
+/// ```
+/// # use renet::{ConnectionConfig, RenetServer, SessionRecording, SessionReplay};
+/// # let recording = SessionRecording::default();
+/// let mut server = RenetServer::new(ConnectionConfig::default());
+/// let mut replay = SessionReplay::new(recording);
+/// while replay.step_server(&mut server) {
+///     // Game logic...
+///     for client_id in server.clients_id() {
+///         server.get_packets_to_send(client_id).unwrap();
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct SessionReplay {
+    events: VecDeque<RecordedEvent>,
+    time: Duration,
+}
+
+impl SessionReplay {
+    pub fn new(recording: SessionRecording) -> Self {
+        Self {
+            events: recording.events.into(),
+            time: Duration::ZERO,
+        }
+    }
+
+    /// Returns the time of the last applied event.
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Applies the next step to the client, returns `false` when the recording is finished.
+    /// Events recorded from a server are ignored.
+    pub fn step_client(&mut self, client: &mut RenetClient) -> bool {
+        self.step(|event| match event {
+            SessionEvent::Update(duration) => client.update(duration),
+            SessionEvent::Packet { client_id: None, payload } => client.process_packet(&payload),
+            SessionEvent::Connected { client_id: None, .. } => client.set_connected(),
+            SessionEvent::Disconnected { client_id: None } => client.disconnect_due_to_transport(),
+            _ => {}
+        })
+    }
+
+    /// Applies the next step to the server, returns `false` when the recording is finished.
+    /// Events recorded from a client are ignored.
+    pub fn step_server(&mut self, server: &mut RenetServer) -> bool {
+        self.step(|event| match event {
+            SessionEvent::Update(duration) => server.update(duration),
+            SessionEvent::Packet {
+                client_id: Some(client_id),
+                payload,
+            } => {
+                let _ = server.process_packet_from(&payload, client_id);
+            }
+            SessionEvent::Connected {
+                client_id: Some(client_id),
+                user_data,
+            } => match user_data {
+                Some(user_data) => server.add_connection_with_user_data(client_id, user_data),
+                None => server.add_connection(client_id),
+            },
+            SessionEvent::Disconnected {
+                client_id: Some(client_id),
+            } => server.remove_connection(client_id),
+            _ => {}
+        })
+    }
+
+    fn step(&mut self, mut apply: impl FnMut(SessionEvent)) -> bool {
+        if self.events.is_empty() {
+            return false;
+        }
+
+        let mut updated = false;
+        while let Some(recorded) = self.events.front() {
+            if updated && matches!(recorded.event, SessionEvent::Update(_)) {
+                break;
+            }
+
+            let recorded = self.events.pop_front().unwrap();
+            updated |= matches!(recorded.event, SessionEvent::Update(_));
+            self.time = recorded.time;
+            apply(recorded.event);
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_round_trip() {
+        let recorder = SessionRecorder::new();
+        recorder.record(SessionEvent::Connected {
+            client_id: Some(ClientId::from_raw(3)),
+            user_data: Some(Bytes::from_static(b"user")),
+        });
+        recorder.record(SessionEvent::Update(Duration::from_millis(16)));
+        recorder.record(SessionEvent::Packet {
+            client_id: Some(ClientId::from_raw(3)),
+            payload: vec![1, 2, 3],
+        });
+        recorder.record(SessionEvent::Update(Duration::from_millis(16)));
+        recorder.record(SessionEvent::Disconnected { client_id: None });
+
+        let recording = recorder.recording();
+        assert_eq!(recording.duration(), Duration::from_millis(32));
+        assert_eq!(recording.events()[2].time, Duration::from_millis(16));
+
+        let mut bytes = vec![];
+        recording.write_to(&mut bytes).unwrap();
+        assert_eq!(SessionRecording::read_from(&bytes[..]).unwrap(), recording);
+
+        // Truncated in the middle of an event
+        let error = SessionRecording::read_from(&bytes[..bytes.len() - 3]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        let error = SessionRecording::read_from(&b"RNRC\x09"[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn steps_end_before_the_next_update() {
+        let recorder = SessionRecorder::new();
+        for event in [
+            SessionEvent::Connected {
+                client_id: None,
+                user_data: None,
+            },
+            SessionEvent::Update(Duration::from_millis(10)),
+            SessionEvent::Packet {
+                client_id: None,
+                payload: vec![],
+            },
+            SessionEvent::Update(Duration::from_millis(10)),
+        ] {
+            recorder.record(event);
+        }
+
+        let mut replay = SessionReplay::new(recorder.recording());
+        let mut steps = vec![];
+        while replay.step(|event| steps.push(event)) {
+            steps.push(SessionEvent::Update(Duration::ZERO));
+        }
+        assert_eq!(replay.time(), Duration::from_millis(20));
+        assert_eq!(steps.len(), 6);
+        assert_eq!(steps[3], SessionEvent::Update(Duration::ZERO));
+    }
+}
@@ -0,0 +1,586 @@
+//! Replication of server objects to the clients.
+//!
+//! The server gives the serialized state of each replicated object to a [`ReplicationServer`], which tracks
+//! the state known by each client and sends them the objects created, changed or destroyed since the last send.
+//! Changes are sent as a diff against the state the client knows, so only the changed bytes are sent.
+//! Clients apply the messages with a [`ReplicationClient`] and receive a [`ReplicationEvent`] for each change.
+//!
+//! Messages are sent over a reliable ordered channel, so the known state of each client is always
+//! the last state sent to it.
+//!
+//! In large worlds, [`ReplicationServer::send_filtered`] only replicates to each client the objects relevant to it,
+//! see [`RelevancyFilter`]. Objects are spawned for a client when they become relevant, and despawned when they no longer are.
+//!
+//! ```
+//! # use renet::{ClientId, ConnectionConfig, RenetClient, RenetServer};
+//! use renet::replication::{ReplicationClient, ReplicationConfig, ReplicationEvent, ReplicationServer};
+//!
+//! # let mut server = RenetServer::new(ConnectionConfig::default());
+//! # let mut client = RenetClient::new(ConnectionConfig::default());
+//! # let client_id = ClientId::from_raw(0);
+//! # server.add_connection(client_id);
+//! # client.set_connected();
+//! let mut server_replication = ReplicationServer::new(ReplicationConfig::default());
+//! let player = server_replication.spawn(vec![0; 16]);
+//! server_replication.send(&mut server);
+//! # for packet in server.get_packets_to_send(client_id).unwrap() {
+//! #     client.process_packet(&packet);
+//! # }
+//!
+//! let mut client_replication = ReplicationClient::new(ReplicationConfig::default());
+//! client_replication.receive(&mut client);
+//! assert!(matches!(client_replication.get_event(), Some(ReplicationEvent::Spawned { id, .. }) if id == player));
+//! ```
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use crate::delta::{apply_diff, write_diff};
+use crate::varint::{read_varint, write_varint};
+use crate::{ClientId, DefaultChannel, RenetClient, RenetServer};
+
+const SPAWN: u8 = 0;
+const UPDATE: u8 = 1;
+const DESPAWN: u8 = 2;
+
+/// Returns the changes from the state known by a client to the new state.
+pub type DiffFn = fn(known: &[u8], state: &[u8]) -> Bytes;
+/// Returns the new state from the known state and the changes, or `None` if they're invalid.
+pub type ApplyFn = fn(known: &[u8], diff: &[u8]) -> Option<Bytes>;
+
+/// Identifier of a replicated object, the same on the server and the clients.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Ord, PartialOrd)]
+pub struct ObjectId(u64);
+
+impl ObjectId {
+    /// Creates an [`ObjectId`] from a raw 64 bit value.
+    pub const fn from_raw(value: u64) -> Self {
+        Self(value)
+    }
+
+    /// Returns the raw 64 bit value of the [`ObjectId`].
+    pub fn raw(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Configuration of the replication, both sides must use the same one.
+#[derive(Debug, Clone)]
+pub struct ReplicationConfig {
+    /// Reliable ordered channel used for the replication messages, it should only be used for them.
+    /// Default: [`DefaultChannel::ReliableOrdered`]
+    pub channel_id: u8,
+    /// Computes the changes sent when the state of an object changes.
+    /// Default: [`byte_diff`]
+    pub diff: DiffFn,
+    /// Applies the changes computed by `diff`, must be its inverse.
+    /// Default: [`apply_byte_diff`]
+    pub apply: ApplyFn,
+    /// Maximum bytes of changes sent to each client per send, the rest are sent in later sends
+    /// by priority, see [`ReplicationServer::send_prioritized`]. Only used by the server.
+    /// Default: None (no limit)
+    pub max_bytes_per_send: Option<usize>,
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self {
+            channel_id: DefaultChannel::ReliableOrdered.into(),
+            diff: byte_diff,
+            apply: apply_byte_diff,
+            max_bytes_per_send: None,
+        }
+    }
+}
+
+/// Diff of the bytes that changed, works well for states with fixed layouts where few fields change.
+pub fn byte_diff(known: &[u8], state: &[u8]) -> Bytes {
+    let mut diff = BytesMut::new();
+    write_diff(&mut diff, known, state);
+    diff.freeze()
+}
+
+/// Applies a diff from [`byte_diff`].
+pub fn apply_byte_diff(known: &[u8], diff: &[u8]) -> Option<Bytes> {
+    apply_diff(known, diff)
+}
+
+/// Objects replicated by the server, see the [module documentation](self).
+#[derive(Debug)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
+pub struct ReplicationServer {
+    config: ReplicationConfig,
+    next_id: u64,
+    // Sorted so objects are spawned in the order they were created
+    objects: BTreeMap<ObjectId, Bytes>,
+    clients: HashMap<ClientId, ClientView>,
+}
+
+impl ReplicationServer {
+    pub fn new(config: ReplicationConfig) -> Self {
+        Self {
+            config,
+            next_id: 0,
+            objects: BTreeMap::new(),
+            clients: HashMap::new(),
+        }
+    }
+
+    /// Registers a new object with its serialized state, it's spawned for the clients in the next send.
+    pub fn spawn<B: Into<Bytes>>(&mut self, state: B) -> ObjectId {
+        let id = ObjectId(self.next_id);
+        self.next_id += 1;
+        self.objects.insert(id, state.into());
+        id
+    }
+
+    /// Replaces the serialized state of an object, the clients receive the changes in the next send.
+    /// Setting the same state is cheap, so it can be called every tick.
+    /// Returns false if the object doesn't exist.
+    pub fn set_state<B: Into<Bytes>>(&mut self, id: ObjectId, state: B) -> bool {
+        match self.objects.get_mut(&id) {
+            Some(current) => {
+                *current = state.into();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes an object, it's despawned for the clients in the next send.
+    pub fn despawn(&mut self, id: ObjectId) -> bool {
+        self.objects.remove(&id).is_some()
+    }
+
+    pub fn state(&self, id: ObjectId) -> Option<&Bytes> {
+        self.objects.get(&id)
+    }
+
+    pub fn objects(&self) -> impl Iterator<Item = (ObjectId, &Bytes)> + '_ {
+        self.objects.iter().map(|(id, state)| (*id, state))
+    }
+
+    /// Sends to each connected client the objects spawned, changed and despawned since the last send.
+    /// New clients receive all the objects.
+    pub fn send(&mut self, server: &mut RenetServer) {
+        self.send_prioritized(server, &|_, _| true, |_, _| 1.0);
+    }
+
+    /// Same as [`ReplicationServer::send`], but each client only knows the objects relevant to it.
+    pub fn send_filtered<F: RelevancyFilter + ?Sized>(&mut self, server: &mut RenetServer, filter: &F) {
+        self.send_prioritized(server, filter, |_, _| 1.0);
+    }
+
+    /// Same as [`ReplicationServer::send_filtered`], with the priority of each object for each client,
+    /// used when the changes don't fit in [`ReplicationConfig::max_bytes_per_send`].
+    ///
+    /// The priority of a changed object is added to its accumulated priority on every send, and the
+    /// changes with the highest accumulated priorities are sent first, resetting them. Objects that are
+    /// not sent keep accumulating, so low priority objects are sent less often instead of never.
+    /// Usually the priority is higher for objects close to the client, or that changed a lot.
+    pub fn send_prioritized<F, P>(&mut self, server: &mut RenetServer, filter: &F, priority: P)
+    where
+        F: RelevancyFilter + ?Sized,
+        P: Fn(ClientId, ObjectId) -> f32,
+    {
+        self.clients.retain(|client_id, _| server.is_connected(*client_id));
+        for client_id in server.clients_id() {
+            let view = self.clients.entry(client_id).or_default();
+            let relevant = |id: ObjectId| filter.is_relevant(client_id, id);
+            let priority = |id: ObjectId| priority(client_id, id);
+            if let Some(message) = build_message(&self.config, &self.objects, view, relevant, priority) {
+                server.send_message(client_id, self.config.channel_id, message);
+            }
+        }
+    }
+}
+
+// Objects known by a client, and the accumulated priority of the changes not sent yet
+#[derive(Debug, Default)]
+struct ClientView {
+    known: HashMap<ObjectId, Bytes>,
+    priorities: HashMap<ObjectId, f32>,
+}
+
+fn build_message(
+    config: &ReplicationConfig,
+    objects: &BTreeMap<ObjectId, Bytes>,
+    view: &mut ClientView,
+    relevant: impl Fn(ObjectId) -> bool,
+    priority: impl Fn(ObjectId) -> f32,
+) -> Option<Bytes> {
+    let mut message = BytesMut::new();
+
+    // Despawns are small and always sent, so clients don't keep objects that no longer exist
+    view.known.retain(|id, _| {
+        let keep = objects.contains_key(id) && relevant(*id);
+        if !keep {
+            message.put_u8(DESPAWN);
+            write_varint(&mut message, id.0);
+        }
+        keep
+    });
+
+    let mut changed = vec![];
+    for (id, state) in objects.iter() {
+        let unchanged = match view.known.get(id) {
+            Some(known_state) => known_state == state,
+            None => !relevant(*id),
+        };
+        if !unchanged {
+            let accumulated = view.priorities.entry(*id).or_default();
+            *accumulated += priority(*id).max(0.0);
+            changed.push((*accumulated, *id, state));
+        }
+    }
+    let changed_ids: HashSet<ObjectId> = changed.iter().map(|(_, id, _)| *id).collect();
+    view.priorities.retain(|id, _| changed_ids.contains(id));
+    if config.max_bytes_per_send.is_some() {
+        // Stable, objects with the same priority are kept in the order they were created
+        changed.sort_by(|(a, ..), (b, ..)| b.total_cmp(a));
+    }
+
+    let mut sent_changes = false;
+    for (_, id, state) in changed {
+        let (kind, payload) = match view.known.get(&id) {
+            Some(known_state) => (UPDATE, (config.diff)(known_state, state)),
+            None => (SPAWN, state.clone()),
+        };
+        let mut op = BytesMut::new();
+        op.put_u8(kind);
+        write_varint(&mut op, id.0);
+        write_varint(&mut op, payload.len() as u64);
+        op.extend_from_slice(&payload);
+
+        // At least one change is sent, even if it's above the limit
+        if sent_changes && config.max_bytes_per_send.is_some_and(|max| message.len() + op.len() > max) {
+            break;
+        }
+        message.extend_from_slice(&op);
+        sent_changes = true;
+        view.known.insert(id, state.clone());
+        view.priorities.remove(&id);
+    }
+
+    (!message.is_empty()).then(|| message.freeze())
+}
+
+/// Decides which objects are sent to each client, consulted by [`ReplicationServer::send_filtered`]
+/// and [`RenetServer::broadcast_message_relevant`].
+///
+/// Closures taking the client and the object can be used as filters.
+pub trait RelevancyFilter {
+    fn is_relevant(&self, client_id: ClientId, object_id: ObjectId) -> bool;
+}
+
+impl<F: Fn(ClientId, ObjectId) -> bool> RelevancyFilter for F {
+    fn is_relevant(&self, client_id: ClientId, object_id: ObjectId) -> bool {
+        self(client_id, object_id)
+    }
+}
+
+/// Objects are relevant to the clients within a distance of them.
+///
+/// Objects without a position are relevant to all clients, and clients without a position only see those.
+/// 2D games can leave the last coordinate at 0.
+#[derive(Debug, Clone)]
+pub struct DistanceFilter {
+    max_distance: f32,
+    objects: HashMap<ObjectId, [f32; 3]>,
+    clients: HashMap<ClientId, [f32; 3]>,
+}
+
+impl DistanceFilter {
+    pub fn new(max_distance: f32) -> Self {
+        Self {
+            max_distance,
+            objects: HashMap::new(),
+            clients: HashMap::new(),
+        }
+    }
+
+    pub fn set_max_distance(&mut self, max_distance: f32) {
+        self.max_distance = max_distance;
+    }
+
+    pub fn set_object_position(&mut self, object_id: ObjectId, position: [f32; 3]) {
+        self.objects.insert(object_id, position);
+    }
+
+    pub fn remove_object(&mut self, object_id: ObjectId) {
+        self.objects.remove(&object_id);
+    }
+
+    /// Sets the position the client sees the world from, usually its camera or player.
+    pub fn set_client_position(&mut self, client_id: ClientId, position: [f32; 3]) {
+        self.clients.insert(client_id, position);
+    }
+
+    pub fn remove_client(&mut self, client_id: ClientId) {
+        self.clients.remove(&client_id);
+    }
+}
+
+impl RelevancyFilter for DistanceFilter {
+    fn is_relevant(&self, client_id: ClientId, object_id: ObjectId) -> bool {
+        let Some(object) = self.objects.get(&object_id) else {
+            return true;
+        };
+        let Some(client) = self.clients.get(&client_id) else {
+            return false;
+        };
+        let distance_squared: f32 = object.iter().zip(client).map(|(a, b)| (a - b) * (a - b)).sum();
+        distance_squared <= self.max_distance * self.max_distance
+    }
+}
+
+/// Objects are relevant to the clients that see the zone they're in, like rooms or cells of a grid.
+///
+/// Objects without a zone are relevant to all clients, and clients without zones only see those.
+#[derive(Debug, Clone, Default)]
+pub struct ZoneFilter {
+    objects: HashMap<ObjectId, u32>,
+    clients: HashMap<ClientId, HashSet<u32>>,
+}
+
+impl ZoneFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_object_zone(&mut self, object_id: ObjectId, zone: u32) {
+        self.objects.insert(object_id, zone);
+    }
+
+    pub fn remove_object(&mut self, object_id: ObjectId) {
+        self.objects.remove(&object_id);
+    }
+
+    /// Sets the zones seen by the client, usually its zone and the neighbouring ones.
+    pub fn set_client_zones<I: IntoIterator<Item = u32>>(&mut self, client_id: ClientId, zones: I) {
+        self.clients.insert(client_id, zones.into_iter().collect());
+    }
+
+    pub fn remove_client(&mut self, client_id: ClientId) {
+        self.clients.remove(&client_id);
+    }
+}
+
+impl RelevancyFilter for ZoneFilter {
+    fn is_relevant(&self, client_id: ClientId, object_id: ObjectId) -> bool {
+        let Some(zone) = self.objects.get(&object_id) else {
+            return true;
+        };
+        self.clients.get(&client_id).is_some_and(|zones| zones.contains(zone))
+    }
+}
+
+/// Change of a replicated object, received with [`ReplicationClient::get_event`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplicationEvent {
+    Spawned {
+        id: ObjectId,
+        state: Bytes,
+    },
+    /// The object changed, with its new full state.
+    Updated {
+        id: ObjectId,
+        state: Bytes,
+    },
+    Despawned {
+        id: ObjectId,
+    },
+}
+
+/// Objects replicated from the server, see the [module documentation](self).
+#[derive(Debug)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
+pub struct ReplicationClient {
+    config: ReplicationConfig,
+    objects: HashMap<ObjectId, Bytes>,
+    events: VecDeque<ReplicationEvent>,
+}
+
+impl ReplicationClient {
+    pub fn new(config: ReplicationConfig) -> Self {
+        Self {
+            config,
+            objects: HashMap::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Applies the replication messages received by the client.
+    pub fn receive(&mut self, client: &mut RenetClient) {
+        while let Some(message) = client.receive_message(self.config.channel_id) {
+            if self.apply_message(&message).is_none() {
+                log::error!("Received invalid replication message, objects may be out of sync");
+            }
+        }
+    }
+
+    fn apply_message(&mut self, mut message: &[u8]) -> Option<()> {
+        while let Some((&kind, rest)) = message.split_first() {
+            message = rest;
+            let id = ObjectId(read_varint(&mut message)?);
+            if kind == DESPAWN {
+                self.objects.remove(&id)?;
+                self.events.push_back(ReplicationEvent::Despawned { id });
+                continue;
+            }
+
+            let len = read_varint(&mut message)? as usize;
+            let payload = message.get(..len)?;
+            message = &message[len..];
+            match kind {
+                SPAWN => {
+                    let state = Bytes::copy_from_slice(payload);
+                    self.objects.insert(id, state.clone());
+                    self.events.push_back(ReplicationEvent::Spawned { id, state });
+                }
+                UPDATE => {
+                    let known = self.objects.get_mut(&id)?;
+                    let state = (self.config.apply)(known, payload)?;
+                    *known = state.clone();
+                    self.events.push_back(ReplicationEvent::Updated { id, state });
+                }
+                _ => return None,
+            }
+        }
+        Some(())
+    }
+
+    pub fn get_event(&mut self) -> Option<ReplicationEvent> {
+        self.events.pop_front()
+    }
+
+    pub fn state(&self, id: ObjectId) -> Option<&Bytes> {
+        self.objects.get(&id)
+    }
+
+    pub fn objects(&self) -> impl Iterator<Item = (ObjectId, &Bytes)> + '_ {
+        self.objects.iter().map(|(id, state)| (*id, state))
+    }
+
+    /// Removes all the objects, without events. Should be called when reconnecting,
+    /// since the server spawns all the objects again for new connections.
+    pub fn clear(&mut self) {
+        self.objects.clear();
+        self.events.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_states() {
+        let config = ReplicationConfig::default();
+        let mut objects = BTreeMap::new();
+        let mut view = ClientView::default();
+        let mut client = ReplicationClient::new(config.clone());
+
+        objects.insert(ObjectId(0), Bytes::from(vec![1; 100]));
+        objects.insert(ObjectId(1), Bytes::from(vec![2; 100]));
+        let message = build_message(&config, &objects, &mut view, |_| true, |_| 1.0).unwrap();
+        client.apply_message(&message).unwrap();
+        assert_eq!(
+            client.get_event(),
+            Some(ReplicationEvent::Spawned {
+                id: ObjectId(0),
+                state: vec![1; 100].into()
+            })
+        );
+        assert!(matches!(
+            client.get_event(),
+            Some(ReplicationEvent::Spawned { id: ObjectId(1), .. })
+        ));
+        assert!(build_message(&config, &objects, &mut view, |_| true, |_| 1.0).is_none());
+
+        let mut state = vec![1; 100];
+        state[10] = 5;
+        objects.insert(ObjectId(0), Bytes::from(state.clone()));
+        objects.remove(&ObjectId(1));
+        let message = build_message(&config, &objects, &mut view, |_| true, |_| 1.0).unwrap();
+        assert!(message.len() < 20);
+        client.apply_message(&message).unwrap();
+        assert_eq!(client.get_event(), Some(ReplicationEvent::Despawned { id: ObjectId(1) }));
+        assert_eq!(
+            client.get_event(),
+            Some(ReplicationEvent::Updated {
+                id: ObjectId(0),
+                state: state.into()
+            })
+        );
+        assert_eq!(client.objects().count(), 1);
+
+        // Update of an unknown object
+        assert!(client.apply_message(&[UPDATE, 7, 0]).is_none());
+    }
+
+    #[test]
+    fn relevancy() {
+        let config = ReplicationConfig::default();
+        let mut objects = BTreeMap::new();
+        let mut view = ClientView::default();
+        let (near, far, global) = (ObjectId(0), ObjectId(1), ObjectId(2));
+        for id in [near, far, global] {
+            objects.insert(id, Bytes::from_static(b"state"));
+        }
+
+        let client_id = ClientId::from_raw(0);
+        let mut filter = DistanceFilter::new(10.0);
+        filter.set_object_position(near, [5.0, 0.0, 0.0]);
+        filter.set_object_position(far, [50.0, 0.0, 0.0]);
+        assert!(!filter.is_relevant(client_id, near));
+        assert!(filter.is_relevant(client_id, global));
+        filter.set_client_position(client_id, [0.0, 0.0, 0.0]);
+
+        build_message(&config, &objects, &mut view, |id| filter.is_relevant(client_id, id), |_| 1.0).unwrap();
+        assert!(view.known.contains_key(&near) && view.known.contains_key(&global) && !view.known.contains_key(&far));
+
+        // Moving away despawns the near object, and spawns the far one
+        filter.set_client_position(client_id, [45.0, 0.0, 0.0]);
+        let message = build_message(&config, &objects, &mut view, |id| filter.is_relevant(client_id, id), |_| 1.0).unwrap();
+        assert_eq!(message[0], DESPAWN);
+        assert!(view.known.contains_key(&far) && !view.known.contains_key(&near));
+
+        let mut filter = ZoneFilter::new();
+        filter.set_object_zone(near, 1);
+        filter.set_object_zone(far, 2);
+        filter.set_client_zones(client_id, [1, 3]);
+        assert!(filter.is_relevant(client_id, near));
+        assert!(!filter.is_relevant(client_id, far));
+        assert!(filter.is_relevant(client_id, global));
+        assert!(!filter.is_relevant(ClientId::from_raw(1), near));
+    }
+
+    #[test]
+    fn priorities() {
+        let config = ReplicationConfig {
+            max_bytes_per_send: Some(50),
+            ..Default::default()
+        };
+        let mut objects = BTreeMap::new();
+        let mut view = ClientView::default();
+        let (low, high) = (ObjectId(0), ObjectId(1));
+        let priority = |id| if id == high { 3.0 } else { 1.0 };
+
+        let mut sent = vec![];
+        for tick in 0..8u8 {
+            // Each change takes the whole budget
+            objects.insert(low, Bytes::from(vec![tick; 40]));
+            objects.insert(high, Bytes::from(vec![tick; 40]));
+            let message = build_message(&config, &objects, &mut view, |_| true, priority).unwrap();
+            sent.push(ObjectId(message[1] as u64));
+        }
+
+        // The high priority object is sent more often, but the low priority one is not starved
+        let low_sent = sent.iter().filter(|id| **id == low).count();
+        assert_eq!(sent[0], high);
+        assert_eq!(low_sent, 2);
+        assert!(view.priorities.contains_key(&low));
+    }
+}
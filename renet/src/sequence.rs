@@ -0,0 +1,61 @@
+//! Wrapping comparison of 16 bits sequence numbers.
+//!
+//! Renet sequences are u64 and never wrap, but custom channels or replication built on top of it
+//! usually send smaller sequences that wrap around. A sequence is considered greater than another
+//! if it's ahead by less than half of the sequence space.
+//!
+//! ```
+//! use renet::sequence::{sequence_distance, sequence_greater_than};
+//!
+//! assert!(sequence_greater_than(1, 0));
+//! assert!(sequence_greater_than(0, u16::MAX));
+//! assert_eq!(sequence_distance(2, u16::MAX), 3);
+//! ```
+
+/// Returns whether `s1` is more recent than `s2`, taking into account wrapping.
+pub fn sequence_greater_than(s1: u16, s2: u16) -> bool {
+    ((s1 > s2) && (s1 - s2 <= 32768)) || ((s1 < s2) && (s2 - s1 > 32768))
+}
+
+/// Returns whether `s1` is older than `s2`, taking into account wrapping.
+pub fn sequence_less_than(s1: u16, s2: u16) -> bool {
+    sequence_greater_than(s2, s1)
+}
+
+/// Returns how far `s1` is ahead of `s2`, taking into account wrapping.
+/// Negative if `s1` is older than `s2`, sequences half of the sequence space apart are always -32768.
+pub fn sequence_distance(s1: u16, s2: u16) -> i32 {
+    s1.wrapping_sub(s2) as i16 as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordering() {
+        assert!(sequence_greater_than(1, 0));
+        assert!(sequence_less_than(0, 1));
+        assert!(!sequence_greater_than(0, 0));
+        assert!(!sequence_less_than(0, 0));
+        assert!(sequence_greater_than(32768, 0));
+        assert!(!sequence_greater_than(32769, 0));
+        assert!(sequence_less_than(32769, 0));
+    }
+
+    #[test]
+    fn wraparound() {
+        for s in 0..=u16::MAX {
+            for offset in [1u16, 2, 100, 1000, 32767] {
+                let next = s.wrapping_add(offset);
+                assert!(sequence_greater_than(next, s), "{next} > {s}");
+                assert!(sequence_less_than(s, next), "{s} < {next}");
+                assert!(!sequence_greater_than(s, next), "!({s} > {next})");
+                assert_eq!(sequence_distance(next, s), offset as i32);
+                assert_eq!(sequence_distance(s, next), -(offset as i32));
+            }
+            assert!(!sequence_greater_than(s, s));
+            assert_eq!(sequence_distance(s, s), 0);
+        }
+    }
+}
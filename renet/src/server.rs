@@ -1,18 +1,36 @@
-use crate::error::{ClientNotFound, DisconnectReason};
+use crate::capture::PacketCapture;
+use crate::connection_stats::{ConnectionStats, NetworkHistory, RttPercentiles};
+use crate::error::{ClientNotFound, DisconnectReason, RenetError};
+use crate::features::Handshake;
+#[cfg(feature = "serde")]
+use crate::message::{MessageError, MessageRegistry, NetworkMessage};
 use crate::packet::Payload;
-use crate::remote_connection::{ConnectionConfig, NetworkInfo, RenetClient};
+use crate::pool::BufferPool;
+use crate::remote_connection::{ClientActivity, ConnectionConfig, NetworkInfo, RenetClient};
+use crate::replay::{SessionEvent, SessionRecorder};
+use crate::replication::{ObjectId, RelevancyFilter};
+use crate::voice::VoiceFrame;
 use crate::ClientId;
 use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
 use std::time::Duration;
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 
 /// Connection and disconnection events in the server.
 #[derive(Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Event))]
 pub enum ServerEvent {
-    ClientConnected { client_id: ClientId },
-    ClientDisconnected { client_id: ClientId, reason: DisconnectReason },
+    /// A new client connected, with the user data sent in the connection request if the transport supports it.
+    /// For the netcode transport, it's the user data from the connect token.
+    ClientConnected {
+        client_id: ClientId,
+        user_data: Option<Bytes>,
+    },
+    ClientDisconnected {
+        client_id: ClientId,
+        reason: DisconnectReason,
+    },
 }
 
 #[derive(Debug)]
@@ -20,7 +38,14 @@ pub enum ServerEvent {
 pub struct RenetServer {
     connections: HashMap<ClientId, RenetClient>,
     connection_config: ConnectionConfig,
+    client_connection_configs: HashMap<ClientId, ConnectionConfig>,
     events: VecDeque<ServerEvent>,
+    packet_capture: Option<Arc<dyn PacketCapture>>,
+    session_recorder: Option<Arc<SessionRecorder>>,
+    buffer_pool: BufferPool,
+    current_time: Duration,
+    #[cfg(feature = "serde")]
+    message_registry: Option<Arc<MessageRegistry>>,
 }
 
 impl RenetServer {
@@ -28,24 +53,146 @@ impl RenetServer {
         Self {
             connections: HashMap::new(),
             connection_config,
+            client_connection_configs: HashMap::new(),
             events: VecDeque::new(),
+            packet_capture: None,
+            session_recorder: None,
+            buffer_pool: BufferPool::default(),
+            current_time: Duration::ZERO,
+            #[cfg(feature = "serde")]
+            message_registry: None,
         }
     }
 
+    /// Creates the server after validating the config, instead of panicking or misbehaving with an invalid one.
+    pub fn try_new(connection_config: ConnectionConfig) -> Result<Self, RenetError> {
+        connection_config.validate()?;
+        Ok(Self::new(connection_config))
+    }
+
     /// Adds a new connection to the server. If a connection already exits it does nothing.
     /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
     /// <strong>Note:</strong> This should only be called by the transport layer.
     /// </p>
     pub fn add_connection(&mut self, client_id: ClientId) {
+        self.add_connection_internal(client_id, None);
+    }
+
+    /// Adds a new connection to the server with the user data sent in the connection request.
+    /// The user data is available in the [`ServerEvent::ClientConnected`] event.
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn add_connection_with_user_data(&mut self, client_id: ClientId, user_data: Bytes) {
+        self.add_connection_internal(client_id, Some(user_data));
+    }
+
+    /// Adds a new connection to the server after checking the [`Handshake`] sent by the client.
+    /// If the client is not compatible, the connection is not added and the reason is returned,
+    /// [`RenetServer::rejection_packets`] returns the packets that notify the client.
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn add_connection_with_handshake(
+        &mut self,
+        client_id: ClientId,
+        user_data: Bytes,
+        handshake: &Handshake,
+    ) -> Result<(), DisconnectReason> {
+        handshake.check(&self.client_connection_config(client_id))?;
+        self.add_connection_internal(client_id, Some(user_data));
+        if let Some(connection) = self.connections.get_mut(&client_id) {
+            connection.set_remote_handshake(handshake);
+        }
+        Ok(())
+    }
+
+    /// Returns the packets to send to a client whose connection was rejected, with the reason why.
+    pub fn rejection_packets(&self, client_id: ClientId, reason: DisconnectReason) -> Vec<Payload> {
+        RenetClient::new_from_server(self.client_connection_config(client_id)).reject(reason)
+    }
+
+    fn add_connection_internal(&mut self, client_id: ClientId, user_data: Option<Bytes>) {
         if self.connections.contains_key(&client_id) {
             return;
         }
+        self.record(SessionEvent::Connected {
+            client_id: Some(client_id),
+            user_data: user_data.clone(),
+        });
 
-        let mut connection = RenetClient::new_from_server(self.connection_config.clone());
+        let mut connection = RenetClient::new_from_server(self.client_connection_config(client_id));
         // Consider newly added connections as connected
         connection.set_connected();
+        connection.set_time_base(self.current_time);
+        if let Some(capture) = &self.packet_capture {
+            connection.set_packet_capture_for(capture.clone(), client_id);
+        }
+        #[cfg(feature = "serde")]
+        if let Some(registry) = &self.message_registry {
+            connection.set_message_registry(registry.clone());
+        }
         self.connections.insert(client_id, connection);
-        self.events.push_back(ServerEvent::ClientConnected { client_id })
+        self.events.push_back(ServerEvent::ClientConnected { client_id, user_data })
+    }
+
+    fn client_connection_config(&self, client_id: ClientId) -> ConnectionConfig {
+        match self.client_connection_configs.get(&client_id) {
+            Some(config) => config.clone(),
+            None => self.connection_config.clone(),
+        }
+    }
+
+    /// Creates a local client connected to the server, without any transport.
+    /// Useful for a player hosting the game in the same process (listen server), or for testing.
+    /// Use [`RenetServer::process_local_client`] to exchange the packets between both.
+    pub fn new_local_client(&mut self, client_id: ClientId) -> RenetClient {
+        let mut client = RenetClient::new(self.client_connection_config(client_id));
+        client.set_connected();
+        self.add_connection(client_id);
+        client
+    }
+
+    /// Exchanges the packets between the server and a local client, messages are delivered instantly.
+    /// Packets are passed directly without serializing them, so they are not captured or recorded,
+    /// and they don't count in the bandwidth stats.
+    /// Should be called every tick, after updating both.
+    pub fn process_local_client(&mut self, client_id: ClientId, client: &mut RenetClient) -> Result<(), ClientNotFound> {
+        let connection = self.connections.get_mut(&client_id).ok_or(ClientNotFound)?;
+        for packet in connection.get_local_packets() {
+            client.process_local_packet(packet);
+        }
+        for packet in client.get_local_packets() {
+            connection.process_local_packet(packet);
+        }
+
+        Ok(())
+    }
+
+    /// Disconnects a local client and removes its connection, emits a disconnect server event.
+    pub fn disconnect_local_client(&mut self, client_id: ClientId, client: &mut RenetClient) {
+        if client.is_disconnected() {
+            return;
+        }
+        client.disconnect();
+
+        if let Some(connection) = self.connections.get_mut(&client_id) {
+            connection.disconnect_with_reason(DisconnectReason::DisconnectedByClient);
+        }
+        self.remove_connection(client_id);
+    }
+
+    /// Sets the connection config used when the client connects, instead of the server config.
+    /// Useful to have different channels for some clients, like smaller budgets for spectators.
+    /// The client must connect using the same channels configuration.
+    /// Only applies to connections added after the call.
+    pub fn set_client_connection_config(&mut self, client_id: ClientId, connection_config: ConnectionConfig) {
+        self.client_connection_configs.insert(client_id, connection_config);
+    }
+
+    /// Removes the connection config set for the client, new connections from it use the server config.
+    pub fn remove_client_connection_config(&mut self, client_id: ClientId) {
+        self.client_connection_configs.remove(&client_id);
     }
 
     /// Returns a server event if available
@@ -56,7 +203,7 @@ impl RenetServer {
     /// # let mut server = RenetServer::new(ConnectionConfig::default());
     /// while let Some(event) = server.get_event() {
     ///     match event {
-    ///         ServerEvent::ClientConnected { client_id } => {
+    ///         ServerEvent::ClientConnected { client_id, .. } => {
     ///             println!("Client {client_id} connected.")
     ///         }
     ///         ServerEvent::ClientDisconnected { client_id, reason } => {
@@ -91,6 +238,14 @@ impl RenetServer {
         }
     }
 
+    /// Returns the percentiles and jitter of the round-trip time for the client, all 0 if the client is not found
+    pub fn rtt_percentiles(&self, client_id: ClientId) -> RttPercentiles {
+        match self.connections.get(&client_id) {
+            Some(connection) => connection.rtt_percentiles(),
+            None => RttPercentiles::default(),
+        }
+    }
+
     /// Returns the packet loss for the client or 0.0 if the client is not found
     pub fn packet_loss(&self, client_id: ClientId) -> f64 {
         match self.connections.get(&client_id) {
@@ -115,6 +270,27 @@ impl RenetServer {
         }
     }
 
+    /// Returns the activity of the client or `None` if the client is not found
+    pub fn client_activity(&self, client_id: ClientId) -> Option<ClientActivity> {
+        self.connections.get(&client_id).map(|connection| connection.remote_activity())
+    }
+
+    /// Returns the kilobits sent per second for the client or 0.0 if the client is not found
+    pub fn sent_kbps(&self, client_id: ClientId) -> f64 {
+        match self.connections.get(&client_id) {
+            Some(connection) => connection.sent_kbps(),
+            None => 0.0,
+        }
+    }
+
+    /// Returns the kilobits received per second for the client or 0.0 if the client is not found
+    pub fn received_kbps(&self, client_id: ClientId) -> f64 {
+        match self.connections.get(&client_id) {
+            Some(connection) => connection.received_kbps(),
+            None => 0.0,
+        }
+    }
+
     /// Returns all network informations for the client
     pub fn network_info(&self, client_id: ClientId) -> Result<NetworkInfo, ClientNotFound> {
         match self.connections.get(&client_id) {
@@ -123,6 +299,22 @@ impl RenetServer {
         }
     }
 
+    /// Returns the totals of packets, bytes and messages of the client since it connected.
+    pub fn connection_stats(&self, client_id: ClientId) -> Result<&ConnectionStats, ClientNotFound> {
+        match self.connections.get(&client_id) {
+            Some(connection) => Ok(connection.connection_stats()),
+            None => Err(ClientNotFound),
+        }
+    }
+
+    /// Returns the samples of the network conditions of the client in the last minute, one per second.
+    pub fn network_history(&self, client_id: ClientId) -> Result<&NetworkHistory, ClientNotFound> {
+        match self.connections.get(&client_id) {
+            Some(connection) => Ok(connection.network_history()),
+            None => Err(ClientNotFound),
+        }
+    }
+
     /// Removes a connection from the server, emits an disconnect server event.
     /// It does nothing if the client does not exits.
     /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
@@ -130,6 +322,9 @@ impl RenetServer {
     /// </p>
     pub fn remove_connection(&mut self, client_id: ClientId) {
         if let Some(connection) = self.connections.remove(&client_id) {
+            self.record(SessionEvent::Disconnected {
+                client_id: Some(client_id),
+            });
             let reason = connection.disconnect_reason().unwrap_or(DisconnectReason::Transport);
             self.events.push_back(ServerEvent::ClientDisconnected { client_id, reason });
         }
@@ -142,6 +337,21 @@ impl RenetServer {
         }
     }
 
+    /// Disconnects a client with an application specific code, it's sent to the client.
+    /// It does nothing if the client does not exist.
+    pub fn disconnect_with_code(&mut self, client_id: ClientId, code: u8) {
+        if let Some(connection) = self.connections.get_mut(&client_id) {
+            connection.disconnect_with_code(code);
+        }
+    }
+
+    /// Disconnects all clients, notifying them that the server is shutting down.
+    pub fn shutdown(&mut self) {
+        for connection in self.connections.values_mut() {
+            connection.disconnect_with_reason(DisconnectReason::ServerShutdown)
+        }
+    }
+
     /// Disconnects all client.
     pub fn disconnect_all(&mut self) {
         for connection in self.connections.values_mut() {
@@ -158,6 +368,73 @@ impl RenetServer {
         }
     }
 
+    /// Send a snapshot to all clients over a channel.
+    /// Each client only receives it if it's due a new snapshot, based on its connection quality.
+    /// See [`RenetClient::send_snapshot`].
+    pub fn broadcast_snapshot<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) {
+        let channel_id = channel_id.into();
+        let message = message.into();
+        for connection in self.connections.values_mut() {
+            connection.send_snapshot(channel_id, message.clone());
+        }
+    }
+
+    /// Send a snapshot to a client over a channel, if the client is due a new snapshot.
+    /// Returns whether the snapshot was sent. See [`RenetClient::send_snapshot`].
+    pub fn send_snapshot<I: Into<u8>, B: Into<Bytes>>(&mut self, client_id: ClientId, channel_id: I, message: B) -> bool {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => connection.send_snapshot(channel_id, message),
+            None => {
+                log::error!("Tried to send a snapshot to invalid client {:?}", client_id);
+                false
+            }
+        }
+    }
+
+    /// Send the state of a tick to all clients, each one as a diff against the last state it acknowledged.
+    /// See [`RenetClient::send_state`].
+    pub fn broadcast_state<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, tick: u64, state: B) {
+        let channel_id = channel_id.into();
+        let state = state.into();
+        for connection in self.connections.values_mut() {
+            connection.send_state(channel_id, tick, state.clone());
+        }
+    }
+
+    /// Send the state of a tick to a client, see [`RenetClient::send_state`].
+    pub fn send_state<I: Into<u8>, B: Into<Bytes>>(&mut self, client_id: ClientId, channel_id: I, tick: u64, state: B) {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => connection.send_state(channel_id, tick, state),
+            None => log::error!("Tried to send a state to invalid client {:?}", client_id),
+        }
+    }
+
+    /// Receive the most recent state sent by a client over a channel, see [`RenetClient::receive_state`].
+    pub fn receive_state<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I) -> Option<(u64, Bytes)> {
+        self.connections.get_mut(&client_id)?.receive_state(channel_id)
+    }
+
+    /// Returns the most recent state tick acknowledged by a client, see [`RenetClient::acked_state_tick`].
+    pub fn acked_state_tick<I: Into<u8>>(&self, client_id: ClientId, channel_id: I) -> Option<u64> {
+        self.connections.get(&client_id)?.acked_state_tick(channel_id)
+    }
+
+    /// Receive the inputs sent by a client over a channel, see [`RenetClient::receive_inputs`].
+    pub fn receive_inputs<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I) -> Vec<(u64, Bytes)> {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => connection.receive_inputs(channel_id),
+            None => vec![],
+        }
+    }
+
+    /// Send an input to a client, see [`RenetClient::send_input`].
+    pub fn send_input<I: Into<u8>, B: Into<Bytes>>(&mut self, client_id: ClientId, channel_id: I, input: B) {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => connection.send_input(channel_id, input),
+            None => log::error!("Tried to send an input to invalid client {:?}", client_id),
+        }
+    }
+
     /// Send a message to all clients, except the specified one, over a channel.
     pub fn broadcast_message_except<I: Into<u8>, B: Into<Bytes>>(&mut self, except_id: ClientId, channel_id: I, message: B) {
         let channel_id = channel_id.into();
@@ -171,6 +448,47 @@ impl RenetServer {
         }
     }
 
+    /// Receive the audio frames sent by a client, with the client as their talker.
+    /// See [`RenetClient::send_voice_frame`].
+    pub fn receive_voice_frames<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I) -> Vec<VoiceFrame> {
+        let Some(connection) = self.connections.get_mut(&client_id) else {
+            return vec![];
+        };
+        let mut frames = connection.receive_voice_frames(channel_id);
+        for frame in frames.iter_mut() {
+            frame.talker = client_id;
+        }
+        frames
+    }
+
+    /// Forwards the audio frames received from each client to all the other clients, over the same channel.
+    pub fn relay_voice_frames<I: Into<u8>>(&mut self, channel_id: I) {
+        let channel_id = channel_id.into();
+        for client_id in self.clients_id() {
+            for frame in self.receive_voice_frames(client_id, channel_id) {
+                self.broadcast_message_except(client_id, channel_id, frame.to_bytes());
+            }
+        }
+    }
+
+    /// Send a message about an object to the clients it's relevant for, over a channel.
+    /// Useful for events of replicated objects, like sounds or effects, see [`RelevancyFilter`].
+    pub fn broadcast_message_relevant<F: RelevancyFilter + ?Sized, I: Into<u8>, B: Into<Bytes>>(
+        &mut self,
+        filter: &F,
+        object_id: ObjectId,
+        channel_id: I,
+        message: B,
+    ) {
+        let channel_id = channel_id.into();
+        let message = message.into();
+        for (connection_id, connection) in self.connections.iter_mut() {
+            if filter.is_relevant(*connection_id, object_id) {
+                connection.send_message(channel_id, message.clone());
+            }
+        }
+    }
+
     /// Returns the available memory in bytes of a channel for the given client.
     /// Returns 0 if the client is not found.
     pub fn channel_available_memory<I: Into<u8>>(&self, client_id: ClientId, channel_id: I) -> usize {
@@ -205,6 +523,129 @@ impl RenetServer {
         None
     }
 
+    /// Send a message to a client over a channel, see [`RenetClient::try_send_message`].
+    pub fn try_send_message<I: Into<u8>, B: Into<Bytes>>(
+        &mut self,
+        client_id: ClientId,
+        channel_id: I,
+        message: B,
+    ) -> Result<(), RenetError> {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => connection.try_send_message(channel_id, message),
+            None => Err(RenetError::ClientNotFound),
+        }
+    }
+
+    /// Receive a message from a client over a channel, see [`RenetClient::try_receive_message`].
+    pub fn try_receive_message<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I) -> Result<Option<Bytes>, RenetError> {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => connection.try_receive_message(channel_id),
+            None => Err(RenetError::ClientNotFound),
+        }
+    }
+
+    /// Returns an empty buffer to write a message into, see [`RenetClient::acquire_buffer`].
+    pub fn acquire_buffer(&mut self) -> BytesMut {
+        self.buffer_pool.acquire()
+    }
+
+    /// Returns payloads from [`RenetServer::get_packets_to_send`] after they were sent,
+    /// so they are reused for the next packets of the client.
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn recycle_payloads(&mut self, client_id: ClientId, payloads: impl IntoIterator<Item = Payload>) {
+        if let Some(connection) = self.connections.get_mut(&client_id) {
+            connection.recycle_payloads(payloads);
+        }
+    }
+
+    /// Sets the hook called with every packet sent and received, for current and future clients.
+    /// See [`PcapWriter`](crate::PcapWriter).
+    pub fn set_packet_capture(&mut self, capture: Arc<dyn PacketCapture>) {
+        for (&client_id, connection) in self.connections.iter_mut() {
+            connection.set_packet_capture_for(capture.clone(), client_id);
+        }
+        self.packet_capture = Some(capture);
+    }
+
+    /// Records the updates, connections and received packets of the server, see [`SessionRecorder`].
+    pub fn set_session_recorder(&mut self, recorder: Arc<SessionRecorder>) {
+        self.session_recorder = Some(recorder);
+    }
+
+    fn record(&self, event: SessionEvent) {
+        if let Some(recorder) = &self.session_recorder {
+            recorder.record(event);
+        }
+    }
+
+    /// Sets the registry used to send and receive typed messages, for current and future clients.
+    /// See [`MessageRegistry`].
+    #[cfg(feature = "serde")]
+    pub fn set_message_registry(&mut self, registry: Arc<MessageRegistry>) {
+        for connection in self.connections.values_mut() {
+            connection.set_message_registry(registry.clone());
+        }
+        self.message_registry = Some(registry);
+    }
+
+    /// Serializes and sends a typed message to a client over the channel registered for its type.
+    /// See [`RenetClient::send`].
+    #[cfg(feature = "serde")]
+    pub fn send<T: NetworkMessage>(&mut self, client_id: ClientId, message: T) {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => connection.send(message),
+            None => log::error!("Tried to send a message to invalid client {:?}", client_id),
+        }
+    }
+
+    /// Serializes a typed message once and sends it to all clients.
+    /// Panics if no registry is set or the type is not registered.
+    #[cfg(feature = "serde")]
+    pub fn broadcast<T: NetworkMessage>(&mut self, message: T) {
+        let registry = self
+            .message_registry
+            .as_ref()
+            .expect("Called 'broadcast' without a message registry");
+        match registry.serialize(&message) {
+            Ok((channel_id, message)) => self.broadcast_message(channel_id, message),
+            Err(e) => log::error!("{}", e),
+        }
+    }
+
+    /// Receive a typed message from a client. See [`RenetClient::receive`].
+    #[cfg(feature = "serde")]
+    pub fn receive<T: NetworkMessage>(&mut self, client_id: ClientId) -> Option<T> {
+        self.connections.get_mut(&client_id)?.receive()
+    }
+
+    /// Returns an error that occurred while sending or receiving typed messages with a client.
+    #[cfg(feature = "serde")]
+    pub fn get_message_error(&mut self) -> Option<(ClientId, MessageError)> {
+        self.connections
+            .iter_mut()
+            .find_map(|(client_id, connection)| connection.get_message_error().map(|e| (*client_id, e)))
+    }
+
+    /// Writes bytes to a reliable ordered channel of a client as a continuous stream.
+    /// See [`RenetClient::write_stream`].
+    pub fn write_stream<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I, data: &[u8]) -> Result<(), RenetError> {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => connection.write_stream(channel_id, data),
+            None => Err(RenetError::ClientNotFound),
+        }
+    }
+
+    /// Reads bytes from a stream written by a client over a reliable ordered channel.
+    /// See [`RenetClient::read_stream`].
+    pub fn read_stream<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I, buf: &mut [u8]) -> Result<usize, RenetError> {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => connection.read_stream(channel_id, buf),
+            None => Err(RenetError::ClientNotFound),
+        }
+    }
+
     /// Return ids for all connected clients (iterator)
     pub fn clients_id_iter(&self) -> impl Iterator<Item = ClientId> + '_ {
         self.connections.iter().filter(|(_, c)| c.is_connected()).map(|(id, _)| *id)
@@ -230,6 +671,7 @@ impl RenetServer {
         self.connections.iter().filter(|(_, c)| c.is_connected()).count()
     }
 
+    /// Returns whether the client is connected.
     pub fn is_connected(&self, client_id: ClientId) -> bool {
         if let Some(connection) = self.connections.get(&client_id) {
             return connection.is_connected();
@@ -238,11 +680,31 @@ impl RenetServer {
         false
     }
 
+    /// Returns the sum of the durations the server was updated with,
+    /// the time estimated by clients with [`RenetClient::server_time_estimate`].
+    pub fn time(&self) -> Duration {
+        self.current_time
+    }
+
     /// Advances the server by the duration.
     /// Should be called every tick
     pub fn update(&mut self, duration: Duration) {
-        for connection in self.connections.values_mut() {
+        self.record(SessionEvent::Update(duration));
+        self.current_time += duration;
+        for (_client_id, connection) in self.connections.iter_mut() {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::trace_span!("renet_client", client_id = %_client_id).entered();
             connection.update(duration);
+            // Buffers reclaimed by the connections are acquired from the server
+            self.buffer_pool.take_buffers(connection.buffer_pool());
+        }
+
+        #[cfg(feature = "metrics")]
+        {
+            for (&client_id, connection) in self.connections.iter() {
+                crate::telemetry::record_connection(connection, Some(client_id));
+            }
+            crate::telemetry::record_server(self.connected_clients());
         }
     }
 
@@ -250,6 +712,7 @@ impl RenetServer {
     /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
     /// <strong>Note:</strong> This should only be called by the transport layer.
     /// </p>
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "renet_client", level = "trace", skip_all, fields(%client_id)))]
     pub fn get_packets_to_send(&mut self, client_id: ClientId) -> Result<Vec<Payload>, ClientNotFound> {
         match self.connections.get_mut(&client_id) {
             Some(connection) => Ok(connection.get_packets_to_send()),
@@ -261,7 +724,12 @@ impl RenetServer {
     /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
     /// <strong>Note:</strong> This should only be called by the transport layer.
     /// </p>
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "renet_client", level = "trace", skip_all, fields(%client_id)))]
     pub fn process_packet_from(&mut self, payload: &[u8], client_id: ClientId) -> Result<(), ClientNotFound> {
+        self.record(SessionEvent::Packet {
+            client_id: Some(client_id),
+            payload: payload.to_vec(),
+        });
         match self.connections.get_mut(&client_id) {
             Some(connection) => {
                 connection.process_packet(payload);
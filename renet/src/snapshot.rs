@@ -0,0 +1,245 @@
+//! Buffer of received state snapshots, rendered with a delay so there are always two snapshots to interpolate between.
+//!
+//! Snapshots are usually sent over an unreliable channel with [`RenetClient::send_snapshot`](crate::RenetClient::send_snapshot),
+//! and can arrive late, duplicated or out of order. The buffer keeps them sorted by the time they were generated,
+//! in the sender's timeline, and is sampled at a render time in the same timeline.
+//!
+//! ```
+//! use std::time::Duration;
+//! use renet::snapshot::SnapshotBuffer;
+//!
+//! let mut buffer = SnapshotBuffer::new(Duration::from_millis(100));
+//! buffer.push(Duration::from_millis(0), 0.0f32);
+//! buffer.push(Duration::from_millis(50), 10.0f32);
+//!
+//! // Rendering at 125ms shows the state 100ms in the past
+//! let position = buffer.interpolate(Duration::from_millis(125)).unwrap();
+//! assert_eq!(position, 5.0);
+//! ```
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Values that can be blended between two snapshots.
+pub trait Interpolate {
+    /// Returns the value between `self` and `other`, with `t` going from 0 (`self`) to 1 (`other`).
+    fn interpolate(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for f64 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t as f64
+    }
+}
+
+impl<T: Interpolate, const N: usize> Interpolate for [T; N] {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        std::array::from_fn(|i| self[i].interpolate(&other[i], t))
+    }
+}
+
+/// Snapshots around a render time, returned by [`SnapshotBuffer::sample`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnapshotSample<'a, T> {
+    /// Most recent snapshot at or before the render time, the oldest one if the render time is before all of them.
+    pub from: &'a T,
+    /// Oldest snapshot after the render time, the same as `from` if there's none.
+    pub to: &'a T,
+    /// Position of the render time between the two snapshots, from 0 to 1.
+    pub t: f32,
+}
+
+/// Counters of the snapshots that were not stored in a [`SnapshotBuffer`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotBufferStats {
+    /// Snapshots with the time of a snapshot already received.
+    pub duplicated: u64,
+    /// Snapshots older than the render time, or than all the snapshots of a full buffer.
+    pub late: u64,
+}
+
+/// Time indexed buffer of snapshots, see the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct SnapshotBuffer<T> {
+    snapshots: VecDeque<(Duration, T)>,
+    interpolation_delay: Duration,
+    capacity: usize,
+    // Snapshots at or before this time are no longer needed to render
+    discarded_until: Option<Duration>,
+    stats: SnapshotBufferStats,
+}
+
+impl<T> SnapshotBuffer<T> {
+    /// Creates a buffer holding up to 32 snapshots.
+    /// The interpolation delay should cover the interval between snapshots plus some jitter,
+    /// usually two or three snapshot intervals.
+    pub fn new(interpolation_delay: Duration) -> Self {
+        Self::with_capacity(interpolation_delay, 32)
+    }
+
+    /// Panics if the capacity is less than 2.
+    pub fn with_capacity(interpolation_delay: Duration, capacity: usize) -> Self {
+        assert!(capacity >= 2, "snapshot buffers need at least 2 snapshots to interpolate");
+        Self {
+            snapshots: VecDeque::with_capacity(capacity),
+            interpolation_delay,
+            capacity,
+            discarded_until: None,
+            stats: SnapshotBufferStats::default(),
+        }
+    }
+
+    pub fn interpolation_delay(&self) -> Duration {
+        self.interpolation_delay
+    }
+
+    pub fn set_interpolation_delay(&mut self, interpolation_delay: Duration) {
+        self.interpolation_delay = interpolation_delay;
+    }
+
+    /// Stores a snapshot generated at the given time.
+    /// Returns false if it was discarded because it's a duplicate or too old to be rendered.
+    pub fn push(&mut self, time: Duration, snapshot: T) -> bool {
+        if self.discarded_until.is_some_and(|discarded| time <= discarded) {
+            self.stats.late += 1;
+            return false;
+        }
+
+        let index = self.snapshots.partition_point(|(t, _)| *t < time);
+        if self.snapshots.get(index).is_some_and(|(t, _)| *t == time) {
+            self.stats.duplicated += 1;
+            return false;
+        }
+        if self.snapshots.len() == self.capacity {
+            if index == 0 {
+                self.stats.late += 1;
+                return false;
+            }
+            self.discard_front();
+            self.snapshots.insert(index - 1, (time, snapshot));
+        } else {
+            self.snapshots.insert(index, (time, snapshot));
+        }
+
+        true
+    }
+
+    /// Returns the snapshots around the render time minus the interpolation delay,
+    /// and discards the snapshots that are too old to be rendered again.
+    /// Render times after the latest snapshot hold it, instead of extrapolating.
+    pub fn sample(&mut self, render_time: Duration) -> Option<SnapshotSample<'_, T>> {
+        if self.snapshots.is_empty() {
+            return None;
+        }
+
+        let time = render_time.saturating_sub(self.interpolation_delay);
+        self.discarded_until = self.discarded_until.max(Some(time));
+        // Keep the last snapshot at or before the time, it's the start of the interpolation
+        while self.snapshots.len() > 1 && self.snapshots[1].0 <= time {
+            self.discard_front();
+        }
+
+        let (from_time, from) = self.snapshots.front()?;
+        let Some((to_time, to)) = self.snapshots.get(1).filter(|_| *from_time <= time) else {
+            return Some(SnapshotSample { from, to: from, t: 0.0 });
+        };
+
+        let t = (time - *from_time).as_secs_f32() / (*to_time - *from_time).as_secs_f32();
+        Some(SnapshotSample { from, to, t })
+    }
+
+    /// Returns the interpolated state at the render time, see [`SnapshotBuffer::sample`].
+    pub fn interpolate(&mut self, render_time: Duration) -> Option<T>
+    where
+        T: Interpolate,
+    {
+        let sample = self.sample(render_time)?;
+        Some(sample.from.interpolate(sample.to, sample.t))
+    }
+
+    /// Returns the time of the most recent snapshot.
+    pub fn latest_time(&self) -> Option<Duration> {
+        self.snapshots.back().map(|(time, _)| *time)
+    }
+
+    pub fn latest(&self) -> Option<&T> {
+        self.snapshots.back().map(|(_, snapshot)| snapshot)
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    pub fn stats(&self) -> SnapshotBufferStats {
+        self.stats
+    }
+
+    /// Removes all the snapshots, for example when the sender's timeline restarted.
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.discarded_until = None;
+    }
+
+    fn discard_front(&mut self) {
+        if let Some((time, _)) = self.snapshots.pop_front() {
+            self.discarded_until = self.discarded_until.max(Some(time));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn interpolation() {
+        let mut buffer = SnapshotBuffer::new(ms(100));
+        assert_eq!(buffer.interpolate(ms(0)), None);
+
+        // Out of order
+        buffer.push(ms(100), [10.0, 100.0]);
+        buffer.push(ms(0), [0.0, 0.0]);
+        buffer.push(ms(200), [20.0, 100.0]);
+
+        assert_eq!(buffer.interpolate(ms(50)), Some([0.0, 0.0]));
+        assert_eq!(buffer.interpolate(ms(150)), Some([5.0, 50.0]));
+        assert_eq!(buffer.interpolate(ms(275)), Some([17.5, 100.0]));
+        // Holds the latest snapshot
+        assert_eq!(buffer.interpolate(ms(500)), Some([20.0, 100.0]));
+        assert_eq!(buffer.len(), 1);
+    }
+
+    #[test]
+    fn duplicated_and_late() {
+        let mut buffer = SnapshotBuffer::with_capacity(ms(0), 3);
+        assert!(buffer.push(ms(10), 1.0f32));
+        assert!(!buffer.push(ms(10), 2.0));
+        assert!(buffer.push(ms(20), 2.0));
+        assert!(buffer.push(ms(30), 3.0));
+
+        // Full, the oldest snapshot is replaced
+        assert!(buffer.push(ms(25), 2.5));
+        assert!(!buffer.push(ms(5), 0.0));
+        assert_eq!(buffer.len(), 3);
+
+        // Already rendered past it
+        assert_eq!(buffer.interpolate(ms(30)), Some(3.0));
+        assert!(!buffer.push(ms(28), 2.8));
+        assert_eq!(buffer.stats(), SnapshotBufferStats { duplicated: 1, late: 2 });
+        assert_eq!(buffer.latest_time(), Some(ms(30)));
+    }
+}
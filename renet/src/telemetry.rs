@@ -0,0 +1,75 @@
+//! Metrics emitted through the `metrics` facade, enabled with the `metrics` feature.
+//!
+//! Connections of the [`RenetServer`](crate::RenetServer) are labeled with their `client_id`.
+
+use metrics::{counter, gauge, histogram, Label};
+
+use crate::{ClientId, RenetClient};
+
+// Records the metrics of a connection, called after it's updated.
+pub(crate) fn record_connection(connection: &RenetClient, client_id: Option<ClientId>) {
+    let labels: Vec<Label> = client_id.map(|id| Label::new("client_id", id.to_string())).into_iter().collect();
+
+    let stats = connection.connection_stats();
+    counter!("renet_packets_sent", labels.clone()).absolute(stats.packets_sent);
+    counter!("renet_packets_received", labels.clone()).absolute(stats.packets_received);
+    counter!("renet_bytes_sent", labels.clone()).absolute(stats.bytes_sent);
+    counter!("renet_bytes_received", labels.clone()).absolute(stats.bytes_received);
+    counter!("renet_packets_lost", labels.clone()).absolute(stats.packets_lost);
+    let resent = stats.channels.values().map(|channel| channel.messages_resent).sum();
+    counter!("renet_resend_count", labels.clone()).absolute(resent);
+    let dropped = stats.channels.values().map(|channel| channel.messages_dropped).sum();
+    counter!("renet_messages_dropped", labels.clone()).absolute(dropped);
+
+    histogram!("renet_rtt_ms", labels.clone()).record(connection.rtt() * 1000.0);
+    gauge!("renet_packet_loss", labels.clone()).set(connection.packet_loss());
+
+    for channel in connection.channel_queues() {
+        let mut labels = labels.clone();
+        labels.push(Label::new("channel_id", channel.channel_id.to_string()));
+        gauge!("renet_send_queue_depth", labels.clone()).set(channel.send_queue as f64);
+        gauge!("renet_receive_queue_depth", labels).set(channel.receive_queue as f64);
+    }
+}
+
+pub(crate) fn record_server(connected_clients: usize) {
+    gauge!("renet_connected_clients").set(connected_clients as f64);
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    use crate::{ConnectionConfig, DefaultChannel, RenetServer};
+
+    use super::*;
+
+    #[test]
+    fn server_metrics() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        metrics::with_local_recorder(&recorder, || {
+            let mut server = RenetServer::new(ConnectionConfig::default());
+            server.add_connection(ClientId::from_raw(7));
+            server.send_message(ClientId::from_raw(7), DefaultChannel::ReliableOrdered, "hello");
+            server.get_packets_to_send(ClientId::from_raw(7)).unwrap();
+            server.update(std::time::Duration::from_millis(16));
+        });
+
+        let metrics = snapshotter.snapshot().into_vec();
+        let value = |name: &str| {
+            metrics
+                .iter()
+                .find(|(key, ..)| key.key().name() == name)
+                .map(|(key, _, _, value)| (key.key().labels().cloned().collect::<Vec<_>>(), value))
+        };
+
+        let (labels, packets_sent) = value("renet_packets_sent").unwrap();
+        assert_eq!(labels, vec![Label::new("client_id", "7")]);
+        assert!(matches!(packets_sent, DebugValue::Counter(sent) if *sent > 0));
+        assert!(matches!(value("renet_connected_clients"), Some((_, DebugValue::Gauge(clients))) if clients.0 == 1.0));
+        assert!(value("renet_rtt_ms").is_some());
+        assert!(value("renet_send_queue_depth").is_some());
+    }
+}
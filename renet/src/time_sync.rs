@@ -0,0 +1,165 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// Clocks of different machines drift by a few ppm, anything above is noise from the samples
+const MAX_DRIFT: f64 = 0.001;
+
+/// Configuration of the clock synchronization with the server, see [`RenetClient::server_time_estimate`](crate::RenetClient::server_time_estimate).
+///
+/// The client periodically sends its time and the server answers with its own, each exchange is a sample
+/// of the offset between the clocks. Samples with a higher rtt than the median are discarded, since the
+/// time the request and the response took is more likely to be asymmetric.
+#[derive(Debug, Clone)]
+pub struct TimeSync {
+    /// Interval between time requests.
+    /// Default: 1s
+    pub interval: Duration,
+    /// Number of recent samples used to estimate the offset and the drift of the server clock.
+    /// Default: 16
+    pub samples: usize,
+}
+
+impl Default for TimeSync {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            samples: 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    // Local time in the middle of the exchange
+    time: f64,
+    offset: f64,
+    rtt: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Estimate {
+    time: f64,
+    offset: f64,
+    drift: f64,
+}
+
+#[derive(Debug)]
+pub(crate) struct TimeSyncEstimator {
+    config: TimeSync,
+    last_request_at: Option<Duration>,
+    samples: VecDeque<Sample>,
+    estimate: Option<Estimate>,
+}
+
+impl TimeSyncEstimator {
+    pub fn new(config: TimeSync) -> Self {
+        Self {
+            config,
+            last_request_at: None,
+            samples: VecDeque::new(),
+            estimate: None,
+        }
+    }
+
+    /// Returns whether a time request should be sent now.
+    pub fn request_due(&mut self, now: Duration) -> bool {
+        if self.last_request_at.is_some_and(|last| now - last < self.config.interval) {
+            return false;
+        }
+        self.last_request_at = Some(now);
+        true
+    }
+
+    /// Adds the sample of a time response, with the local times the request was sent and the response received,
+    /// and the server times it received the request and sent the response.
+    pub fn add_sample(&mut self, request_time: Duration, receive_time: Duration, send_time: Duration, now: Duration) {
+        if request_time > now {
+            return;
+        }
+        let (t0, t1, t2, t3) = (
+            request_time.as_secs_f64(),
+            receive_time.as_secs_f64(),
+            send_time.as_secs_f64(),
+            now.as_secs_f64(),
+        );
+        let sample = Sample {
+            time: (t0 + t3) / 2.0,
+            offset: ((t1 - t0) + (t2 - t3)) / 2.0,
+            rtt: ((t3 - t0) - (t2 - t1)).max(0.0),
+        };
+
+        if self.samples.len() >= self.config.samples.max(1) {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+        self.estimate = estimate(&self.samples);
+    }
+
+    /// Returns the server time at the local time.
+    pub fn server_time(&self, now: Duration) -> Option<Duration> {
+        let estimate = self.estimate?;
+        let now = now.as_secs_f64();
+        let server_time = now + estimate.offset + estimate.drift * (now - estimate.time);
+        Some(Duration::from_secs_f64(server_time.max(0.0)))
+    }
+}
+
+fn estimate(samples: &VecDeque<Sample>) -> Option<Estimate> {
+    let mut rtts: Vec<f64> = samples.iter().map(|sample| sample.rtt).collect();
+    rtts.sort_by(f64::total_cmp);
+    let median_rtt = *rtts.get(rtts.len() / 2)?;
+    let accepted: Vec<&Sample> = samples.iter().filter(|sample| sample.rtt <= median_rtt).collect();
+
+    // Linear fit of the offsets over time, the drift is the slope
+    let count = accepted.len() as f64;
+    let time = accepted.iter().map(|sample| sample.time).sum::<f64>() / count;
+    let offset = accepted.iter().map(|sample| sample.offset).sum::<f64>() / count;
+    let variance: f64 = accepted.iter().map(|sample| (sample.time - time).powi(2)).sum();
+    let drift = if variance > f64::EPSILON {
+        let covariance: f64 = accepted.iter().map(|sample| (sample.time - time) * (sample.offset - offset)).sum();
+        (covariance / variance).clamp(-MAX_DRIFT, MAX_DRIFT)
+    } else {
+        0.0
+    };
+
+    Some(Estimate { time, offset, drift })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    #[test]
+    fn offset_and_drift() {
+        let mut estimator = TimeSyncEstimator::new(TimeSync::default());
+        assert!(estimator.server_time(secs(1.0)).is_none());
+        assert!(estimator.request_due(secs(0.0)));
+        assert!(!estimator.request_due(secs(0.5)));
+        assert!(estimator.request_due(secs(1.0)));
+
+        // The server clock is 10s ahead, and runs 100ppm faster
+        let server = |local: f64| local + 10.0 + (local - 5.0) * 0.0001;
+        for i in 0..16 {
+            let sent = i as f64;
+            // Every 4th sample is delayed on the way there, making its offset wrong
+            let delay = if i % 4 == 0 { 0.2 } else { 0.02 };
+            let received = server(sent + delay);
+            estimator.add_sample(secs(sent), secs(received), secs(received + 0.001), secs(sent + delay + 0.021));
+        }
+
+        let estimate = estimator.server_time(secs(20.0)).unwrap().as_secs_f64();
+        assert!((estimate - server(20.0)).abs() < 0.001, "{estimate}");
+    }
+
+    #[test]
+    fn max_drift() {
+        let mut estimator = TimeSyncEstimator::new(TimeSync::default());
+        estimator.add_sample(secs(0.0), secs(0.0), secs(0.0), secs(0.0));
+        estimator.add_sample(secs(1.0), secs(2.0), secs(2.0), secs(1.0));
+        assert_eq!(estimator.estimate.unwrap().drift, MAX_DRIFT);
+    }
+}
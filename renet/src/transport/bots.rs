@@ -0,0 +1,236 @@
+use std::{net::UdpSocket, time::Duration};
+
+use renetcode::ClientAuthentication;
+
+use crate::{remote_connection::RenetClient, ConnectionConfig};
+
+use super::{NetcodeClientTransport, NetcodeTransportError};
+
+/// A headless client managed by a [`BotRunner`].
+#[derive(Debug)]
+pub struct Bot {
+    id: usize,
+    client: RenetClient,
+    transport: NetcodeClientTransport,
+}
+
+impl Bot {
+    /// Returns the identifier of the bot inside its runner.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Returns the connection of the bot, use it to send and receive messages.
+    pub fn client(&mut self) -> &mut RenetClient {
+        &mut self.client
+    }
+
+    /// Returns the transport of the bot.
+    pub fn transport(&self) -> &NetcodeClientTransport {
+        &self.transport
+    }
+
+    /// Disconnect the bot, the disconnect packet is sent and the bot removed from the runner in the next update.
+    pub fn disconnect(&mut self) {
+        self.client.disconnect();
+    }
+}
+
+/// Runs multiple headless clients, useful for load testing servers or filling matches with simple AI clients.
+///
+/// Each bot has its own connection and socket, the behaviour of the bots is driven by a callback
+/// that is called for every bot on each update.
+///
+/// # Usage
+/// ```no_run
+/// # use renet::{ConnectionConfig, DefaultChannel};
+/// # use renet::transport::{BotRunner, ClientAuthentication};
+/// # use std::time::Duration;
+/// let mut runner = BotRunner::new();
+/// for client_id in 0..10 {
+///     let authentication = ClientAuthentication::Unsecure {
+///         protocol_id: 0,
+///         client_id,
+///         server_addr: "127.0.0.1:5000".parse().unwrap(),
+///         user_data: None,
+///     };
+///     runner.spawn(Duration::ZERO, authentication, ConnectionConfig::default()).unwrap();
+/// }
+///
+/// loop {
+///     let delta = Duration::from_millis(16);
+///     runner.update(delta, |bot| {
+///         if bot.client().is_connected() {
+///             bot.client().send_message(DefaultChannel::Unreliable, "input");
+///         }
+///     });
+///     std::thread::sleep(delta);
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct BotRunner {
+    bots: Vec<Bot>,
+    next_bot_id: usize,
+}
+
+impl BotRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new bot that starts connecting to the server, returns the bot id.
+    pub fn spawn(
+        &mut self,
+        current_time: Duration,
+        authentication: ClientAuthentication,
+        connection_config: ConnectionConfig,
+    ) -> Result<usize, NetcodeTransportError> {
+        let server_addr = match &authentication {
+            ClientAuthentication::Secure { connect_token } => connect_token.server_addresses[0],
+            ClientAuthentication::Unsecure { server_addr, .. } => Some(*server_addr),
+        };
+        let socket = match server_addr {
+            Some(addr) if addr.is_ipv6() => UdpSocket::bind("[::]:0")?,
+            _ => UdpSocket::bind("0.0.0.0:0")?,
+        };
+        let transport = NetcodeClientTransport::new(current_time, authentication, socket)?;
+
+        let id = self.next_bot_id;
+        self.next_bot_id += 1;
+        self.bots.push(Bot {
+            id,
+            client: RenetClient::new(connection_config),
+            transport,
+        });
+
+        Ok(id)
+    }
+
+    /// Returns the number of running bots.
+    pub fn len(&self) -> usize {
+        self.bots.len()
+    }
+
+    /// Returns whether there are no running bots.
+    pub fn is_empty(&self) -> bool {
+        self.bots.is_empty()
+    }
+
+    /// Returns the bot with the given id.
+    pub fn bot(&mut self, id: usize) -> Option<&mut Bot> {
+        self.bots.iter_mut().find(|bot| bot.id == id)
+    }
+
+    /// Returns all running bots (iterator).
+    pub fn bots_iter_mut(&mut self) -> impl Iterator<Item = &mut Bot> {
+        self.bots.iter_mut()
+    }
+
+    /// Advances all bots by the duration, calling `behaviour` for each bot after receiving its packets.
+    ///
+    /// Bots that are disconnected or had a transport error are removed,
+    /// their ids and the error that caused the removal are returned.
+    pub fn update<F: FnMut(&mut Bot)>(&mut self, duration: Duration, mut behaviour: F) -> Vec<(usize, NetcodeTransportError)> {
+        let mut removed = vec![];
+        self.bots.retain_mut(|bot| {
+            bot.client.update(duration);
+            if let Err(e) = bot.transport.update(duration, &mut bot.client) {
+                removed.push((bot.id, e));
+                return false;
+            }
+
+            behaviour(bot);
+
+            if bot.client.is_connected() {
+                if let Err(e) = bot.transport.send_packets(&mut bot.client) {
+                    removed.push((bot.id, e));
+                    return false;
+                }
+            }
+
+            true
+        });
+
+        removed
+    }
+
+    /// Disconnects and removes all bots.
+    pub fn disconnect_all(&mut self) {
+        for bot in self.bots.iter_mut() {
+            bot.transport.disconnect();
+        }
+        self.bots.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{NetcodeServerTransport, ServerAuthentication, ServerConfig};
+    use crate::{DefaultChannel, DisconnectReason, RenetServer};
+    use std::collections::HashSet;
+
+    #[test]
+    fn bots_connect_and_disconnect() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = socket.local_addr().unwrap();
+        let server_config = ServerConfig {
+            current_time: Duration::ZERO,
+            max_clients: 4,
+            protocol_id: 0,
+            public_addresses: vec![server_addr],
+            authentication: ServerAuthentication::Unsecure,
+        };
+        let mut transport = NetcodeServerTransport::new(server_config, socket).unwrap();
+        let mut server = RenetServer::new(ConnectionConfig::default());
+
+        let mut runner = BotRunner::new();
+        for client_id in 0..3 {
+            let authentication = ClientAuthentication::Unsecure {
+                protocol_id: 0,
+                client_id,
+                server_addr,
+                user_data: None,
+            };
+            runner.spawn(Duration::ZERO, authentication, ConnectionConfig::default()).unwrap();
+        }
+
+        let delta = Duration::from_millis(10);
+        let mut sent = HashSet::new();
+        let mut received = 0;
+        let mut removed = vec![];
+        for _ in 0..200 {
+            server.update(delta);
+            transport.update(delta, &mut server).unwrap();
+            for client_id in server.clients_id() {
+                while server.receive_message(client_id, DefaultChannel::ReliableOrdered).is_some() {
+                    received += 1;
+                }
+            }
+            transport.send_packets(&mut server);
+
+            removed.extend(runner.update(delta, |bot| {
+                if !bot.client().is_connected() {
+                    return;
+                }
+                if sent.insert(bot.id()) {
+                    bot.client().send_message(DefaultChannel::ReliableOrdered, "input");
+                } else if received == 3 {
+                    bot.disconnect();
+                }
+            }));
+
+            if runner.is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(received, 3);
+        assert!(runner.is_empty());
+        assert_eq!(removed.len(), 3);
+        assert!(removed
+            .iter()
+            .all(|(_, e)| matches!(e, NetcodeTransportError::Renet(DisconnectReason::DisconnectedByClient))));
+    }
+}
@@ -8,30 +8,38 @@ use renetcode::{ClientAuthentication, DisconnectReason, NetcodeClient, NetcodeEr
 
 use crate::{remote_connection::RenetClient, ClientId};
 
-use super::NetcodeTransportError;
+use super::{is_datagram_error, NetcodeTransportError, TransportSocket, UdpTransport};
 
 #[derive(Debug)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
 pub struct NetcodeClientTransport {
-    socket: UdpSocket,
+    socket: Box<dyn TransportSocket>,
     netcode_client: NetcodeClient,
     buffer: [u8; NETCODE_MAX_PACKET_BYTES],
 }
 
 impl NetcodeClientTransport {
     pub fn new(current_time: Duration, authentication: ClientAuthentication, socket: UdpSocket) -> Result<Self, NetcodeError> {
-        socket.set_nonblocking(true)?;
+        Self::new_with_transport_socket(current_time, authentication, UdpTransport::from_socket(socket)?)
+    }
+
+    /// Creates a transport that sends and receive packets with a custom socket.
+    pub fn new_with_transport_socket(
+        current_time: Duration,
+        authentication: ClientAuthentication,
+        socket: impl TransportSocket,
+    ) -> Result<Self, NetcodeError> {
         let netcode_client = NetcodeClient::new(current_time, authentication)?;
 
         Ok(Self {
             buffer: [0u8; NETCODE_MAX_PACKET_BYTES],
-            socket,
+            socket: Box::new(socket),
             netcode_client,
         })
     }
 
     pub fn addr(&self) -> io::Result<SocketAddr> {
-        self.socket.local_addr()
+        self.socket.addr()
     }
 
     pub fn client_id(&self) -> ClientId {
@@ -54,7 +62,7 @@ impl NetcodeClientTransport {
 
         match self.netcode_client.disconnect() {
             Ok((addr, packet)) => {
-                if let Err(e) = self.socket.send_to(packet, addr) {
+                if let Err(e) = self.socket.send_to(packet, addr).and_then(|_| self.socket.flush()) {
                     log::error!("Failed to send disconnect packet: {e}");
                 }
             }
@@ -75,10 +83,12 @@ impl NetcodeClientTransport {
         }
 
         let packets = connection.get_packets_to_send();
-        for packet in packets {
-            let (addr, payload) = self.netcode_client.generate_payload_packet(&packet)?;
-            self.socket.send_to(payload, addr)?;
+        for packet in packets.iter() {
+            let (addr, payload) = self.netcode_client.generate_payload_packet(packet)?;
+            send_to(self.socket.as_mut(), payload, addr)?;
         }
+        connection.recycle_payloads(packets);
+        flush(self.socket.as_mut())?;
 
         Ok(())
     }
@@ -93,20 +103,37 @@ impl NetcodeClientTransport {
         }
 
         if let Some(error) = client.disconnect_reason() {
+            // Send the last packets, so the server knows why we disconnected
+            if self.netcode_client.is_connected() {
+                for packet in client.get_packets_to_send() {
+                    let (addr, payload) = self.netcode_client.generate_payload_packet(&packet)?;
+                    send_to(self.socket.as_mut(), payload, addr)?;
+                }
+            }
+
             let (addr, disconnect_packet) = self.netcode_client.disconnect()?;
-            self.socket.send_to(disconnect_packet, addr)?;
+            send_to(self.socket.as_mut(), disconnect_packet, addr)?;
+            flush(self.socket.as_mut())?;
             return Err(error.into());
         }
 
+        // Sent with the connection response, so the server can reject incompatible clients before accepting them
+        if self.netcode_client.is_connecting() {
+            self.netcode_client.set_request_data(client.start_handshake().to_bytes());
+        }
+
         if self.netcode_client.is_connected() {
             client.set_connected();
+        } else if self.netcode_client.is_challenged() {
+            client.set_challenged();
         } else if self.netcode_client.is_connecting() {
             client.set_connecting();
         }
 
+        self.socket.update(duration)?;
         loop {
             let packet = match self.socket.recv_from(&mut self.buffer) {
-                Ok((len, addr)) => {
+                Ok(Some((len, addr))) => {
                     if addr != self.netcode_client.server_addr() {
                         log::debug!("Discarded packet from unknown server {:?}", addr);
                         continue;
@@ -114,8 +141,11 @@ impl NetcodeClientTransport {
 
                     &mut self.buffer[..len]
                 }
-                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
-                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => break,
+                Ok(None) => break,
+                Err(ref e) if is_datagram_error(e) => {
+                    log::debug!("Ignored socket error while receiving packets: {e}");
+                    continue;
+                }
                 Err(e) => return Err(NetcodeTransportError::IO(e)),
             };
 
@@ -125,9 +155,38 @@ impl NetcodeClientTransport {
         }
 
         if let Some((packet, addr)) = self.netcode_client.update(duration) {
-            self.socket.send_to(packet, addr)?;
+            send_to(self.socket.as_mut(), packet, addr)?;
         }
+        flush(self.socket.as_mut())?;
 
         Ok(())
     }
 }
+
+// Errors that only affected the packet are ignored, if the server is unreachable the connection times out.
+fn send_to(socket: &mut dyn TransportSocket, packet: &[u8], addr: SocketAddr) -> io::Result<()> {
+    match socket.send_to(packet, addr) {
+        Err(ref e) if is_datagram_error(e) => {
+            log::debug!("Ignored socket error while sending packet to {addr}: {e}");
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+fn flush(socket: &mut dyn TransportSocket) -> io::Result<()> {
+    match socket.flush() {
+        Err(ref e) if is_datagram_error(e) => log::debug!("Ignored socket error while sending packets: {e}"),
+        result => result?,
+    }
+
+    let mut result = Ok(());
+    for (addr, error) in socket.take_send_errors() {
+        if is_datagram_error(&error) {
+            log::debug!("Ignored socket error while sending packet to {addr}: {error}");
+        } else if result.is_ok() {
+            result = Err(error);
+        }
+    }
+    result
+}
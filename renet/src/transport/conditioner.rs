@@ -0,0 +1,399 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    io,
+    net::SocketAddr,
+    time::Duration,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(target_arch = "wasm32")]
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use renetcode::NETCODE_MAX_PACKET_BYTES;
+
+use super::TransportSocket;
+
+/// Distribution of the jitter added to the latency of each packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JitterDistribution {
+    /// Jitter between `-jitter` and `+jitter`.
+    #[default]
+    Uniform,
+    /// Normal distribution with `jitter` as the standard deviation.
+    Normal,
+}
+
+/// Simulated network conditions, applied to the packets in both directions.
+/// The default has no latency or losses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkConditions {
+    /// Delay added to every packet.
+    pub latency: Duration,
+    /// Variation of the delay, see [`JitterDistribution`].
+    pub jitter: Duration,
+    pub jitter_distribution: JitterDistribution,
+    /// Chance from 0 to 1 of a packet being dropped.
+    pub packet_loss: f64,
+    /// Chance from 0 to 1 of a packet being delivered twice.
+    pub duplicate: f64,
+    /// Chance from 0 to 1 of a packet being delayed by an extra `reorder_delay`,
+    /// so the packets sent after it arrive first.
+    pub reorder: f64,
+    pub reorder_delay: Duration,
+}
+
+impl Default for NetworkConditions {
+    fn default() -> Self {
+        Self {
+            latency: Duration::ZERO,
+            jitter: Duration::ZERO,
+            jitter_distribution: JitterDistribution::Uniform,
+            packet_loss: 0.0,
+            duplicate: 0.0,
+            reorder: 0.0,
+            reorder_delay: Duration::from_millis(20),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct DelayedPacket {
+    deliver_at: Duration,
+    // Keeps the order of packets with the same delivery time
+    sequence: u64,
+    addr: SocketAddr,
+    payload: Vec<u8>,
+}
+
+impl PartialEq for DelayedPacket {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for DelayedPacket {}
+
+impl PartialOrd for DelayedPacket {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DelayedPacket {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.deliver_at, self.sequence).cmp(&(other.deliver_at, other.sequence))
+    }
+}
+
+// SplitMix64, good enough for simulations and reproducible from the seed.
+#[derive(Debug, Clone)]
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Returns a number in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.next_f64() < probability
+    }
+}
+
+/// Wraps a socket to simulate latency, jitter, packet loss, duplication and reordering.
+///
+/// Useful to test how the game behaves in bad networks, the delays are advanced by the duration passed to the transport update.
+///
+/// # Usage
+/// ```
+/// # use renet::transport::{ConditionedTransport, NetworkConditions, UdpTransport};
+/// # use std::time::Duration;
+/// let conditions = NetworkConditions {
+///     latency: Duration::from_millis(100),
+///     jitter: Duration::from_millis(10),
+///     packet_loss: 0.05,
+///     ..Default::default()
+/// };
+/// let socket = ConditionedTransport::new(UdpTransport::bind("127.0.0.1:0").unwrap(), conditions);
+/// ```
+#[derive(Debug)]
+pub struct ConditionedTransport<T> {
+    inner: T,
+    conditions: NetworkConditions,
+    rng: Rng,
+    current_time: Duration,
+    sequence: u64,
+    incoming: BinaryHeap<Reverse<DelayedPacket>>,
+    outgoing: BinaryHeap<Reverse<DelayedPacket>>,
+    buffer: Box<[u8; NETCODE_MAX_PACKET_BYTES]>,
+}
+
+impl<T: TransportSocket> ConditionedTransport<T> {
+    /// Wraps the socket, with a random seed.
+    pub fn new(inner: T, conditions: NetworkConditions) -> Self {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+        Self::with_seed(inner, conditions, seed)
+    }
+
+    /// Wraps the socket, the same seed always applies the same conditions to the same packets.
+    pub fn with_seed(inner: T, conditions: NetworkConditions, seed: u64) -> Self {
+        Self {
+            inner,
+            conditions,
+            rng: Rng { state: seed },
+            current_time: Duration::ZERO,
+            sequence: 0,
+            incoming: BinaryHeap::new(),
+            outgoing: BinaryHeap::new(),
+            buffer: Box::new([0; NETCODE_MAX_PACKET_BYTES]),
+        }
+    }
+
+    pub fn conditions(&self) -> &NetworkConditions {
+        &self.conditions
+    }
+
+    /// Changes the conditions, packets already delayed keep their delivery time.
+    pub fn set_conditions(&mut self, conditions: NetworkConditions) {
+        self.conditions = conditions;
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Returns the number of packets waiting to be delivered, in both directions.
+    pub fn delayed_packets(&self) -> usize {
+        self.incoming.len() + self.outgoing.len()
+    }
+
+    fn delay(&mut self) -> Duration {
+        let conditions = &self.conditions;
+        let jitter = conditions.jitter.as_secs_f64();
+        let jitter = match conditions.jitter_distribution {
+            _ if jitter == 0.0 => 0.0,
+            JitterDistribution::Uniform => (self.rng.next_f64() * 2.0 - 1.0) * jitter,
+            JitterDistribution::Normal => {
+                // Box-Muller transform
+                let u1 = 1.0 - self.rng.next_f64();
+                let u2 = self.rng.next_f64();
+                (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos() * jitter
+            }
+        };
+
+        let mut delay = (conditions.latency.as_secs_f64() + jitter).max(0.0);
+        if self.rng.chance(self.conditions.reorder) {
+            delay += self.conditions.reorder_delay.as_secs_f64();
+        }
+
+        Duration::from_secs_f64(delay)
+    }
+
+    // Applies the conditions to the packet, queueing the copies to be delivered.
+    fn condition(&mut self, payload: &[u8], addr: SocketAddr, queue: Queue) {
+        if self.rng.chance(self.conditions.packet_loss) {
+            return;
+        }
+
+        let copies = if self.rng.chance(self.conditions.duplicate) { 2 } else { 1 };
+        for _ in 0..copies {
+            let packet = DelayedPacket {
+                deliver_at: self.current_time + self.delay(),
+                sequence: self.sequence,
+                addr,
+                payload: payload.to_vec(),
+            };
+            self.sequence += 1;
+
+            match queue {
+                Queue::Incoming => self.incoming.push(Reverse(packet)),
+                Queue::Outgoing => self.outgoing.push(Reverse(packet)),
+            }
+        }
+    }
+
+    fn send_due_packets(&mut self) -> io::Result<()> {
+        while self.outgoing.peek().is_some_and(|packet| packet.0.deliver_at <= self.current_time) {
+            let Reverse(packet) = self.outgoing.pop().unwrap();
+            self.inner.send_to(&packet.payload, packet.addr)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Queue {
+    Incoming,
+    Outgoing,
+}
+
+impl<T: TransportSocket> TransportSocket for ConditionedTransport<T> {
+    fn addr(&self) -> io::Result<SocketAddr> {
+        self.inner.addr()
+    }
+
+    fn update(&mut self, duration: Duration) -> io::Result<()> {
+        self.inner.update(duration)?;
+
+        // The packets arrived since the last update, so they are delayed from the start of this update
+        while let Some((len, addr)) = self.inner.recv_from(&mut self.buffer[..])? {
+            let payload = self.buffer[..len].to_vec();
+            self.condition(&payload, addr, Queue::Incoming);
+        }
+
+        self.current_time += duration;
+        self.send_due_packets()
+    }
+
+    fn recv_from(&mut self, buffer: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        match self.incoming.peek() {
+            Some(packet) if packet.0.deliver_at <= self.current_time => {}
+            _ => return Ok(None),
+        }
+
+        let Reverse(packet) = self.incoming.pop().unwrap();
+        let len = packet.payload.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&packet.payload[..len]);
+        Ok(Some((len, packet.addr)))
+    }
+
+    fn send_to(&mut self, packet: &[u8], addr: SocketAddr) -> io::Result<()> {
+        self.condition(packet, addr, Queue::Outgoing);
+        // Packets without delay are sent right away
+        self.send_due_packets()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    fn take_send_errors(&mut self) -> Vec<(SocketAddr, io::Error)> {
+        self.inner.take_send_errors()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MemoryTransport;
+
+    fn pair(conditions: NetworkConditions, seed: u64) -> (ConditionedTransport<MemoryTransport>, MemoryTransport) {
+        let (a, b) = MemoryTransport::pair("127.0.0.1:1".parse().unwrap(), "127.0.0.1:2".parse().unwrap());
+        (ConditionedTransport::with_seed(a, conditions, seed), b)
+    }
+
+    fn receive_all(socket: &mut impl TransportSocket) -> Vec<u8> {
+        let mut buffer = [0u8; 16];
+        let mut received = vec![];
+        while let Some((len, _)) = socket.recv_from(&mut buffer).unwrap() {
+            assert_eq!(len, 1);
+            received.push(buffer[0]);
+        }
+        received
+    }
+
+    #[test]
+    fn no_conditions() {
+        let (mut conditioned, mut other) = pair(NetworkConditions::default(), 0);
+        let conditioned_addr = conditioned.addr().unwrap();
+        let other_addr = other.addr().unwrap();
+
+        conditioned.send_to(&[1], other_addr).unwrap();
+        assert_eq!(receive_all(&mut other), vec![1]);
+
+        other.send_to(&[2], conditioned_addr).unwrap();
+        conditioned.update(Duration::ZERO).unwrap();
+        assert_eq!(receive_all(&mut conditioned), vec![2]);
+    }
+
+    #[test]
+    fn latency() {
+        let conditions = NetworkConditions {
+            latency: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let (mut conditioned, mut other) = pair(conditions, 0);
+        let conditioned_addr = conditioned.addr().unwrap();
+        let other_addr = other.addr().unwrap();
+
+        conditioned.send_to(&[1], other_addr).unwrap();
+        other.send_to(&[2], conditioned_addr).unwrap();
+        conditioned.update(Duration::from_millis(50)).unwrap();
+        assert!(receive_all(&mut other).is_empty());
+        assert!(receive_all(&mut conditioned).is_empty());
+        assert_eq!(conditioned.delayed_packets(), 2);
+
+        conditioned.update(Duration::from_millis(50)).unwrap();
+        assert_eq!(receive_all(&mut other), vec![1]);
+        assert_eq!(receive_all(&mut conditioned), vec![2]);
+    }
+
+    #[test]
+    fn loss_and_duplicate() {
+        let conditions = NetworkConditions {
+            packet_loss: 1.0,
+            ..Default::default()
+        };
+        let (mut conditioned, mut other) = pair(conditions, 0);
+        let other_addr = other.addr().unwrap();
+        conditioned.send_to(&[1], other_addr).unwrap();
+        assert!(receive_all(&mut other).is_empty());
+
+        conditioned.set_conditions(NetworkConditions {
+            duplicate: 1.0,
+            ..Default::default()
+        });
+        conditioned.send_to(&[1], other_addr).unwrap();
+        assert_eq!(receive_all(&mut other), vec![1, 1]);
+    }
+
+    #[test]
+    fn seeded_conditions_are_reproducible() {
+        let conditions = NetworkConditions {
+            latency: Duration::from_millis(50),
+            jitter: Duration::from_millis(30),
+            jitter_distribution: JitterDistribution::Normal,
+            packet_loss: 0.2,
+            duplicate: 0.1,
+            reorder: 0.1,
+            ..Default::default()
+        };
+
+        let run = |seed: u64| {
+            let (mut conditioned, mut other) = pair(conditions.clone(), seed);
+            let other_addr = other.addr().unwrap();
+            let mut received = vec![];
+            for i in 0..100 {
+                conditioned.send_to(&[i], other_addr).unwrap();
+                conditioned.update(Duration::from_millis(10)).unwrap();
+                received.extend(receive_all(&mut other));
+            }
+            conditioned.update(Duration::from_secs(1)).unwrap();
+            received.extend(receive_all(&mut other));
+            received
+        };
+
+        let received = run(42);
+        assert_eq!(received, run(42));
+        assert!(received.len() < 100);
+        // Jitter and reordering change the order of the packets
+        assert!(received.windows(2).any(|w| w[0] > w[1]));
+    }
+}
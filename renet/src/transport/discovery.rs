@@ -0,0 +1,308 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, SocketAddr, UdpSocket},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{Clock, SystemClock};
+
+const DISCOVERY_MAGIC: &[u8; 8] = b"RENETLAN";
+const MAX_NAME_BYTES: usize = 255;
+// magic + protocol id
+const HEADER_BYTES: usize = 8 + 8;
+// header + port + players + max players + name length + name
+const MAX_RESPONSE_BYTES: usize = HEADER_BYTES + 2 + 2 + 2 + 1 + MAX_NAME_BYTES;
+// Probes are padded with zeros to the size of the biggest response,
+// so the server can't be used to amplify traffic sent to a spoofed address.
+const PROBE_BYTES: usize = MAX_RESPONSE_BYTES;
+// Each address gets at most one response in this interval
+const MIN_RESPONSE_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_TRACKED_ADDRESSES: usize = 4096;
+
+/// Information sent by a [`DiscoveryServer`] to the clients probing the network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    /// Name of the server, truncated to 255 bytes.
+    pub name: String,
+    /// Port of the game server, the address is the same as the one answering the probe.
+    pub port: u16,
+    pub players: u16,
+    pub max_players: u16,
+}
+
+/// A game server found by [`discover_servers`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredServer {
+    /// Address of the game server.
+    pub addr: SocketAddr,
+    pub protocol_id: u64,
+    pub info: ServerInfo,
+}
+
+/// Answers the discovery probes sent by clients in the local network, see [`discover_servers`].
+///
+/// Uses its own socket, separated from the game server, so probes can be sent to a well known port.
+/// Integers in probes and responses are big-endian, like in [renet packets](crate::packet).
+#[derive(Debug)]
+pub struct DiscoveryServer {
+    socket: UdpSocket,
+    protocol_id: u64,
+    buffer: [u8; PROBE_BYTES],
+    clock: Arc<dyn Clock>,
+    // Last response sent to each address
+    last_responses: HashMap<IpAddr, Duration>,
+}
+
+impl DiscoveryServer {
+    /// Creates a discovery server that answers probes with the same protocol id as the game.
+    pub fn new(socket: UdpSocket, protocol_id: u64) -> Result<Self, io::Error> {
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            protocol_id,
+            buffer: [0; PROBE_BYTES],
+            clock: Arc::new(SystemClock::new()),
+            last_responses: HashMap::new(),
+        })
+    }
+
+    /// Replaces the clock measuring the interval between responses, by default the wall-clock time is used.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.last_responses.clear();
+        self.clock = clock;
+    }
+
+    /// Returns the address of the discovery socket.
+    pub fn addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Answers the probes received since the last call with the server info.
+    /// Each ip address gets at most one response every 100ms.
+    /// Should be called every tick.
+    pub fn update(&mut self, info: &ServerInfo) -> Result<(), io::Error> {
+        let now = self.clock.now();
+        self.last_responses
+            .retain(|_, sent_at| now.saturating_sub(*sent_at) < MIN_RESPONSE_INTERVAL);
+
+        loop {
+            match self.socket.recv_from(&mut self.buffer) {
+                Ok((len, addr)) => {
+                    if len != PROBE_BYTES || read_probe(&self.buffer) != Some(self.protocol_id) {
+                        continue;
+                    }
+                    // Floods from many addresses are not answered until the tracked ones expire
+                    if self.last_responses.contains_key(&addr.ip()) || self.last_responses.len() >= MAX_TRACKED_ADDRESSES {
+                        continue;
+                    }
+                    self.last_responses.insert(addr.ip(), now);
+
+                    let mut response = [0u8; MAX_RESPONSE_BYTES];
+                    let len = write_response(&mut response, self.protocol_id, info);
+                    if let Err(e) = self.socket.send_to(&response[..len], addr) {
+                        log::error!("Failed to send discovery response to {}: {}", addr, e);
+                    }
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref e) if matches!(e.kind(), io::ErrorKind::Interrupted | io::ErrorKind::ConnectionReset) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Sends a discovery probe to the address and collects the answers until the timeout.
+/// Only servers with the same protocol id answer.
+///
+/// To search the local network use the broadcast address with the port of the [`DiscoveryServer`],
+/// for example `(Ipv4Addr::BROADCAST, 5001).into()`.
+pub fn discover_servers(addr: SocketAddr, protocol_id: u64, timeout: Duration) -> Result<Vec<DiscoveredServer>, io::Error> {
+    let socket = match addr {
+        SocketAddr::V4(_) => UdpSocket::bind("0.0.0.0:0")?,
+        SocketAddr::V6(_) => UdpSocket::bind("[::]:0")?,
+    };
+    socket.set_broadcast(true)?;
+
+    let mut probe = [0u8; PROBE_BYTES];
+    probe[..8].copy_from_slice(DISCOVERY_MAGIC);
+    probe[8..16].copy_from_slice(&protocol_id.to_be_bytes());
+    socket.send_to(&probe, addr)?;
+
+    let mut servers: Vec<DiscoveredServer> = vec![];
+    let mut buffer = [0u8; MAX_RESPONSE_BYTES];
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(remaining))?;
+
+        match socket.recv_from(&mut buffer) {
+            Ok((len, from)) => {
+                let Some(info) = read_response(&buffer[..len], protocol_id) else {
+                    continue;
+                };
+                let addr = SocketAddr::new(from.ip(), info.port);
+                if !servers.iter().any(|server| server.addr == addr) {
+                    servers.push(DiscoveredServer { addr, protocol_id, info });
+                }
+            }
+            Err(ref e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => break,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(ref e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(servers)
+}
+
+fn read_probe(buffer: &[u8]) -> Option<u64> {
+    if buffer.len() < HEADER_BYTES || &buffer[..8] != DISCOVERY_MAGIC {
+        return None;
+    }
+
+    Some(u64::from_be_bytes(buffer[8..16].try_into().unwrap()))
+}
+
+fn write_response(buffer: &mut [u8; MAX_RESPONSE_BYTES], protocol_id: u64, info: &ServerInfo) -> usize {
+    let mut name_len = info.name.len().min(MAX_NAME_BYTES);
+    while !info.name.is_char_boundary(name_len) {
+        name_len -= 1;
+    }
+
+    buffer[..8].copy_from_slice(DISCOVERY_MAGIC);
+    buffer[8..16].copy_from_slice(&protocol_id.to_be_bytes());
+    buffer[16..18].copy_from_slice(&info.port.to_be_bytes());
+    buffer[18..20].copy_from_slice(&info.players.to_be_bytes());
+    buffer[20..22].copy_from_slice(&info.max_players.to_be_bytes());
+    buffer[22] = name_len as u8;
+    buffer[23..23 + name_len].copy_from_slice(&info.name.as_bytes()[..name_len]);
+
+    23 + name_len
+}
+
+fn read_response(buffer: &[u8], protocol_id: u64) -> Option<ServerInfo> {
+    if read_probe(buffer)? != protocol_id || buffer.len() < 23 {
+        return None;
+    }
+
+    let name_len = buffer[22] as usize;
+    let name = buffer.get(23..23 + name_len)?;
+    Some(ServerInfo {
+        name: String::from_utf8(name.to_vec()).ok()?,
+        port: u16::from_be_bytes([buffer[16], buffer[17]]),
+        players: u16::from_be_bytes([buffer[18], buffer[19]]),
+        max_players: u16::from_be_bytes([buffer[20], buffer[21]]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::ManualClock;
+
+    #[test]
+    fn serialize_response() {
+        let info = ServerInfo {
+            name: "é".repeat(200),
+            port: 5000,
+            players: 3,
+            max_players: 8,
+        };
+        let mut buffer = [0u8; MAX_RESPONSE_BYTES];
+        let len = write_response(&mut buffer, 7, &info);
+
+        // The name is truncated in a character boundary
+        let read_info = read_response(&buffer[..len], 7).unwrap();
+        assert_eq!(read_info.name, "é".repeat(127));
+        assert_eq!(read_info.port, 5000);
+        assert_eq!(read_info.players, 3);
+        assert_eq!(read_info.max_players, 8);
+
+        assert!(read_response(&buffer[..len], 8).is_none());
+        assert!(read_response(&buffer[..len - 1], 7).is_none());
+        assert!(len <= PROBE_BYTES);
+    }
+
+    #[test]
+    fn response_rate_limit() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut server = DiscoveryServer::new(socket, 7).unwrap();
+        let clock = ManualClock::new(Duration::ZERO);
+        server.set_clock(Arc::new(clock.clone()));
+        let discovery_addr = server.addr().unwrap();
+        let info = ServerInfo {
+            name: "LAN game".to_string(),
+            port: 5000,
+            players: 1,
+            max_players: 4,
+        };
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+        let mut probe = [0u8; PROBE_BYTES];
+        probe[..8].copy_from_slice(DISCOVERY_MAGIC);
+        probe[8..16].copy_from_slice(&7u64.to_be_bytes());
+        for _ in 0..3 {
+            client.send_to(&probe, discovery_addr).unwrap();
+        }
+        // Unpadded probes are ignored
+        client.send_to(&probe[..HEADER_BYTES], discovery_addr).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        server.update(&info).unwrap();
+
+        let mut buffer = [0u8; MAX_RESPONSE_BYTES];
+        assert!(client.recv_from(&mut buffer).is_ok());
+        assert!(client.recv_from(&mut buffer).is_err());
+
+        // Answered again once the interval elapsed
+        let mut probe_and_update = |elapsed: Duration| {
+            clock.advance(elapsed);
+            client.send_to(&probe, discovery_addr).unwrap();
+            thread::sleep(Duration::from_millis(20));
+            server.update(&info).unwrap();
+            client.recv_from(&mut buffer).is_ok()
+        };
+        assert!(!probe_and_update(Duration::from_millis(50)));
+        assert!(probe_and_update(Duration::from_millis(50)));
+    }
+
+    #[test]
+    fn discovery() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut server = DiscoveryServer::new(socket, 7).unwrap();
+        let discovery_addr = server.addr().unwrap();
+        let info = ServerInfo {
+            name: "LAN game".to_string(),
+            port: 5000,
+            players: 1,
+            max_players: 4,
+        };
+
+        let client = thread::spawn(move || discover_servers(discovery_addr, 7, Duration::from_millis(500)).unwrap());
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(500) {
+            server.update(&info).unwrap();
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        let servers = client.join().unwrap();
+        assert_eq!(
+            servers,
+            vec![DiscoveredServer {
+                addr: "127.0.0.1:5000".parse().unwrap(),
+                protocol_id: 7,
+                info,
+            }]
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use super::TransportSocket;
+
+type Inboxes = HashMap<SocketAddr, VecDeque<(SocketAddr, Vec<u8>)>>;
+
+/// In-memory network connecting [`MemoryTransport`]s, useful for testing without binding sockets.
+///
+/// Packets are delivered instantly and in order, packets sent to addresses without a transport are dropped.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryNetwork {
+    inboxes: Arc<Mutex<Inboxes>>,
+}
+
+impl MemoryNetwork {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a transport with the given address in this network.
+    /// Panics if the address is already in use.
+    pub fn bind(&self, addr: SocketAddr) -> MemoryTransport {
+        let mut inboxes = self.inboxes.lock().unwrap();
+        assert!(!inboxes.contains_key(&addr), "address {addr} already in use");
+        inboxes.insert(addr, VecDeque::new());
+
+        MemoryTransport {
+            addr,
+            inboxes: self.inboxes.clone(),
+        }
+    }
+}
+
+/// Socket of a [`MemoryNetwork`], packets are moved between queues without touching the network.
+#[derive(Debug)]
+pub struct MemoryTransport {
+    addr: SocketAddr,
+    inboxes: Arc<Mutex<Inboxes>>,
+}
+
+impl MemoryTransport {
+    /// Creates two transports connected to each other.
+    pub fn pair(addr_a: SocketAddr, addr_b: SocketAddr) -> (Self, Self) {
+        let network = MemoryNetwork::new();
+        (network.bind(addr_a), network.bind(addr_b))
+    }
+}
+
+impl Drop for MemoryTransport {
+    fn drop(&mut self) {
+        if let Ok(mut inboxes) = self.inboxes.lock() {
+            inboxes.remove(&self.addr);
+        }
+    }
+}
+
+impl TransportSocket for MemoryTransport {
+    fn addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn recv_from(&mut self, buffer: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        let mut inboxes = self.inboxes.lock().unwrap();
+        let Some((from, packet)) = inboxes.get_mut(&self.addr).and_then(|inbox| inbox.pop_front()) else {
+            return Ok(None);
+        };
+
+        // Like UDP, the remaining of the packet is discarded if the buffer is too small
+        let len = packet.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&packet[..len]);
+        Ok(Some((len, from)))
+    }
+
+    fn send_to(&mut self, packet: &[u8], addr: SocketAddr) -> io::Result<()> {
+        let mut inboxes = self.inboxes.lock().unwrap();
+        if let Some(inbox) = inboxes.get_mut(&addr) {
+            inbox.push_back((self.addr, packet.to_vec()));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::transport::{ClientAuthentication, NetcodeClientTransport, NetcodeServerTransport, ServerAuthentication, ServerConfig};
+    use crate::{ClientId, ConnectionConfig, DefaultChannel, DisconnectCode, DisconnectReason, RenetClient, RenetServer, ServerEvent};
+
+    #[test]
+    fn pair() {
+        let (mut a, mut b) = MemoryTransport::pair("127.0.0.1:1".parse().unwrap(), "127.0.0.1:2".parse().unwrap());
+        let mut buffer = [0u8; 2];
+        a.send_to(&[1, 2, 3], b.addr().unwrap()).unwrap();
+        assert_eq!(b.recv_from(&mut buffer).unwrap(), Some((2, a.addr().unwrap())));
+        assert_eq!(buffer, [1, 2]);
+        assert_eq!(b.recv_from(&mut buffer).unwrap(), None);
+
+        drop(b);
+        a.send_to(&[1], "127.0.0.1:2".parse().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn netcode_over_memory() {
+        let network = MemoryNetwork::new();
+        let server_addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let server_config = ServerConfig {
+            current_time: Duration::ZERO,
+            max_clients: 4,
+            protocol_id: 0,
+            public_addresses: vec![server_addr],
+            authentication: ServerAuthentication::Unsecure,
+        };
+        let mut server_transport = NetcodeServerTransport::new_with_transport_socket(server_config, network.bind(server_addr)).unwrap();
+        let mut server = RenetServer::new(ConnectionConfig::default());
+
+        let authentication = ClientAuthentication::Unsecure {
+            protocol_id: 0,
+            client_id: 7,
+            server_addr,
+            user_data: None,
+        };
+        let client_socket = network.bind("127.0.0.1:6000".parse().unwrap());
+        let mut client_transport =
+            NetcodeClientTransport::new_with_transport_socket(Duration::ZERO, authentication, client_socket).unwrap();
+        let mut client = RenetClient::new(ConnectionConfig::default());
+
+        let delta = Duration::from_millis(10);
+        let mut received = None;
+        for _ in 0..20 {
+            client.update(delta);
+            client_transport.update(delta, &mut client).unwrap();
+            if client.is_connected() {
+                client.send_message(DefaultChannel::ReliableOrdered, "hello");
+            }
+            client_transport.send_packets(&mut client).unwrap();
+
+            server.update(delta);
+            server_transport.update(delta, &mut server).unwrap();
+            for client_id in server.clients_id() {
+                if let Some(message) = server.receive_message(client_id, DefaultChannel::ReliableOrdered) {
+                    received = Some((client_id, message));
+                }
+            }
+            server_transport.send_packets(&mut server);
+
+            if received.is_some() {
+                break;
+            }
+        }
+
+        let (client_id, message) = received.unwrap();
+        assert_eq!(client_id.raw(), 7);
+        assert_eq!(message, "hello");
+    }
+
+    #[test]
+    fn netcode_incompatible_version() {
+        let network = MemoryNetwork::new();
+        let server_addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let server_config = ServerConfig {
+            current_time: Duration::ZERO,
+            max_clients: 4,
+            protocol_id: 0,
+            public_addresses: vec![server_addr],
+            authentication: ServerAuthentication::Unsecure,
+        };
+        let mut server_transport = NetcodeServerTransport::new_with_transport_socket(server_config, network.bind(server_addr)).unwrap();
+        let mut server = RenetServer::new(ConnectionConfig {
+            version: 2,
+            ..Default::default()
+        });
+
+        let authentication = ClientAuthentication::Unsecure {
+            protocol_id: 0,
+            client_id: 7,
+            server_addr,
+            user_data: None,
+        };
+        let client_socket = network.bind("127.0.0.1:6000".parse().unwrap());
+        let mut client_transport =
+            NetcodeClientTransport::new_with_transport_socket(Duration::ZERO, authentication, client_socket).unwrap();
+        let mut client = RenetClient::new(ConnectionConfig {
+            version: 1,
+            ..Default::default()
+        });
+
+        let delta = Duration::from_millis(10);
+        for _ in 0..20 {
+            client.update(delta);
+            if client_transport.update(delta, &mut client).is_err() || client_transport.send_packets(&mut client).is_err() {
+                break;
+            }
+
+            server.update(delta);
+            server_transport.update(delta, &mut server).unwrap();
+            server_transport.send_packets(&mut server);
+        }
+
+        assert_eq!(
+            client.disconnect_reason(),
+            Some(DisconnectReason::DisconnectedByRemote(DisconnectCode::IncompatibleVersion))
+        );
+        // The client was rejected before its connection was added
+        assert_eq!(server.connected_clients(), 0);
+        assert!(!matches!(server.get_event(), Some(ServerEvent::ClientConnected { .. })));
+    }
+
+    // Buffers the packets like a batched socket, failing all of them once `failing` is set
+    #[derive(Debug)]
+    struct BufferedSocket {
+        inner: MemoryTransport,
+        queued: Vec<(SocketAddr, Vec<u8>)>,
+        failed: Vec<(SocketAddr, io::Error)>,
+        failing: Arc<AtomicBool>,
+    }
+
+    impl TransportSocket for BufferedSocket {
+        fn addr(&self) -> io::Result<SocketAddr> {
+            self.inner.addr()
+        }
+
+        fn recv_from(&mut self, buffer: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+            self.inner.recv_from(buffer)
+        }
+
+        fn send_to(&mut self, packet: &[u8], addr: SocketAddr) -> io::Result<()> {
+            self.queued.push((addr, packet.to_vec()));
+            Ok(())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            for (addr, packet) in std::mem::take(&mut self.queued) {
+                if self.failing.load(Ordering::Relaxed) {
+                    self.failed.push((addr, io::ErrorKind::PermissionDenied.into()));
+                } else {
+                    self.inner.send_to(&packet, addr)?;
+                }
+            }
+            Ok(())
+        }
+
+        fn take_send_errors(&mut self) -> Vec<(SocketAddr, io::Error)> {
+            std::mem::take(&mut self.failed)
+        }
+    }
+
+    #[test]
+    fn netcode_buffered_send_errors() {
+        let network = MemoryNetwork::new();
+        let server_addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let server_config = ServerConfig {
+            current_time: Duration::ZERO,
+            max_clients: 4,
+            protocol_id: 0,
+            public_addresses: vec![server_addr],
+            authentication: ServerAuthentication::Unsecure,
+        };
+        let failing = Arc::new(AtomicBool::new(false));
+        let server_socket = BufferedSocket {
+            inner: network.bind(server_addr),
+            queued: vec![],
+            failed: vec![],
+            failing: failing.clone(),
+        };
+        let mut server_transport = NetcodeServerTransport::new_with_transport_socket(server_config, server_socket).unwrap();
+        let mut server = RenetServer::new(ConnectionConfig::default());
+
+        let authentication = ClientAuthentication::Unsecure {
+            protocol_id: 0,
+            client_id: 7,
+            server_addr,
+            user_data: None,
+        };
+        let client_socket = network.bind("127.0.0.1:6000".parse().unwrap());
+        let mut client_transport =
+            NetcodeClientTransport::new_with_transport_socket(Duration::ZERO, authentication, client_socket).unwrap();
+        let mut client = RenetClient::new(ConnectionConfig::default());
+
+        let delta = Duration::from_millis(10);
+        for _ in 0..20 {
+            client.update(delta);
+            client_transport.update(delta, &mut client).unwrap();
+            client_transport.send_packets(&mut client).unwrap();
+
+            server.update(delta);
+            server_transport.update(delta, &mut server).unwrap();
+            server_transport.send_packets(&mut server);
+
+            if client.is_connected() {
+                break;
+            }
+        }
+        assert!(client.is_connected());
+        assert!(server_transport.get_client_error().is_none());
+
+        // The errors reported after the flush are matched to the client
+        failing.store(true, Ordering::Relaxed);
+        server.send_message(ClientId::from_raw(7), DefaultChannel::ReliableOrdered, "hello");
+        server_transport.send_packets(&mut server);
+        let error = server_transport.get_client_error().unwrap();
+        assert_eq!(error.client_id.raw(), 7);
+        assert_eq!(error.error.kind(), io::ErrorKind::PermissionDenied);
+    }
+}
@@ -0,0 +1,315 @@
+//! Batched send and receive with `sendmmsg`/`recvmmsg`, one syscall for multiple datagrams.
+
+use std::{
+    io, mem,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, UdpSocket},
+    os::fd::AsRawFd,
+    ptr,
+};
+
+use renetcode::NETCODE_MAX_PACKET_BYTES;
+
+use super::is_datagram_error;
+
+pub(crate) const BATCH_SIZE: usize = 32;
+
+#[derive(Debug)]
+pub(crate) struct RecvBatch {
+    buffers: Vec<[u8; NETCODE_MAX_PACKET_BYTES]>,
+    received: Vec<(usize, SocketAddr)>,
+    next: usize,
+}
+
+impl RecvBatch {
+    pub fn new() -> Self {
+        Self {
+            buffers: vec![[0; NETCODE_MAX_PACKET_BYTES]; BATCH_SIZE],
+            received: Vec::with_capacity(BATCH_SIZE),
+            next: 0,
+        }
+    }
+
+    /// Copies the next received datagram to the buffer, receiving a new batch when all were read.
+    pub fn recv_from(&mut self, socket: &UdpSocket, buffer: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        if self.next >= self.received.len() {
+            self.next = 0;
+            self.received.clear();
+            self.recv_batch(socket)?;
+        }
+
+        let Some(&(len, addr)) = self.received.get(self.next) else {
+            return Ok(None);
+        };
+        let index = self.next;
+        self.next += 1;
+
+        let len = len.min(buffer.len());
+        buffer[..len].copy_from_slice(&self.buffers[index][..len]);
+        Ok(Some((len, addr)))
+    }
+
+    fn recv_batch(&mut self, socket: &UdpSocket) -> io::Result<()> {
+        // SAFETY: zeroed sockaddr_storage and iovec are valid values
+        let mut addresses: [libc::sockaddr_storage; BATCH_SIZE] = unsafe { mem::zeroed() };
+        let mut iovecs: [libc::iovec; BATCH_SIZE] = unsafe { mem::zeroed() };
+        let mut headers: [libc::mmsghdr; BATCH_SIZE] = unsafe { mem::zeroed() };
+        for i in 0..BATCH_SIZE {
+            iovecs[i] = libc::iovec {
+                iov_base: self.buffers[i].as_mut_ptr().cast(),
+                iov_len: NETCODE_MAX_PACKET_BYTES,
+            };
+            headers[i].msg_hdr.msg_name = ptr::addr_of_mut!(addresses[i]).cast();
+            headers[i].msg_hdr.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+            headers[i].msg_hdr.msg_iov = ptr::addr_of_mut!(iovecs[i]);
+            headers[i].msg_hdr.msg_iovlen = 1;
+        }
+
+        loop {
+            // SAFETY: the headers point to buffers that live until the end of the call
+            let count = unsafe {
+                libc::recvmmsg(
+                    socket.as_raw_fd(),
+                    headers.as_mut_ptr(),
+                    BATCH_SIZE as _,
+                    libc::MSG_DONTWAIT,
+                    ptr::null_mut(),
+                )
+            };
+
+            if count < 0 {
+                let error = io::Error::last_os_error();
+                match error.kind() {
+                    io::ErrorKind::WouldBlock => return Ok(()),
+                    io::ErrorKind::Interrupted => continue,
+                    _ if is_datagram_error(&error) => continue,
+                    _ => return Err(error),
+                }
+            }
+
+            for i in 0..count as usize {
+                if let Some(addr) = to_socket_addr(&addresses[i]) {
+                    self.received.push((headers[i].msg_len as usize, addr));
+                }
+            }
+            return Ok(());
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct SendBatch {
+    buffers: Vec<[u8; NETCODE_MAX_PACKET_BYTES]>,
+    queued: Vec<(usize, SocketAddr)>,
+    failed: Vec<(SocketAddr, io::Error)>,
+}
+
+impl SendBatch {
+    pub fn new() -> Self {
+        Self {
+            buffers: vec![[0; NETCODE_MAX_PACKET_BYTES]; BATCH_SIZE],
+            queued: Vec::with_capacity(BATCH_SIZE),
+            failed: Vec::new(),
+        }
+    }
+
+    /// Returns the datagrams that failed to be sent since the last call, with their address.
+    pub fn take_failed(&mut self) -> Vec<(SocketAddr, io::Error)> {
+        mem::take(&mut self.failed)
+    }
+
+    /// Queues the datagram, sending the batch when it's full.
+    pub fn send_to(&mut self, socket: &UdpSocket, packet: &[u8], addr: SocketAddr) -> io::Result<()> {
+        if packet.len() > NETCODE_MAX_PACKET_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "packet is larger than the batch buffers",
+            ));
+        }
+
+        let index = self.queued.len();
+        self.buffers[index][..packet.len()].copy_from_slice(packet);
+        self.queued.push((packet.len(), addr));
+
+        if self.queued.len() == BATCH_SIZE {
+            self.flush(socket);
+        }
+
+        Ok(())
+    }
+
+    /// Sends all queued datagrams, the ones that fail are kept to be returned by [`SendBatch::take_failed`].
+    pub fn flush(&mut self, socket: &UdpSocket) {
+        let queued = self.queued.len();
+        if queued == 0 {
+            return;
+        }
+
+        // SAFETY: zeroed sockaddr_storage and iovec are valid values
+        let mut addresses: [libc::sockaddr_storage; BATCH_SIZE] = unsafe { mem::zeroed() };
+        let mut iovecs: [libc::iovec; BATCH_SIZE] = unsafe { mem::zeroed() };
+        let mut headers: [libc::mmsghdr; BATCH_SIZE] = unsafe { mem::zeroed() };
+        for (i, &(len, addr)) in self.queued.iter().enumerate() {
+            iovecs[i] = libc::iovec {
+                iov_base: self.buffers[i].as_mut_ptr().cast(),
+                iov_len: len,
+            };
+            headers[i].msg_hdr.msg_name = ptr::addr_of_mut!(addresses[i]).cast();
+            headers[i].msg_hdr.msg_namelen = write_sockaddr(addr, &mut addresses[i]);
+            headers[i].msg_hdr.msg_iov = ptr::addr_of_mut!(iovecs[i]);
+            headers[i].msg_hdr.msg_iovlen = 1;
+        }
+
+        let mut sent = 0;
+        while sent < queued {
+            // SAFETY: the headers point to buffers that live until the end of the call
+            let count = unsafe { libc::sendmmsg(socket.as_raw_fd(), headers[sent..].as_mut_ptr(), (queued - sent) as _, 0) };
+
+            if count < 0 {
+                let error = io::Error::last_os_error();
+                match error.kind() {
+                    // The send buffer is full, the remaining packets are dropped like they were lost in the network
+                    io::ErrorKind::WouldBlock => break,
+                    io::ErrorKind::Interrupted => continue,
+                    _ => {
+                        // Skip the packet that failed, the others can still be sent
+                        self.failed.push((self.queued[sent].1, error));
+                        sent += 1;
+                        continue;
+                    }
+                }
+            }
+            sent += count as usize;
+        }
+        self.queued.clear();
+    }
+}
+
+fn to_socket_addr(storage: &libc::sockaddr_storage) -> Option<SocketAddr> {
+    match storage.ss_family as libc::c_int {
+        libc::AF_INET => {
+            // SAFETY: the family says it's a sockaddr_in, sockaddr_storage is large enough and aligned for it
+            let addr = unsafe { &*(storage as *const libc::sockaddr_storage).cast::<libc::sockaddr_in>() };
+            let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+            Some(SocketAddrV4::new(ip, u16::from_be(addr.sin_port)).into())
+        }
+        libc::AF_INET6 => {
+            // SAFETY: the family says it's a sockaddr_in6, sockaddr_storage is large enough and aligned for it
+            let addr = unsafe { &*(storage as *const libc::sockaddr_storage).cast::<libc::sockaddr_in6>() };
+            let ip = Ipv6Addr::from(addr.sin6_addr.s6_addr);
+            Some(SocketAddrV6::new(ip, u16::from_be(addr.sin6_port), addr.sin6_flowinfo, addr.sin6_scope_id).into())
+        }
+        _ => None,
+    }
+}
+
+fn write_sockaddr(addr: SocketAddr, storage: &mut libc::sockaddr_storage) -> libc::socklen_t {
+    match addr {
+        SocketAddr::V4(addr) => {
+            // SAFETY: sockaddr_storage is large enough and aligned for a sockaddr_in
+            let sockaddr = unsafe { &mut *(storage as *mut libc::sockaddr_storage).cast::<libc::sockaddr_in>() };
+            sockaddr.sin_family = libc::AF_INET as libc::sa_family_t;
+            sockaddr.sin_port = addr.port().to_be();
+            sockaddr.sin_addr = libc::in_addr {
+                s_addr: u32::from(*addr.ip()).to_be(),
+            };
+            mem::size_of::<libc::sockaddr_in>() as libc::socklen_t
+        }
+        SocketAddr::V6(addr) => {
+            // SAFETY: sockaddr_storage is large enough and aligned for a sockaddr_in6
+            let sockaddr = unsafe { &mut *(storage as *mut libc::sockaddr_storage).cast::<libc::sockaddr_in6>() };
+            sockaddr.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sockaddr.sin6_port = addr.port().to_be();
+            sockaddr.sin6_flowinfo = addr.flowinfo();
+            sockaddr.sin6_addr = libc::in6_addr {
+                s6_addr: addr.ip().octets(),
+            };
+            sockaddr.sin6_scope_id = addr.scope_id();
+            mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn batched_send_and_receive() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_nonblocking(true).unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let receiver_addr = receiver.local_addr().unwrap();
+
+        // More packets than the batch size, the first batch is sent when it's full
+        let mut send_batch = SendBatch::new();
+        for i in 0..BATCH_SIZE + 8 {
+            send_batch.send_to(&sender, &[i as u8], receiver_addr).unwrap();
+        }
+        send_batch.flush(&sender);
+        assert!(send_batch.take_failed().is_empty());
+
+        let mut recv_batch = RecvBatch::new();
+        let mut buffer = [0u8; 16];
+        let mut received = vec![];
+        for _ in 0..100 {
+            while let Some((len, addr)) = recv_batch.recv_from(&receiver, &mut buffer).unwrap() {
+                assert_eq!(addr, sender.local_addr().unwrap());
+                received.push(buffer[..len].to_vec());
+            }
+            if received.len() == BATCH_SIZE + 8 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let expected: Vec<Vec<u8>> = (0..BATCH_SIZE + 8).map(|i| vec![i as u8]).collect();
+        assert_eq!(received, expected);
+    }
+
+    #[test]
+    fn failed_datagrams() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_nonblocking(true).unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let receiver_addr = receiver.local_addr().unwrap();
+        // An IPv4 socket can't send to an IPv6 address
+        let invalid_addr: SocketAddr = "[::1]:5000".parse().unwrap();
+
+        let mut send_batch = SendBatch::new();
+        send_batch.send_to(&sender, &[0], receiver_addr).unwrap();
+        send_batch.send_to(&sender, &[1], invalid_addr).unwrap();
+        send_batch.send_to(&sender, &[2], receiver_addr).unwrap();
+        send_batch.flush(&sender);
+
+        let failed = send_batch.take_failed();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, invalid_addr);
+        assert!(send_batch.take_failed().is_empty());
+
+        let mut recv_batch = RecvBatch::new();
+        let mut buffer = [0u8; 16];
+        let mut received = vec![];
+        for _ in 0..100 {
+            while let Some((len, _)) = recv_batch.recv_from(&receiver, &mut buffer).unwrap() {
+                received.push(buffer[..len].to_vec());
+            }
+            if received.len() == 2 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(received, vec![vec![0], vec![2]]);
+    }
+
+    #[test]
+    fn sockaddr_conversion() {
+        let addresses: [SocketAddr; 2] = ["127.0.0.1:5000".parse().unwrap(), "[::1]:5000".parse().unwrap()];
+        for addr in addresses {
+            // SAFETY: zeroed sockaddr_storage is a valid value
+            let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+            write_sockaddr(addr, &mut storage);
+            assert_eq!(to_socket_addr(&storage), Some(addr));
+        }
+    }
+}
@@ -1,14 +1,47 @@
 use std::{error::Error, fmt};
 
+mod bots;
 mod client;
+mod conditioner;
+mod discovery;
+mod memory;
+#[cfg(all(feature = "mmsg", target_os = "linux"))]
+mod mmsg;
+#[cfg(feature = "quic")]
+mod quic;
+#[cfg(all(feature = "reuseport", target_os = "linux"))]
+mod reuseport;
 mod server;
+mod socket;
+#[cfg(feature = "tokio")]
+mod tokio_socket;
+#[cfg(all(feature = "webrtc", target_arch = "wasm32"))]
+mod webrtc;
+#[cfg(feature = "websocket")]
+mod websocket;
 
+pub use bots::*;
 pub use client::*;
+pub use conditioner::*;
+pub use discovery::*;
+pub use memory::*;
+#[cfg(feature = "quic")]
+pub use quic::*;
+#[cfg(all(feature = "reuseport", target_os = "linux"))]
+pub use reuseport::*;
 pub use server::*;
+pub use socket::*;
+#[cfg(feature = "tokio")]
+pub use tokio_socket::*;
+#[cfg(all(feature = "webrtc", target_arch = "wasm32"))]
+pub use webrtc::*;
+#[cfg(feature = "websocket")]
+pub use websocket::*;
 
 pub use renetcode::{
-    generate_random_bytes, ClientAuthentication, ConnectToken, DisconnectReason as NetcodeDisconnectReason, NetcodeError,
-    ServerAuthentication, ServerConfig, TokenGenerationError, NETCODE_KEY_BYTES, NETCODE_USER_DATA_BYTES,
+    generate_random_bytes, ClientAuthentication, ConnectToken, ConnectTokenBuilder, ConnectionRequestStats,
+    DisconnectReason as NetcodeDisconnectReason, NetcodeError, ServerAuthentication, ServerConfig, TokenGenerationError, NETCODE_KEY_BYTES,
+    NETCODE_USER_DATA_BYTES,
 };
 
 #[derive(Debug)]
@@ -0,0 +1,446 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt, io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bytes::{Bytes, BytesMut};
+use quinn_proto::{
+    ClientConfig, Connection, ConnectionHandle, DatagramEvent, Endpoint, EndpointConfig, Event, SendDatagramError, ServerConfig,
+};
+
+use super::{is_datagram_error, TransportSocket};
+
+/// Re-exported so the QUIC configs can be created without depending on the same version.
+pub use quinn_proto;
+
+// Large enough for any netcode packet
+const MAX_DATAGRAM_BYTES: usize = 65535;
+
+struct QuicConnection {
+    connection: Connection,
+    // Address when the connection was established, kept after the peer migrates
+    addr: SocketAddr,
+    connected: bool,
+}
+
+// Drives a quinn endpoint with a non-blocking UDP socket.
+struct QuicEndpoint {
+    socket: UdpSocket,
+    endpoint: Endpoint,
+    connections: HashMap<ConnectionHandle, QuicConnection>,
+    received: VecDeque<(SocketAddr, Bytes)>,
+    recv_buffer: Vec<u8>,
+    send_buffer: Vec<u8>,
+}
+
+impl QuicEndpoint {
+    fn new(socket: UdpSocket, server_config: Option<ServerConfig>) -> io::Result<Self> {
+        socket.set_nonblocking(true)?;
+        let endpoint = Endpoint::new(Arc::new(EndpointConfig::default()), server_config.map(Arc::new), false, None);
+
+        Ok(Self {
+            socket,
+            endpoint,
+            connections: HashMap::new(),
+            received: VecDeque::new(),
+            recv_buffer: vec![0; MAX_DATAGRAM_BYTES],
+            send_buffer: Vec::with_capacity(MAX_DATAGRAM_BYTES),
+        })
+    }
+
+    fn update(&mut self, now: Instant) -> io::Result<()> {
+        loop {
+            let (len, addr) = match self.socket.recv_from(&mut self.recv_buffer) {
+                Ok(received) => received,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(ref e) if is_datagram_error(e) => continue,
+                Err(e) => return Err(e),
+            };
+
+            self.send_buffer.clear();
+            let data = BytesMut::from(&self.recv_buffer[..len]);
+            match self.endpoint.handle(now, addr, None, None, data, &mut self.send_buffer) {
+                Some(DatagramEvent::ConnectionEvent(handle, event)) => {
+                    if let Some(connection) = self.connections.get_mut(&handle) {
+                        connection.connection.handle_event(event);
+                    }
+                }
+                Some(DatagramEvent::NewConnection(incoming)) => {
+                    self.send_buffer.clear();
+                    match self.endpoint.accept(incoming, now, &mut self.send_buffer, None) {
+                        Ok((handle, connection)) => {
+                            let connection = QuicConnection {
+                                connection,
+                                addr,
+                                connected: false,
+                            };
+                            self.connections.insert(handle, connection);
+                        }
+                        Err(e) => {
+                            log::debug!("Failed to accept QUIC connection from {addr}: {}", e.cause);
+                            if let Some(transmit) = e.response {
+                                send_datagram(&self.socket, &self.send_buffer[..transmit.size], transmit.destination)?;
+                            }
+                        }
+                    }
+                }
+                Some(DatagramEvent::Response(transmit)) => {
+                    send_datagram(&self.socket, &self.send_buffer[..transmit.size], transmit.destination)?;
+                }
+                None => {}
+            }
+        }
+
+        let handles: Vec<ConnectionHandle> = self.connections.keys().copied().collect();
+        for handle in handles {
+            self.drive_connection(handle, now)?;
+        }
+
+        Ok(())
+    }
+
+    // Handles the timeouts and events of the connection, and sends its pending packets.
+    fn drive_connection(&mut self, handle: ConnectionHandle, now: Instant) -> io::Result<()> {
+        let Some(connection) = self.connections.get_mut(&handle) else {
+            return Ok(());
+        };
+
+        if connection.connection.poll_timeout().is_some_and(|timeout| timeout <= now) {
+            connection.connection.handle_timeout(now);
+        }
+
+        while let Some(event) = connection.connection.poll_endpoint_events() {
+            if let Some(event) = self.endpoint.handle_event(handle, event) {
+                connection.connection.handle_event(event);
+            }
+        }
+
+        while let Some(event) = connection.connection.poll() {
+            match event {
+                Event::Connected => connection.connected = true,
+                Event::ConnectionLost { reason } => log::debug!("QUIC connection with {} lost: {reason}", connection.addr),
+                Event::DatagramReceived => {
+                    while let Some(datagram) = connection.connection.datagrams().recv() {
+                        self.received.push_back((connection.addr, datagram));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        loop {
+            self.send_buffer.clear();
+            let Some(transmit) = connection.connection.poll_transmit(now, 1, &mut self.send_buffer) else {
+                break;
+            };
+            send_datagram(&self.socket, &self.send_buffer[..transmit.size], transmit.destination)?;
+        }
+
+        if connection.connection.is_drained() {
+            self.connections.remove(&handle);
+        }
+
+        Ok(())
+    }
+
+    fn send(&mut self, handle: ConnectionHandle, packet: &[u8]) -> io::Result<()> {
+        let Some(connection) = self.connections.get_mut(&handle) else {
+            return Ok(());
+        };
+        // Like UDP, packets sent before the connection is established are dropped
+        if !connection.connected {
+            return Ok(());
+        }
+
+        match connection.connection.datagrams().send(Bytes::copy_from_slice(packet), true) {
+            Ok(()) => {}
+            Err(SendDatagramError::TooLarge) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "packet is larger than the QUIC datagram limit",
+                ));
+            }
+            Err(e) => return Err(io::Error::other(e)),
+        }
+
+        // Send it right away instead of waiting for the next update
+        self.drive_connection(handle, Instant::now())
+    }
+
+    fn recv_from(&mut self, buffer: &mut [u8]) -> Option<(usize, SocketAddr)> {
+        let (addr, packet) = self.received.pop_front()?;
+
+        let len = packet.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&packet[..len]);
+        Some((len, addr))
+    }
+}
+
+fn send_datagram(socket: &UdpSocket, packet: &[u8], addr: SocketAddr) -> io::Result<()> {
+    loop {
+        match socket.send_to(packet, addr) {
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            // QUIC recovers from the lost packet, and times out the connection if the peer is gone
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(ref e) if is_datagram_error(e) => {
+                log::debug!("Failed to send QUIC datagram to {addr}: {e}");
+                return Ok(());
+            }
+            result => return result.map(|_| ()),
+        }
+    }
+}
+
+/// Server socket that receives packets as QUIC unreliable datagrams.
+///
+/// QUIC encrypts the packets and keeps the connection when the client address changes,
+/// the client is still reported with the address it connected from.
+/// Netcode packets can have up to 1400 bytes, the transport config should have an
+/// [`initial_mtu`](quinn_proto::TransportConfig::initial_mtu) of 1452 or more.
+pub struct QuicServerSocket {
+    endpoint: QuicEndpoint,
+    addresses: HashMap<SocketAddr, ConnectionHandle>,
+}
+
+impl fmt::Debug for QuicServerSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuicServerSocket")
+            .field("socket", &self.endpoint.socket)
+            .field("connections", &self.endpoint.connections.len())
+            .finish()
+    }
+}
+
+impl QuicServerSocket {
+    /// Listens for QUIC connections in the address.
+    pub fn bind(addr: impl ToSocketAddrs, server_config: ServerConfig) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+
+        Ok(Self {
+            endpoint: QuicEndpoint::new(socket, Some(server_config))?,
+            addresses: HashMap::new(),
+        })
+    }
+
+    /// Returns the number of QUIC connections, including the ones still handshaking.
+    pub fn connections(&self) -> usize {
+        self.endpoint.connections.len()
+    }
+}
+
+impl TransportSocket for QuicServerSocket {
+    fn addr(&self) -> io::Result<SocketAddr> {
+        self.endpoint.socket.local_addr()
+    }
+
+    fn update(&mut self, _duration: Duration) -> io::Result<()> {
+        self.endpoint.update(Instant::now())?;
+
+        let connections = &self.endpoint.connections;
+        for (handle, connection) in connections.iter() {
+            self.addresses.insert(connection.addr, *handle);
+        }
+        self.addresses
+            .retain(|addr, handle| connections.get(handle).is_some_and(|c| c.addr == *addr));
+
+        Ok(())
+    }
+
+    fn recv_from(&mut self, buffer: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        Ok(self.endpoint.recv_from(buffer))
+    }
+
+    fn send_to(&mut self, packet: &[u8], addr: SocketAddr) -> io::Result<()> {
+        let Some(handle) = self.addresses.get(&addr) else {
+            return Ok(());
+        };
+
+        self.endpoint.send(*handle, packet)
+    }
+}
+
+/// Client socket that sends packets as QUIC unreliable datagrams, see [`QuicServerSocket`].
+pub struct QuicClientSocket {
+    endpoint: QuicEndpoint,
+    handle: ConnectionHandle,
+    server_addr: SocketAddr,
+}
+
+impl fmt::Debug for QuicClientSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuicClientSocket")
+            .field("socket", &self.endpoint.socket)
+            .field("server_addr", &self.server_addr)
+            .field("connected", &self.is_connected())
+            .finish()
+    }
+}
+
+impl QuicClientSocket {
+    /// Starts a QUIC connection with the server, the handshake is done in the next updates.
+    /// The server name is used to validate the server certificate.
+    pub fn connect(server_addr: SocketAddr, server_name: &str, client_config: ClientConfig) -> io::Result<Self> {
+        let socket = match server_addr {
+            SocketAddr::V4(_) => UdpSocket::bind("0.0.0.0:0")?,
+            SocketAddr::V6(_) => UdpSocket::bind("[::]:0")?,
+        };
+        let mut endpoint = QuicEndpoint::new(socket, None)?;
+
+        let now = Instant::now();
+        let (handle, connection) = endpoint
+            .endpoint
+            .connect(now, client_config, server_addr, server_name)
+            .map_err(io::Error::other)?;
+        let connection = QuicConnection {
+            connection,
+            addr: server_addr,
+            connected: false,
+        };
+        endpoint.connections.insert(handle, connection);
+        endpoint.drive_connection(handle, now)?;
+
+        Ok(Self {
+            endpoint,
+            handle,
+            server_addr,
+        })
+    }
+
+    /// Returns whether the QUIC handshake has finished.
+    pub fn is_connected(&self) -> bool {
+        self.endpoint.connections.get(&self.handle).is_some_and(|c| c.connected)
+    }
+}
+
+impl TransportSocket for QuicClientSocket {
+    fn addr(&self) -> io::Result<SocketAddr> {
+        self.endpoint.socket.local_addr()
+    }
+
+    fn update(&mut self, _duration: Duration) -> io::Result<()> {
+        self.endpoint.update(Instant::now())?;
+
+        if !self.endpoint.connections.contains_key(&self.handle) {
+            return Err(io::Error::new(io::ErrorKind::NotConnected, "quic connection closed"));
+        }
+
+        Ok(())
+    }
+
+    fn recv_from(&mut self, buffer: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        Ok(self.endpoint.recv_from(buffer))
+    }
+
+    fn send_to(&mut self, packet: &[u8], addr: SocketAddr) -> io::Result<()> {
+        if addr != self.server_addr {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "quic can only send to the server address",
+            ));
+        }
+
+        self.endpoint.send(self.handle, packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quinn_proto::{
+        rustls::{
+            pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer},
+            RootCertStore,
+        },
+        TransportConfig,
+    };
+
+    use super::*;
+    use crate::transport::{ClientAuthentication, NetcodeClientTransport, NetcodeServerTransport, ServerAuthentication, ServerConfig};
+    use crate::{ConnectionConfig, DefaultChannel, RenetClient, RenetServer};
+
+    #[test]
+    fn skip_datagram_errors() {
+        // Nothing listens in this address, so the ICMP errors are reported by the connected socket
+        let closed_addr = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(closed_addr).unwrap();
+
+        let mut endpoint = QuicEndpoint::new(socket, None).unwrap();
+        for _ in 0..3 {
+            send_datagram(&endpoint.socket, &[0; 10], closed_addr).unwrap();
+            std::thread::sleep(Duration::from_millis(10));
+            endpoint.update(Instant::now()).unwrap();
+        }
+    }
+
+    #[test]
+    fn netcode_over_quic() {
+        let certificate = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(certificate.signing_key.serialize_der()));
+        let mut transport_config = TransportConfig::default();
+        transport_config.initial_mtu(1500);
+        let transport_config = Arc::new(transport_config);
+
+        let mut quic_server_config = quinn_proto::ServerConfig::with_single_cert(vec![certificate.cert.der().clone()], key).unwrap();
+        quic_server_config.transport_config(transport_config.clone());
+        let mut roots = RootCertStore::empty();
+        roots.add(certificate.cert.der().clone()).unwrap();
+        let mut quic_client_config = ClientConfig::with_root_certificates(Arc::new(roots)).unwrap();
+        quic_client_config.transport_config(transport_config);
+
+        let server_socket = QuicServerSocket::bind("127.0.0.1:0", quic_server_config).unwrap();
+        let server_addr = server_socket.addr().unwrap();
+        let server_config = ServerConfig {
+            current_time: Duration::ZERO,
+            max_clients: 4,
+            protocol_id: 0,
+            public_addresses: vec![server_addr],
+            authentication: ServerAuthentication::Unsecure,
+        };
+        let mut server_transport = NetcodeServerTransport::new_with_transport_socket(server_config, server_socket).unwrap();
+        let mut server = RenetServer::new(ConnectionConfig::default());
+
+        let authentication = ClientAuthentication::Unsecure {
+            protocol_id: 0,
+            client_id: 7,
+            server_addr,
+            user_data: None,
+        };
+        let client_socket = QuicClientSocket::connect(server_addr, "localhost", quic_client_config).unwrap();
+        let mut client_transport =
+            NetcodeClientTransport::new_with_transport_socket(Duration::ZERO, authentication, client_socket).unwrap();
+        let mut client = RenetClient::new(ConnectionConfig::default());
+
+        let delta = Duration::from_millis(10);
+        let mut received = None;
+        for _ in 0..500 {
+            client.update(delta);
+            client_transport.update(delta, &mut client).unwrap();
+            if client.is_connected() {
+                client.send_message(DefaultChannel::ReliableOrdered, "hello");
+            }
+            client_transport.send_packets(&mut client).unwrap();
+
+            server.update(delta);
+            server_transport.update(delta, &mut server).unwrap();
+            for client_id in server.clients_id() {
+                if let Some(message) = server.receive_message(client_id, DefaultChannel::ReliableOrdered) {
+                    received = Some((client_id, message));
+                }
+            }
+            server_transport.send_packets(&mut server);
+
+            if received.is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let (client_id, message) = received.unwrap();
+        assert_eq!(client_id.raw(), 7);
+        assert_eq!(message, "hello");
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+/// Binds multiple UDP sockets to the same address with `SO_REUSEPORT`.
+///
+/// The kernel distributes the packets between the sockets by hashing the address of the sender,
+/// so the packets of a client always arrive in the same socket. Each socket can be processed by a
+/// different thread with its own [`RenetServer`](crate::RenetServer) and
+/// [`NetcodeServerTransport`](super::NetcodeServerTransport), scaling the server across cores.
+///
+/// All servers should use the same protocol id and private key, so any of them accepts the connect tokens.
+/// The number of sockets must not change while clients are connected, or the clients are redistributed.
+/// Connection migration doesn't work between sockets, since a client with a new address can arrive in another socket.
+///
+/// # Usage
+/// ```no_run
+/// # use renet::{ConnectionConfig, RenetServer};
+/// # use renet::transport::{bind_reuse_port_sockets, NetcodeServerTransport, ServerConfig};
+/// # fn server_config() -> ServerConfig { unimplemented!() }
+/// let sockets = bind_reuse_port_sockets("0.0.0.0:5000".parse().unwrap(), 4).unwrap();
+/// for socket in sockets {
+///     std::thread::spawn(move || {
+///         let mut transport = NetcodeServerTransport::new(server_config(), socket).unwrap();
+///         let mut server = RenetServer::new(ConnectionConfig::default());
+///         // Run the server loop
+///     });
+/// }
+/// ```
+pub fn bind_reuse_port_sockets(addr: SocketAddr, count: usize) -> io::Result<Vec<UdpSocket>> {
+    if count == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "at least one socket is required"));
+    }
+
+    let mut addr = addr;
+    let mut sockets = Vec::with_capacity(count);
+    for _ in 0..count {
+        let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_port(true)?;
+        socket.bind(&addr.into())?;
+        let socket: UdpSocket = socket.into();
+
+        // When binding to port 0, the other sockets use the port chosen for the first one
+        addr = socket.local_addr()?;
+        sockets.push(socket);
+    }
+
+    Ok(sockets)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn reuse_port() {
+        let sockets = bind_reuse_port_sockets("127.0.0.1:0".parse().unwrap(), 4).unwrap();
+        let addr = sockets[0].local_addr().unwrap();
+        assert!(sockets.iter().all(|socket| socket.local_addr().unwrap() == addr));
+
+        let clients: Vec<UdpSocket> = (0..16).map(|_| UdpSocket::bind("127.0.0.1:0").unwrap()).collect();
+        for client in clients.iter() {
+            client.send_to(&[1], addr).unwrap();
+        }
+
+        let mut received = 0;
+        let mut buffer = [0u8; 16];
+        for socket in sockets.iter() {
+            socket.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+            while socket.recv_from(&mut buffer).is_ok() {
+                received += 1;
+            }
+        }
+        assert_eq!(received, clients.len());
+    }
+}
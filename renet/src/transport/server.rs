@@ -1,34 +1,83 @@
 use std::{
+    collections::VecDeque,
     io,
-    net::{SocketAddr, UdpSocket},
+    net::{IpAddr, SocketAddr, UdpSocket},
     time::Duration,
 };
 
-use renetcode::{NetcodeServer, ServerConfig, ServerResult, NETCODE_MAX_PACKET_BYTES, NETCODE_USER_DATA_BYTES};
+use bytes::Bytes;
+use renetcode::{ConnectionRequestStats, NetcodeServer, ServerConfig, ServerResult, NETCODE_MAX_PACKET_BYTES, NETCODE_USER_DATA_BYTES};
 
-use crate::ClientId;
-use crate::RenetServer;
+use crate::{ClientId, DisconnectReason, Handshake, RenetServer};
 
-use super::NetcodeTransportError;
+use super::{is_datagram_error, NetcodeTransportError, TransportSocket, UdpTransport};
+
+// Oldest errors are discarded when they are not read
+const MAX_CLIENT_ERRORS: usize = 256;
+
+/// Socket error while sending packets to a client.
+///
+/// The client isn't disconnected, usually it stopped responding and will time out.
+#[derive(Debug)]
+pub struct ClientSocketError {
+    pub client_id: ClientId,
+    pub error: io::Error,
+}
 
 #[derive(Debug)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
 pub struct NetcodeServerTransport {
-    socket: UdpSocket,
+    sockets: Vec<ServerSocket>,
     netcode_server: NetcodeServer,
     buffer: [u8; NETCODE_MAX_PACKET_BYTES],
+    client_errors: VecDeque<ClientSocketError>,
+    rejected_clients: Vec<(ClientId, DisconnectReason)>,
 }
 
 impl NetcodeServerTransport {
     pub fn new(server_config: ServerConfig, socket: UdpSocket) -> Result<Self, std::io::Error> {
-        socket.set_nonblocking(true)?;
+        Self::new_with_sockets(server_config, vec![socket])
+    }
+
+    /// Creates a transport that sends and receive packets with a custom socket.
+    pub fn new_with_transport_socket(server_config: ServerConfig, socket: impl TransportSocket) -> Result<Self, std::io::Error> {
+        Self::from_sockets(server_config, vec![Box::new(socket)])
+    }
+
+    /// Creates a transport listening in multiple sockets, for example an IPv4 and an IPv6 socket,
+    /// so clients from both address families can connect to the same server.
+    /// Packets are sent from the first socket with the same address family as the client.
+    /// The server config should contain the public addresses of all the sockets.
+    pub fn new_with_sockets(server_config: ServerConfig, sockets: Vec<UdpSocket>) -> Result<Self, std::io::Error> {
+        if sockets.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "at least one socket is required"));
+        }
+
+        let mut transport_sockets: Vec<Box<dyn TransportSocket>> = Vec::with_capacity(sockets.len());
+        for socket in sockets {
+            transport_sockets.push(Box::new(UdpTransport::from_socket(socket)?));
+        }
+
+        Self::from_sockets(server_config, transport_sockets)
+    }
+
+    fn from_sockets(server_config: ServerConfig, sockets: Vec<Box<dyn TransportSocket>>) -> Result<Self, std::io::Error> {
+        let sockets = sockets
+            .into_iter()
+            .map(|socket| {
+                let is_ipv4 = socket.addr()?.is_ipv4();
+                Ok(ServerSocket { socket, is_ipv4 })
+            })
+            .collect::<Result<Vec<_>, io::Error>>()?;
 
         let netcode_server = NetcodeServer::new(server_config);
 
         Ok(Self {
-            socket,
+            sockets,
             netcode_server,
             buffer: [0; NETCODE_MAX_PACKET_BYTES],
+            client_errors: VecDeque::new(),
+            rejected_clients: Vec::new(),
         })
     }
 
@@ -47,6 +96,11 @@ impl NetcodeServerTransport {
         self.netcode_server.connected_clients()
     }
 
+    /// Returns how many clients can still connect.
+    pub fn available_slots(&self) -> usize {
+        self.netcode_server.available_slots()
+    }
+
     /// Returns the user data for client if connected.
     pub fn user_data(&self, client_id: ClientId) -> Option<[u8; NETCODE_USER_DATA_BYTES]> {
         self.netcode_server.user_data(client_id.raw())
@@ -57,14 +111,71 @@ impl NetcodeServerTransport {
         self.netcode_server.client_addr(client_id.raw())
     }
 
+    /// Enables connected clients to keep their connection when their address changes.
+    /// See [NetcodeServer::set_connection_migration][renetcode::NetcodeServer::set_connection_migration].
+    pub fn set_connection_migration(&mut self, enabled: bool) {
+        self.netcode_server.set_connection_migration(enabled);
+    }
+
+    /// Bans a client id, its connection requests are ignored until the ban expires.
+    /// The ban is permanent if no duration is given. Connected clients are not disconnected.
+    pub fn ban_client(&mut self, client_id: ClientId, duration: Option<Duration>) {
+        self.netcode_server.ban_client(client_id.raw(), duration);
+    }
+
+    /// Removes the ban from a client id.
+    pub fn unban_client(&mut self, client_id: ClientId) {
+        self.netcode_server.unban_client(client_id.raw());
+    }
+
+    /// Bans an ip address, its connection requests are ignored until the ban expires.
+    /// The ban is permanent if no duration is given. Connected clients are not disconnected.
+    pub fn ban_ip(&mut self, ip: IpAddr, duration: Option<Duration>) {
+        self.netcode_server.ban_ip(ip, duration);
+    }
+
+    /// Removes the ban from an ip address.
+    pub fn unban_ip(&mut self, ip: IpAddr) {
+        self.netcode_server.unban_ip(ip);
+    }
+
+    /// Sets the client ids allowed to connect, use `None` to allow all clients.
+    pub fn set_allow_list(&mut self, allow_list: Option<Vec<ClientId>>) {
+        let allow_list = allow_list.map(|ids| ids.iter().map(|id| id.raw()).collect());
+        self.netcode_server.set_allow_list(allow_list);
+    }
+
+    /// Sets how many packets each ip address that is not a connected client can send per second, and how many at once.
+    /// See [NetcodeServer::set_packet_rate_limit][renetcode::NetcodeServer::set_packet_rate_limit].
+    pub fn set_packet_rate_limit(&mut self, packets_per_second: u32, burst: u32) {
+        self.netcode_server.set_packet_rate_limit(packets_per_second, burst);
+    }
+
+    /// Returns the number of packets dropped by the packet rate limit.
+    pub fn rate_limited_packets(&self) -> u64 {
+        self.netcode_server.rate_limited_packets()
+    }
+
+    /// Sets the maximum ratio between the size of a packet sent to an address not connected yet and the packet it answers.
+    /// See [NetcodeServer::set_amplification_limit][renetcode::NetcodeServer::set_amplification_limit].
+    pub fn set_amplification_limit(&mut self, ratio: f64) {
+        self.netcode_server.set_amplification_limit(ratio);
+    }
+
+    /// Returns the counters of the connection requests received, useful to detect handshake floods.
+    pub fn connection_request_stats(&self) -> ConnectionRequestStats {
+        self.netcode_server.connection_request_stats()
+    }
+
     /// Disconnects all connected clients.
     /// This sends the disconnect packet instantly, use this when closing/exiting games,
     /// should use [RenetServer::disconnect_all][crate::RenetServer::disconnect_all] otherwise.
     pub fn disconnect_all(&mut self, server: &mut RenetServer) {
         for client_id in self.netcode_server.clients_id() {
             let server_result = self.netcode_server.disconnect(client_id);
-            handle_server_result(server_result, &self.socket, server);
+            handle_server_result(server_result, &mut self.sockets, server, &mut self.rejected_clients);
         }
+        self.flush_sockets();
     }
 
     /// Returns the duration since the connected client last received a packet.
@@ -77,59 +188,146 @@ impl NetcodeServerTransport {
     pub fn update(&mut self, duration: Duration, server: &mut RenetServer) -> Result<(), NetcodeTransportError> {
         self.netcode_server.update(duration);
 
-        loop {
-            match self.socket.recv_from(&mut self.buffer) {
-                Ok((len, addr)) => {
-                    let server_result = self.netcode_server.process_packet(addr, &mut self.buffer[..len]);
-                    handle_server_result(server_result, &self.socket, server);
-                }
-                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
-                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => break,
-                Err(ref e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
-                Err(e) => return Err(e.into()),
-            };
+        for i in 0..self.sockets.len() {
+            self.sockets[i].socket.update(duration)?;
+            loop {
+                match self.sockets[i].socket.recv_from(&mut self.buffer) {
+                    Ok(Some((len, addr))) => {
+                        let server_result = self.netcode_server.process_packet(addr, &mut self.buffer[..len]);
+                        handle_server_result(server_result, &mut self.sockets, server, &mut self.rejected_clients);
+                    }
+                    Ok(None) => break,
+                    // Received errors don't say which client caused them, the client times out if it's gone
+                    Err(ref e) if is_datagram_error(e) => continue,
+                    Err(e) => return Err(e.into()),
+                };
+            }
+        }
+
+        // The rejected clients are connected in the netcode server, send them the reason before disconnecting them
+        for (client_id, reason) in std::mem::take(&mut self.rejected_clients) {
+            for packet in server.rejection_packets(client_id, reason) {
+                self.send_last_packet(client_id, &packet);
+            }
+            let server_result = self.netcode_server.disconnect(client_id.raw());
+            handle_server_result(server_result, &mut self.sockets, server, &mut self.rejected_clients);
         }
 
         for client_id in self.netcode_server.clients_id() {
             let server_result = self.netcode_server.update_client(client_id);
-            handle_server_result(server_result, &self.socket, server);
+            handle_server_result(server_result, &mut self.sockets, server, &mut self.rejected_clients);
         }
 
         for disconnection_id in server.disconnections_id() {
+            // Send the last packets, so the client knows why it was disconnected
+            for packet in server.get_packets_to_send(disconnection_id).unwrap() {
+                self.send_last_packet(disconnection_id, &packet);
+            }
+
             let server_result = self.netcode_server.disconnect(disconnection_id.raw());
-            handle_server_result(server_result, &self.socket, server);
+            handle_server_result(server_result, &mut self.sockets, server, &mut self.rejected_clients);
         }
+        self.flush_sockets();
 
         Ok(())
     }
 
+    /// Returns the next socket error that happened while sending packets to a client.
+    /// Only the last 256 errors are kept.
+    pub fn get_client_error(&mut self) -> Option<ClientSocketError> {
+        self.client_errors.pop_front()
+    }
+
     /// Send packets to connected clients.
     pub fn send_packets(&mut self, server: &mut RenetServer) {
-        'clients: for client_id in server.clients_id() {
+        for client_id in server.clients_id() {
             let packets = server.get_packets_to_send(client_id).unwrap();
-            for packet in packets {
-                match self.netcode_server.generate_payload_packet(client_id.raw(), &packet) {
+            for packet in packets.iter() {
+                match self.netcode_server.generate_payload_packet(client_id.raw(), packet) {
                     Ok((addr, payload)) => {
-                        if let Err(e) = self.socket.send_to(payload, addr) {
-                            log::error!("Failed to send packet to client {client_id} ({addr}): {e}");
-                            continue 'clients;
+                        if let Err(error) = socket_for(&mut self.sockets, addr).send_to(payload, addr) {
+                            push_client_error(&mut self.client_errors, client_id, addr, error);
+                            break;
                         }
                     }
                     Err(e) => {
                         log::error!("Failed to encrypt payload packet for client {client_id}: {e}");
-                        continue 'clients;
+                        break;
                     }
                 }
             }
+            server.recycle_payloads(client_id, packets);
         }
+        self.flush_sockets();
     }
-}
 
-fn handle_server_result(server_result: ServerResult, socket: &UdpSocket, reliable_server: &mut RenetServer) {
-    let send_packet = |packet: &[u8], addr: SocketAddr| {
-        if let Err(err) = socket.send_to(packet, addr) {
-            log::error!("Failed to send packet to {addr}: {err}");
+    // Sends a packet to a client that is about to be disconnected, errors are only logged
+    fn send_last_packet(&mut self, client_id: ClientId, packet: &[u8]) {
+        match self.netcode_server.generate_payload_packet(client_id.raw(), packet) {
+            Ok((addr, payload)) => {
+                if let Err(e) = socket_for(&mut self.sockets, addr).send_to(payload, addr) {
+                    log::error!("Failed to send packet to client {client_id} ({addr}): {e}");
+                }
+            }
+            Err(e) => log::error!("Failed to encrypt payload packet for client {client_id}: {e}"),
+        }
+    }
+
+    fn flush_sockets(&mut self) {
+        for socket in self.sockets.iter_mut() {
+            if let Err(e) = socket.socket.flush() {
+                log::error!("Failed to send packets: {e}");
+            }
+
+            // Buffered packets fail after they were queued, so they are matched to the client by address
+            for (addr, error) in socket.socket.take_send_errors() {
+                let client_id = self
+                    .netcode_server
+                    .clients_id_iter()
+                    .find(|&client_id| self.netcode_server.client_addr(client_id) == Some(addr));
+                match client_id {
+                    Some(client_id) => push_client_error(&mut self.client_errors, ClientId::from_raw(client_id), addr, error),
+                    None => log::debug!("Failed to send packet to {addr}: {error}"),
+                }
+            }
         }
+    }
+}
+
+fn push_client_error(client_errors: &mut VecDeque<ClientSocketError>, client_id: ClientId, addr: SocketAddr, error: io::Error) {
+    if is_datagram_error(&error) {
+        log::debug!("Failed to send packet to client {client_id} ({addr}): {error}");
+    } else {
+        log::error!("Failed to send packet to client {client_id} ({addr}): {error}");
+    }
+    if client_errors.len() >= MAX_CLIENT_ERRORS {
+        client_errors.pop_front();
+    }
+    client_errors.push_back(ClientSocketError { client_id, error });
+}
+
+#[derive(Debug)]
+struct ServerSocket {
+    socket: Box<dyn TransportSocket>,
+    is_ipv4: bool,
+}
+
+// IPv4 clients of dual-stack sockets have IPv4-mapped IPv6 addresses, so they use the IPv6 socket.
+fn socket_for(sockets: &mut [ServerSocket], addr: SocketAddr) -> &mut dyn TransportSocket {
+    let index = sockets.iter().position(|s| s.is_ipv4 == addr.is_ipv4()).unwrap_or(0);
+    sockets[index].socket.as_mut()
+}
+
+fn handle_server_result(
+    server_result: ServerResult,
+    sockets: &mut [ServerSocket],
+    reliable_server: &mut RenetServer,
+    rejected_clients: &mut Vec<(ClientId, DisconnectReason)>,
+) {
+    let mut send_packet = |packet: &[u8], addr: SocketAddr| match socket_for(sockets, addr).send_to(packet, addr) {
+        Err(ref e) if is_datagram_error(e) => log::debug!("Failed to send packet to {addr}: {e}"),
+        Err(e) => log::error!("Failed to send packet to {addr}: {e}"),
+        Ok(()) => {}
     };
 
     match server_result {
@@ -145,11 +343,24 @@ fn handle_server_result(server_result: ServerResult, socket: &UdpSocket, reliabl
         }
         ServerResult::ClientConnected {
             client_id,
-            user_data: _,
+            user_data,
+            request_data,
             addr,
             payload,
         } => {
-            reliable_server.add_connection(ClientId::from_raw(client_id));
+            let client_id = ClientId::from_raw(client_id);
+            let user_data = Bytes::copy_from_slice(&user_data[..]);
+            match request_data {
+                // Clients without handshake are checked when their features packet is received
+                None => reliable_server.add_connection_with_user_data(client_id, user_data),
+                Some(request_data) => {
+                    let handshake = Handshake::from_bytes(&request_data);
+                    if let Err(reason) = reliable_server.add_connection_with_handshake(client_id, user_data, &handshake) {
+                        log::debug!("Rejected connection of client {client_id}: {reason}");
+                        rejected_clients.push((client_id, reason));
+                    }
+                }
+            }
             send_packet(payload, addr);
         }
         ServerResult::ClientDisconnected { client_id, addr, payload } => {
@@ -0,0 +1,233 @@
+use std::{
+    fmt, io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::Duration,
+};
+
+#[cfg(all(feature = "mmsg", target_os = "linux"))]
+use super::mmsg::{RecvBatch, SendBatch};
+
+/// Datagram socket used by the netcode transports to send and receive packets.
+///
+/// Implemented by [`UdpTransport`], custom implementations allow the transports to run over
+/// other kinds of networks, or to wrap another socket.
+pub trait TransportSocket: fmt::Debug + Send + Sync + 'static {
+    /// Returns the local address of the socket.
+    fn addr(&self) -> io::Result<SocketAddr>;
+
+    /// Advances the socket by the duration, called by the transport before receiving packets.
+    fn update(&mut self, _duration: Duration) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Receives a packet, returns `None` when there are no more packets available.
+    /// Must not block.
+    fn recv_from(&mut self, buffer: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>>;
+
+    /// Sends a packet to the address.
+    fn send_to(&mut self, packet: &[u8], addr: SocketAddr) -> io::Result<()>;
+
+    /// Sends the packets buffered by the socket, called by the transport after sending packets.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Returns the buffered packets that failed to be sent since the last call, with the address they were sent to.
+    /// Called by the transport after [`flush`](TransportSocket::flush), sockets that don't buffer packets
+    /// return their errors from [`send_to`](TransportSocket::send_to) instead.
+    fn take_send_errors(&mut self) -> Vec<(SocketAddr, io::Error)> {
+        Vec::new()
+    }
+}
+
+/// Returns whether the error only affected a single datagram, the socket can still be used.
+///
+/// These errors are usually caused by ICMP messages from a peer that is no longer listening,
+/// the transports skip them instead of failing.
+pub fn is_datagram_error(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::HostUnreachable
+            | io::ErrorKind::NetworkUnreachable
+    )
+}
+
+/// Non-blocking UDP socket, used by the transports created with an [`UdpSocket`].
+///
+/// With the `mmsg` feature on Linux, packets are received and sent in batches with `recvmmsg`/`sendmmsg`,
+/// reducing the number of syscalls for servers with many clients.
+/// Sent packets are buffered until [`flush`](TransportSocket::flush), and the packets that failed are then
+/// reported by [`take_send_errors`](TransportSocket::take_send_errors).
+#[derive(Debug)]
+pub struct UdpTransport {
+    socket: UdpSocket,
+    #[cfg(all(feature = "mmsg", target_os = "linux"))]
+    recv_batch: RecvBatch,
+    #[cfg(all(feature = "mmsg", target_os = "linux"))]
+    send_batch: SendBatch,
+}
+
+impl UdpTransport {
+    /// Binds a new socket to the address.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Self::from_socket(UdpSocket::bind(addr)?)
+    }
+
+    /// Uses an already bound socket, it's changed to non-blocking mode.
+    pub fn from_socket(socket: UdpSocket) -> io::Result<Self> {
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            #[cfg(all(feature = "mmsg", target_os = "linux"))]
+            recv_batch: RecvBatch::new(),
+            #[cfg(all(feature = "mmsg", target_os = "linux"))]
+            send_batch: SendBatch::new(),
+        })
+    }
+
+    /// Returns the underlying socket.
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+}
+
+impl TransportSocket for UdpTransport {
+    fn addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    #[cfg(all(feature = "mmsg", target_os = "linux"))]
+    fn recv_from(&mut self, buffer: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        self.recv_batch.recv_from(&self.socket, buffer)
+    }
+
+    #[cfg(all(feature = "mmsg", target_os = "linux"))]
+    fn send_to(&mut self, packet: &[u8], addr: SocketAddr) -> io::Result<()> {
+        self.send_batch.send_to(&self.socket, packet, addr)
+    }
+
+    #[cfg(all(feature = "mmsg", target_os = "linux"))]
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_batch.flush(&self.socket);
+        Ok(())
+    }
+
+    #[cfg(all(feature = "mmsg", target_os = "linux"))]
+    fn take_send_errors(&mut self) -> Vec<(SocketAddr, io::Error)> {
+        self.send_batch.take_failed()
+    }
+
+    #[cfg(not(all(feature = "mmsg", target_os = "linux")))]
+    fn recv_from(&mut self, buffer: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        loop {
+            match self.socket.recv_from(buffer) {
+                Ok((len, addr)) => return Ok(Some((len, addr))),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    #[cfg(not(all(feature = "mmsg", target_os = "linux")))]
+    fn send_to(&mut self, packet: &[u8], addr: SocketAddr) -> io::Result<()> {
+        loop {
+            match self.socket.send_to(packet, addr) {
+                Ok(_) => return Ok(()),
+                // The send buffer is full, the packet is dropped like it was lost in the network
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{ClientAuthentication, NetcodeClientTransport, NetcodeServerTransport, ServerAuthentication, ServerConfig};
+    use crate::{ConnectionConfig, RenetClient, RenetServer};
+
+    // Reports an error like the ICMP port unreachable from a peer that is no longer listening
+    #[derive(Debug)]
+    struct RefusedSocket {
+        pending_error: bool,
+    }
+
+    impl TransportSocket for RefusedSocket {
+        fn addr(&self) -> io::Result<SocketAddr> {
+            Ok("127.0.0.1:5000".parse().unwrap())
+        }
+
+        fn recv_from(&mut self, _buffer: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+            if std::mem::take(&mut self.pending_error) {
+                return Err(io::ErrorKind::ConnectionRefused.into());
+            }
+            Ok(None)
+        }
+
+        fn send_to(&mut self, _packet: &[u8], _addr: SocketAddr) -> io::Result<()> {
+            Err(io::ErrorKind::ConnectionRefused.into())
+        }
+    }
+
+    #[test]
+    fn datagram_errors_are_not_fatal() {
+        assert!(is_datagram_error(&io::ErrorKind::ConnectionReset.into()));
+        assert!(!is_datagram_error(&io::ErrorKind::PermissionDenied.into()));
+
+        let authentication = ClientAuthentication::Unsecure {
+            protocol_id: 0,
+            client_id: 0,
+            server_addr: "127.0.0.1:5000".parse().unwrap(),
+            user_data: None,
+        };
+        let mut client_transport =
+            NetcodeClientTransport::new_with_transport_socket(Duration::ZERO, authentication, RefusedSocket { pending_error: true })
+                .unwrap();
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        client_transport.update(Duration::from_millis(10), &mut client).unwrap();
+        client_transport.send_packets(&mut client).unwrap();
+
+        let server_config = ServerConfig {
+            current_time: Duration::ZERO,
+            max_clients: 4,
+            protocol_id: 0,
+            public_addresses: vec!["127.0.0.1:5000".parse().unwrap()],
+            authentication: ServerAuthentication::Unsecure,
+        };
+        let mut server_transport =
+            NetcodeServerTransport::new_with_transport_socket(server_config, RefusedSocket { pending_error: true }).unwrap();
+        let mut server = RenetServer::new(ConnectionConfig::default());
+        server_transport.update(Duration::from_millis(10), &mut server).unwrap();
+    }
+
+    #[test]
+    fn udp_transport() {
+        let mut a = UdpTransport::bind("127.0.0.1:0").unwrap();
+        let mut b = UdpTransport::bind("127.0.0.1:0").unwrap();
+        let mut buffer = [0u8; 16];
+        // Non-blocking, nothing to receive
+        assert!(a.recv_from(&mut buffer).unwrap().is_none());
+
+        b.send_to(&[1, 2, 3], a.addr().unwrap()).unwrap();
+        b.send_to(&[4, 5], a.addr().unwrap()).unwrap();
+        b.flush().unwrap();
+        let mut received = vec![];
+        for _ in 0..100 {
+            while let Some((len, addr)) = a.recv_from(&mut buffer).unwrap() {
+                assert_eq!(addr, b.addr().unwrap());
+                received.push(buffer[..len].to_vec());
+            }
+            if received.len() == 2 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(received, vec![vec![1, 2, 3], vec![4, 5]]);
+    }
+}
@@ -0,0 +1,270 @@
+use std::{io, net::SocketAddr, sync::Arc, time::Duration};
+
+use renetcode::{ClientAuthentication, NetcodeError, ServerConfig};
+use tokio::net::UdpSocket;
+
+use crate::{Clock, RenetClient, RenetServer, SystemClock};
+
+use super::{NetcodeClientTransport, NetcodeServerTransport, NetcodeTransportError, TransportSocket};
+
+/// UDP socket from tokio, used by the async transports.
+#[derive(Debug, Clone)]
+pub struct TokioUdpTransport {
+    socket: Arc<UdpSocket>,
+}
+
+impl TokioUdpTransport {
+    pub fn new(socket: UdpSocket) -> Self {
+        Self { socket: Arc::new(socket) }
+    }
+
+    /// Returns the underlying socket.
+    pub fn socket(&self) -> &Arc<UdpSocket> {
+        &self.socket
+    }
+
+    // Waits until there are packets to receive or the timeout elapses.
+    async fn readable(&self, timeout: Duration) -> io::Result<()> {
+        match tokio::time::timeout(timeout, self.socket.readable()).await {
+            Ok(result) => result,
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+impl TransportSocket for TokioUdpTransport {
+    fn addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    fn recv_from(&mut self, buffer: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        match self.socket.try_recv_from(buffer) {
+            Ok((len, addr)) => Ok(Some((len, addr))),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn send_to(&mut self, packet: &[u8], addr: SocketAddr) -> io::Result<()> {
+        match self.socket.try_send_to(packet, addr) {
+            // Like a full UDP buffer, the packet is dropped
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            result => result.map(|_| ()),
+        }
+    }
+}
+
+/// Server transport for async runtimes, waits for packets instead of polling the socket.
+///
+/// # Usage
+/// ```no_run
+/// # use renet::{ConnectionConfig, RenetServer};
+/// # use renet::transport::{AsyncNetcodeServerTransport, ServerAuthentication, ServerConfig};
+/// # use std::time::Duration;
+/// # async fn run(server_config: ServerConfig) -> Result<(), Box<dyn std::error::Error>> {
+/// let socket = tokio::net::UdpSocket::bind("127.0.0.1:5000").await?;
+/// let mut transport = AsyncNetcodeServerTransport::new(server_config, socket)?;
+/// let mut server = RenetServer::new(ConnectionConfig::default());
+/// loop {
+///     transport.update(Duration::from_millis(16), &mut server).await?;
+///     // Handle the server events and messages
+///     transport.send_packets(&mut server);
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AsyncNetcodeServerTransport {
+    transport: NetcodeServerTransport,
+    socket: TokioUdpTransport,
+    clock: Arc<dyn Clock>,
+    last_updated: Duration,
+}
+
+impl AsyncNetcodeServerTransport {
+    pub fn new(server_config: ServerConfig, socket: UdpSocket) -> Result<Self, io::Error> {
+        let socket = TokioUdpTransport::new(socket);
+        let transport = NetcodeServerTransport::new_with_transport_socket(server_config, socket.clone())?;
+
+        Ok(Self {
+            transport,
+            socket,
+            clock: Arc::new(SystemClock::new()),
+            last_updated: Duration::ZERO,
+        })
+    }
+
+    pub fn transport(&self) -> &NetcodeServerTransport {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut NetcodeServerTransport {
+        &mut self.transport
+    }
+
+    /// Replaces the clock measuring the time between updates, by default the wall-clock time is used.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.last_updated = clock.now();
+        self.clock = clock;
+    }
+
+    /// Waits until packets are received or the timeout elapses, then updates the server
+    /// and the transport with the time elapsed since the last update.
+    /// Returns the elapsed time.
+    pub async fn update(&mut self, timeout: Duration, server: &mut RenetServer) -> Result<Duration, NetcodeTransportError> {
+        self.socket.readable(timeout).await?;
+
+        let now = self.clock.now();
+        let duration = now.saturating_sub(self.last_updated);
+        self.last_updated = now;
+
+        server.update(duration);
+        self.transport.update(duration, server)?;
+
+        Ok(duration)
+    }
+
+    /// Send packets to connected clients.
+    pub fn send_packets(&mut self, server: &mut RenetServer) {
+        self.transport.send_packets(server);
+    }
+}
+
+/// Client transport for async runtimes, see [`AsyncNetcodeServerTransport`].
+#[derive(Debug)]
+pub struct AsyncNetcodeClientTransport {
+    transport: NetcodeClientTransport,
+    socket: TokioUdpTransport,
+    clock: Arc<dyn Clock>,
+    last_updated: Duration,
+}
+
+impl AsyncNetcodeClientTransport {
+    pub fn new(current_time: Duration, authentication: ClientAuthentication, socket: UdpSocket) -> Result<Self, NetcodeError> {
+        let socket = TokioUdpTransport::new(socket);
+        let transport = NetcodeClientTransport::new_with_transport_socket(current_time, authentication, socket.clone())?;
+
+        Ok(Self {
+            transport,
+            socket,
+            clock: Arc::new(SystemClock::new()),
+            last_updated: Duration::ZERO,
+        })
+    }
+
+    pub fn transport(&self) -> &NetcodeClientTransport {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut NetcodeClientTransport {
+        &mut self.transport
+    }
+
+    /// Replaces the clock measuring the time between updates, by default the wall-clock time is used.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.last_updated = clock.now();
+        self.clock = clock;
+    }
+
+    /// Waits until packets are received or the timeout elapses, then updates the client
+    /// and the transport with the time elapsed since the last update.
+    /// Returns the elapsed time.
+    pub async fn update(&mut self, timeout: Duration, client: &mut RenetClient) -> Result<Duration, NetcodeTransportError> {
+        self.socket.readable(timeout).await?;
+
+        let now = self.clock.now();
+        let duration = now.saturating_sub(self.last_updated);
+        self.last_updated = now;
+
+        client.update(duration);
+        self.transport.update(duration, client)?;
+
+        Ok(duration)
+    }
+
+    /// Send packets to the server.
+    pub fn send_packets(&mut self, client: &mut RenetClient) -> Result<(), NetcodeTransportError> {
+        self.transport.send_packets(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::ServerAuthentication;
+    use crate::{ConnectionConfig, DefaultChannel};
+
+    #[tokio::test]
+    async fn async_transports() {
+        let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = server_socket.local_addr().unwrap();
+        let server_config = ServerConfig {
+            current_time: Duration::ZERO,
+            max_clients: 4,
+            protocol_id: 0,
+            public_addresses: vec![server_addr],
+            authentication: ServerAuthentication::Unsecure,
+        };
+        let mut server_transport = AsyncNetcodeServerTransport::new(server_config, server_socket).unwrap();
+        let mut server = RenetServer::new(ConnectionConfig::default());
+
+        let authentication = ClientAuthentication::Unsecure {
+            protocol_id: 0,
+            client_id: 7,
+            server_addr,
+            user_data: None,
+        };
+        let client_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut client_transport = AsyncNetcodeClientTransport::new(Duration::ZERO, authentication, client_socket).unwrap();
+        let mut client = RenetClient::new(ConnectionConfig::default());
+
+        let timeout = Duration::from_millis(5);
+        let mut received = None;
+        for _ in 0..200 {
+            client_transport.update(timeout, &mut client).await.unwrap();
+            if client.is_connected() {
+                client.send_message(DefaultChannel::ReliableOrdered, "hello");
+            }
+            client_transport.send_packets(&mut client).unwrap();
+
+            server_transport.update(timeout, &mut server).await.unwrap();
+            for client_id in server.clients_id() {
+                if let Some(message) = server.receive_message(client_id, DefaultChannel::ReliableOrdered) {
+                    received = Some((client_id, message));
+                }
+            }
+            server_transport.send_packets(&mut server);
+
+            if received.is_some() {
+                break;
+            }
+        }
+
+        let (client_id, message) = received.unwrap();
+        assert_eq!(client_id.raw(), 7);
+        assert_eq!(message, "hello");
+    }
+
+    #[tokio::test]
+    async fn manual_clock() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server_config = ServerConfig {
+            current_time: Duration::ZERO,
+            max_clients: 4,
+            protocol_id: 0,
+            public_addresses: vec![socket.local_addr().unwrap()],
+            authentication: ServerAuthentication::Unsecure,
+        };
+        let mut transport = AsyncNetcodeServerTransport::new(server_config, socket).unwrap();
+        let mut server = RenetServer::new(ConnectionConfig::default());
+
+        let clock = crate::ManualClock::new(Duration::from_secs(10));
+        transport.set_clock(Arc::new(clock.clone()));
+        clock.advance(Duration::from_millis(16));
+
+        let elapsed = transport.update(Duration::from_millis(1), &mut server).await.unwrap();
+        assert_eq!(elapsed, Duration::from_millis(16));
+        let elapsed = transport.update(Duration::from_millis(1), &mut server).await.unwrap();
+        assert_eq!(elapsed, Duration::ZERO);
+    }
+}
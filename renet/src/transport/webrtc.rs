@@ -0,0 +1,192 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt, io,
+    net::{Ipv4Addr, SocketAddr},
+    rc::Rc,
+    time::Duration,
+};
+
+use js_sys::{Array, Uint8Array};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{
+    MessageEvent, RtcConfiguration, RtcDataChannel, RtcDataChannelInit, RtcDataChannelState, RtcDataChannelType, RtcIceGatheringState,
+    RtcIceServer, RtcPeerConnection, RtcSdpType, RtcSessionDescriptionInit,
+};
+
+use super::TransportSocket;
+
+#[derive(Default)]
+struct SharedState {
+    received: VecDeque<Vec<u8>>,
+    error: Option<String>,
+}
+
+/// Client socket for browsers that sends packets over an unreliable and unordered WebRTC data channel.
+///
+/// The peer connection is established with an offer/answer exchange done by the application,
+/// usually with an HTTP request to the game backend:
+/// send the SDP from [`local_description`](Self::local_description) to the server,
+/// and pass its answer to [`set_remote_description`](Self::set_remote_description).
+/// Packets sent before the data channel opens are dropped.
+///
+/// The server must accept the data channel and forward its messages as datagrams to the netcode server,
+/// for example with a custom [`TransportSocket`].
+pub struct WebRtcClientSocket {
+    server_addr: SocketAddr,
+    peer_connection: RtcPeerConnection,
+    data_channel: RtcDataChannel,
+    state: Rc<RefCell<SharedState>>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+}
+
+// SAFETY: wasm32-unknown-unknown is single threaded, the socket is never accessed from another thread.
+unsafe impl Send for WebRtcClientSocket {}
+unsafe impl Sync for WebRtcClientSocket {}
+
+impl fmt::Debug for WebRtcClientSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebRtcClientSocket")
+            .field("server_addr", &self.server_addr)
+            .field("ready_state", &self.data_channel.ready_state())
+            .finish()
+    }
+}
+
+impl WebRtcClientSocket {
+    /// Creates the peer connection and starts gathering the ICE candidates for the offer.
+    /// The server address is the one in the connect token, received packets are reported as coming from it.
+    pub fn new(server_addr: SocketAddr, ice_servers: &[&str]) -> io::Result<Self> {
+        let configuration = RtcConfiguration::new();
+        if !ice_servers.is_empty() {
+            let servers = Array::new();
+            for url in ice_servers {
+                let server = RtcIceServer::new();
+                server.set_urls_str(url);
+                servers.push(&server);
+            }
+            configuration.set_ice_servers(&servers);
+        }
+        let peer_connection = RtcPeerConnection::new_with_configuration(&configuration).map_err(js_error)?;
+
+        let channel_init = RtcDataChannelInit::new();
+        channel_init.set_ordered(false);
+        channel_init.set_max_retransmits(0);
+        let data_channel = peer_connection.create_data_channel_with_data_channel_dict("renet", &channel_init);
+        data_channel.set_binary_type(RtcDataChannelType::Arraybuffer);
+
+        let state: Rc<RefCell<SharedState>> = Rc::default();
+        let message_state = state.clone();
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            let packet = Uint8Array::new(&event.data()).to_vec();
+            message_state.borrow_mut().received.push_back(packet);
+        });
+        data_channel.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+        let offer_connection = peer_connection.clone();
+        let offer_state = state.clone();
+        spawn_local(async move {
+            let result = async {
+                let offer = JsFuture::from(offer_connection.create_offer()).await?;
+                JsFuture::from(offer_connection.set_local_description(offer.unchecked_ref())).await
+            };
+            if let Err(e) = result.await {
+                offer_state.borrow_mut().error = Some(format!("failed to create offer: {e:?}"));
+            }
+        });
+
+        Ok(Self {
+            server_addr,
+            peer_connection,
+            data_channel,
+            state,
+            _on_message: on_message,
+        })
+    }
+
+    /// Returns the SDP offer to be sent to the server, once all ICE candidates are gathered.
+    pub fn local_description(&self) -> Option<String> {
+        if self.peer_connection.ice_gathering_state() != RtcIceGatheringState::Complete {
+            return None;
+        }
+
+        self.peer_connection.local_description().map(|description| description.sdp())
+    }
+
+    /// Sets the SDP answer received from the server, the data channel opens after it.
+    pub fn set_remote_description(&mut self, sdp: &str) {
+        let description = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
+        description.set_sdp(sdp);
+
+        let state = self.state.clone();
+        let promise = self.peer_connection.set_remote_description(&description);
+        spawn_local(async move {
+            if let Err(e) = JsFuture::from(promise).await {
+                state.borrow_mut().error = Some(format!("failed to set remote description: {e:?}"));
+            }
+        });
+    }
+
+    /// Returns whether the data channel is open.
+    pub fn is_connected(&self) -> bool {
+        self.data_channel.ready_state() == RtcDataChannelState::Open
+    }
+}
+
+impl Drop for WebRtcClientSocket {
+    fn drop(&mut self) {
+        self.data_channel.set_onmessage(None);
+        self.data_channel.close();
+        self.peer_connection.close();
+    }
+}
+
+impl TransportSocket for WebRtcClientSocket {
+    fn addr(&self) -> io::Result<SocketAddr> {
+        // Browsers don't expose the local address
+        Ok(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0))
+    }
+
+    fn update(&mut self, _duration: Duration) -> io::Result<()> {
+        if let Some(error) = self.state.borrow_mut().error.take() {
+            return Err(io::Error::other(error));
+        }
+
+        match self.data_channel.ready_state() {
+            RtcDataChannelState::Closing | RtcDataChannelState::Closed => {
+                Err(io::Error::new(io::ErrorKind::NotConnected, "webrtc data channel closed"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn recv_from(&mut self, buffer: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        let Some(packet) = self.state.borrow_mut().received.pop_front() else {
+            return Ok(None);
+        };
+
+        let len = packet.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&packet[..len]);
+        Ok(Some((len, self.server_addr)))
+    }
+
+    fn send_to(&mut self, packet: &[u8], addr: SocketAddr) -> io::Result<()> {
+        if addr != self.server_addr {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "webrtc can only send to the server address",
+            ));
+        }
+
+        if !self.is_connected() {
+            return Ok(());
+        }
+
+        self.data_channel.send_with_u8_array(packet).map_err(js_error)
+    }
+}
+
+fn js_error(error: JsValue) -> io::Error {
+    io::Error::other(format!("{error:?}"))
+}
@@ -0,0 +1,458 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt, io,
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use renetcode::NETCODE_MAX_PACKET_BYTES;
+use tungstenite::{
+    handshake::{client::ClientHandshake, server::NoCallback, server::ServerHandshake, MidHandshake},
+    protocol::WebSocketConfig,
+    HandshakeError, Message, WebSocket,
+};
+
+use super::TransportSocket;
+
+// Packets received but not read yet, new packets are dropped when full like in a UDP socket buffer.
+const MAX_RECEIVED_PACKETS: usize = 1024;
+
+/// Limits of the connections accepted by a [`WebSocketServerSocket`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebSocketServerConfig {
+    /// Maximum number of connections, including the ones still handshaking.
+    /// New connections are closed right away when reached.
+    /// Default: 1024
+    pub max_connections: usize,
+    /// Time a connection has to finish the WebSocket handshake before being closed.
+    /// Default: 5 seconds
+    pub handshake_timeout: Duration,
+    /// Maximum size of a received message, bigger messages close the connection.
+    /// Default: the maximum size of a netcode packet
+    pub max_message_size: usize,
+}
+
+impl Default for WebSocketServerConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 1024,
+            handshake_timeout: Duration::from_secs(5),
+            max_message_size: NETCODE_MAX_PACKET_BYTES,
+        }
+    }
+}
+
+enum ServerConnection {
+    Handshake {
+        handshake: MidHandshake<ServerHandshake<TcpStream, NoCallback>>,
+        started_at: Duration,
+    },
+    Open(WebSocket<TcpStream>),
+}
+
+/// Server socket that receives packets as binary messages from WebSocket connections.
+///
+/// Useful for deployments where UDP is blocked, packets are delivered over TCP so they are never lost,
+/// but a lost segment delays all the packets after it.
+/// Each connection is identified by the address of its peer.
+pub struct WebSocketServerSocket {
+    listener: TcpListener,
+    config: WebSocketServerConfig,
+    current_time: Duration,
+    connections: HashMap<SocketAddr, ServerConnection>,
+    received: VecDeque<(SocketAddr, Vec<u8>)>,
+}
+
+impl fmt::Debug for WebSocketServerSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebSocketServerSocket")
+            .field("listener", &self.listener)
+            .field("config", &self.config)
+            .field("connections", &self.connections.len())
+            .field("received", &self.received.len())
+            .finish()
+    }
+}
+
+impl WebSocketServerSocket {
+    /// Listens for WebSocket connections in the address, with the default [`WebSocketServerConfig`].
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Self::bind_with_config(addr, WebSocketServerConfig::default())
+    }
+
+    /// Listens for WebSocket connections in the address, with the given limits.
+    pub fn bind_with_config(addr: impl ToSocketAddrs, config: WebSocketServerConfig) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            config,
+            current_time: Duration::ZERO,
+            connections: HashMap::new(),
+            received: VecDeque::new(),
+        })
+    }
+
+    /// Returns the number of open connections, including the ones still handshaking.
+    pub fn connections(&self) -> usize {
+        self.connections.len()
+    }
+
+    fn accept_connections(&mut self) -> io::Result<()> {
+        loop {
+            let (stream, addr) = match self.listener.accept() {
+                Ok(accepted) => accepted,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(ref e) if e.kind() == io::ErrorKind::ConnectionAborted => continue,
+                Err(e) => return Err(e),
+            };
+            if self.connections.len() >= self.config.max_connections {
+                // Dropping the stream closes the connection
+                log::debug!("WebSocket connection from {addr} closed because the server is full");
+                continue;
+            }
+            stream.set_nonblocking(true)?;
+            stream.set_nodelay(true)?;
+
+            match tungstenite::accept_with_config(stream, Some(websocket_config(self.config.max_message_size))) {
+                Ok(websocket) => {
+                    self.connections.insert(addr, ServerConnection::Open(websocket));
+                }
+                Err(HandshakeError::Interrupted(handshake)) => {
+                    let connection = ServerConnection::Handshake {
+                        handshake,
+                        started_at: self.current_time,
+                    };
+                    self.connections.insert(addr, connection);
+                }
+                Err(HandshakeError::Failure(e)) => log::debug!("WebSocket handshake with {addr} failed: {e}"),
+            }
+        }
+    }
+}
+
+impl TransportSocket for WebSocketServerSocket {
+    fn addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    fn update(&mut self, duration: Duration) -> io::Result<()> {
+        self.current_time += duration;
+        self.accept_connections()?;
+
+        let addresses: Vec<SocketAddr> = self.connections.keys().copied().collect();
+        for addr in addresses {
+            let Some(connection) = self.connections.remove(&addr) else {
+                continue;
+            };
+
+            let mut websocket = match connection {
+                ServerConnection::Open(websocket) => websocket,
+                ServerConnection::Handshake { handshake, started_at } => match handshake.handshake() {
+                    Ok(websocket) => websocket,
+                    Err(HandshakeError::Interrupted(_)) if self.current_time - started_at >= self.config.handshake_timeout => {
+                        log::debug!("WebSocket handshake with {addr} timed out");
+                        continue;
+                    }
+                    Err(HandshakeError::Interrupted(handshake)) => {
+                        self.connections.insert(addr, ServerConnection::Handshake { handshake, started_at });
+                        continue;
+                    }
+                    Err(HandshakeError::Failure(e)) => {
+                        log::debug!("WebSocket handshake with {addr} failed: {e}");
+                        continue;
+                    }
+                },
+            };
+
+            let received = &mut self.received;
+            let result = read_messages(&mut websocket, |packet| {
+                if received.len() < MAX_RECEIVED_PACKETS {
+                    received.push_back((addr, packet));
+                }
+            });
+            match result {
+                Ok(()) => {
+                    self.connections.insert(addr, ServerConnection::Open(websocket));
+                }
+                Err(e) => log::debug!("WebSocket connection with {addr} closed: {e}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn recv_from(&mut self, buffer: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        let Some((addr, packet)) = self.received.pop_front() else {
+            return Ok(None);
+        };
+
+        let len = packet.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&packet[..len]);
+        Ok(Some((len, addr)))
+    }
+
+    fn send_to(&mut self, packet: &[u8], addr: SocketAddr) -> io::Result<()> {
+        // Like UDP, packets sent to unknown or handshaking addresses are dropped.
+        let Some(ServerConnection::Open(websocket)) = self.connections.get_mut(&addr) else {
+            return Ok(());
+        };
+
+        if let Err(e) = send_message(websocket, packet) {
+            self.connections.remove(&addr);
+            return Err(e);
+        }
+
+        Ok(())
+    }
+}
+
+enum ClientConnection {
+    Handshake(MidHandshake<ClientHandshake<TcpStream>>),
+    Open(WebSocket<TcpStream>),
+    Closed,
+}
+
+/// Client socket that sends packets as binary messages over a WebSocket connection,
+/// see [`WebSocketServerSocket`].
+pub struct WebSocketClientSocket {
+    server_addr: SocketAddr,
+    local_addr: SocketAddr,
+    connection: ClientConnection,
+    received: VecDeque<Vec<u8>>,
+}
+
+impl fmt::Debug for WebSocketClientSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebSocketClientSocket")
+            .field("server_addr", &self.server_addr)
+            .field("local_addr", &self.local_addr)
+            .field("connected", &self.is_connected())
+            .finish()
+    }
+}
+
+impl WebSocketClientSocket {
+    /// Opens a WebSocket connection with the server, the handshake is done in the next updates.
+    /// Packets sent before the handshake finishes are dropped.
+    pub fn connect(server_addr: SocketAddr) -> io::Result<Self> {
+        let stream = TcpStream::connect(server_addr)?;
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        let local_addr = stream.local_addr()?;
+
+        let config = websocket_config(NETCODE_MAX_PACKET_BYTES);
+        let connection = match tungstenite::client::client_with_config(format!("ws://{server_addr}/"), stream, Some(config)) {
+            Ok((websocket, _)) => ClientConnection::Open(websocket),
+            Err(HandshakeError::Interrupted(handshake)) => ClientConnection::Handshake(handshake),
+            Err(HandshakeError::Failure(e)) => return Err(into_io_error(e)),
+        };
+
+        Ok(Self {
+            server_addr,
+            local_addr,
+            connection,
+            received: VecDeque::new(),
+        })
+    }
+
+    /// Returns whether the WebSocket handshake has finished.
+    pub fn is_connected(&self) -> bool {
+        matches!(self.connection, ClientConnection::Open(_))
+    }
+}
+
+impl TransportSocket for WebSocketClientSocket {
+    fn addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.local_addr)
+    }
+
+    fn update(&mut self, _duration: Duration) -> io::Result<()> {
+        let mut websocket = match std::mem::replace(&mut self.connection, ClientConnection::Closed) {
+            ClientConnection::Open(websocket) => websocket,
+            ClientConnection::Handshake(handshake) => match handshake.handshake() {
+                Ok((websocket, _)) => websocket,
+                Err(HandshakeError::Interrupted(handshake)) => {
+                    self.connection = ClientConnection::Handshake(handshake);
+                    return Ok(());
+                }
+                Err(HandshakeError::Failure(e)) => return Err(into_io_error(e)),
+            },
+            ClientConnection::Closed => return Err(io::Error::new(io::ErrorKind::NotConnected, "websocket connection closed")),
+        };
+
+        let received = &mut self.received;
+        read_messages(&mut websocket, |packet| {
+            if received.len() < MAX_RECEIVED_PACKETS {
+                received.push_back(packet);
+            }
+        })?;
+        self.connection = ClientConnection::Open(websocket);
+
+        Ok(())
+    }
+
+    fn recv_from(&mut self, buffer: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        let Some(packet) = self.received.pop_front() else {
+            return Ok(None);
+        };
+
+        let len = packet.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&packet[..len]);
+        Ok(Some((len, self.server_addr)))
+    }
+
+    fn send_to(&mut self, packet: &[u8], addr: SocketAddr) -> io::Result<()> {
+        if addr != self.server_addr {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "websocket can only send to the server address",
+            ));
+        }
+
+        match &mut self.connection {
+            ClientConnection::Open(websocket) => {
+                if let Err(e) = send_message(websocket, packet) {
+                    self.connection = ClientConnection::Closed;
+                    return Err(e);
+                }
+                Ok(())
+            }
+            ClientConnection::Handshake(_) => Ok(()),
+            ClientConnection::Closed => Err(io::Error::new(io::ErrorKind::NotConnected, "websocket connection closed")),
+        }
+    }
+}
+
+// Reads all available binary messages, also flushing pending writes.
+fn read_messages(websocket: &mut WebSocket<TcpStream>, mut on_packet: impl FnMut(Vec<u8>)) -> io::Result<()> {
+    loop {
+        match websocket.read() {
+            Ok(Message::Binary(data)) => on_packet(data.to_vec()),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => break,
+            Err(e) => return Err(into_io_error(e)),
+        }
+    }
+
+    match websocket.flush() {
+        Err(tungstenite::Error::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+        result => result.map_err(into_io_error),
+    }
+}
+
+// Packets are sent as single frame messages, so both are limited to the packet size.
+fn websocket_config(max_message_size: usize) -> WebSocketConfig {
+    WebSocketConfig::default()
+        .max_message_size(Some(max_message_size))
+        .max_frame_size(Some(max_message_size))
+}
+
+fn send_message(websocket: &mut WebSocket<TcpStream>, packet: &[u8]) -> io::Result<()> {
+    match websocket.send(Message::binary(packet.to_vec())) {
+        // The message is buffered, it's written in the next flush
+        Err(tungstenite::Error::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+        result => result.map_err(into_io_error),
+    }
+}
+
+fn into_io_error(error: tungstenite::Error) -> io::Error {
+    match error {
+        tungstenite::Error::Io(e) => e,
+        e => io::Error::other(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::transport::{ClientAuthentication, NetcodeClientTransport, NetcodeServerTransport, ServerAuthentication, ServerConfig};
+    use crate::{ConnectionConfig, DefaultChannel, RenetClient, RenetServer};
+
+    #[test]
+    fn connection_limits() {
+        let config = WebSocketServerConfig {
+            max_connections: 1,
+            handshake_timeout: Duration::from_secs(1),
+            ..Default::default()
+        };
+        let mut server_socket = WebSocketServerSocket::bind_with_config("127.0.0.1:0", config).unwrap();
+        let server_addr = server_socket.addr().unwrap();
+
+        // Never starts the handshake
+        let _idle = TcpStream::connect(server_addr).unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+        server_socket.update(Duration::ZERO).unwrap();
+        assert_eq!(server_socket.connections(), 1);
+
+        // Closed because the server is full
+        let mut full = TcpStream::connect(server_addr).unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+        server_socket.update(Duration::ZERO).unwrap();
+        assert_eq!(server_socket.connections(), 1);
+        full.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        assert_eq!(io::Read::read(&mut full, &mut [0; 1]).unwrap(), 0);
+
+        server_socket.update(Duration::from_secs(1)).unwrap();
+        assert_eq!(server_socket.connections(), 0);
+    }
+
+    #[test]
+    fn netcode_over_websocket() {
+        let server_socket = WebSocketServerSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = server_socket.addr().unwrap();
+        let server_config = ServerConfig {
+            current_time: Duration::ZERO,
+            max_clients: 4,
+            protocol_id: 0,
+            public_addresses: vec![server_addr],
+            authentication: ServerAuthentication::Unsecure,
+        };
+        let mut server_transport = NetcodeServerTransport::new_with_transport_socket(server_config, server_socket).unwrap();
+        let mut server = RenetServer::new(ConnectionConfig::default());
+
+        let authentication = ClientAuthentication::Unsecure {
+            protocol_id: 0,
+            client_id: 7,
+            server_addr,
+            user_data: None,
+        };
+        let client_socket = WebSocketClientSocket::connect(server_addr).unwrap();
+        let mut client_transport =
+            NetcodeClientTransport::new_with_transport_socket(Duration::ZERO, authentication, client_socket).unwrap();
+        let mut client = RenetClient::new(ConnectionConfig::default());
+
+        let delta = Duration::from_millis(10);
+        let mut received = None;
+        for _ in 0..500 {
+            client.update(delta);
+            client_transport.update(delta, &mut client).unwrap();
+            if client.is_connected() {
+                client.send_message(DefaultChannel::ReliableOrdered, "hello");
+            }
+            client_transport.send_packets(&mut client).unwrap();
+
+            server.update(delta);
+            server_transport.update(delta, &mut server).unwrap();
+            for client_id in server.clients_id() {
+                if let Some(message) = server.receive_message(client_id, DefaultChannel::ReliableOrdered) {
+                    received = Some((client_id, message));
+                }
+            }
+            server_transport.send_packets(&mut server);
+
+            if received.is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let (client_id, message) = received.unwrap();
+        assert_eq!(client_id.raw(), 7);
+        assert_eq!(message, "hello");
+    }
+}
@@ -0,0 +1,54 @@
+//! Varints of the formats built on top of messages (states, replication, voice),
+//! the same QUIC variable-length integers as the `varint` fields of the [packet](crate::packet) wire format.
+
+use bytes::{BufMut, BytesMut};
+
+/// Largest value that can be written as a varint.
+pub(crate) const MAX_VARINT: u64 = (1 << 62) - 1;
+
+/// Panics if the value is above [`MAX_VARINT`].
+pub(crate) fn write_varint(buffer: &mut BytesMut, value: u64) {
+    assert!(value <= MAX_VARINT, "value {value} is too large for a varint");
+    let mut bytes = [0u8; 8];
+    let written = octets::OctetsMut::with_slice(&mut bytes)
+        .put_varint(value)
+        .expect("varints are at most 8 bytes")
+        .len();
+    buffer.put_slice(&bytes[..written]);
+}
+
+/// Reads a varint and advances the bytes past it.
+pub(crate) fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut b = octets::Octets::with_slice(bytes);
+    let value = b.get_varint().ok()?;
+    *bytes = &bytes[b.off()..];
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_encoding_as_packets() {
+        for value in [0, 63, 64, 16383, 16384, 1 << 30, MAX_VARINT] {
+            let mut buffer = BytesMut::new();
+            write_varint(&mut buffer, value);
+
+            let mut expected = [0u8; 8];
+            let mut b = octets::OctetsMut::with_slice(&mut expected);
+            b.put_varint(value).unwrap();
+            let len = b.off();
+            assert_eq!(&buffer[..], &expected[..len]);
+
+            buffer.put_u8(0xFF);
+            let mut bytes = &buffer[..];
+            assert_eq!(read_varint(&mut bytes), Some(value));
+            assert_eq!(bytes, [0xFF]);
+        }
+
+        // 300 as a 2 bytes varint, truncated
+        let mut bytes = &[0x41][..];
+        assert_eq!(read_varint(&mut bytes), None);
+    }
+}
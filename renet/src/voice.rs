@@ -0,0 +1,409 @@
+//! Voice chat over an unreliable channel, with a jitter buffer per talker.
+//!
+//! Clients encode audio in fixed duration frames (for example 20ms Opus frames) and send each one with
+//! [`RenetClient::send_voice_frame`](crate::RenetClient::send_voice_frame). The server sets the talker of the
+//! frames it receives and relays them to the other clients with [`RenetServer::relay_voice_frames`](crate::RenetServer::relay_voice_frames).
+//! Received frames are pushed to a [`VoiceReceiver`], which plays them back at the frame rate after a delay
+//! that absorbs the jitter of the network, and reports the lost frames so the codec can conceal them.
+//!
+//! The voice channel should be unreliable, and placed before the game channels that can wait,
+//! since the channel order is their priority when generating packets.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::varint::{read_varint, write_varint};
+use crate::ClientId;
+
+/// Returns a frame to play instead of a lost one, from the last frame played if any.
+pub type ConcealFn = fn(previous: Option<&[u8]>) -> Option<Bytes>;
+
+/// Configuration of the playback of the voice frames.
+#[derive(Debug, Clone)]
+pub struct VoiceConfig {
+    /// Duration of the audio of each frame, frames are played back at this rate.
+    /// Default: 20ms
+    pub frame_duration: Duration,
+    /// Time frames are buffered before they are played, higher values absorb more jitter but add latency.
+    /// Default: 60ms
+    pub target_delay: Duration,
+    /// Buffered audio above this is dropped to catch up, after bursts of late frames.
+    /// Default: 200ms
+    pub max_delay: Duration,
+    /// Packet loss concealment, used to generate the frames that were lost.
+    /// If `None`, lost frames are returned as [`VoicePlayout::Lost`], codecs like Opus conceal them when decoding.
+    /// Default: None
+    pub conceal: Option<ConcealFn>,
+}
+
+impl Default for VoiceConfig {
+    fn default() -> Self {
+        Self {
+            frame_duration: Duration::from_millis(20),
+            target_delay: Duration::from_millis(60),
+            max_delay: Duration::from_millis(200),
+            conceal: None,
+        }
+    }
+}
+
+/// Encoded audio frame of a talker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoiceFrame {
+    /// Client that is talking, set by the server.
+    pub talker: ClientId,
+    pub sequence: u64,
+    pub data: Bytes,
+}
+
+impl VoiceFrame {
+    /// Message format: `talker: u64`, `sequence: varint`, then the frame data.
+    /// Client ids can use all 64 bits, so the talker is not a varint.
+    pub fn to_bytes(&self) -> Bytes {
+        let mut message = BytesMut::with_capacity(self.data.len() + 10);
+        message.put_u64(self.talker.raw());
+        write_varint(&mut message, self.sequence);
+        message.extend_from_slice(&self.data);
+        message.freeze()
+    }
+
+    /// Returns `None` if the message is malformed.
+    pub fn from_bytes(mut message: &[u8]) -> Option<Self> {
+        if message.len() < 8 {
+            return None;
+        }
+        let talker = ClientId::from_raw(message.get_u64());
+        let sequence = read_varint(&mut message)?;
+        Some(Self {
+            talker,
+            sequence,
+            data: Bytes::copy_from_slice(message),
+        })
+    }
+}
+
+/// Audio to play next for a talker, returned by [`JitterBuffer::pop`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VoicePlayout {
+    Frame(Bytes),
+    /// Generated by [`VoiceConfig::conceal`] for a lost frame.
+    Concealed(Bytes),
+    /// The frame was lost or arrived too late.
+    Lost,
+}
+
+/// Counters of a [`JitterBuffer`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct JitterBufferStats {
+    pub played: u64,
+    pub lost: u64,
+    /// Frames received after their playback time, or duplicated.
+    pub late: u64,
+    /// Frames dropped to keep the delay under [`VoiceConfig::max_delay`],
+    /// or with the last sequence, that would overflow the playback.
+    pub dropped: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Playback {
+    next_sequence: u64,
+    // Time the next sequence is played
+    next_time: Duration,
+}
+
+/// Reorders the frames of a talker and plays them back at the frame rate.
+#[derive(Debug, Clone)]
+pub struct JitterBuffer {
+    config: VoiceConfig,
+    frames: BTreeMap<u64, Bytes>,
+    playback: Option<Playback>,
+    previous: Option<Bytes>,
+    last_received_at: Option<Duration>,
+    stats: JitterBufferStats,
+}
+
+impl JitterBuffer {
+    pub fn new(config: VoiceConfig) -> Self {
+        Self {
+            config,
+            frames: BTreeMap::new(),
+            playback: None,
+            previous: None,
+            last_received_at: None,
+            stats: JitterBufferStats::default(),
+        }
+    }
+
+    /// Adds a received frame, `now` is the local time used to play them back.
+    pub fn push(&mut self, sequence: u64, data: Bytes, now: Duration) {
+        self.last_received_at = Some(now);
+        // The sequence is picked by the talker, no sequence can be played after the last one
+        if sequence == u64::MAX {
+            self.stats.dropped += 1;
+            return;
+        }
+
+        let max_frames = self.frames_in(self.config.max_delay).max(1);
+        match &mut self.playback {
+            Some(playback) if sequence < playback.next_sequence => {
+                self.stats.late += 1;
+                return;
+            }
+            // Too far ahead, the talker stopped and started talking again
+            Some(playback) if self.frames.is_empty() && sequence >= playback.next_sequence.saturating_add(max_frames) => {
+                self.start(sequence, now);
+            }
+            // Ran out of frames, buffer again before resuming the playback
+            Some(playback) if self.frames.is_empty() && playback.next_time < now => {
+                playback.next_time = now + self.config.target_delay;
+            }
+            Some(_) => {}
+            None => self.start(sequence, now),
+        }
+        if self.frames.insert(sequence, data).is_some() {
+            self.stats.late += 1;
+        }
+
+        // Catch up when more audio is buffered than the max delay allows
+        while self.frames.len() as u64 > max_frames {
+            let Some((oldest, _)) = self.frames.pop_first() else {
+                break;
+            };
+            self.stats.dropped += 1;
+            if let Some(playback) = &mut self.playback {
+                playback.next_sequence = oldest + 1;
+            }
+        }
+    }
+
+    /// Returns the next audio to play if it's due, should be called until it returns `None`.
+    /// Returns `None` while the talker is silent.
+    pub fn pop(&mut self, now: Duration) -> Option<VoicePlayout> {
+        let playback = self.playback.as_mut()?;
+        // Nothing left to play, lost frames are only known when a later one arrives
+        if self.frames.is_empty() || now < playback.next_time {
+            return None;
+        }
+
+        let sequence = playback.next_sequence;
+        playback.next_sequence = playback.next_sequence.saturating_add(1);
+        playback.next_time += self.config.frame_duration;
+        match self.frames.remove(&sequence) {
+            Some(frame) => {
+                self.stats.played += 1;
+                self.previous = Some(frame.clone());
+                Some(VoicePlayout::Frame(frame))
+            }
+            None => {
+                self.stats.lost += 1;
+                let concealed = self.config.conceal.and_then(|conceal| conceal(self.previous.as_deref()));
+                Some(concealed.map_or(VoicePlayout::Lost, VoicePlayout::Concealed))
+            }
+        }
+    }
+
+    /// Returns whether a frame was received within the max delay.
+    pub fn is_talking(&self, now: Duration) -> bool {
+        !self.frames.is_empty()
+            || self
+                .last_received_at
+                .is_some_and(|at| now.saturating_sub(at) <= self.config.max_delay)
+    }
+
+    pub fn buffered_frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn stats(&self) -> JitterBufferStats {
+        self.stats
+    }
+
+    fn start(&mut self, sequence: u64, now: Duration) {
+        self.frames.clear();
+        self.previous = None;
+        self.playback = Some(Playback {
+            next_sequence: sequence,
+            next_time: now + self.config.target_delay,
+        });
+    }
+
+    fn frames_in(&self, duration: Duration) -> u64 {
+        (duration.as_nanos() / self.config.frame_duration.as_nanos().max(1)) as u64
+    }
+}
+
+/// Jitter buffers of all the talkers heard by a client.
+#[derive(Debug, Clone)]
+pub struct VoiceReceiver {
+    config: VoiceConfig,
+    talkers: HashMap<ClientId, JitterBuffer>,
+}
+
+impl VoiceReceiver {
+    pub fn new(config: VoiceConfig) -> Self {
+        Self {
+            config,
+            talkers: HashMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, frame: VoiceFrame, now: Duration) {
+        let config = &self.config;
+        self.talkers
+            .entry(frame.talker)
+            .or_insert_with(|| JitterBuffer::new(config.clone()))
+            .push(frame.sequence, frame.data, now);
+    }
+
+    /// Returns the audio due for each talker, to be decoded and mixed.
+    pub fn pop(&mut self, now: Duration) -> Vec<(ClientId, VoicePlayout)> {
+        let mut playouts = vec![];
+        for (talker, buffer) in self.talkers.iter_mut() {
+            while let Some(playout) = buffer.pop(now) {
+                playouts.push((*talker, playout));
+            }
+        }
+        playouts
+    }
+
+    /// Returns the clients currently talking, useful to show speaker indicators.
+    pub fn talkers(&self, now: Duration) -> impl Iterator<Item = ClientId> + '_ {
+        self.talkers
+            .iter()
+            .filter(move |(_, buffer)| buffer.is_talking(now))
+            .map(|(talker, _)| *talker)
+    }
+
+    pub fn jitter_buffer(&self, talker: ClientId) -> Option<&JitterBuffer> {
+        self.talkers.get(&talker)
+    }
+
+    /// Removes the jitter buffer of a talker, for example when it disconnects.
+    pub fn remove_talker(&mut self, talker: ClientId) {
+        self.talkers.remove(&talker);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn frame(sequence: u64) -> Bytes {
+        Bytes::from(sequence.to_be_bytes().to_vec())
+    }
+
+    #[test]
+    fn frame_roundtrip() {
+        let frame = VoiceFrame {
+            talker: ClientId::from_raw(u64::MAX),
+            sequence: 7,
+            data: Bytes::from_static(b"opus"),
+        };
+        assert_eq!(VoiceFrame::from_bytes(&frame.to_bytes()), Some(frame));
+        assert_eq!(VoiceFrame::from_bytes(&[]), None);
+    }
+
+    #[test]
+    fn jitter_buffer() {
+        let mut buffer = JitterBuffer::new(VoiceConfig::default());
+        assert_eq!(buffer.pop(ms(0)), None);
+
+        // Out of order, frame 2 is lost
+        buffer.push(0, frame(0), ms(0));
+        buffer.push(3, frame(3), ms(15));
+        buffer.push(1, frame(1), ms(30));
+        assert_eq!(buffer.pop(ms(59)), None);
+        assert_eq!(buffer.pop(ms(60)), Some(VoicePlayout::Frame(frame(0))));
+        assert_eq!(buffer.pop(ms(60)), None);
+        assert_eq!(buffer.pop(ms(100)), Some(VoicePlayout::Frame(frame(1))));
+        assert_eq!(buffer.pop(ms(100)), Some(VoicePlayout::Lost));
+        assert_eq!(buffer.pop(ms(100)), None);
+
+        // Late
+        buffer.push(2, frame(2), ms(110));
+        assert_eq!(buffer.pop(ms(120)), Some(VoicePlayout::Frame(frame(3))));
+        assert_eq!(buffer.pop(ms(140)), None);
+        assert!(buffer.is_talking(ms(140)));
+        assert_eq!(
+            buffer.stats(),
+            JitterBufferStats {
+                played: 3,
+                lost: 1,
+                late: 1,
+                dropped: 0
+            }
+        );
+
+        // Talking again after a silence restarts the playback
+        buffer.push(100, frame(100), ms(2000));
+        assert_eq!(buffer.pop(ms(2059)), None);
+        assert_eq!(buffer.pop(ms(2060)), Some(VoicePlayout::Frame(frame(100))));
+    }
+
+    #[test]
+    fn jitter_buffer_last_sequences() {
+        let mut buffer = JitterBuffer::new(VoiceConfig::default());
+        buffer.push(u64::MAX - 1, frame(u64::MAX - 1), ms(0));
+        buffer.push(u64::MAX, frame(u64::MAX), ms(0));
+        assert_eq!(buffer.buffered_frames(), 1);
+        assert_eq!(buffer.pop(ms(60)), Some(VoicePlayout::Frame(frame(u64::MAX - 1))));
+        assert_eq!(buffer.pop(ms(100)), None);
+        assert_eq!(buffer.stats().dropped, 1);
+
+        // Nothing can be played after the last sequences
+        buffer.push(0, frame(0), ms(2000));
+        assert_eq!(buffer.stats().late, 1);
+        buffer.push(u64::MAX, frame(u64::MAX), ms(2000));
+        assert_eq!(buffer.stats().dropped, 2);
+    }
+
+    #[test]
+    fn concealment_and_max_delay() {
+        let config = VoiceConfig {
+            conceal: Some(|previous| previous.map(Bytes::copy_from_slice)),
+            ..Default::default()
+        };
+        let mut receiver = VoiceReceiver::new(config);
+        let talker = ClientId::from_raw(1);
+        let push = |receiver: &mut VoiceReceiver, sequence: u64, now: Duration| {
+            receiver.push(
+                VoiceFrame {
+                    talker,
+                    sequence,
+                    data: frame(sequence),
+                },
+                now,
+            )
+        };
+
+        push(&mut receiver, 0, ms(0));
+        push(&mut receiver, 2, ms(0));
+        assert_eq!(
+            receiver.pop(ms(100)),
+            vec![
+                (talker, VoicePlayout::Frame(frame(0))),
+                (talker, VoicePlayout::Concealed(frame(0))),
+                (talker, VoicePlayout::Frame(frame(2)))
+            ]
+        );
+        assert_eq!(receiver.talkers(ms(100)).collect::<Vec<_>>(), vec![talker]);
+        assert_eq!(receiver.talkers(ms(1000)).count(), 0);
+
+        // A burst of frames above the max delay drops the oldest ones
+        for sequence in 3..20 {
+            push(&mut receiver, sequence, ms(1000));
+        }
+        let buffer = receiver.jitter_buffer(talker).unwrap();
+        assert_eq!(buffer.buffered_frames(), 10);
+        assert_eq!(buffer.stats().dropped, 7);
+        // It ran out of frames before, so they are buffered again
+        assert!(receiver.pop(ms(1059)).is_empty());
+        assert_eq!(receiver.pop(ms(1060)).first(), Some(&(talker, VoicePlayout::Frame(frame(10)))));
+    }
+}
@@ -1,5 +1,11 @@
 use bytes::Bytes;
-use renet::{ClientId, ConnectionConfig, DefaultChannel, DisconnectReason, RenetClient, RenetServer, ServerEvent};
+use renet::replication::{ReplicationClient, ReplicationConfig, ReplicationEvent, ReplicationServer};
+use renet::voice::{VoiceConfig, VoicePlayout, VoiceReceiver};
+use renet::{
+    ChannelConfig, ClientId, ConnectionConfig, DefaultChannel, DisconnectCode, DisconnectReason, RenetClient, RenetServer, SendType,
+    ServerEvent, TimeSync,
+};
+use std::time::Duration;
 
 pub fn init_log() {
     let _ = env_logger::builder().is_test(true).try_init();
@@ -15,7 +21,13 @@ fn test_remote_connection_reliable_channel() {
     server.add_connection(client_id);
     assert_eq!(server.connected_clients(), 1);
     assert!(server.has_connections());
-    assert_eq!(ServerEvent::ClientConnected { client_id }, server.get_event().unwrap());
+    assert_eq!(
+        ServerEvent::ClientConnected {
+            client_id,
+            user_data: None
+        },
+        server.get_event().unwrap()
+    );
 
     for _ in 0..200 {
         server.send_message(client_id, DefaultChannel::ReliableOrdered, Bytes::from("test"));
@@ -68,3 +80,598 @@ fn test_remote_connection_reliable_channel() {
         server.get_event().unwrap()
     );
 }
+
+#[test]
+fn test_remote_connection_multiple_channels() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client.set_connected();
+
+    let client_id = ClientId::from_raw(0);
+    server.add_connection(client_id);
+    let max_memory = server.channel_available_memory(client_id, DefaultChannel::ReliableOrdered);
+
+    server.send_message(client_id, DefaultChannel::Unreliable, Bytes::from("unreliable"));
+    server.send_message(client_id, DefaultChannel::ReliableUnordered, Bytes::from("reliable unordered"));
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, Bytes::from("reliable ordered"));
+
+    for packet in server.get_packets_to_send(client_id).unwrap() {
+        client.process_packet(&packet);
+    }
+
+    assert_eq!(client.receive_message(DefaultChannel::Unreliable).unwrap(), "unreliable");
+    assert_eq!(
+        client.receive_message(DefaultChannel::ReliableUnordered).unwrap(),
+        "reliable unordered"
+    );
+    assert_eq!(client.receive_message(DefaultChannel::ReliableOrdered).unwrap(), "reliable ordered");
+
+    // Reliable messages are kept until acked
+    assert!(server.channel_available_memory(client_id, DefaultChannel::ReliableOrdered) < max_memory);
+
+    // Acks are generated and processed by the connections
+    for packet in client.get_packets_to_send() {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+
+    assert_eq!(
+        server.channel_available_memory(client_id, DefaultChannel::ReliableOrdered),
+        max_memory
+    );
+    assert_eq!(
+        server.channel_available_memory(client_id, DefaultChannel::ReliableUnordered),
+        max_memory
+    );
+}
+
+#[test]
+fn test_connection_user_data() {
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let client_id = ClientId::from_raw(0);
+    server.add_connection_with_user_data(client_id, Bytes::from("player name"));
+    assert_eq!(
+        ServerEvent::ClientConnected {
+            client_id,
+            user_data: Some(Bytes::from("player name"))
+        },
+        server.get_event().unwrap()
+    );
+
+    // Adding an existing connection does nothing
+    server.add_connection(client_id);
+    assert!(server.get_event().is_none());
+}
+
+#[test]
+fn test_client_connection_config() {
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let spectator_channels = vec![ChannelConfig {
+        channel_id: 0,
+        max_memory_usage_bytes: 1024,
+        send_type: SendType::Unreliable,
+        ..Default::default()
+    }];
+    let spectator_config = ConnectionConfig {
+        server_channels_config: spectator_channels.clone(),
+        client_channels_config: spectator_channels,
+        ..Default::default()
+    };
+    let spectator_id = ClientId::from_raw(0);
+    let player_id = ClientId::from_raw(1);
+    server.set_client_connection_config(spectator_id, spectator_config);
+    server.add_connection(spectator_id);
+    server.add_connection(player_id);
+
+    assert_eq!(server.channel_available_memory(spectator_id, 0), 1024);
+    assert_eq!(server.channel_available_memory(player_id, 0), 5 * 1024 * 1024);
+}
+
+#[test]
+fn test_kick_client_with_code() {
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client.set_connected();
+    let client_id = ClientId::from_raw(0);
+    server.add_connection(client_id);
+
+    // Exchange packets so both sides know the features of the other
+    for _ in 0..2 {
+        for packet in server.get_packets_to_send(client_id).unwrap() {
+            client.process_packet(&packet);
+        }
+        for packet in client.get_packets_to_send() {
+            server.process_packet_from(&packet, client_id).unwrap();
+        }
+    }
+
+    server.disconnect_with_code(client_id, 3);
+    for packet in server.get_packets_to_send(client_id).unwrap() {
+        client.process_packet(&packet);
+    }
+    assert_eq!(
+        client.disconnect_reason(),
+        Some(DisconnectReason::DisconnectedByRemote(DisconnectCode::Custom(3)))
+    );
+
+    // The transport removes the disconnected connections
+    assert_eq!(server.disconnections_id(), vec![client_id]);
+    assert!(matches!(server.get_event(), Some(ServerEvent::ClientConnected { .. })));
+    server.remove_connection(client_id);
+    assert_eq!(
+        server.get_event(),
+        Some(ServerEvent::ClientDisconnected {
+            client_id,
+            reason: DisconnectReason::Custom(3)
+        })
+    );
+}
+
+#[test]
+fn test_local_client() {
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let client_id = ClientId::from_raw(0);
+    let mut client = server.new_local_client(client_id);
+    assert!(client.is_connected());
+    assert!(server.is_connected(client_id));
+    assert!(matches!(server.get_event(), Some(ServerEvent::ClientConnected { .. })));
+
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, "from server");
+    client.send_message(DefaultChannel::ReliableOrdered, "from client");
+    server.process_local_client(client_id, &mut client).unwrap();
+    assert_eq!(client.receive_message(DefaultChannel::ReliableOrdered).unwrap(), "from server");
+    assert_eq!(
+        server.receive_message(client_id, DefaultChannel::ReliableOrdered).unwrap(),
+        "from client"
+    );
+    // Packets are passed without serializing them
+    let stats = client.connection_stats();
+    assert!(stats.packets_sent > 0 && stats.packets_received > 0);
+    assert_eq!(stats.bytes_sent, 0);
+    assert_eq!(stats.bytes_received, 0);
+
+    // The acks are also exchanged, so the reliable messages are released
+    server.process_local_client(client_id, &mut client).unwrap();
+    assert_eq!(client.channel_available_memory(DefaultChannel::ReliableOrdered), 5 * 1024 * 1024);
+    assert_eq!(
+        server.channel_available_memory(client_id, DefaultChannel::ReliableOrdered),
+        5 * 1024 * 1024
+    );
+
+    server.disconnect_local_client(client_id, &mut client);
+    assert!(client.is_disconnected());
+    assert_eq!(
+        server.get_event(),
+        Some(ServerEvent::ClientDisconnected {
+            client_id,
+            reason: DisconnectReason::DisconnectedByClient
+        })
+    );
+    assert!(server.process_local_client(client_id, &mut client).is_err());
+}
+
+#[test]
+fn test_custom_packet_serializer() {
+    use renet::packet::{DefaultPacketSerializer, Packet, PacketSerializer, SerializationError};
+    use std::sync::Arc;
+
+    // Prefixes packets with a magic byte
+    #[derive(Debug)]
+    struct MagicSerializer;
+
+    impl PacketSerializer for MagicSerializer {
+        fn serialize(&self, packet: &Packet, buffer: &mut [u8]) -> Result<usize, SerializationError> {
+            buffer[0] = 0xAB;
+            Ok(DefaultPacketSerializer.serialize(packet, &mut buffer[1..])? + 1)
+        }
+
+        fn deserialize(&self, buffer: &[u8]) -> Result<Packet, SerializationError> {
+            match buffer.split_first() {
+                Some((0xAB, packet)) => DefaultPacketSerializer.deserialize(packet),
+                _ => Err(SerializationError::InvalidPacketType),
+            }
+        }
+    }
+
+    let config = ConnectionConfig {
+        packet_serializer: Arc::new(MagicSerializer),
+        ..Default::default()
+    };
+    let mut server = RenetServer::new(config.clone());
+    let mut client = RenetClient::new(config);
+    let client_id = ClientId::from_raw(0);
+    server.add_connection(client_id);
+    assert!(matches!(server.get_event(), Some(ServerEvent::ClientConnected { .. })));
+
+    client.send_message(DefaultChannel::ReliableOrdered, "hello");
+    for packet in client.get_packets_to_send() {
+        assert_eq!(packet[0], 0xAB);
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+    assert_eq!(server.receive_message(client_id, DefaultChannel::ReliableOrdered).unwrap(), "hello");
+
+    // Packets in the default format are dropped, like packets of unknown types
+    let mut default_client = RenetClient::new(ConnectionConfig::default());
+    default_client.send_message(DefaultChannel::ReliableOrdered, "hello");
+    for packet in default_client.get_packets_to_send() {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+    assert!(server.receive_message(client_id, DefaultChannel::ReliableOrdered).is_none());
+    assert!(server.is_connected(client_id));
+}
+
+#[test]
+fn test_corrupted_packets_are_dropped() {
+    use renet::ChecksumPacketSerializer;
+    use std::sync::Arc;
+
+    let config = ConnectionConfig {
+        packet_serializer: Arc::new(ChecksumPacketSerializer::new()),
+        ..Default::default()
+    };
+    let mut server = RenetServer::new(config.clone());
+    let mut client = RenetClient::new(config);
+    let client_id = ClientId::from_raw(0);
+    server.add_connection(client_id);
+
+    client.send_message(DefaultChannel::ReliableOrdered, "hello");
+    for mut packet in client.get_packets_to_send() {
+        let last = packet.len() - 1;
+        packet[last] ^= 0xFF;
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+    assert!(server.is_connected(client_id));
+    assert!(server.receive_message(client_id, DefaultChannel::ReliableOrdered).is_none());
+
+    // The reliable message is resent after being lost
+    client.update(Duration::from_secs(1));
+    for packet in client.get_packets_to_send() {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+    assert_eq!(server.receive_message(client_id, DefaultChannel::ReliableOrdered).unwrap(), "hello");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_typed_messages() {
+    use renet::MessageRegistry;
+    use serde::{Deserialize, Serialize};
+    use std::sync::Arc;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct PlayerInput {
+        up: bool,
+        down: bool,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Chat(String);
+
+    let registry = Arc::new(
+        MessageRegistry::new()
+            .register::<PlayerInput>(DefaultChannel::Unreliable)
+            .register::<Chat>(DefaultChannel::ReliableOrdered),
+    );
+
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    server.set_message_registry(registry.clone());
+    let client_id = ClientId::from_raw(0);
+    let mut client = server.new_local_client(client_id);
+    client.set_message_registry(registry);
+
+    client.send(PlayerInput { up: true, down: false });
+    server.broadcast(Chat("hello".to_string()));
+    // Not a typed message, fails to deserialize
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, "raw");
+    server.process_local_client(client_id, &mut client).unwrap();
+
+    assert_eq!(
+        server.receive::<PlayerInput>(client_id),
+        Some(PlayerInput { up: true, down: false })
+    );
+    assert_eq!(client.receive::<Chat>(), Some(Chat("hello".to_string())));
+    assert_eq!(client.receive::<Chat>(), None);
+    assert!(client.get_message_error().is_some());
+    assert!(server.get_message_error().is_none());
+}
+
+#[test]
+fn test_packet_capture() {
+    use renet::{CaptureDirection, PacketCapture};
+    use std::sync::{Arc, Mutex};
+
+    type CapturedPacket = (Option<ClientId>, CaptureDirection, Vec<u8>);
+
+    #[derive(Debug, Default)]
+    struct RecordedPackets(Mutex<Vec<CapturedPacket>>);
+
+    impl PacketCapture for RecordedPackets {
+        fn capture(&self, client_id: Option<ClientId>, direction: CaptureDirection, packet: &[u8]) {
+            self.0.lock().unwrap().push((client_id, direction, packet.to_vec()));
+        }
+    }
+
+    let server_capture = Arc::new(RecordedPackets::default());
+    let client_capture = Arc::new(RecordedPackets::default());
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    server.set_packet_capture(server_capture.clone());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client.set_packet_capture(client_capture.clone());
+
+    let client_id = ClientId::from_raw(7);
+    server.add_connection(client_id);
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, "hello");
+    for packet in server.get_packets_to_send(client_id).unwrap() {
+        client.process_packet(&packet);
+    }
+    assert_eq!(client.receive_message(DefaultChannel::ReliableOrdered).unwrap(), "hello");
+
+    let sent = server_capture.0.lock().unwrap();
+    let received = client_capture.0.lock().unwrap();
+    assert!(!sent.is_empty());
+    assert_eq!(sent.len(), received.len());
+    for (sent, received) in sent.iter().zip(received.iter()) {
+        assert_eq!(sent.0, Some(client_id));
+        assert_eq!(sent.1, CaptureDirection::Sent);
+        assert_eq!(received.0, None);
+        assert_eq!(received.1, CaptureDirection::Received);
+        assert_eq!(sent.2, received.2);
+    }
+}
+
+#[test]
+fn test_session_replay() {
+    use renet::{SessionRecorder, SessionRecording, SessionReplay};
+    use std::sync::Arc;
+
+    fn receive_all(server: &mut RenetServer, client_id: ClientId) -> Vec<Bytes> {
+        let mut messages = vec![];
+        while let Some(message) = server.receive_message(client_id, DefaultChannel::ReliableOrdered) {
+            messages.push(message);
+        }
+        messages
+    }
+
+    let recorder = Arc::new(SessionRecorder::new());
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    server.set_session_recorder(recorder.clone());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    let client_id = ClientId::from_raw(5);
+    server.add_connection(client_id);
+    client.set_connected();
+
+    let mut received = vec![];
+    for tick in 0..20u8 {
+        server.update(Duration::from_millis(16));
+        client.update(Duration::from_millis(16));
+        client.send_message(DefaultChannel::ReliableOrdered, vec![tick]);
+        for (i, packet) in client.get_packets_to_send().into_iter().enumerate() {
+            // Drop some packets, so messages are resent
+            if tick % 3 != 0 || i > 0 {
+                server.process_packet_from(&packet, client_id).unwrap();
+            }
+        }
+        received.push(receive_all(&mut server, client_id));
+        for packet in server.get_packets_to_send(client_id).unwrap() {
+            client.process_packet(&packet);
+        }
+    }
+
+    let mut bytes = vec![];
+    recorder.recording().write_to(&mut bytes).unwrap();
+    let recording = SessionRecording::read_from(&bytes[..]).unwrap();
+    assert_eq!(recording.duration(), Duration::from_millis(16 * 20));
+
+    let mut replayed_server = RenetServer::new(ConnectionConfig::default());
+    let mut replay = SessionReplay::new(recording);
+    let mut replayed = vec![];
+    while replay.step_server(&mut replayed_server) {
+        replayed.push(receive_all(&mut replayed_server, client_id));
+        replayed_server.get_packets_to_send(client_id).unwrap();
+    }
+
+    assert_eq!(received, replayed);
+    assert_eq!(
+        server.connection_stats(client_id).unwrap(),
+        replayed_server.connection_stats(client_id).unwrap()
+    );
+}
+
+#[test]
+fn test_delta_states() {
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    let client_id = ClientId::from_raw(3);
+    server.add_connection(client_id);
+    client.set_connected();
+
+    // Large enough to be sliced when sent in full
+    let state = |tick: u64| {
+        let mut state = vec![0u8; 3000];
+        state[..8].copy_from_slice(&tick.to_be_bytes());
+        state[2000] = tick as u8;
+        state
+    };
+
+    let mut bytes_sent = vec![];
+    for tick in 0..20 {
+        server.update(Duration::from_millis(16));
+        client.update(Duration::from_millis(16));
+        server.broadcast_state(DefaultChannel::Unreliable, tick, state(tick));
+        let packets = server.get_packets_to_send(client_id).unwrap();
+        bytes_sent.push(packets.iter().map(|packet| packet.len()).sum::<usize>());
+        // Lose a state, later deltas must not use it as baseline
+        if tick != 5 {
+            for packet in packets {
+                client.process_packet(&packet);
+            }
+            let (received_tick, received) = client.receive_state(DefaultChannel::Unreliable).unwrap();
+            assert_eq!(received_tick, tick);
+            assert_eq!(received, state(tick));
+        }
+
+        for packet in client.get_packets_to_send() {
+            server.process_packet_from(&packet, client_id).unwrap();
+        }
+    }
+
+    assert!(bytes_sent[0] > 3000);
+    assert!(bytes_sent[19] < 100);
+    assert_eq!(server.acked_state_tick(client_id, DefaultChannel::Unreliable), Some(19));
+}
+
+#[test]
+fn test_input_redundancy() {
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    let client_id = ClientId::from_raw(3);
+    server.add_connection(client_id);
+    client.set_connected();
+
+    let mut received = vec![];
+    for tick in 0..20u64 {
+        server.update(Duration::from_millis(16));
+        client.update(Duration::from_millis(16));
+        client.send_input(DefaultChannel::Unreliable, tick.to_be_bytes().to_vec());
+        let packets = client.get_packets_to_send();
+        // Lose a few consecutive packets, the next ones still carry their inputs
+        if !(5..8).contains(&tick) {
+            for packet in packets {
+                server.process_packet_from(&packet, client_id).unwrap();
+            }
+        }
+        received.extend(server.receive_inputs(client_id, DefaultChannel::Unreliable));
+
+        for packet in server.get_packets_to_send(client_id).unwrap() {
+            client.process_packet(&packet);
+        }
+    }
+
+    let expected: Vec<(u64, Bytes)> = (0..20u64).map(|tick| (tick, Bytes::from(tick.to_be_bytes().to_vec()))).collect();
+    assert_eq!(received, expected);
+    // All inputs were acknowledged
+    assert_eq!(client.unacked_inputs(DefaultChannel::Unreliable), 0);
+}
+
+#[test]
+fn test_time_sync() {
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig {
+        time_sync: Some(TimeSync::default()),
+        ..Default::default()
+    });
+    // The server was running before the client connected
+    server.update(Duration::from_secs(30));
+    let client_id = ClientId::from_raw(3);
+    server.add_connection(client_id);
+    client.set_connected();
+    assert_eq!(client.server_time_estimate(), None);
+
+    // Packets take a tick to arrive
+    let delta = Duration::from_millis(20);
+    for _ in 0..200 {
+        client.update(delta);
+        server.update(delta);
+        for packet in client.get_packets_to_send() {
+            server.process_packet_from(&packet, client_id).unwrap();
+        }
+        for packet in server.get_packets_to_send(client_id).unwrap() {
+            client.process_packet(&packet);
+        }
+    }
+
+    let estimate = client.server_time_estimate().unwrap();
+    let error = estimate.as_secs_f64() - server.time().as_secs_f64();
+    assert!(error.abs() < 0.02, "{error}");
+}
+
+#[test]
+fn test_replication() {
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut replication = ReplicationServer::new(ReplicationConfig::default());
+    let player = replication.spawn(vec![0u8; 64]);
+    let tree = replication.spawn(vec![1u8; 64]);
+
+    let mut clients = vec![];
+    for raw in 0..2 {
+        let client_id = ClientId::from_raw(raw);
+        server.add_connection(client_id);
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        client.set_connected();
+        clients.push((client_id, client, ReplicationClient::new(ReplicationConfig::default())));
+    }
+
+    let exchange =
+        |server: &mut RenetServer, replication: &mut ReplicationServer, clients: &mut Vec<(ClientId, RenetClient, ReplicationClient)>| {
+            replication.send(server);
+            for (client_id, client, client_replication) in clients.iter_mut() {
+                for packet in server.get_packets_to_send(*client_id).unwrap() {
+                    client.process_packet(&packet);
+                }
+                client_replication.receive(client);
+            }
+        };
+
+    exchange(&mut server, &mut replication, &mut clients);
+    replication.set_state(player, vec![5u8; 64]);
+    replication.despawn(tree);
+    exchange(&mut server, &mut replication, &mut clients);
+
+    for (_, _, client_replication) in clients.iter_mut() {
+        let mut events = vec![];
+        while let Some(event) = client_replication.get_event() {
+            events.push(event);
+        }
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[2], ReplicationEvent::Despawned { id: tree });
+        assert_eq!(
+            events[3],
+            ReplicationEvent::Updated {
+                id: player,
+                state: vec![5u8; 64].into()
+            }
+        );
+        assert_eq!(client_replication.state(player), replication.state(player));
+    }
+}
+
+#[test]
+fn test_voice_relay() {
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let (talker_id, listener_id) = (ClientId::from_raw(1), ClientId::from_raw(2));
+    server.add_connection(talker_id);
+    server.add_connection(listener_id);
+    let mut talker = RenetClient::new(ConnectionConfig::default());
+    let mut listener = RenetClient::new(ConnectionConfig::default());
+    talker.set_connected();
+    listener.set_connected();
+    let mut receiver = VoiceReceiver::new(VoiceConfig::default());
+
+    let mut played = vec![];
+    for tick in 0..10u64 {
+        let now = Duration::from_millis(tick * 20);
+        talker.send_voice_frame(DefaultChannel::Unreliable, vec![tick as u8; 40]);
+        for packet in talker.get_packets_to_send() {
+            server.process_packet_from(&packet, talker_id).unwrap();
+        }
+        server.relay_voice_frames(DefaultChannel::Unreliable);
+        assert!(server
+            .get_packets_to_send(talker_id)
+            .unwrap()
+            .iter()
+            .all(|packet| packet.len() < 40));
+        for packet in server.get_packets_to_send(listener_id).unwrap() {
+            listener.process_packet(&packet);
+        }
+
+        for frame in listener.receive_voice_frames(DefaultChannel::Unreliable) {
+            receiver.push(frame, now);
+        }
+        played.extend(receiver.pop(now));
+    }
+
+    // Played after the target delay of 60ms
+    assert_eq!(played.len(), 7);
+    assert_eq!(played[0], (talker_id, VoicePlayout::Frame(vec![0; 40].into())));
+    assert_eq!(receiver.talkers(Duration::from_millis(200)).collect::<Vec<_>>(), vec![talker_id]);
+}
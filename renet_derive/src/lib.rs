@@ -0,0 +1,54 @@
+//! Derive macros for renet, enabled with its `derive` feature.
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Expr, ExprLit, Fields, Lit};
+
+/// Implements `renet::ChannelId` and `From<Self> for u8` for a fieldless enum.
+///
+/// The channel id of each variant is its discriminant, explicit discriminants must fit in a `u8`.
+/// The enum must also derive `Clone` and `Copy`.
+#[proc_macro_derive(ChannelId)]
+pub fn derive_channel_id(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_channel_id(input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn expand_channel_id(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new_spanned(&input.ident, "ChannelId can only be derived for enums"));
+    };
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(&input.generics, "ChannelId can't be derived for generic enums"));
+    }
+
+    let mut variants = Vec::with_capacity(data.variants.len());
+    let mut next_id: u16 = 0;
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new_spanned(variant, "ChannelId variants can't have fields"));
+        }
+        let id = match &variant.discriminant {
+            Some((_, Expr::Lit(ExprLit { lit: Lit::Int(int), .. }))) => int.base10_parse::<u16>().ok(),
+            Some(_) => None,
+            None => Some(next_id),
+        };
+        match id {
+            Some(id) if id <= u8::MAX as u16 => next_id = id + 1,
+            _ => return Err(Error::new_spanned(variant, "channel ids must be integers from 0 to 255")),
+        }
+        variants.push(&variant.ident);
+    }
+
+    let ident = &input.ident;
+    Ok(quote! {
+        impl ::core::convert::From<#ident> for u8 {
+            fn from(channel: #ident) -> Self {
+                channel as u8
+            }
+        }
+
+        impl ::renet::ChannelId for #ident {
+            const ALL: &'static [Self] = &[#(#ident::#variants),*];
+        }
+    })
+}
@@ -0,0 +1,43 @@
+use renet::{ChannelConfig, ChannelId, ConnectionConfig, RenetClient};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ChannelId)]
+enum GameChannel {
+    Input,
+    Chat = 5,
+    Events,
+}
+
+#[test]
+fn derive_channel_id() {
+    assert_eq!(u8::from(GameChannel::Input), 0);
+    assert_eq!(u8::from(GameChannel::Chat), 5);
+    assert_eq!(u8::from(GameChannel::Events), 6);
+    assert_eq!(GameChannel::ALL, &[GameChannel::Input, GameChannel::Chat, GameChannel::Events]);
+
+    let channels = vec![
+        ChannelConfig::unreliable(0).build().unwrap(),
+        ChannelConfig::reliable_ordered(5).build().unwrap(),
+        ChannelConfig::reliable_ordered(6).build().unwrap(),
+    ];
+    assert!(GameChannel::configured_in(&channels));
+    assert!(!GameChannel::configured_in(&channels[..2]));
+
+    let config = ConnectionConfig {
+        client_channels_config: channels.clone(),
+        server_channels_config: channels,
+        ..Default::default()
+    };
+    let mut client = RenetClient::new(config);
+    client.set_connected();
+    client.send_message(GameChannel::Chat, "hello");
+    assert_eq!(
+        client
+            .network_info()
+            .channels
+            .iter()
+            .find(|c| c.channel_id == 5)
+            .unwrap()
+            .send_queue,
+        1
+    );
+}
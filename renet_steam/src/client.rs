@@ -149,9 +149,10 @@ impl SteamClientTransport {
             unreachable!()
         };
         let packets = client.get_packets_to_send();
-        for packet in packets {
-            connection.send_message(&packet, SendFlags::UNRELIABLE)?;
+        for packet in packets.iter() {
+            connection.send_message(packet, SendFlags::UNRELIABLE)?;
         }
+        client.recycle_payloads(packets);
 
         connection.flush_messages()
     }
@@ -153,18 +153,20 @@ impl<T: Manager + 'static> SteamServerTransport<T> {
 
     /// Send packets to connected clients.
     pub fn send_packets(&mut self, server: &mut RenetServer) {
-        'clients: for client_id in server.clients_id() {
+        for client_id in server.clients_id() {
             let Some(connection) = self.connections.get(&client_id) else {
                 log::error!("Error while sending packet: connection not found");
                 continue;
             };
             let packets = server.get_packets_to_send(client_id).unwrap();
             // TODO: while this works fine we should probaly use the send_messages function from the listen_socket
-            for packet in packets {
-                if let Err(e) = connection.send_message(&packet, SendFlags::UNRELIABLE) {
-                    log::error!("Failed to send packet to client {client_id}: {e}");
-                    continue 'clients;
-                }
+            let sent = packets
+                .iter()
+                .try_for_each(|packet| connection.send_message(packet, SendFlags::UNRELIABLE).map(|_| ()));
+            server.recycle_payloads(client_id, packets);
+            if let Err(e) = sent {
+                log::error!("Failed to send packet to client {client_id}: {e}");
+                continue;
             }
 
             if let Err(e) = connection.flush_messages() {
@@ -10,6 +10,9 @@ use renet::{ClientId, NetworkInfo, RenetServer};
 use circular_buffer::CircularBuffer;
 
 mod circular_buffer;
+mod overlay;
+
+pub use overlay::NetworkOverlay;
 
 /// Egui visualizer for the renet client. Draws graphs with metrics:
 /// RTT, Packet Loss, Kbitps Sent/Received.
@@ -205,7 +208,7 @@ impl<const N: usize> RenetServerVisualizer<N> {
     /// # let mut visualizer = RenetServerVisualizer::<5>::new(Default::default());
     /// while let Some(event) = renet_server.get_event() {
     ///     match event {
-    ///         ServerEvent::ClientConnected { client_id } => {
+    ///         ServerEvent::ClientConnected { client_id, .. } => {
     ///             visualizer.add_client(client_id);
     ///             // ...
     ///         }
@@ -0,0 +1,130 @@
+use std::hash::Hash;
+
+use egui::{Response, RichText, Ui, Widget};
+use renet::{ChannelQueueInfo, NetworkHistory, NetworkSample, RenetClient, RenetServer};
+
+use crate::{show_graph, RenetVisualizerStyle, TextFormat, TopValue};
+
+enum Source<'a> {
+    Client(&'a RenetClient),
+    Server(&'a RenetServer),
+}
+
+/// Egui widget drawing the network health of a client, or of each client of a server:
+/// RTT, Packet Loss, Kbitps Sent/Received graphs for the last minute and the channel queue depths.
+///
+/// Unlike the visualizers it keeps no state, the graphs are drawn from the
+/// [`NetworkHistory`] sampled by renet, so it only needs to be added to the ui.
+///
+/// # Usage
+/// ```
+/// # use renet::RenetServer;
+/// # use renet_visualizer::NetworkOverlay;
+/// fn debug_ui(ui: &mut egui::Ui, server: &RenetServer) {
+///     ui.add(NetworkOverlay::server(server));
+/// }
+/// ```
+pub struct NetworkOverlay<'a> {
+    source: Source<'a>,
+    style: RenetVisualizerStyle,
+}
+
+impl<'a> NetworkOverlay<'a> {
+    pub fn client(client: &'a RenetClient) -> Self {
+        Self {
+            source: Source::Client(client),
+            style: RenetVisualizerStyle::default(),
+        }
+    }
+
+    /// Draws each client in a collapsible section.
+    pub fn server(server: &'a RenetServer) -> Self {
+        Self {
+            source: Source::Server(server),
+            style: RenetVisualizerStyle::default(),
+        }
+    }
+
+    pub fn style(mut self, style: RenetVisualizerStyle) -> Self {
+        self.style = style;
+        self
+    }
+}
+
+impl Widget for NetworkOverlay<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        ui.vertical(|ui| match self.source {
+            Source::Client(client) => {
+                draw_connection(ui, &self.style, "client", client.network_history(), &client.network_info().channels);
+            }
+            Source::Server(server) => {
+                let mut clients_id = server.clients_id();
+                clients_id.sort();
+                for client_id in clients_id {
+                    let (Ok(history), Ok(network_info)) = (server.network_history(client_id), server.network_info(client_id)) else {
+                        continue;
+                    };
+                    egui::CollapsingHeader::new(RichText::new(format!("Client {}", client_id)).color(self.style.text_color))
+                        .id_source(client_id)
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            draw_connection(ui, &self.style, client_id, history, &network_info.channels);
+                        });
+                }
+            }
+        })
+        .response
+    }
+}
+
+fn draw_connection(ui: &mut Ui, style: &RenetVisualizerStyle, id: impl Hash, history: &NetworkHistory, channels: &[ChannelQueueInfo]) {
+    let samples = |value: fn(&NetworkSample) -> f64| history.iter().map(|sample| value(sample) as f32).collect::<Vec<_>>();
+
+    ui.horizontal(|ui| {
+        show_graph(
+            ui,
+            style,
+            "Received Kbitps",
+            TextFormat::Normal,
+            TopValue::MaxValue { multiplicated: 1.5 },
+            samples(|sample| sample.received_kbps),
+        );
+        show_graph(
+            ui,
+            style,
+            "Sent Kbitps",
+            TextFormat::Normal,
+            TopValue::MaxValue { multiplicated: 1.5 },
+            samples(|sample| sample.sent_kbps),
+        );
+        show_graph(
+            ui,
+            style,
+            "Round Time Trip (ms)",
+            TextFormat::Normal,
+            TopValue::SuggestedValues([32., 64., 128., 256., 512.]),
+            samples(|sample| sample.rtt * 1000.),
+        );
+        show_graph(
+            ui,
+            style,
+            "Packet Loss",
+            TextFormat::Percentage,
+            TopValue::SuggestedValues([0.05, 0.1, 0.25, 0.5, 1.]),
+            samples(|sample| sample.packet_loss),
+        );
+    });
+
+    egui::Grid::new(("renet_channel_queues", id)).striped(true).show(ui, |ui| {
+        for header in ["Channel", "Send queue", "Receive queue"] {
+            ui.label(RichText::new(header).strong().color(style.text_color));
+        }
+        ui.end_row();
+        for channel in channels {
+            ui.label(RichText::new(channel.channel_id.to_string()).color(style.text_color));
+            ui.label(RichText::new(channel.send_queue.to_string()).color(style.text_color));
+            ui.label(RichText::new(channel.receive_queue.to_string()).color(style.text_color));
+            ui.end_row();
+        }
+    });
+}
@@ -101,6 +101,7 @@ fn handle_server_result(
             user_data,
             payload,
             addr,
+            ..
         } => {
             let username = Username::from_user_data(&user_data);
             println!("Client {} with id {} connected.", username.0, client_id);
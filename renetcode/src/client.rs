@@ -2,7 +2,8 @@ use std::{error::Error, fmt, net::SocketAddr, time::Duration};
 
 use crate::{
     packet::Packet, replay_protection::ReplayProtection, token::ConnectToken, NetcodeError, NETCODE_CHALLENGE_TOKEN_BYTES,
-    NETCODE_KEY_BYTES, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_SEND_RATE, NETCODE_USER_DATA_BYTES,
+    NETCODE_KEY_BYTES, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_REQUEST_DATA_BYTES, NETCODE_SEND_RATE,
+    NETCODE_USER_DATA_BYTES,
 };
 
 /// The reason why a client is in error state
@@ -12,6 +13,7 @@ pub enum DisconnectReason {
     ConnectionTimedOut,
     ConnectionResponseTimedOut,
     ConnectionRequestTimedOut,
+    /// The server denied the connection because it's full.
     ConnectionDenied,
     DisconnectedByClient,
     DisconnectedByServer,
@@ -62,6 +64,8 @@ pub struct NetcodeClient {
     connect_token: ConnectToken,
     challenge_token_sequence: u64,
     challenge_token_data: [u8; NETCODE_CHALLENGE_TOKEN_BYTES],
+    request_data: Option<[u8; NETCODE_REQUEST_DATA_BYTES]>,
+    path_challenge: Option<u64>,
     max_clients: u32,
     client_index: u32,
     send_rate: Duration,
@@ -125,6 +129,8 @@ impl NetcodeClient {
             client_index: 0,
             send_rate: NETCODE_SEND_RATE,
             challenge_token_data: [0u8; NETCODE_CHALLENGE_TOKEN_BYTES],
+            request_data: None,
+            path_challenge: None,
             connect_token,
             replay_protection: ReplayProtection::new(),
             out: [0u8; NETCODE_MAX_PACKET_BYTES],
@@ -138,6 +144,12 @@ impl NetcodeClient {
         )
     }
 
+    /// Returns whether the client received the challenge from the server,
+    /// and is waiting for the server to accept the connection.
+    pub fn is_challenged(&self) -> bool {
+        self.state == ClientState::SendingConnectionResponse
+    }
+
     pub fn is_connected(&self) -> bool {
         self.state == ClientState::Connected
     }
@@ -160,6 +172,11 @@ impl NetcodeClient {
         self.current_time - self.last_packet_received_time
     }
 
+    /// Returns the number of packets from the server dropped because they were replayed.
+    pub fn replayed_packets(&self) -> u64 {
+        self.replay_protection.replayed_packets()
+    }
+
     /// Returns the reason that the client was disconnected for.
     pub fn disconnect_reason(&self) -> Option<DisconnectReason> {
         if let ClientState::Disconnected(reason) = &self.state {
@@ -168,6 +185,14 @@ impl NetcodeClient {
         None
     }
 
+    /// Sets the data sent to the server with the connection response, available in
+    /// [`ServerResult::ClientConnected`](crate::ServerResult::ClientConnected).
+    /// Unlike the user data of the connect token, it's chosen by the client.
+    /// Must be set before the client receives the challenge of the server.
+    pub fn set_request_data(&mut self, request_data: [u8; NETCODE_REQUEST_DATA_BYTES]) {
+        self.request_data = Some(request_data);
+    }
+
     /// Returns the current server address the client is connected or trying to connect.
     pub fn server_addr(&self) -> SocketAddr {
         self.server_addr
@@ -236,6 +261,11 @@ impl NetcodeClient {
                 self.last_packet_received_time = self.current_time;
                 return Some(p);
             }
+            // The server received a packet from a new address of the client, and checks that it can answer from it
+            (Packet::PathChallenge { token }, ClientState::Connected) => {
+                self.last_packet_received_time = self.current_time;
+                self.path_challenge = Some(token);
+            }
             (Packet::Disconnect, ClientState::Connected) => {
                 self.state = ClientState::Disconnected(DisconnectReason::DisconnectedByServer);
                 self.last_packet_received_time = self.current_time;
@@ -334,6 +364,13 @@ impl NetcodeClient {
     }
 
     fn generate_packet(&mut self) -> Option<(&mut [u8], SocketAddr)> {
+        // Path challenges are answered right away, the server only migrates the client after the response
+        if let Some(token) = self.path_challenge.take() {
+            if self.state == ClientState::Connected {
+                return self.encode_packet(Packet::PathResponse { token });
+            }
+        }
+
         if let Some(last_packet_send_time) = self.last_packet_send_time {
             if self.current_time - last_packet_send_time < self.send_rate {
                 return None;
@@ -351,6 +388,7 @@ impl NetcodeClient {
             ClientState::SendingConnectionResponse => Packet::Response {
                 token_sequence: self.challenge_token_sequence,
                 token_data: self.challenge_token_data,
+                request_data: self.request_data,
             },
             ClientState::Connected => Packet::KeepAlive {
                 client_index: 0,
@@ -359,6 +397,10 @@ impl NetcodeClient {
             _ => return None,
         };
 
+        self.encode_packet(packet)
+    }
+
+    fn encode_packet(&mut self, packet: Packet) -> Option<(&mut [u8], SocketAddr)> {
         let result = packet.encode(
             &mut self.out,
             self.connect_token.protocol_id,
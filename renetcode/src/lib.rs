@@ -28,8 +28,8 @@ mod token;
 pub use client::{ClientAuthentication, DisconnectReason, NetcodeClient};
 pub use crypto::generate_random_bytes;
 pub use error::NetcodeError;
-pub use server::{NetcodeServer, ServerAuthentication, ServerConfig, ServerResult};
-pub use token::{ConnectToken, TokenGenerationError};
+pub use server::{ConnectionRequestStats, NetcodeServer, ServerAuthentication, ServerConfig, ServerResult};
+pub use token::{ConnectToken, ConnectTokenBuilder, TokenGenerationError};
 
 use std::time::Duration;
 
@@ -52,6 +52,9 @@ pub const NETCODE_KEY_BYTES: usize = 32;
 const NETCODE_MAC_BYTES: usize = 16;
 /// The number of bytes that an user data can contain in the ConnectToken.
 pub const NETCODE_USER_DATA_BYTES: usize = 256;
+/// The number of bytes of the optional data the client sends with the connection response,
+/// see [`NetcodeClient::set_request_data`]. It's encrypted and authenticated like the rest of the packet.
+pub const NETCODE_REQUEST_DATA_BYTES: usize = 32;
 const NETCODE_CHALLENGE_TOKEN_BYTES: usize = 300;
 const NETCODE_CONNECT_TOKEN_XNONCE_BYTES: usize = 24;
 
@@ -7,7 +7,12 @@ use crate::{
     serialize::*, NetcodeError, NETCODE_CHALLENGE_TOKEN_BYTES, NETCODE_CONNECT_TOKEN_PRIVATE_BYTES, NETCODE_CONNECT_TOKEN_XNONCE_BYTES,
     NETCODE_KEY_BYTES, NETCODE_MAC_BYTES,
 };
-use crate::{NETCODE_USER_DATA_BYTES, NETCODE_VERSION_INFO};
+use crate::{NETCODE_REQUEST_DATA_BYTES, NETCODE_USER_DATA_BYTES, NETCODE_VERSION_INFO};
+
+// Prefix, version info, protocol id, expire timestamp, xnonce and the encrypted private connect token.
+// Responses to unauthenticated addresses are smaller than this, so the server can't be used to amplify traffic.
+pub(crate) const CONNECTION_REQUEST_BYTES: usize =
+    1 + 13 + 8 + 8 + NETCODE_CONNECT_TOKEN_XNONCE_BYTES + NETCODE_CONNECT_TOKEN_PRIVATE_BYTES;
 
 #[derive(Debug)]
 #[repr(u8)]
@@ -19,6 +24,8 @@ pub enum PacketType {
     KeepAlive = 4,
     Payload = 5,
     Disconnect = 6,
+    PathChallenge = 7,
+    PathResponse = 8,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -39,6 +46,8 @@ pub enum Packet<'a> {
     Response {
         token_sequence: u64,
         token_data: [u8; NETCODE_CHALLENGE_TOKEN_BYTES], // encrypted ChallengeToken
+        // Not part of the netcode standard, only written when set so standard servers still accept the packet.
+        request_data: Option<[u8; NETCODE_REQUEST_DATA_BYTES]>,
     },
     KeepAlive {
         client_index: u32,
@@ -46,6 +55,13 @@ pub enum Packet<'a> {
     },
     Payload(&'a [u8]),
     Disconnect,
+    // Not part of the netcode standard, used to validate the new address of a migrating client.
+    PathChallenge {
+        token: u64,
+    },
+    PathResponse {
+        token: u64,
+    },
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -66,6 +82,8 @@ impl PacketType {
             4 => KeepAlive,
             5 => Payload,
             6 => Disconnect,
+            7 => PathChallenge,
+            8 => PathResponse,
             _ => return Err(NetcodeError::InvalidPacketType),
         };
         Ok(packet_type)
@@ -74,7 +92,7 @@ impl PacketType {
     fn apply_replay_protection(&self) -> bool {
         use PacketType::*;
 
-        matches!(self, KeepAlive | Payload | Disconnect)
+        matches!(self, KeepAlive | Payload | Disconnect | PathChallenge | PathResponse)
     }
 }
 
@@ -88,6 +106,8 @@ impl<'a> Packet<'a> {
             Packet::KeepAlive { .. } => PacketType::KeepAlive,
             Packet::Payload { .. } => PacketType::Payload,
             Packet::Disconnect => PacketType::Disconnect,
+            Packet::PathChallenge { .. } => PacketType::PathChallenge,
+            Packet::PathResponse { .. } => PacketType::PathResponse,
         }
     }
 
@@ -140,13 +160,20 @@ impl<'a> Packet<'a> {
             Packet::Challenge {
                 token_data,
                 token_sequence,
+            } => {
+                writer.write_all(&token_sequence.to_le_bytes())?;
+                writer.write_all(token_data)?;
             }
-            | Packet::Response {
+            Packet::Response {
                 token_data,
                 token_sequence,
+                request_data,
             } => {
                 writer.write_all(&token_sequence.to_le_bytes())?;
                 writer.write_all(token_data)?;
+                if let Some(request_data) = request_data {
+                    writer.write_all(request_data)?;
+                }
             }
             Packet::KeepAlive { max_clients, client_index } => {
                 writer.write_all(&client_index.to_le_bytes())?;
@@ -155,6 +182,9 @@ impl<'a> Packet<'a> {
             Packet::Payload(p) => {
                 writer.write_all(p)?;
             }
+            Packet::PathChallenge { token } | Packet::PathResponse { token } => {
+                writer.write_all(&token.to_le_bytes())?;
+            }
             Packet::ConnectionDenied | Packet::Disconnect => {}
         }
 
@@ -196,10 +226,13 @@ impl<'a> Packet<'a> {
             PacketType::Response => {
                 let token_sequence = read_u64(src)?;
                 let token_data = read_bytes(src)?;
+                let remaining = src.get_ref().len() - src.position() as usize;
+                let request_data = if remaining >= NETCODE_REQUEST_DATA_BYTES { Some(read_bytes(src)?) } else { None };
 
                 Ok(Packet::Response {
                     token_data,
                     token_sequence,
+                    request_data,
                 })
             }
             PacketType::KeepAlive => {
@@ -208,6 +241,8 @@ impl<'a> Packet<'a> {
 
                 Ok(Packet::KeepAlive { client_index, max_clients })
             }
+            PacketType::PathChallenge => Ok(Packet::PathChallenge { token: read_u64(src)? }),
+            PacketType::PathResponse => Ok(Packet::PathResponse { token: read_u64(src)? }),
             PacketType::ConnectionDenied => Ok(Packet::ConnectionDenied),
             PacketType::Disconnect => Ok(Packet::Disconnect),
             PacketType::Payload => unreachable!(),
@@ -267,6 +302,9 @@ impl<'a> Packet<'a> {
         let packet_type = PacketType::from_u8(packet_type)?;
 
         if matches!(packet_type, PacketType::ConnectionRequest) {
+            if buffer.len() < CONNECTION_REQUEST_BYTES {
+                return Err(NetcodeError::PacketTooSmall);
+            }
             Ok((0, Packet::read(PacketType::ConnectionRequest, &buffer[1..])?))
         } else if let Some(private_key) = private_key {
             let (sequence, aad, read_pos) = {
@@ -277,8 +315,10 @@ impl<'a> Packet<'a> {
                 (sequence, additional_data, src.position() as usize)
             };
 
-            if let Some(ref replay_protection) = replay_protection {
+            let mut replay_protection = replay_protection;
+            if let Some(replay_protection) = replay_protection.as_deref_mut() {
                 if packet_type.apply_replay_protection() && replay_protection.already_received(sequence) {
+                    replay_protection.dropped_replayed_packet();
                     return Err(NetcodeError::DuplicatedSequence);
                 }
             }
@@ -396,6 +436,12 @@ mod tests {
         let deserialized = Packet::read(PacketType::ConnectionRequest, &buffer).unwrap();
 
         assert_eq!(deserialized, connection_request);
+
+        let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+        let len = connection_request.encode(&mut buffer, 1, None).unwrap();
+        assert_eq!(len, CONNECTION_REQUEST_BYTES);
+        let truncated = Packet::decode(&mut buffer[..len - 1], 1, None, None);
+        assert!(matches!(truncated, Err(NetcodeError::PacketTooSmall)));
     }
 
     #[test]
@@ -412,6 +458,23 @@ mod tests {
         assert_eq!(deserialized, connection_challenge);
     }
 
+    #[test]
+    fn connection_response_serialization() {
+        for request_data in [None, Some([3u8; NETCODE_REQUEST_DATA_BYTES])] {
+            let connection_response = Packet::Response {
+                token_sequence: 5,
+                token_data: [1u8; 300],
+                request_data,
+            };
+
+            let mut buffer = Vec::new();
+            connection_response.write(&mut buffer).unwrap();
+            let deserialized = Packet::read(PacketType::Response, buffer.as_slice()).unwrap();
+
+            assert_eq!(deserialized, connection_response);
+        }
+    }
+
     #[test]
     fn connection_keep_alive_serialization() {
         let connection_keep_alive = Packet::KeepAlive {
@@ -488,6 +551,39 @@ mod tests {
         assert_eq!(packet, d_packet);
     }
 
+    #[test]
+    fn tampered_payload_packet() {
+        let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+        let payload = vec![7u8; 10];
+        let key = b"an example very very secret key."; // 32-bytes
+        let packet = Packet::Payload(&payload);
+        let len = packet.encode(&mut buffer, 12, Some((0, key))).unwrap();
+        assert!(
+            !buffer[..len].windows(payload.len()).any(|w| w == payload),
+            "payload should be encrypted"
+        );
+
+        for i in 1..len {
+            let mut tampered = buffer;
+            tampered[i] ^= 1;
+            let result = Packet::decode(&mut tampered[..len], 12, Some(key), None);
+            assert!(matches!(result, Err(NetcodeError::CryptoError)), "tampered byte {i}");
+        }
+    }
+
+    #[test]
+    fn invalid_protocol_id() {
+        let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+        let payload = vec![7u8; 10];
+        let key = b"an example very very secret key."; // 32-bytes
+        let packet = Packet::Payload(&payload);
+        let len = packet.encode(&mut buffer, 12, Some((0, key))).unwrap();
+
+        // The protocol id is part of the additional data, so packets from other protocols fail to decrypt
+        let result = Packet::decode(&mut buffer[..len], 13, Some(key), None);
+        assert!(matches!(result, Err(NetcodeError::CryptoError)));
+    }
+
     #[test]
     fn encrypt_decrypt_challenge_token() {
         let client_id = 0;
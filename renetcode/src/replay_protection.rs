@@ -5,6 +5,7 @@ const EMPTY: u64 = u64::MAX;
 pub struct ReplayProtection {
     most_recent_sequence: u64,
     received_packet: [u64; NETCODE_REPLAY_BUFFER_SIZE],
+    replayed_packets: u64,
 }
 
 impl Default for ReplayProtection {
@@ -18,6 +19,7 @@ impl ReplayProtection {
         Self {
             most_recent_sequence: 0,
             received_packet: [EMPTY; NETCODE_REPLAY_BUFFER_SIZE],
+            replayed_packets: 0,
         }
     }
 
@@ -38,6 +40,16 @@ impl ReplayProtection {
         false
     }
 
+    /// Marks that a packet was dropped because it was already received.
+    pub fn dropped_replayed_packet(&mut self) {
+        self.replayed_packets += 1;
+    }
+
+    /// Returns the number of packets dropped because they were already received.
+    pub fn replayed_packets(&self) -> u64 {
+        self.replayed_packets
+    }
+
     pub fn advance_sequence(&mut self, sequence: u64) {
         if sequence > self.most_recent_sequence {
             self.most_recent_sequence = sequence;
@@ -1,15 +1,80 @@
-use std::{collections::HashMap, net::SocketAddr, time::Duration};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
 
 use crate::{
     crypto::generate_random_bytes,
-    packet::{ChallengeToken, Packet},
+    packet::{ChallengeToken, Packet, PacketType},
     replay_protection::ReplayProtection,
     token::PrivateConnectToken,
     NetcodeError, NETCODE_CONNECT_TOKEN_PRIVATE_BYTES, NETCODE_CONNECT_TOKEN_XNONCE_BYTES, NETCODE_KEY_BYTES, NETCODE_MAC_BYTES,
-    NETCODE_MAX_CLIENTS, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_MAX_PENDING_CLIENTS, NETCODE_SEND_RATE,
-    NETCODE_USER_DATA_BYTES, NETCODE_VERSION_INFO,
+    NETCODE_MAX_CLIENTS, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_MAX_PENDING_CLIENTS, NETCODE_REQUEST_DATA_BYTES,
+    NETCODE_SEND_RATE, NETCODE_USER_DATA_BYTES, NETCODE_VERSION_INFO,
 };
 
+// Maximum of addresses tracked by each rate limit.
+const MAX_RATE_LIMITED_ADDRESSES: usize = 4096;
+// Trial decryptions of packets from unknown addresses between updates, for all addresses.
+// The per address limit doesn't bound them, spoofed addresses are free.
+const MIGRATION_DECRYPTIONS_PER_UPDATE: usize = 256;
+
+// Token bucket for each address, addresses with a full bucket are not tracked.
+#[derive(Debug)]
+struct RateLimiter {
+    tokens: HashMap<IpAddr, f64>,
+    // Order in which the tracked addresses were added
+    order: VecDeque<IpAddr>,
+    per_second: f64,
+    burst: f64,
+}
+
+impl RateLimiter {
+    fn new(per_second: u32, burst: u32) -> Self {
+        Self {
+            tokens: HashMap::new(),
+            order: VecDeque::new(),
+            per_second: per_second as f64,
+            burst: burst as f64,
+        }
+    }
+
+    fn consume(&mut self, ip: IpAddr) -> bool {
+        if !self.tokens.contains_key(&ip) {
+            // Evict the oldest address when too many are tracked, instead of refusing new addresses,
+            // so spoofed addresses filling the limiter can't lock out legitimate ones
+            if self.tokens.len() >= MAX_RATE_LIMITED_ADDRESSES {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.tokens.remove(&oldest);
+                }
+            }
+            self.tokens.insert(ip, self.burst);
+            self.order.push_back(ip);
+        }
+
+        let tokens = self.tokens.get_mut(&ip).unwrap();
+        if *tokens < 1.0 {
+            return false;
+        }
+
+        *tokens -= 1.0;
+        true
+    }
+
+    fn refill(&mut self, duration: Duration) {
+        let refill = self.per_second * duration.as_secs_f64();
+        for tokens in self.tokens.values_mut() {
+            *tokens += refill;
+        }
+        // Full buckets are the same as untracked addresses
+        let burst = self.burst;
+        self.tokens.retain(|_, tokens| *tokens < burst);
+        let tokens = &self.tokens;
+        self.order.retain(|ip| tokens.contains_key(ip));
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ConnectionState {
     Disconnected,
@@ -32,6 +97,15 @@ struct Connection {
     sequence: u64,
     expire_timestamp: u64,
     replay_protection: ReplayProtection,
+    path_challenge: Option<PathChallenge>,
+}
+
+// Challenge sent to the new address of a migrating client
+#[derive(Debug, Clone, Copy)]
+struct PathChallenge {
+    addr: SocketAddr,
+    token: u64,
+    sent_at: Duration,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -58,9 +132,34 @@ pub struct NetcodeServer {
     current_time: Duration,
     global_sequence: u64,
     secure: bool,
+    connection_migration: bool,
+    // Value is when the ban expires
+    banned_ips: HashMap<IpAddr, Option<Duration>>,
+    banned_clients: HashMap<u64, Option<Duration>>,
+    allow_list: Option<HashSet<u64>>,
+    request_limiter: RateLimiter,
+    packet_limiter: RateLimiter,
+    migration_limiter: RateLimiter,
+    migration_budget: usize,
+    rate_limited_packets: u64,
+    request_stats: ConnectionRequestStats,
+    amplification_limit: f64,
     out: [u8; NETCODE_MAX_PACKET_BYTES],
 }
 
+/// Counters for the connection requests received by the server.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionRequestStats {
+    /// Connection requests received.
+    pub received: u64,
+    /// Requests dropped because its address sent too many requests.
+    pub rate_limited: u64,
+    /// Requests dropped because the maximum amount of pending clients was reached.
+    pub pending_limit_reached: u64,
+    /// Responses to unauthenticated addresses not sent because they were above the amplification limit.
+    pub amplification_limited: u64,
+}
+
 /// Result from processing an packet in the server
 #[derive(Debug, PartialEq, Eq)]
 pub enum ServerResult<'a, 's> {
@@ -75,6 +174,8 @@ pub enum ServerResult<'a, 's> {
         client_id: u64,
         addr: SocketAddr,
         user_data: Box<[u8; NETCODE_USER_DATA_BYTES]>,
+        /// Data set by the client with [`NetcodeClient::set_request_data`](crate::NetcodeClient::set_request_data).
+        request_data: Option<[u8; NETCODE_REQUEST_DATA_BYTES]>,
         payload: &'s mut [u8],
     },
     /// The client connection has been terminated.
@@ -145,6 +246,17 @@ impl NetcodeServer {
             public_addresses: config.public_addresses,
             current_time: config.current_time,
             secure,
+            connection_migration: false,
+            banned_ips: HashMap::new(),
+            banned_clients: HashMap::new(),
+            allow_list: None,
+            request_limiter: RateLimiter::new(10, 20),
+            packet_limiter: RateLimiter::new(1000, 2000),
+            migration_limiter: RateLimiter::new(10, 20),
+            migration_budget: MIGRATION_DECRYPTIONS_PER_UPDATE,
+            rate_limited_packets: 0,
+            request_stats: ConnectionRequestStats::default(),
+            amplification_limit: 1.0,
             out: [0u8; NETCODE_MAX_PACKET_BYTES],
         }
     }
@@ -223,6 +335,11 @@ impl NetcodeServer {
         None
     }
 
+    /// Returns the number of packets from the connected client dropped because they were replayed.
+    pub fn replayed_packets(&self, client_id: u64) -> Option<u64> {
+        find_client_by_id(&self.clients, client_id).map(|client| client.replay_protection.replayed_packets())
+    }
+
     /// Returns the client address if connected.
     pub fn client_addr(&self, client_id: u64) -> Option<SocketAddr> {
         if let Some(client) = find_client_by_id(&self.clients, client_id) {
@@ -232,15 +349,218 @@ impl NetcodeServer {
         None
     }
 
+    /// Enables connected clients to keep their connection when their address changes,
+    /// for example mobile clients switching from Wi-Fi to cellular.
+    /// Packets from unknown addresses are decrypted with the keys of each connected client,
+    /// at most 10 per second for each ip address, and at most 256 decryptions between updates
+    /// for all addresses. When one matches, the server sends a challenge
+    /// to the new address, and the client is only moved there once it answers from it.
+    /// Disabled by default.
+    pub fn set_connection_migration(&mut self, enabled: bool) {
+        self.connection_migration = enabled;
+    }
+
+    /// Bans a client id, its connection requests are ignored until the ban expires.
+    /// The ban is permanent if no duration is given.
+    /// Does not disconnect the client if it's already connected, use [`NetcodeServer::disconnect`] for that.
+    pub fn ban_client(&mut self, client_id: u64, duration: Option<Duration>) {
+        self.banned_clients.insert(client_id, duration.map(|d| self.current_time + d));
+        self.pending_clients.retain(|_, pending| pending.client_id != client_id);
+    }
+
+    /// Removes the ban from a client id.
+    pub fn unban_client(&mut self, client_id: u64) {
+        self.banned_clients.remove(&client_id);
+    }
+
+    /// Bans an ip address, its connection requests are ignored until the ban expires.
+    /// The ban is permanent if no duration is given.
+    /// Does not disconnect clients already connected from it, use [`NetcodeServer::disconnect`] for that.
+    pub fn ban_ip(&mut self, ip: IpAddr, duration: Option<Duration>) {
+        self.banned_ips.insert(ip, duration.map(|d| self.current_time + d));
+        self.pending_clients.retain(|addr, _| addr.ip() != ip);
+    }
+
+    /// Removes the ban from an ip address.
+    pub fn unban_ip(&mut self, ip: IpAddr) {
+        self.banned_ips.remove(&ip);
+    }
+
+    /// Returns whether the client id is banned or not in the allow list.
+    pub fn is_client_banned(&self, client_id: u64) -> bool {
+        if let Some(allow_list) = &self.allow_list {
+            if !allow_list.contains(&client_id) {
+                return true;
+            }
+        }
+
+        self.banned_clients.contains_key(&client_id)
+    }
+
+    /// Returns whether the ip address is banned.
+    pub fn is_ip_banned(&self, ip: IpAddr) -> bool {
+        self.banned_ips.contains_key(&ip)
+    }
+
+    /// Sets the client ids allowed to connect, connection requests from other clients are ignored.
+    /// Banned clients are still ignored when they are in the list. Use `None` to allow all clients.
+    pub fn set_allow_list(&mut self, allow_list: Option<HashSet<u64>>) {
+        self.allow_list = allow_list;
+    }
+
+    /// Sets how many connection requests each ip address can send per second, and how many at once.
+    /// Requests above the limit are dropped before the connect token is decrypted.
+    /// Default: 10 requests per second with a burst of 20.
+    pub fn set_connection_request_rate_limit(&mut self, requests_per_second: u32, burst: u32) {
+        self.request_limiter = RateLimiter::new(requests_per_second, burst);
+    }
+
+    /// Sets how many packets each ip address can send per second, and how many at once.
+    /// Packets above the limit are dropped before they are parsed or decrypted, so a single address
+    /// can't use all the time spent processing packets. Only packets from addresses that are not
+    /// connected clients are limited, so clients behind the same NAT are not affected.
+    /// Default: 1000 packets per second with a burst of 2000.
+    pub fn set_packet_rate_limit(&mut self, packets_per_second: u32, burst: u32) {
+        self.packet_limiter = RateLimiter::new(packets_per_second, burst);
+    }
+
+    /// Returns the number of packets dropped by the packet rate limit.
+    pub fn rate_limited_packets(&self) -> u64 {
+        self.rate_limited_packets
+    }
+
+    /// Sets the maximum ratio between the size of a packet sent to an address not connected yet
+    /// and the size of the packet it answers. Responses above it are dropped, so spoofed connection
+    /// requests can't make the server send more traffic to a victim than the attacker sent.
+    /// Default: 1.0, the server never sends more bytes than it received.
+    pub fn set_amplification_limit(&mut self, ratio: f64) {
+        self.amplification_limit = ratio.max(0.0);
+    }
+
+    /// Returns the counters of the connection requests received.
+    pub fn connection_request_stats(&self) -> ConnectionRequestStats {
+        self.request_stats
+    }
+
+    fn exceeds_amplification_limit(&mut self, received: usize, len: usize) -> bool {
+        if len as f64 > received as f64 * self.amplification_limit {
+            self.request_stats.amplification_limited += 1;
+            log::trace!(
+                "Dropped response of {} bytes to a packet of {} bytes: above amplification limit",
+                len,
+                received
+            );
+            return true;
+        }
+        false
+    }
+
+    // Validates the new address of the connected client that sent the packet.
+    // A payload or keep-alive that passes the replay protection makes the server send a challenge to the address,
+    // the client is only moved there when it answers from it. An attacker re-sending captured packets
+    // from its own address can't redirect the connection, since it can't read the challenge.
+    // Returns the length of the challenge packet written in `out`.
+    fn migrate_client(&mut self, addr: SocketAddr, buffer: &[u8]) -> Result<Option<usize>, NetcodeError> {
+        if buffer.len() > NETCODE_MAX_PACKET_BYTES || buffer[0] == PacketType::ConnectionRequest as u8 {
+            return Ok(None);
+        }
+        if self.pending_clients.contains_key(&addr) {
+            return Ok(None);
+        }
+        // Each packet is decrypted with the keys of every client
+        let decryptions = self
+            .clients
+            .iter()
+            .flatten()
+            .filter(|c| c.state == ConnectionState::Connected)
+            .count();
+        if decryptions > self.migration_budget {
+            self.rate_limited_packets += 1;
+            log::trace!("Dropped packet: too many packets from unknown addresses, from {}", addr);
+            return Ok(None);
+        }
+        if !self.migration_limiter.consume(addr.ip()) {
+            self.rate_limited_packets += 1;
+            log::trace!("Dropped packet: too many packets from unknown address {}", addr);
+            return Ok(None);
+        }
+        self.migration_budget -= decryptions;
+
+        // The buffer is only modified when the decryption succeeds
+        let mut decoded = [0u8; NETCODE_MAX_PACKET_BYTES];
+        let decoded = &mut decoded[..buffer.len()];
+        decoded.copy_from_slice(buffer);
+        for client in self.clients.iter_mut().flatten() {
+            if client.state != ConnectionState::Connected {
+                continue;
+            }
+
+            let mut replay_protection = client.replay_protection.clone();
+            let packet = Packet::decode(decoded, self.protocol_id, Some(&client.receive_key), Some(&mut replay_protection));
+            match packet {
+                Ok((_, Packet::PathResponse { token })) => {
+                    if matches!(client.path_challenge, Some(challenge) if challenge.addr == addr && challenge.token == token) {
+                        log::debug!("Client {} migrated from {} to {}", client.client_id, client.addr, addr);
+                        client.addr = addr;
+                        client.path_challenge = None;
+                    }
+                    return Ok(None);
+                }
+                Ok((_, Packet::Payload(_) | Packet::KeepAlive { .. })) => {
+                    let recently_challenged = matches!(
+                        client.path_challenge,
+                        Some(challenge) if challenge.addr == addr && self.current_time - challenge.sent_at < NETCODE_SEND_RATE
+                    );
+                    if recently_challenged {
+                        return Ok(None);
+                    }
+
+                    let token = u64::from_le_bytes(generate_random_bytes());
+                    client.path_challenge = Some(PathChallenge {
+                        addr,
+                        token,
+                        sent_at: self.current_time,
+                    });
+                    let packet = Packet::PathChallenge { token };
+                    let len = packet.encode(&mut self.out, self.protocol_id, Some((client.sequence, &client.send_key)))?;
+                    client.sequence += 1;
+                    log::trace!("Sent path challenge to {} for Client {}", addr, client.client_id);
+                    return Ok(Some(len));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(None)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn handle_connection_request<'a>(
         &mut self,
         addr: SocketAddr,
+        received: usize,
         version_info: [u8; 13],
         protocol_id: u64,
         expire_timestamp: u64,
         xnonce: [u8; NETCODE_CONNECT_TOKEN_XNONCE_BYTES],
         data: [u8; NETCODE_CONNECT_TOKEN_PRIVATE_BYTES],
     ) -> Result<ServerResult<'a, '_>, NetcodeError> {
+        self.request_stats.received += 1;
+        if !self.request_limiter.consume(addr.ip()) {
+            self.request_stats.rate_limited += 1;
+            log::trace!("Connection request denied: too many requests from {}", addr.ip());
+            return Ok(ServerResult::None);
+        }
+
+        if !self.pending_clients.contains_key(&addr) && self.pending_clients.len() >= NETCODE_MAX_PENDING_CLIENTS {
+            self.request_stats.pending_limit_reached += 1;
+            log::warn!(
+                "Connection request denied: reached max amount allowed of pending clients ({}).",
+                NETCODE_MAX_PENDING_CLIENTS
+            );
+            return Ok(ServerResult::None);
+        }
+
         if version_info != *NETCODE_VERSION_INFO {
             return Err(NetcodeError::InvalidVersion);
         }
@@ -268,6 +588,14 @@ impl NetcodeServer {
             }
         }
 
+        if self.is_client_banned(connect_token.client_id) {
+            log::debug!(
+                "Connection request denied: client {} is banned or not allowed.",
+                connect_token.client_id
+            );
+            return Ok(ServerResult::None);
+        }
+
         let addr_already_connected = find_client_mut_by_addr(&mut self.clients, addr).is_some();
         let id_already_connected = find_client_mut_by_id(&mut self.clients, connect_token.client_id).is_some();
         if id_already_connected || addr_already_connected {
@@ -279,14 +607,6 @@ impl NetcodeServer {
             return Ok(ServerResult::None);
         }
 
-        if !self.pending_clients.contains_key(&addr) && self.pending_clients.len() >= NETCODE_MAX_PENDING_CLIENTS {
-            log::warn!(
-                "Connection request denied: reached max amount allowed of pending clients ({}).",
-                NETCODE_MAX_PENDING_CLIENTS
-            );
-            return Ok(ServerResult::None);
-        }
-
         let mut mac = [0u8; NETCODE_MAC_BYTES];
         mac.copy_from_slice(&data[NETCODE_CONNECT_TOKEN_PRIVATE_BYTES - NETCODE_MAC_BYTES..]);
         let connect_token_entry = ConnectTokenEntry {
@@ -309,6 +629,9 @@ impl NetcodeServer {
                 Some((self.global_sequence, &connect_token.server_to_client_key)),
             )?;
             self.global_sequence += 1;
+            if self.exceeds_amplification_limit(received, len) {
+                return Ok(ServerResult::None);
+            }
             return Ok(ServerResult::PacketToSend {
                 addr,
                 payload: &mut self.out[..len],
@@ -329,6 +652,9 @@ impl NetcodeServer {
             Some((self.global_sequence, &connect_token.server_to_client_key)),
         )?;
         self.global_sequence += 1;
+        if self.exceeds_amplification_limit(received, len) {
+            return Ok(ServerResult::None);
+        }
 
         log::trace!("Connection request from Client {}", connect_token.client_id);
 
@@ -346,6 +672,7 @@ impl NetcodeServer {
             expire_timestamp,
             user_data: connect_token.user_data,
             replay_protection: ReplayProtection::new(),
+            path_challenge: None,
         });
         pending.last_packet_received_time = self.current_time;
         pending.last_packet_send_time = self.current_time;
@@ -390,6 +717,27 @@ impl NetcodeServer {
         if buffer.len() < 2 + NETCODE_MAC_BYTES {
             return Err(NetcodeError::PacketTooSmall);
         }
+        let received = buffer.len();
+
+        // Only unauthenticated traffic is limited, many connected clients can share an address behind a NAT
+        if find_client_mut_by_addr(&mut self.clients, addr).is_none() {
+            if !self.packet_limiter.consume(addr.ip()) {
+                self.rate_limited_packets += 1;
+                log::trace!("Dropped packet: too many packets from {}", addr.ip());
+                return Ok(ServerResult::None);
+            }
+
+            if self.connection_migration {
+                // Not checked against the amplification limit, the challenge can be bigger than small payloads.
+                // It's only sent for authenticated packets, at most once per send rate for each address.
+                if let Some(len) = self.migrate_client(addr, buffer)? {
+                    return Ok(ServerResult::PacketToSend {
+                        addr,
+                        payload: &mut self.out[..len],
+                    });
+                }
+            }
+        }
 
         // Handle connected client
         if let Some((slot, client)) = find_client_mut_by_addr(&mut self.clients, addr) {
@@ -460,11 +808,12 @@ impl NetcodeServer {
                     xnonce,
                     version_info,
                 } => {
-                    return self.handle_connection_request(addr, version_info, protocol_id, expire_timestamp, xnonce, data);
+                    return self.handle_connection_request(addr, received, version_info, protocol_id, expire_timestamp, xnonce, data);
                 }
                 Packet::Response {
                     token_data,
                     token_sequence,
+                    request_data,
                 } => {
                     let challenge_token = ChallengeToken::decode(token_data, token_sequence, &self.challenge_key)?;
                     let mut pending = self.pending_clients.remove(&addr).unwrap();
@@ -475,13 +824,15 @@ impl NetcodeServer {
                         );
                         return Ok(ServerResult::None);
                     }
-                    match self.clients.iter().position(|c| c.is_none()) {
+                    let server_full = self.connected_clients() >= self.max_clients;
+                    match self.clients.iter().position(|c| c.is_none()).filter(|_| !server_full) {
                         None => {
                             let packet = Packet::ConnectionDenied;
                             let len = packet.encode(&mut self.out, self.protocol_id, Some((self.global_sequence, &pending.send_key)))?;
-                            pending.state = ConnectionState::Disconnected;
                             self.global_sequence += 1;
-                            pending.last_packet_send_time = self.current_time;
+                            if self.exceeds_amplification_limit(received, len) {
+                                return Ok(ServerResult::None);
+                            }
                             return Ok(ServerResult::PacketToSend {
                                 addr,
                                 payload: &mut self.out[..len],
@@ -507,6 +858,7 @@ impl NetcodeServer {
                                 client_id,
                                 addr,
                                 user_data: Box::new(user_data),
+                                request_data,
                                 payload: &mut self.out[..len],
                             });
                         }
@@ -517,6 +869,11 @@ impl NetcodeServer {
         }
 
         // Handle new client
+        if self.is_ip_banned(addr.ip()) {
+            log::trace!("Ignored packet from banned address {}", addr);
+            return Ok(ServerResult::None);
+        }
+
         let (_, packet) = Packet::decode(buffer, self.protocol_id, None, None)?;
         match packet {
             Packet::ConnectionRequest {
@@ -525,7 +882,7 @@ impl NetcodeServer {
                 expire_timestamp,
                 xnonce,
                 version_info,
-            } => self.handle_connection_request(addr, version_info, protocol_id, expire_timestamp, xnonce, data),
+            } => self.handle_connection_request(addr, received, version_info, protocol_id, expire_timestamp, xnonce, data),
             _ => unreachable!("Decoding packet without key can only return ConnectionRequest packets"),
         }
     }
@@ -566,6 +923,12 @@ impl NetcodeServer {
         self.clients.iter().filter(|slot| slot.is_some()).count()
     }
 
+    /// Returns how many clients can still connect.
+    /// Connection requests when there are no slots available are denied.
+    pub fn available_slots(&self) -> usize {
+        self.max_clients.min(self.clients.len()).saturating_sub(self.connected_clients())
+    }
+
     /// Advance the server current time, and remove any pending connections that have expired.
     pub fn update(&mut self, duration: Duration) {
         self.current_time += duration;
@@ -578,6 +941,16 @@ impl NetcodeServer {
         }
 
         self.pending_clients.retain(|_, c| c.state != ConnectionState::Disconnected);
+
+        let current_time = self.current_time;
+        let not_expired = |expire_at: &Option<Duration>| !matches!(expire_at, Some(expire_at) if *expire_at <= current_time);
+        self.banned_ips.retain(|_, expire_at| not_expired(expire_at));
+        self.banned_clients.retain(|_, expire_at| not_expired(expire_at));
+
+        self.request_limiter.refill(duration);
+        self.packet_limiter.refill(duration);
+        self.migration_limiter.refill(duration);
+        self.migration_budget = MIGRATION_DECRYPTIONS_PER_UPDATE;
     }
 
     /// Updates the client, returns a ServerResult.
@@ -718,7 +1091,7 @@ fn find_client_mut_by_addr(clients: &mut [Option<Connection>], addr: SocketAddr)
 
 #[cfg(test)]
 mod tests {
-    use crate::{client::NetcodeClient, token::ConnectToken, ClientAuthentication};
+    use crate::{client::NetcodeClient, token::ConnectToken, ClientAuthentication, DisconnectReason};
 
     use super::*;
 
@@ -758,6 +1131,7 @@ mod tests {
         .unwrap();
         let client_auth = ClientAuthentication::Secure { connect_token };
         let mut client = NetcodeClient::new(Duration::ZERO, client_auth).unwrap();
+        client.set_request_data([9; NETCODE_REQUEST_DATA_BYTES]);
         let (client_packet, _) = client.update(Duration::ZERO).unwrap();
 
         let result = server.process_packet(client_addr, client_packet);
@@ -775,11 +1149,13 @@ mod tests {
             ServerResult::ClientConnected {
                 client_id: r_id,
                 user_data: r_data,
+                request_data,
                 payload,
                 ..
             } => {
                 assert_eq!(client_id, r_id);
                 assert_eq!(user_data, *r_data);
+                assert_eq!(request_data, Some([9; NETCODE_REQUEST_DATA_BYTES]));
                 client.process_packet(payload)
             }
             _ => unreachable!(),
@@ -808,6 +1184,7 @@ mod tests {
 
         let client_payload = [2u8; 300];
         let (_, packet) = client.generate_payload_packet(&client_payload).unwrap();
+        let mut replayed_packet = packet.to_vec();
 
         match server.process_packet(client_addr, packet) {
             ServerResult::Payload { client_id: id, payload } => {
@@ -817,6 +1194,9 @@ mod tests {
             _ => unreachable!(),
         }
 
+        assert_eq!(server.process_packet(client_addr, &mut replayed_packet), ServerResult::None);
+        assert_eq!(server.replayed_packets(client_id), Some(1));
+
         assert!(server.is_client_connected(client_id));
         let result = server.disconnect(client_id);
         match result {
@@ -833,6 +1213,494 @@ mod tests {
         assert!(!server.is_client_connected(client_id));
     }
 
+    #[test]
+    fn payload_from_unknown_address() {
+        let mut server = new_server();
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let spoofed_addr: SocketAddr = "127.0.0.1:3001".parse().unwrap();
+        let connect_token = ConnectToken::generate(Duration::ZERO, TEST_PROTOCOL_ID, 3, 4, 5, server.addresses(), None, TEST_KEY).unwrap();
+        let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+
+        // Request and challenge
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        match server.process_packet(client_addr, client_packet) {
+            ServerResult::PacketToSend { payload, .. } => client.process_packet(payload),
+            _ => unreachable!(),
+        };
+
+        // A challenge response replayed from another address is not accepted
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        let mut spoofed_packet = client_packet.to_vec();
+        assert_eq!(server.process_packet(spoofed_addr, &mut spoofed_packet), ServerResult::None);
+        assert_eq!(server.connected_clients(), 0);
+
+        match server.process_packet(client_addr, client_packet) {
+            ServerResult::ClientConnected { payload, .. } => client.process_packet(payload),
+            _ => unreachable!(),
+        };
+        assert!(client.is_connected());
+
+        // Payloads are only accepted from the address that completed the handshake
+        let (_, packet) = client.generate_payload_packet(&[1u8; 10]).unwrap();
+        assert_eq!(server.process_packet(spoofed_addr, packet), ServerResult::None);
+    }
+
+    #[test]
+    fn spoofed_packets_from_connected_address() {
+        let mut server = new_server();
+
+        let connect = |server: &mut NetcodeServer, client_id: u64, addr: SocketAddr| {
+            let connect_token = ConnectToken::generate(
+                Duration::ZERO,
+                TEST_PROTOCOL_ID,
+                3,
+                client_id,
+                5,
+                server.addresses(),
+                None,
+                TEST_KEY,
+            )
+            .unwrap();
+            let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+            let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+            match server.process_packet(addr, client_packet) {
+                ServerResult::PacketToSend { payload, .. } => client.process_packet(payload),
+                _ => unreachable!(),
+            };
+            let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+            match server.process_packet(addr, client_packet) {
+                ServerResult::ClientConnected { payload, .. } => client.process_packet(payload),
+                _ => unreachable!(),
+            };
+            assert!(client.is_connected());
+            client
+        };
+
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let attacker_addr: SocketAddr = "127.0.0.1:3001".parse().unwrap();
+        let _client = connect(&mut server, 4, client_addr);
+        let mut attacker = connect(&mut server, 5, attacker_addr);
+
+        // Packets from the client address must be encrypted with the keys of the client,
+        // an attacker spoofing the address can't disconnect it or take over the connection.
+        let (_, packet) = attacker.disconnect().unwrap();
+        let mut spoofed_packet = packet.to_vec();
+        assert_eq!(server.process_packet(client_addr, &mut spoofed_packet), ServerResult::None);
+        assert!(server.is_client_connected(4));
+
+        // New connection requests from an address already connected are ignored
+        let connect_token = ConnectToken::generate(Duration::ZERO, TEST_PROTOCOL_ID, 3, 6, 5, server.addresses(), None, TEST_KEY).unwrap();
+        let mut attacker = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+        let (client_packet, _) = attacker.update(Duration::ZERO).unwrap();
+        assert_eq!(server.process_packet(client_addr, client_packet), ServerResult::None);
+        assert!(server.is_client_connected(4));
+        assert!(!server.is_client_connected(6));
+    }
+
+    #[test]
+    fn connection_migration() {
+        let mut server = new_server();
+        server.set_connection_migration(true);
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let new_addr: SocketAddr = "127.0.0.1:3001".parse().unwrap();
+        let connect_token = ConnectToken::generate(Duration::ZERO, TEST_PROTOCOL_ID, 3, 4, 5, server.addresses(), None, TEST_KEY).unwrap();
+        let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        match server.process_packet(client_addr, client_packet) {
+            ServerResult::PacketToSend { payload, .. } => client.process_packet(payload),
+            _ => unreachable!(),
+        };
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        match server.process_packet(client_addr, client_packet) {
+            ServerResult::ClientConnected { payload, .. } => client.process_packet(payload),
+            _ => unreachable!(),
+        };
+        assert!(client.is_connected());
+
+        let (_, packet) = client.generate_payload_packet(&[1, 2, 3]).unwrap();
+        let replayed_packet = packet.to_vec();
+        assert_eq!(
+            server.process_packet(client_addr, packet),
+            ServerResult::Payload {
+                client_id: 4,
+                payload: &[1, 2, 3]
+            }
+        );
+
+        // Replayed packets from another address don't move the client
+        let mut packet = replayed_packet.clone();
+        assert_eq!(server.process_packet(new_addr, &mut packet), ServerResult::None);
+        assert_eq!(server.client_addr(4), Some(client_addr));
+
+        // A fresh packet captured and re-sent from another address only gets a challenge,
+        // that the attacker can't answer
+        let attacker_addr: SocketAddr = "127.0.0.1:3002".parse().unwrap();
+        let (_, packet) = client.generate_payload_packet(&[4, 5, 6]).unwrap();
+        let mut captured_packet = packet.to_vec();
+        match server.process_packet(attacker_addr, &mut captured_packet) {
+            ServerResult::PacketToSend { addr, .. } => assert_eq!(addr, attacker_addr),
+            _ => unreachable!(),
+        }
+        assert_eq!(server.client_addr(4), Some(client_addr));
+
+        // The client is moved once it answers the challenge from the new address
+        let (_, packet) = client.generate_payload_packet(&[4, 5, 6]).unwrap();
+        match server.process_packet(new_addr, packet) {
+            ServerResult::PacketToSend { addr, payload } => {
+                assert_eq!(addr, new_addr);
+                assert!(client.process_packet(payload).is_none());
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(server.client_addr(4), Some(client_addr));
+        let (response, _) = client.update(Duration::ZERO).unwrap();
+        assert_eq!(server.process_packet(new_addr, response), ServerResult::None);
+        assert_eq!(server.client_addr(4), Some(new_addr));
+
+        let (_, packet) = client.generate_payload_packet(&[4, 5, 6]).unwrap();
+        assert_eq!(
+            server.process_packet(new_addr, packet),
+            ServerResult::Payload {
+                client_id: 4,
+                payload: &[4, 5, 6]
+            }
+        );
+
+        // Without migration, packets from an unknown address are dropped
+        server.set_connection_migration(false);
+        let (_, packet) = client.generate_payload_packet(&[7, 8, 9]).unwrap();
+        assert_eq!(server.process_packet(client_addr, packet), ServerResult::None);
+        assert_eq!(server.client_addr(4), Some(new_addr));
+    }
+
+    #[test]
+    fn migration_rate_limit() {
+        let mut server = new_server();
+        server.set_connection_migration(true);
+        let mut packet = [0u8; 100];
+        packet[0] = PacketType::Payload as u8;
+
+        // Trial decryptions of packets from unknown addresses are limited
+        for _ in 0..25 {
+            server.process_packet("127.0.0.1:3000".parse().unwrap(), &mut packet);
+        }
+        assert_eq!(server.rate_limited_packets(), 5);
+    }
+
+    #[test]
+    fn migration_global_budget() {
+        let mut server = new_server();
+        server.set_connection_migration(true);
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let connect_token = ConnectToken::generate(Duration::ZERO, TEST_PROTOCOL_ID, 3, 4, 5, server.addresses(), None, TEST_KEY).unwrap();
+        let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+        for _ in 0..2 {
+            let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+            match server.process_packet(client_addr, client_packet) {
+                ServerResult::PacketToSend { payload, .. } | ServerResult::ClientConnected { payload, .. } => {
+                    client.process_packet(payload)
+                }
+                _ => unreachable!(),
+            };
+        }
+        assert!(server.is_client_connected(4));
+
+        // Spoofed addresses, each one below its own limit, share the budget of trial decryptions
+        let mut packet = [0u8; 100];
+        packet[0] = PacketType::Payload as u8;
+        for i in 0..MIGRATION_DECRYPTIONS_PER_UPDATE as u32 + 10 {
+            let addr = SocketAddr::new(IpAddr::from((0x0A00_0000 + i).to_be_bytes()), 5000);
+            server.process_packet(addr, &mut packet);
+        }
+        assert_eq!(server.rate_limited_packets(), 10);
+
+        // The budget is restored on update
+        server.update(Duration::ZERO);
+        let (_, packet) = client.generate_payload_packet(&[1, 2, 3]).unwrap();
+        let new_addr: SocketAddr = "127.0.0.1:3001".parse().unwrap();
+        assert!(matches!(server.process_packet(new_addr, packet), ServerResult::PacketToSend { addr, .. } if addr == new_addr));
+        assert_eq!(server.rate_limited_packets(), 10);
+    }
+
+    #[test]
+    fn server_full() {
+        let mut server = new_server();
+        server.set_max_clients(1);
+        assert_eq!(server.available_slots(), 1);
+
+        let connect_client = |server: &mut NetcodeServer, client_id: u64, addr: SocketAddr| {
+            let connect_token = ConnectToken::generate(
+                Duration::ZERO,
+                TEST_PROTOCOL_ID,
+                3,
+                client_id,
+                5,
+                server.addresses(),
+                None,
+                TEST_KEY,
+            )
+            .unwrap();
+            let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+            for _ in 0..2 {
+                let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+                match server.process_packet(addr, client_packet) {
+                    ServerResult::PacketToSend { payload, .. } | ServerResult::ClientConnected { payload, .. } => {
+                        client.process_packet(payload)
+                    }
+                    _ => unreachable!(),
+                };
+                if client.is_disconnected() {
+                    break;
+                }
+            }
+            client
+        };
+
+        let client = connect_client(&mut server, 4, "127.0.0.1:3000".parse().unwrap());
+        assert!(client.is_connected());
+        assert_eq!(server.available_slots(), 0);
+
+        let client = connect_client(&mut server, 5, "127.0.0.1:3001".parse().unwrap());
+        assert_eq!(client.disconnect_reason(), Some(DisconnectReason::ConnectionDenied));
+        assert!(!server.is_client_connected(5));
+    }
+
+    #[test]
+    fn ban_and_allow_list() {
+        let mut server = new_server();
+        let mut port = 3000;
+        let mut challenged = |server: &mut NetcodeServer, client_id: u64, ip: &str| {
+            port += 1;
+            let addr = SocketAddr::new(ip.parse().unwrap(), port);
+            let connect_token = ConnectToken::generate(
+                Duration::ZERO,
+                TEST_PROTOCOL_ID,
+                30,
+                client_id,
+                5,
+                server.addresses(),
+                None,
+                TEST_KEY,
+            )
+            .unwrap();
+            let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+            let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+            matches!(server.process_packet(addr, client_packet), ServerResult::PacketToSend { .. })
+        };
+
+        server.ban_client(4, Some(Duration::from_secs(10)));
+        assert!(!challenged(&mut server, 4, "127.0.0.1"));
+        assert!(challenged(&mut server, 5, "127.0.0.1"));
+        server.update(Duration::from_secs(10));
+        assert!(challenged(&mut server, 4, "127.0.0.1"));
+
+        let ip: IpAddr = "127.0.0.2".parse().unwrap();
+        server.ban_ip(ip, None);
+        assert!(server.is_ip_banned(ip));
+        assert!(!challenged(&mut server, 6, "127.0.0.2"));
+        server.update(Duration::from_secs(10));
+        assert!(!challenged(&mut server, 6, "127.0.0.2"));
+        server.unban_ip(ip);
+        assert!(challenged(&mut server, 6, "127.0.0.2"));
+
+        server.set_allow_list(Some(HashSet::from([7])));
+        assert!(!challenged(&mut server, 8, "127.0.0.1"));
+        assert!(challenged(&mut server, 7, "127.0.0.1"));
+        server.set_allow_list(None);
+        assert!(challenged(&mut server, 8, "127.0.0.1"));
+    }
+
+    #[test]
+    fn connection_request_rate_limit() {
+        let mut server = new_server();
+        server.set_connection_request_rate_limit(2, 3);
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let send_request = |server: &mut NetcodeServer, addr: SocketAddr| {
+            let connect_token =
+                ConnectToken::generate(Duration::ZERO, TEST_PROTOCOL_ID, 30, 4, 5, server.addresses(), None, TEST_KEY).unwrap();
+            let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+            let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+            matches!(server.process_packet(addr, client_packet), ServerResult::PacketToSend { .. })
+        };
+
+        for _ in 0..3 {
+            assert!(send_request(&mut server, client_addr));
+        }
+        assert!(!send_request(&mut server, client_addr));
+        // Other addresses are not affected
+        assert!(send_request(&mut server, "127.0.0.2:3000".parse().unwrap()));
+
+        server.update(Duration::from_millis(500));
+        assert!(send_request(&mut server, client_addr));
+        assert!(!send_request(&mut server, client_addr));
+
+        let stats = server.connection_request_stats();
+        assert_eq!(stats.received, 7);
+        assert_eq!(stats.rate_limited, 2);
+        assert_eq!(stats.pending_limit_reached, 0);
+    }
+
+    #[test]
+    fn rate_limiter_eviction() {
+        let mut limiter = RateLimiter::new(10, 1);
+        let ip = |i: usize| IpAddr::from([10, 0, (i >> 8) as u8, i as u8]);
+        for i in 0..MAX_RATE_LIMITED_ADDRESSES {
+            assert!(limiter.consume(ip(i)));
+        }
+        assert!(!limiter.consume(ip(0)));
+
+        // A full limiter evicts the oldest address instead of refusing new ones
+        assert!(limiter.consume(ip(MAX_RATE_LIMITED_ADDRESSES)));
+        assert_eq!(limiter.tokens.len(), MAX_RATE_LIMITED_ADDRESSES);
+        assert!(limiter.consume(ip(0)));
+        assert!(!limiter.consume(ip(2)));
+
+        limiter.refill(Duration::from_secs(1));
+        assert!(limiter.tokens.is_empty());
+        assert!(limiter.order.is_empty());
+    }
+
+    #[test]
+    fn packet_rate_limit() {
+        let mut server = new_server();
+        server.set_packet_rate_limit(10, 5);
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let mut packet = [0u8; 100];
+        packet[0] = PacketType::Payload as u8;
+
+        for _ in 0..8 {
+            server.process_packet(client_addr, &mut packet);
+        }
+        assert_eq!(server.rate_limited_packets(), 3);
+        // Other addresses are not affected
+        server.process_packet("127.0.0.2:3000".parse().unwrap(), &mut packet);
+        assert_eq!(server.rate_limited_packets(), 3);
+
+        server.update(Duration::from_millis(200));
+        for _ in 0..3 {
+            server.process_packet(client_addr, &mut packet);
+        }
+        assert_eq!(server.rate_limited_packets(), 4);
+    }
+
+    #[test]
+    fn packet_rate_limit_connected_clients() {
+        let mut server = new_server();
+        let mut clients = Vec::new();
+        for client_id in 0..8 {
+            let client_addr: SocketAddr = format!("127.0.0.1:{}", 3000 + client_id).parse().unwrap();
+            let connect_token = ConnectToken::generate(
+                Duration::ZERO,
+                TEST_PROTOCOL_ID,
+                3,
+                client_id,
+                5,
+                server.addresses(),
+                None,
+                TEST_KEY,
+            )
+            .unwrap();
+            let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+            for _ in 0..2 {
+                let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+                match server.process_packet(client_addr, client_packet) {
+                    ServerResult::PacketToSend { payload, .. } | ServerResult::ClientConnected { payload, .. } => {
+                        client.process_packet(payload);
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            assert!(client.is_connected());
+            clients.push((client_addr, client));
+        }
+
+        // Clients behind the same address are not limited
+        server.set_packet_rate_limit(10, 5);
+        for (client_addr, client) in clients.iter_mut() {
+            for _ in 0..20 {
+                let (_, packet) = client.generate_payload_packet(&[1u8; 10]).unwrap();
+                assert!(matches!(server.process_packet(*client_addr, packet), ServerResult::Payload { .. }));
+            }
+        }
+        assert_eq!(server.rate_limited_packets(), 0);
+
+        // Unknown addresses with the same ip still are
+        let mut packet = [0u8; 100];
+        packet[0] = PacketType::Payload as u8;
+        for _ in 0..8 {
+            server.process_packet("127.0.0.1:4000".parse().unwrap(), &mut packet);
+        }
+        assert_eq!(server.rate_limited_packets(), 3);
+    }
+
+    #[test]
+    fn amplification_limit() {
+        let mut server = new_server();
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let connect_token = ConnectToken::generate(Duration::ZERO, TEST_PROTOCOL_ID, 30, 4, 5, server.addresses(), None, TEST_KEY).unwrap();
+        let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        let mut request = client_packet.to_vec();
+        match server.process_packet(client_addr, &mut request.clone()) {
+            ServerResult::PacketToSend { payload, .. } => assert!(payload.len() < request.len()),
+            _ => unreachable!(),
+        }
+
+        // Responses larger than allowed are dropped
+        server.set_amplification_limit(0.1);
+        let result = server.process_packet(client_addr, &mut request);
+        assert_eq!(result, ServerResult::None);
+        assert_eq!(server.connection_request_stats().amplification_limited, 1);
+
+        // Connection requests without the full padding are ignored
+        let len = request.len();
+        assert_eq!(server.process_packet(client_addr, &mut request[..len - 100]), ServerResult::None);
+        assert_eq!(server.connection_request_stats().received, 2);
+    }
+
+    #[test]
+    fn invalid_protocol_id() {
+        let mut server = new_server();
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let connect_token =
+            ConnectToken::generate(Duration::ZERO, TEST_PROTOCOL_ID + 1, 3, 4, 5, server.addresses(), None, TEST_KEY).unwrap();
+        let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        assert_eq!(server.process_packet(client_addr, client_packet), ServerResult::None);
+        assert!(server.pending_clients.is_empty());
+    }
+
+    #[test]
+    fn invalid_connect_tokens() {
+        let mut server = new_server();
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let generate_token = |server_addresses: Vec<SocketAddr>, private_key: &[u8; NETCODE_KEY_BYTES]| {
+            ConnectToken::generate(Duration::ZERO, TEST_PROTOCOL_ID, 3, 4, 5, server_addresses, None, private_key).unwrap()
+        };
+        let try_connect = |server: &mut NetcodeServer, connect_token: ConnectToken| {
+            let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+            let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+            server.process_packet(client_addr, client_packet) == ServerResult::None
+        };
+
+        // Signed with another key
+        let token = generate_token(server.addresses(), b"another very very secret key 32.");
+        assert!(try_connect(&mut server, token));
+
+        // Not issued for this server
+        let token = generate_token(vec!["127.0.0.1:6000".parse().unwrap()], TEST_KEY);
+        assert!(try_connect(&mut server, token));
+
+        // Expired
+        let token = generate_token(server.addresses(), TEST_KEY);
+        server.update(Duration::from_secs(3));
+        assert!(try_connect(&mut server, token));
+
+        assert!(server.pending_clients.is_empty());
+    }
+
     #[test]
     fn connect_token_already_used() {
         let mut server = new_server();
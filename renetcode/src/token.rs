@@ -6,6 +6,11 @@ use std::{
     time::Duration,
 };
 
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::SystemTime;
+#[cfg(target_arch = "wasm32")]
+use web_time::SystemTime;
+
 use crate::{
     crypto::{dencrypted_in_place_xnonce, encrypt_in_place_xnonce, generate_random_bytes},
     serialize::*,
@@ -53,6 +58,8 @@ pub enum TokenGenerationError {
     CryptoError,
     IoError(io::Error),
     NoServerAddressAvailable,
+    /// The system clock is set before the unix epoch, the token would have already expired
+    InvalidSystemTime,
 }
 
 impl From<io::Error> for TokenGenerationError {
@@ -78,10 +85,106 @@ impl fmt::Display for TokenGenerationError {
             CryptoError => write!(fmt, "error while encoding or decoding the connect token"),
             IoError(ref io_err) => write!(fmt, "{}", io_err),
             NoServerAddressAvailable => write!(fmt, "connect token must have at least one server address"),
+            InvalidSystemTime => write!(fmt, "system time is before the unix epoch"),
         }
     }
 }
 
+/// Builder to generate connect tokens, used by the matchmaker or backend that authenticates the clients.
+///
+/// # Usage
+/// ```
+/// # use renetcode::ConnectTokenBuilder;
+/// let private_key = b"an example very very secret key."; // 32-bytes
+/// let token = ConnectTokenBuilder::new(7, 42, *private_key)
+///     .server_address("127.0.0.1:5000".parse().unwrap())
+///     .expire_seconds(60)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConnectTokenBuilder {
+    protocol_id: u64,
+    client_id: u64,
+    private_key: [u8; NETCODE_KEY_BYTES],
+    current_time: Option<Duration>,
+    expire_seconds: u64,
+    timeout_seconds: i32,
+    server_addresses: Vec<SocketAddr>,
+    user_data: Option<[u8; NETCODE_USER_DATA_BYTES]>,
+}
+
+impl ConnectTokenBuilder {
+    /// Creates a builder for the client, the private key and the protocol id must be the same used in server.
+    pub fn new(protocol_id: u64, client_id: u64, private_key: [u8; NETCODE_KEY_BYTES]) -> Self {
+        Self {
+            protocol_id,
+            client_id,
+            private_key,
+            current_time: None,
+            expire_seconds: 300,
+            timeout_seconds: 15,
+            server_addresses: vec![],
+            user_data: None,
+        }
+    }
+
+    /// Time since the unix epoch used as the creation time of the token.
+    /// Default: the current system time
+    pub fn current_time(mut self, current_time: Duration) -> Self {
+        self.current_time = Some(current_time);
+        self
+    }
+
+    /// Seconds until the token expires, clients can't start connecting with expired tokens.
+    /// Default: 300
+    pub fn expire_seconds(mut self, expire_seconds: u64) -> Self {
+        self.expire_seconds = expire_seconds;
+        self
+    }
+
+    /// Seconds without receiving packets until the connection times out, negative values disable the timeout.
+    /// Default: 15
+    pub fn timeout_seconds(mut self, timeout_seconds: i32) -> Self {
+        self.timeout_seconds = timeout_seconds;
+        self
+    }
+
+    /// Adds a server address the client can connect to, they are tried in order. At most 32 addresses are allowed.
+    pub fn server_address(mut self, server_address: SocketAddr) -> Self {
+        self.server_addresses.push(server_address);
+        self
+    }
+
+    /// Sets the data available to the server when the client connects.
+    pub fn user_data(mut self, user_data: [u8; NETCODE_USER_DATA_BYTES]) -> Self {
+        self.user_data = Some(user_data);
+        self
+    }
+
+    /// Generates the token, encrypting its private part with the private key.
+    /// Without a current time, fails if the system clock is before the unix epoch.
+    pub fn build(&self) -> Result<ConnectToken, TokenGenerationError> {
+        let current_time = match self.current_time {
+            Some(current_time) => current_time,
+            None => SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_err(|_| TokenGenerationError::InvalidSystemTime)?,
+        };
+
+        ConnectToken::generate(
+            current_time,
+            self.protocol_id,
+            self.expire_seconds,
+            self.client_id,
+            self.timeout_seconds,
+            self.server_addresses.clone(),
+            self.user_data.as_ref(),
+            &self.private_key,
+        )
+    }
+}
+
 impl ConnectToken {
     /// Generate a token to be sent to an client. The user data is available to the server after an
     /// successfull conection. The private key and the protocol id must be the same used in server.
@@ -374,6 +477,34 @@ mod tests {
         assert_eq!(token, result);
     }
 
+    #[test]
+    fn connect_token_builder() {
+        let private_key = b"an example very very secret key."; // 32-bytes
+        let user_data = generate_random_bytes();
+        let token = ConnectTokenBuilder::new(2, 4, *private_key)
+            .current_time(Duration::from_secs(100))
+            .expire_seconds(3)
+            .timeout_seconds(5)
+            .server_address("127.0.0.1:8080".parse().unwrap())
+            .server_address("127.0.0.2:3000".parse().unwrap())
+            .user_data(user_data)
+            .build()
+            .unwrap();
+
+        assert_eq!(token.client_id, 4);
+        assert_eq!(token.protocol_id, 2);
+        assert_eq!(token.expire_timestamp, 103);
+        assert_eq!(token.timeout_seconds, 5);
+
+        let private = PrivateConnectToken::decode(&token.private_data, 2, token.expire_timestamp, &token.xnonce, private_key).unwrap();
+        assert_eq!(private.client_id, 4);
+        assert_eq!(private.user_data, user_data);
+        assert_eq!(private.server_addresses, token.server_addresses);
+
+        let error = ConnectTokenBuilder::new(2, 4, *private_key).build();
+        assert!(matches!(error, Err(TokenGenerationError::NoServerAddressAvailable)));
+    }
+
     #[test]
     fn connect_token_serialization() {
         let server_addresses: Vec<SocketAddr> = vec!["127.0.0.1:8080".parse().unwrap(), "127.0.0.2:3000".parse().unwrap()];